@@ -48,9 +48,7 @@ pub struct FDTableEntry {
 /// It is the default if no close handlers are defined
 pub const fn NULL_FUNC(_: FDTableEntry, _: u64) {}
 
-// BUG / TODO: Use this in some sane way...
-#[allow(dead_code)]
-/// Global maximum number of fds... (checks may not be implemented)
+/// Global maximum number of open (fdkind,underfd) items, across all cages
 pub const TOTAL_FD_MAX: u64 = 4096;
 
 // replicating these constants here so this can compile on systems other than
@@ -58,9 +56,11 @@ pub const TOTAL_FD_MAX: u64 = 4096;
 /// copied from libc
 pub const EPOLL_CTL_ADD: i32 = 1;
 /// copied from libc
-pub const EPOLL_CTL_MOD: i32 = 2;
+pub const EPOLL_CTL_DEL: i32 = 2;
 /// copied from libc
-pub const EPOLL_CTL_DEL: i32 = 3;
+pub const EPOLL_CTL_MOD: i32 = 3;
+/// copied from Linux (fs/eventpoll.c).  How deeply epollfds may be nested
+pub const EP_MAX_NESTS: u64 = 4;
 
 #[allow(non_camel_case_types)]
 /// i32 copied from libc.  used in EPOLL event flags even though events are u32
@@ -25,10 +25,12 @@ pub use super::commonconstants::*;
 pub const ALGONAME: &str = "DashMapVecGlobal";
 
 // It's fairly easy to check the fd count on a per-process basis (I just check
-// when I would add a new fd).
+// when I would add a new fd).  Each cage may also lower its own limit (for
+// RLIMIT_NOFILE), which is kept in FDLIMIT and inherited across fork.
 //
-// TODO: I will ignore the total limit for now.  I would ideally do this on
-// every creation, close, fork, etc. but it's a PITA to track this.
+// The total limit (TOTAL_FD_MAX) is on the number of distinct (fdkind,underfd)
+// items, i.e., the FDCOUNT entries.  Like the system-wide file table in Linux,
+// dups and forks of an fd don't count again.
 
 // We will raise a panic anywhere we receive an unknown cageid.  This frankly
 // should not be possible and indicates some sort of internal error in our
@@ -68,6 +70,42 @@ lazy_static! {
         DashMap::new()
     };
 
+    // The number of fds each cage may use, if it set one.  Otherwise the
+    // limit is FD_PER_PROCESS_MAX.
+    #[derive(Debug)]
+    static ref FDLIMIT: DashMap<u64, u64> = {
+        DashMap::new()
+    };
+
+}
+
+// #[doc = include_str!("../docs/get_fd_limit.md")]
+pub fn get_fd_limit(cageid: u64) -> u64 {
+    match FDLIMIT.get(&cageid) {
+        Some(limit) => *limit,
+        None => FD_PER_PROCESS_MAX,
+    }
+}
+
+// Virtual fds at or above the limit can no longer be handed out.  Ones that
+// are already open stay open, as in Linux...
+// #[doc = include_str!("../docs/set_fd_limit.md")]
+pub fn set_fd_limit(cageid: u64, limit: u64) -> Result<(), threei::RetVal> {
+
+    assert!(FDTABLE.contains_key(&cageid),"Unknown cageid in fdtable access");
+
+    // The table can't grow past this...
+    if limit > FD_PER_PROCESS_MAX {
+        return Err(threei::Errno::EINVAL as u64);
+    }
+    FDLIMIT.insert(cageid, limit);
+    Ok(())
+}
+
+// Would one more (fdkind,underfd) item go over TOTAL_FD_MAX?
+#[doc(hidden)]
+fn _over_total_fd_max(entry:&FDTableEntry) -> bool {
+    !FDCOUNT.contains_key(&(entry.fdkind, entry.underfd)) && FDCOUNT.len() as u64 >= TOTAL_FD_MAX
 }
 
 // #[doc = include_str!("../docs/init_empty_cage.md")]
@@ -113,6 +151,19 @@ pub fn get_unused_virtual_fd(
     should_cloexec: bool,
     perfdinfo: u64,
 ) -> Result<u64, threei::RetVal> {
+    get_unused_virtual_fd_from(cageid, 0, fdkind, underfd, should_cloexec, perfdinfo)
+}
+
+// Same as get_unused_virtual_fd, but hands out the lowest unused fd that is
+// at least startfd.  This is what fcntl's F_DUPFD needs...
+pub fn get_unused_virtual_fd_from(
+    cageid: u64,
+    startfd: u64,
+    fdkind: u32,
+    underfd: u64,
+    should_cloexec: bool,
+    perfdinfo: u64,
+) -> Result<u64, threei::RetVal> {
 
     assert!(FDTABLE.contains_key(&cageid),"Unknown cageid in fdtable access");
     // Set up the entry so it has the right info...
@@ -125,10 +176,15 @@ pub fn get_unused_virtual_fd(
         perfdinfo,
     };
 
+    if _over_total_fd_max(&myentry) {
+        return Err(threei::Errno::ENFILE as u64);
+    }
+
+    let fdlimit = get_fd_limit(cageid);
     let mut myfdrow = FDTABLE.get_mut(&cageid).unwrap();
 
     // Check the fds in order.
-    for fdcandidate in 0..FD_PER_PROCESS_MAX {
+    for fdcandidate in startfd..fdlimit {
         // FIXME: This is likely very slow.  Should do something smarter...
         if myfdrow[fdcandidate as usize].is_none() {
             // I just checked.  Should not be there...
@@ -157,10 +213,10 @@ pub fn get_specific_virtual_fd(
     assert!(FDTABLE.contains_key(&cageid),"Unknown cageid in fdtable access");
 
     // If you ask for a FD number that is too large, I'm going to reject it.
-    // Note that, I need to use the FD_PER_PROCESS_MAX setting because this
+    // Note that, the limit is never above FD_PER_PROCESS_MAX because this
     // is also how I'm tracking how many values you have open.  If this
     // changed, then these constants could be decoupled...
-    if requested_virtualfd > FD_PER_PROCESS_MAX {
+    if requested_virtualfd >= get_fd_limit(cageid) {
         return Err(threei::Errno::EBADF as u64);
     }
 
@@ -174,6 +230,10 @@ pub fn get_specific_virtual_fd(
         perfdinfo,
     };
 
+    if _over_total_fd_max(&myentry) {
+        return Err(threei::Errno::ENFILE as u64);
+    }
+
     // This is before the FDTABLE action, so if I decrement the same fd, it
     // calls the intermediate handler instead of the last one.
     _increment_fdcount(myentry);
@@ -244,6 +304,11 @@ pub fn copy_fdtable_for_cage(srccageid: u64, newcageid: u64) -> Result<(), three
     }
 
     assert!(FDTABLE.insert(newcageid, hmcopy).is_none());
+
+    // The child starts out with the same limit...
+    if let Some(limit) = FDLIMIT.get(&srccageid).map(|limit| *limit) {
+        FDLIMIT.insert(newcageid, limit);
+    }
     
     // I'm not going to bother to check the number of fds used overall yet...
     //    Err(threei::Errno::EMFILE as u64),
@@ -261,6 +326,7 @@ pub fn remove_cage_from_fdtable(cageid: u64) {
     // remove the item first and then we clean up and call their close
     // handlers.
     let myfdrow = FDTABLE.remove(&cageid).unwrap().1;
+    FDLIMIT.remove(&cageid);
 
     // Take only the Some items in here (clippy suggested)
     for entry in myfdrow.into_iter().flatten() {
@@ -425,7 +491,14 @@ fn _decrement_fdcount(entry:FDTableEntry) {
         // Remove before calling their close handler in case they do operations
         // inside the close handler which create / close fds...
         FDCOUNT.remove(&mytuple);
+        // Like Linux, the registrations go away with the last reference...
+        _remove_epoll_registrations(entry.fdkind, entry.underfd);
         (lastch)(entry,0);
+        // An epollfd owns its epoll table entry and the underfds it calls
+        // down to, so those go away with its last reference...
+        if entry.fdkind == FDT_KINDEPOLL {
+            _remove_epoll_entry(entry.underfd);
+        }
     }
 }
 
@@ -443,6 +516,18 @@ fn _increment_fdcount(entry:FDTableEntry) {
     }
 }
 
+// Keeps the (fdkind,underfd) of an entry open while no cage's table has it,
+// e.g. while it is in flight in an SCM_RIGHTS message.  Every hold must be
+// matched by a release, which calls the close handlers if it was the last
+// reference.
+pub fn hold_fdentry(entry:FDTableEntry) {
+    _increment_fdcount(entry);
+}
+
+pub fn release_fdentry(entry:FDTableEntry) {
+    _decrement_fdcount(entry);
+}
+
 
 
 /***************   Code for handling select() ****************/
@@ -793,11 +878,18 @@ struct EPollDescriptorInfo {
                                       // This has all of the things the user
                                       // will virtualize and handle.  The key
                                       // is the fdkind.  
+    nestedepollhashmap: HashMap<u64,u64>, // The epoll table entry of each
+                                      // epollfd (by virtfd) in this one.
+                                      // Used to find loops and the nesting
+                                      // depth.
+    registeredunderfds: HashMap<(u32,u64),u64>, // The underfd each virtfd
+                                      // (by fdkind) referred to when it was
+                                      // added.  Used to drop the registration
+                                      // once that underfd is closed for good.
 }
 
-// TODO: I don't clean up this table yet.  I probably should when the last 
-// reference to a fd is closed, but this bookkeeping seems excessive at this
-// time...
+// An entry is removed once the last reference to its epollfd is closed (see
+// _decrement_fdcount).
 #[derive(Clone, Debug)]
 struct EPollTable {
     highestneverusedentry: u64, // Never resets (even after close).  Used to
@@ -818,6 +910,97 @@ lazy_static! {
     };
 }
 
+// Drops an epoll table entry and calls the last close handler of each fdkind
+// that had an underfd, since nothing else refers to those underfds...
+#[doc(hidden)]
+fn _remove_epoll_entry(entrynum:u64) {
+    // Don't hold the lock while calling their close handlers...
+    let mut ept = EPOLLTABLE.lock().unwrap();
+    let removed = ept.thisepolltable.remove(&entrynum);
+
+    // It also stops being in any other epollfd, like any other closed fd...
+    for epollinfo in ept.thisepolltable.values_mut() {
+        let gone: Vec<u64> = epollinfo.nestedepollhashmap.iter().filter(|(_, nested)| **nested == entrynum).map(|(virtfd, _)| *virtfd).collect();
+        for virtfd in gone {
+            epollinfo.nestedepollhashmap.remove(&virtfd);
+            if let Some(thisuserhm) = epollinfo.userhandledhashmap.get_mut(&FDT_KINDEPOLL) {
+                thisuserhm.remove(&virtfd);
+                if thisuserhm.is_empty() {
+                    epollinfo.userhandledhashmap.remove(&FDT_KINDEPOLL);
+                }
+            }
+        }
+    }
+    drop(ept);
+
+    if let Some(epollinfo) = removed {
+        for (fdkind, underfd) in epollinfo.underfdhashmap {
+            let lastch = match CLOSEHANDLERTABLE.lock().unwrap().get(&fdkind) {
+                Some(closehandlerentry) => closehandlerentry.last,
+                None => NULL_FUNC,
+            };
+            let underentry = FDTableEntry {
+                fdkind,
+                underfd,
+                should_cloexec: false,
+                perfdinfo: 0,
+            };
+            (lastch)(underentry,0);
+        }
+    }
+}
+
+// Drops every registration made for a (fdkind,underfd) that has just been
+// closed for good, in whichever epollfd it is...
+#[doc(hidden)]
+fn _remove_epoll_registrations(fdkind:u32, underfd:u64) {
+    let mut ept = EPOLLTABLE.lock().unwrap();
+    for epollinfo in ept.thisepolltable.values_mut() {
+        let gone: Vec<u64> = epollinfo.registeredunderfds.iter().filter(|((kind, _), under)| *kind == fdkind && **under == underfd).map(|((_, virtfd), _)| *virtfd).collect();
+        for virtfd in gone {
+            epollinfo.registeredunderfds.remove(&(fdkind, virtfd));
+            epollinfo.nestedepollhashmap.remove(&virtfd);
+            if let Some(thisuserhm) = epollinfo.userhandledhashmap.get_mut(&fdkind) {
+                thisuserhm.remove(&virtfd);
+                if thisuserhm.is_empty() {
+                    epollinfo.userhandledhashmap.remove(&fdkind);
+                }
+            }
+        }
+    }
+}
+
+// How deep the epollfds below this entry go.  0 if it has none in it...
+#[doc(hidden)]
+fn _epoll_depth_below(ept:&EPollTable, entrynum:u64) -> u64 {
+    match ept.thisepolltable.get(&entrynum) {
+        None => 0,
+        Some(epollinfo) => epollinfo.nestedepollhashmap.values().map(|nested| 1 + _epoll_depth_below(ept, *nested)).max().unwrap_or(0),
+    }
+}
+
+// How deep the epollfds above this entry go.  0 if it isn't in any...
+#[doc(hidden)]
+fn _epoll_depth_above(ept:&EPollTable, entrynum:u64) -> u64 {
+    ept.thisepolltable.iter()
+        .filter(|(_, epollinfo)| epollinfo.nestedepollhashmap.values().any(|nested| *nested == entrynum))
+        .map(|(parent, _)| 1 + _epoll_depth_above(ept, *parent))
+        .max()
+        .unwrap_or(0)
+}
+
+// Can I get from this entry down to the target one?
+#[doc(hidden)]
+fn _epoll_reaches(ept:&EPollTable, entrynum:u64, target:u64) -> bool {
+    if entrynum == target {
+        return true;
+    }
+    match ept.thisepolltable.get(&entrynum) {
+        None => false,
+        Some(epollinfo) => epollinfo.nestedepollhashmap.values().any(|nested| _epoll_reaches(ept, *nested, target)),
+    }
+}
+
 fn _get_epoll_entrynum_or_error(cageid:u64, epfd:u64) -> Result<u64,threei::RetVal> {
     // Is the epfd ok? 
     match FDTABLE.get(&cageid).unwrap()[epfd as usize] {
@@ -907,14 +1090,16 @@ pub fn virtualize_epoll_ctl(cageid:u64, epfd:u64, op:i32, virtfd:u64, event:epol
 
 
     let virtfdkind:u32;
+    let virtunderfd:u64;
+    // the table entry, if virtfd is an epollfd itself...
+    let mut nestedentrynum = None;
 
     if let Some(tableentry) = FDTABLE.get(&cageid).unwrap()[virtfd as usize] {
-        // Right now, I don't support this, so error...
         if tableentry.fdkind == FDT_KINDEPOLL {
-            // TODO: support EPOLLFDs...
-            return Err(threei::Errno::ENOSYS as u64);
+            nestedentrynum = Some(tableentry.underfd);
         }
         virtfdkind = tableentry.fdkind;
+        virtunderfd = tableentry.underfd;
     }
     else {
         // The virtual Fd doesn't exist -- error...
@@ -922,8 +1107,22 @@ pub fn virtualize_epoll_ctl(cageid:u64, epfd:u64, op:i32, virtfd:u64, event:epol
     }
 
     let mut eptable = EPOLLTABLE.lock().unwrap();
-//    let userhm = eptable.thisepolltable.get_mut(&epentrynum).unwrap().userhandledhashmap.entry(virtfdkind).or_default();
-    let userhm = &mut eptable.thisepolltable.get_mut(&epentrynum).unwrap().userhandledhashmap;
+
+    // Adding an epollfd must neither make a loop nor nest them too deeply.
+    // Linux returns ELOOP for both...
+    if op == EPOLL_CTL_ADD {
+        if let Some(nested) = nestedentrynum {
+            if _epoll_reaches(&eptable, nested, epentrynum) {
+                return Err(threei::Errno::ELOOP as u64);
+            }
+            if _epoll_depth_below(&eptable, nested) + 1 + _epoll_depth_above(&eptable, epentrynum) > EP_MAX_NESTS {
+                return Err(threei::Errno::ELOOP as u64);
+            }
+        }
+    }
+
+    let epollinfo = eptable.thisepolltable.get_mut(&epentrynum).unwrap();
+    let userhm = &mut epollinfo.userhandledhashmap;
 
     match op {
         EPOLL_CTL_ADD => {
@@ -931,10 +1130,12 @@ pub fn virtualize_epoll_ctl(cageid:u64, epfd:u64, op:i32, virtfd:u64, event:epol
             if thisuserhm.contains_key(&virtfd) {
                 return Err(threei::Errno::EEXIST as u64);
             }
-            // BUG: Need to check for ELOOP here once I support EPOLLFDs
-            // referencing each other...
 
             thisuserhm.insert(virtfd, event);
+            epollinfo.registeredunderfds.insert((virtfdkind, virtfd), virtunderfd);
+            if let Some(nested) = nestedentrynum {
+                epollinfo.nestedepollhashmap.insert(virtfd, nested);
+            }
         },
         EPOLL_CTL_MOD => {
             if !userhm.contains_key(&virtfdkind) {
@@ -959,6 +1160,8 @@ pub fn virtualize_epoll_ctl(cageid:u64, epfd:u64, op:i32, virtfd:u64, event:epol
             if thisuserhm.is_empty() {
                 userhm.remove(&virtfdkind);
             }
+            epollinfo.registeredunderfds.remove(&(virtfdkind, virtfd));
+            epollinfo.nestedepollhashmap.remove(&virtfd);
         },
         _ => {
             return Err(threei::Errno::EINVAL as u64);
@@ -982,6 +1185,32 @@ pub fn get_virtual_epoll_wait_data(cageid:u64, epfd:u64) -> Result<HashMap<u32,H
 
 
 
+// Same as get_virtual_epoll_wait_data, but for an epoll table entry (the
+// underfd of an epollfd).  Useful when the caller has no cage, e.g. when
+// asking whether an epollfd inside another epollfd is ready...
+// #[doc = include_str!("../docs/get_virtual_epoll_wait_data_by_entrynum.md")]
+pub fn get_virtual_epoll_wait_data_by_entrynum(entrynum:u64) -> Result<HashMap<u32,HashMap<u64,epoll_event>>,threei::RetVal> {
+
+    let eptable = EPOLLTABLE.lock().unwrap();
+    match eptable.thisepolltable.get(&entrynum) {
+        Some(epollinfo) => Ok(epollinfo.userhandledhashmap.clone()),
+        None => Err(threei::Errno::EBADF as u64),
+    }
+}
+
+// Same as epoll_get_underfd_hashmap, but for an epoll table entry.
+// #[doc = include_str!("../docs/epoll_get_underfd_hashmap_by_entrynum.md")]
+pub fn epoll_get_underfd_hashmap_by_entrynum(entrynum:u64) -> Result<HashMap<u32,u64>,threei::RetVal> {
+
+    let eptable = EPOLLTABLE.lock().unwrap();
+    match eptable.thisepolltable.get(&entrynum) {
+        Some(epollinfo) => Ok(epollinfo.underfdhashmap.clone()),
+        None => Err(threei::Errno::EBADF as u64),
+    }
+}
+
+
+
 /********************** TESTING HELPER FUNCTION **********************/
 
 #[doc(hidden)]
@@ -990,6 +1219,7 @@ pub fn get_virtual_epoll_wait_data(cageid:u64, epfd:u64) -> Result<HashMap<u32,H
 pub fn refresh() {
     FDTABLE.clear();
     FDTABLE.insert(threei::TESTING_CAGEID,vec![Option::None;FD_PER_PROCESS_MAX as usize]);
+    FDLIMIT.clear();
     let mut closehandlers = CLOSEHANDLERTABLE.lock().unwrap_or_else(|e| {
         CLOSEHANDLERTABLE.clear_poison();
         e.into_inner()
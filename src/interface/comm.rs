@@ -107,6 +107,18 @@ impl GenIpaddr {
             GenIpaddr::V6(v6ip) => v6ip.s6_addr == [0; 16],
         }
     }
+    // 127.0.0.0/8, ::1, or 127.0.0.0/8 mapped into IPv6
+    pub fn is_loopback(&self) -> bool {
+        match self {
+            GenIpaddr::V4(v4ip) => v4ip.s_addr.to_ne_bytes()[0] == 127,
+            GenIpaddr::V6(v6ip) => {
+                let mut loopback = [0u8; 16];
+                loopback[15] = 1;
+                let v4mapped = v6ip.s6_addr[..10] == [0; 10] && v6ip.s6_addr[10..13] == [0xff, 0xff, 127];
+                v6ip.s6_addr == loopback || v4mapped
+            }
+        }
+    }
     pub fn from_string(string: &str) -> Option<Self> {
         let v4candidate: Vec<&str> = string.split('.').collect();
         let v6candidate: Vec<&str> = string.split(':').collect();
@@ -231,6 +243,38 @@ pub struct SockaddrV6 {
     pub sin6_scope_id: u32,
 }
 
+// One interface address of getifaddrs. The sockaddrs are kept as the bytes of the host
+// struct (sockaddr_in, sockaddr_in6 or sockaddr_ll), which a wasm32 cage lays out the same way.
+#[derive(Debug, Clone, Default)]
+pub struct IfaddrEntry {
+    pub name: String,
+    pub flags: u32,
+    pub addr: Option<Vec<u8>>,
+    pub netmask: Option<Vec<u8>>,
+    pub ifu_addr: Option<Vec<u8>>, // broadcast address, or destination address with IFF_POINTOPOINT
+}
+
+// Host interface name -> the name the cages see it under, None hiding it from them
+static INTERFACE_POLICY: interface::RustLazyGlobal<interface::RustHashMap<String, Option<String>>> =
+    interface::RustLazyGlobal::new(interface::RustHashMap::new);
+
+// Renames the host interface for the cages, or hides it from them when cagename is None
+pub fn set_interface_policy(hostname: &str, cagename: Option<&str>) {
+    INTERFACE_POLICY.insert(hostname.to_string(), cagename.map(|name| name.to_string()));
+}
+
+pub fn clear_interface_policy() {
+    INTERFACE_POLICY.clear();
+}
+
+// The name a host interface goes by in the cages, None if it is hidden
+pub fn cage_interface_name(hostname: &str) -> Option<String> {
+    match INTERFACE_POLICY.get(hostname) {
+        Some(policy) => policy.clone(),
+        None => Some(hostname.to_string()),
+    }
+}
+
 // Implementations of select related FD_SET structure
 pub struct FdSet(libc::fd_set);
 
@@ -13,6 +13,9 @@ pub struct ShmFile {
     size: usize,
 }
 
+// keeps the names of backing files apart, IPC_PRIVATE segments all having key 0
+static SHM_BACKING_COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
 pub fn new_shm_backing(key: i32, size: usize) -> std::io::Result<ShmFile> {
     ShmFile::new(key, size)
 }
@@ -22,8 +25,9 @@ pub fn new_shm_backing(key: i32, size: usize) -> std::io::Result<ShmFile> {
 // which we can use to map shared across cages.
 impl ShmFile {
     fn new(key: i32, size: usize) -> std::io::Result<ShmFile> {
-        // open file "shm-#id"
-        let filename = format!("{}{}", "shm-", key);
+        // open file "shm-#id-#n"
+        let n = SHM_BACKING_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let filename = format!("{}{}-{}", "shm-", key, n);
         let f = OpenOptions::new()
             .read(true)
             .write(true)
@@ -91,6 +91,11 @@ pub fn cagetable_getref_opt(cageid: u64) -> Option<RustRfc<Cage>> {
     }
 }
 
+// number of cages running programs, the utility cage 0 is not one of them
+pub fn cagetable_count() -> usize {
+    (1..MAXCAGEID as usize).filter(|&cageid| unsafe { CAGE_TABLE[cageid].is_some() }).count()
+}
+
 pub fn cagetable_clear() {
     let mut exitvec = Vec::new();
     unsafe {
@@ -299,7 +304,13 @@ pub fn lind_sigismember(set: SigsetType, signum: i32) -> bool {
 pub fn lind_kill_from_id(cage_id: u64, sig: i32) {
     if let Some(cage) = cagetable_getref_opt(cage_id as u64) {
         let cage_main_thread_id = cage.main_threadid.load(RustAtomicOrdering::Relaxed);
-        assert!(cage_main_thread_id != 0);
+        if cage_main_thread_id == 0 {
+            // the cage hasn't established its main thread yet, so there is nobody to signal
+            // park the signal at entry 0, rustposix_thread_init delivers it once the thread exists
+            let pendingset = cage.pendingsigset.entry(0).or_insert(RustAtomicU64::new(0));
+            pendingset.fetch_or(lind_sigaddset(0, sig), RustAtomicOrdering::Relaxed);
+            return;
+        }
         lind_threadkill(cage_main_thread_id, sig);
     }
 }
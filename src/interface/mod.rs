@@ -2,11 +2,23 @@ mod comm;
 pub mod errnos;
 mod file;
 mod misc;
+mod namedsem;
+mod netns;
+mod pipe;
+mod posixshm;
+mod readiness;
 mod timer;
 pub mod types;
+mod unixsock;
 pub use comm::*;
 pub use errnos::*;
 pub use file::*;
 pub use misc::*;
+pub use namedsem::*;
+pub use netns::*;
+pub use pipe::*;
+pub use posixshm::*;
+pub use readiness::*;
 pub use timer::*;
 pub use types::*;
+pub use unixsock::*;
@@ -0,0 +1,60 @@
+// Named POSIX semaphores, which unrelated cages share through one global namespace.
+//
+// sem_open puts the semaphore into the cage's sem_table under the sem_t the cage handed in,
+// so sem_wait/sem_post and the rest treat it like an unnamed one. The name only leads to the
+// semaphore: sem_unlink forgets it, and the semaphore lives on until the last cage that has
+// it open closes it or exits.
+#![allow(dead_code)]
+
+use crate::interface;
+
+#[derive(Debug)]
+pub struct NamedSemaphore {
+    pub sem: interface::RustRfc<interface::RustSemaphore>,
+    pub uid: u32,
+    pub gid: u32,
+    pub mode: u32,
+}
+
+impl NamedSemaphore {
+    pub fn new(value: u32, uid: u32, gid: u32, mode: u32) -> NamedSemaphore {
+        NamedSemaphore {
+            sem: interface::RustRfc::new(interface::RustSemaphore::new(value, true)),
+            uid,
+            gid,
+            mode,
+        }
+    }
+}
+
+static NAMED_SEM_TABLE: interface::RustLazyGlobal<interface::RustHashMap<String, interface::RustRfc<NamedSemaphore>>> =
+    interface::RustLazyGlobal::new(interface::RustHashMap::new);
+
+/*
+ *   Looks up the semaphore called name, creating it from create if that is given and there
+ *   is none. Returns the semaphore and whether it was just created, or the errno: EEXIST if
+ *   exclusive is set and the semaphore exists, ENOENT if it doesn't and create is None.
+ */
+pub fn named_sem(name: &str, create: Option<NamedSemaphore>, exclusive: bool) -> Result<(interface::RustRfc<NamedSemaphore>, bool), i32> {
+    match NAMED_SEM_TABLE.entry(name.to_string()) {
+        interface::RustHashEntry::Occupied(entry) => {
+            if create.is_some() && exclusive {
+                return Err(libc::EEXIST);
+            }
+            Ok((entry.get().clone(), false))
+        }
+        interface::RustHashEntry::Vacant(entry) => match create {
+            Some(newsem) => {
+                let newsem = interface::RustRfc::new(newsem);
+                entry.insert(newsem.clone());
+                Ok((newsem, true))
+            }
+            None => Err(libc::ENOENT),
+        },
+    }
+}
+
+// Forgets the name, cages that have the semaphore open keep using it
+pub fn named_sem_remove(name: &str) -> bool {
+    NAMED_SEM_TABLE.remove(name).is_some()
+}
@@ -0,0 +1,141 @@
+// Virtual network namespaces for the kernel-backed AF_INET/AF_INET6 sockets of cages.
+//
+// A cage in a namespace sees its own port space: the ports it binds are only ports of the
+// namespace, while its host socket is bound to an ephemeral port (or to the host port the
+// namespace publishes the cage port on). Connections to a loopback address only reach
+// sockets bound in the same namespace, so any number of cages can run the same server
+// config side by side. Addresses other than loopback go out to the host network unchanged.
+#![allow(dead_code)]
+
+use crate::interface;
+use crate::interface::errnos::{syscall_error, Errno};
+use std::collections::HashMap;
+
+// Cages outside of any namespace use the host network directly
+pub const HOST_NETNS: u64 = 0;
+
+// Linux's default net.ipv4.ip_local_port_range, where a cage port is picked from when it
+// binds port 0 and the host port it was given is already taken in its namespace
+const EPHEMERAL_PORTS: std::ops::RangeInclusive<u16> = 32768..=60999;
+
+#[derive(Debug, Default)]
+struct NetNamespace {
+    // (socket type, cage port) -> host port of the socket bound there, 0 while it is being bound
+    bound: HashMap<(i32, u16), u16>,
+    // (socket type, cage port) -> host port a socket binding the cage port is bound to
+    published: HashMap<(i32, u16), u16>,
+}
+
+static NETNS_TABLE: interface::RustLazyGlobal<interface::RustHashMap<u64, NetNamespace>> =
+    interface::RustLazyGlobal::new(interface::RustHashMap::new);
+// host fd -> (namespace, socket type, cage port) of the sockets bound in a namespace
+static NETNS_BINDINGS: interface::RustLazyGlobal<interface::RustHashMap<u64, (u64, i32, u16)>> =
+    interface::RustLazyGlobal::new(interface::RustHashMap::new);
+static NEXT_NETNS_ID: interface::RustAtomicU64 = interface::RustAtomicU64::new(HOST_NETNS + 1);
+
+pub fn netns_create() -> u64 {
+    let nsid = NEXT_NETNS_ID.fetch_add(1, interface::RustAtomicOrdering::Relaxed);
+    NETNS_TABLE.insert(nsid, NetNamespace::default());
+    nsid
+}
+
+// Sockets still bound in the namespace keep their host ports, but are no longer found by it
+pub fn netns_remove(nsid: u64) {
+    NETNS_TABLE.remove(&nsid);
+    NETNS_BINDINGS.retain(|_, binding| binding.0 != nsid);
+}
+
+pub fn netns_exists(nsid: u64) -> bool {
+    nsid == HOST_NETNS || NETNS_TABLE.contains_key(&nsid)
+}
+
+// Has sockets of socktype binding cageport in the namespace bind hostport on the host, so
+// the service is reachable from outside there. Returns false if there is no such namespace.
+pub fn netns_publish_port(nsid: u64, socktype: i32, cageport: u16, hostport: u16) -> bool {
+    match NETNS_TABLE.get_mut(&nsid) {
+        Some(mut ns) => {
+            ns.published.insert((socktype, cageport), hostport);
+            true
+        }
+        None => false,
+    }
+}
+
+/*
+ *   Reserves cageport in the namespace for the host socket hostfd before it is bound, and
+ *   returns the host port to bind it to, 0 leaving the choice to the kernel. Binding port 0
+ *   reserves nothing yet, netns_bound picks the cage port once the host port is known.
+ *   Fails with EADDRINUSE if the port is taken in the namespace, and with EINVAL if the
+ *   socket is bound already.
+ */
+pub fn netns_reserve_port(nsid: u64, hostfd: u64, socktype: i32, cageport: u16) -> Result<u16, i32> {
+    let mut ns = match NETNS_TABLE.get_mut(&nsid) {
+        Some(ns) => ns,
+        None => return Err(syscall_error(Errno::ENETUNREACH, "bind", "the network namespace is gone")),
+    };
+    if NETNS_BINDINGS.contains_key(&hostfd) {
+        return Err(syscall_error(Errno::EINVAL, "bind", "the socket is already bound to an address"));
+    }
+    if cageport == 0 {
+        return Ok(0);
+    }
+    if ns.bound.contains_key(&(socktype, cageport)) {
+        return Err(syscall_error(Errno::EADDRINUSE, "bind", "the port is already in use in the network namespace"));
+    }
+    ns.bound.insert((socktype, cageport), 0);
+    NETNS_BINDINGS.insert(hostfd, (nsid, socktype, cageport));
+    Ok(ns.published.get(&(socktype, cageport)).copied().unwrap_or(0))
+}
+
+// Records the host port hostfd was bound to, and returns the cage port it is bound to
+pub fn netns_bound(nsid: u64, hostfd: u64, socktype: i32, hostport: u16) -> u16 {
+    let mut ns = match NETNS_TABLE.get_mut(&nsid) {
+        Some(ns) => ns,
+        None => return hostport,
+    };
+    let cageport = match NETNS_BINDINGS.get(&hostfd) {
+        Some(binding) => binding.2,
+        None => {
+            // bound to port 0: the cage sees the host port, unless that is taken in the namespace
+            let cageport = if ns.bound.contains_key(&(socktype, hostport)) {
+                match EPHEMERAL_PORTS.clone().find(|port| !ns.bound.contains_key(&(socktype, *port))) {
+                    Some(port) => port,
+                    None => return hostport,
+                }
+            } else {
+                hostport
+            };
+            NETNS_BINDINGS.insert(hostfd, (nsid, socktype, cageport));
+            cageport
+        }
+    };
+    ns.bound.insert((socktype, cageport), hostport);
+    cageport
+}
+
+// Frees the cage port of a host socket that is closed, or that failed to bind
+pub fn netns_release(hostfd: u64) {
+    if let Some((_, (nsid, socktype, cageport))) = NETNS_BINDINGS.remove(&hostfd) {
+        if let Some(mut ns) = NETNS_TABLE.get_mut(&nsid) {
+            ns.bound.remove(&(socktype, cageport));
+        }
+    }
+}
+
+// The host port of the socket bound to cageport in the namespace
+pub fn netns_host_port(nsid: u64, socktype: i32, cageport: u16) -> Option<u16> {
+    let ns = NETNS_TABLE.get(&nsid)?;
+    match ns.bound.get(&(socktype, cageport)) {
+        Some(0) | None => None,
+        Some(hostport) => Some(*hostport),
+    }
+}
+
+// The cage port of the socket bound to hostport in the namespace
+pub fn netns_cage_port(nsid: u64, socktype: i32, hostport: u16) -> Option<u16> {
+    let ns = NETNS_TABLE.get(&nsid)?;
+    ns.bound
+        .iter()
+        .find(|(key, port)| key.0 == socktype && **port == hostport)
+        .map(|(key, _)| key.1)
+}
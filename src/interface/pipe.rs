@@ -0,0 +1,222 @@
+// In-memory pipes used for FDKIND_IMPIPE file descriptors.
+#![allow(dead_code)]
+
+use crate::interface;
+use crate::interface::errnos::{syscall_error, Errno};
+use ringbuf::{Consumer, Producer, RingBuffer};
+use std::slice;
+
+// same defaults as Linux
+pub const PIPE_CAPACITY: usize = 65536;
+pub const PIPE_BUF: usize = 4096;
+// Linux hands out pipe space a page at a time
+const PIPE_PAGE_SIZE: usize = 4096;
+
+// Every pipe gets two ids, one per end, which are used as the underfd of the
+// fdtables entries. Read ends are even and write ends are odd so the close
+// handlers can tell which side just lost its last reference.
+pub static PIPE_TABLE: interface::RustLazyGlobal<
+    interface::RustHashMap<u64, interface::RustRfc<EmulatedPipe>>,
+> = interface::RustLazyGlobal::new(interface::RustHashMap::new);
+static NEXT_PIPE_ID: interface::RustAtomicU64 = interface::RustAtomicU64::new(0);
+
+struct PipeEnds {
+    producer: Producer<u8>,
+    consumer: Consumer<u8>,
+    read_open: bool,
+    write_open: bool,
+}
+
+impl PipeEnds {
+    // A write only goes ahead while a whole page of the pipe is free, as in Linux, and then
+    // fills up the partly used page at the end along with the free ones. Reading less than
+    // a page doesn't make room for the writer.
+    fn has_free_page(&self) -> bool {
+        self.producer.remaining() >= PIPE_PAGE_SIZE.min(self.producer.capacity())
+    }
+}
+
+pub struct EmulatedPipe {
+    ends: interface::Mutex<PipeEnds>,
+    cv: interface::Condvar, // notified whenever data moves or an end is closed
+}
+
+// creates a pipe and returns the ids of its (read, write) ends
+pub fn new_pipe(capacity: usize) -> (u64, u64) {
+    let readid = NEXT_PIPE_ID.fetch_add(2, interface::RustAtomicOrdering::Relaxed);
+    let writeid = readid + 1;
+    let pipe = interface::RustRfc::new(EmulatedPipe::new_with_capacity(capacity));
+    PIPE_TABLE.insert(readid, pipe.clone());
+    PIPE_TABLE.insert(writeid, pipe);
+    (readid, writeid)
+}
+
+pub fn get_pipe(endid: u64) -> Option<interface::RustRfc<EmulatedPipe>> {
+    PIPE_TABLE.get(&endid).map(|pipe| pipe.clone())
+}
+
+pub fn is_pipe_write_end(endid: u64) -> bool {
+    endid & 1 == 1
+}
+
+// called once the last fd referring to this end is gone
+pub fn close_pipe_end(endid: u64) {
+    if let Some((_, pipe)) = PIPE_TABLE.remove(&endid) {
+        if is_pipe_write_end(endid) {
+            pipe.close_write_end();
+        } else {
+            pipe.close_read_end();
+        }
+    }
+}
+
+// poll revents for one end of a pipe, with the same rules as Linux pipe_poll
+pub fn pipe_poll_events(endid: u64, events: i16) -> i16 {
+    let pipe = match get_pipe(endid) {
+        Some(pipe) => pipe,
+        None => return libc::POLLNVAL,
+    };
+    let mut revents = 0;
+    if is_pipe_write_end(endid) {
+        if events & libc::POLLOUT != 0 && pipe.check_select_write() {
+            revents |= libc::POLLOUT;
+        }
+        if pipe.is_read_closed() {
+            revents |= libc::POLLERR;
+        }
+    } else {
+        if events & libc::POLLIN != 0 && pipe.get_read_avail() > 0 {
+            revents |= libc::POLLIN;
+        }
+        if pipe.is_write_closed() {
+            revents |= libc::POLLHUP;
+        }
+    }
+    revents
+}
+
+impl EmulatedPipe {
+    pub fn new_with_capacity(capacity: usize) -> EmulatedPipe {
+        let (producer, consumer) = RingBuffer::<u8>::new(capacity).split();
+        EmulatedPipe {
+            ends: interface::Mutex::new(PipeEnds {
+                producer,
+                consumer,
+                read_open: true,
+                write_open: true,
+            }),
+            cv: interface::Condvar::new(),
+        }
+    }
+
+    // wakes up blocked readers/writers as well as anyone polling the pipe
+    fn wake(&self) {
+        self.cv.notify_all();
+        interface::notify_readiness();
+    }
+
+    pub fn close_write_end(&self) {
+        self.ends.lock().write_open = false;
+        self.wake();
+    }
+
+    pub fn close_read_end(&self) {
+        self.ends.lock().read_open = false;
+        self.wake();
+    }
+
+    // number of bytes that can be read without blocking
+    pub fn get_read_avail(&self) -> usize {
+        self.ends.lock().consumer.len()
+    }
+
+    // readable when there is data, or when every writer is gone so read returns EOF
+    pub fn check_select_read(&self) -> bool {
+        let ends = self.ends.lock();
+        !ends.consumer.is_empty() || !ends.write_open
+    }
+
+    // writable when a page is free, or when every reader is gone so write fails with EPIPE
+    pub fn check_select_write(&self) -> bool {
+        let ends = self.ends.lock();
+        ends.has_free_page() || !ends.read_open
+    }
+
+    pub fn is_read_closed(&self) -> bool {
+        !self.ends.lock().read_open
+    }
+
+    pub fn is_write_closed(&self) -> bool {
+        !self.ends.lock().write_open
+    }
+
+    /*
+     *   Writes up to length bytes from ptr into the pipe, waiting for a free page whenever
+     *   there is none. Writes of at most PIPE_BUF bytes are atomic; larger ones may be split
+     *   up while blocking.
+     *   Returns EPIPE once no read end is left, the caller is in charge of raising SIGPIPE.
+     */
+    pub fn write_to_pipe(&self, ptr: *const u8, length: usize, nonblocking: bool) -> i32 {
+        if length == 0 {
+            return 0;
+        }
+        let buf = unsafe { slice::from_raw_parts(ptr, length) };
+        let mut written = 0;
+        let mut ends = self.ends.lock();
+
+        loop {
+            if !ends.read_open {
+                if written > 0 {
+                    break;
+                }
+                return syscall_error(Errno::EPIPE, "write", "read end of the pipe is closed");
+            }
+            if written == length {
+                break;
+            }
+
+            let fits = length > PIPE_BUF || ends.producer.remaining() >= length;
+            if ends.has_free_page() && fits {
+                written += ends.producer.push_slice(&buf[written..]);
+                self.wake();
+                continue;
+            }
+
+            if nonblocking {
+                if written > 0 {
+                    break;
+                }
+                return syscall_error(Errno::EAGAIN, "write", "there is no room in the pipe");
+            }
+            self.cv.wait(&mut ends);
+        }
+        written as i32
+    }
+
+    /*
+     *   Reads up to length bytes into ptr. Returns 0 (EOF) once the pipe is empty
+     *   and every write end has been closed.
+     */
+    pub fn read_from_pipe(&self, ptr: *mut u8, length: usize, nonblocking: bool) -> i32 {
+        if length == 0 {
+            return 0;
+        }
+        let buf = unsafe { slice::from_raw_parts_mut(ptr, length) };
+        let mut ends = self.ends.lock();
+
+        loop {
+            if !ends.consumer.is_empty() {
+                let bytes_read = ends.consumer.pop_slice(buf);
+                self.wake();
+                return bytes_read as i32;
+            }
+            if !ends.write_open {
+                return 0;
+            }
+            if nonblocking {
+                return syscall_error(Errno::EAGAIN, "read", "there is no data in the pipe");
+            }
+            self.cv.wait(&mut ends);
+        }
+    }
+}
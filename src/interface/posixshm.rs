@@ -0,0 +1,125 @@
+// POSIX shared memory objects, the /dev/shm that cages see.
+//
+// An object is a host memfd kept under its name. Every shm_open opens the memfd anew
+// through /proc/self/fd, so each open has its own offset and access mode like a file on
+// tmpfs, and that host fd is the underfd of the FDKIND_IMSHM fd handed to the cage. Unlinking
+// only forgets the name: open fds and MAP_SHARED mappings keep the memory alive until the
+// host drops the last of them.
+//
+// The memfd itself stays at 0600 so lind can always reopen it. The mode the cages see is
+// kept on the object, and each open holds on to its object so fstat and fchmod find it.
+#![allow(dead_code)]
+
+use crate::interface;
+use std::ffi::CString;
+
+// the longest name /dev/shm takes, NAME_MAX
+pub const SHM_NAME_MAX: usize = 255;
+
+pub struct ShmObject {
+    memfd: i32,
+    mode: interface::RustAtomicU32,
+}
+
+impl ShmObject {
+    // The memfd behind the object, open for as long as the object is held
+    pub fn fd(&self) -> i32 {
+        self.memfd
+    }
+
+    // The permission bits the cages see, without the file type
+    pub fn mode(&self) -> u32 {
+        self.mode.load(interface::RustAtomicOrdering::Relaxed)
+    }
+
+    pub fn set_mode(&self, mode: u32) {
+        self.mode.store(mode & 0o7777, interface::RustAtomicOrdering::Relaxed);
+    }
+}
+
+impl Drop for ShmObject {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.memfd) };
+    }
+}
+
+static SHM_OBJECT_TABLE: interface::RustLazyGlobal<interface::RustHashMap<String, interface::RustRfc<ShmObject>>> =
+    interface::RustLazyGlobal::new(interface::RustHashMap::new);
+
+// The host fd of each open -> the object it is an open of
+static SHM_OPEN_TABLE: interface::RustLazyGlobal<interface::RustHashMap<u64, interface::RustRfc<ShmObject>>> =
+    interface::RustLazyGlobal::new(interface::RustHashMap::new);
+
+// The name of the object in /dev/shm, as glibc takes it: leading slashes are dropped and
+// what remains has to be a single path component. None if the name is not valid.
+pub fn shm_object_name(name: &str) -> Option<&str> {
+    let name = name.trim_start_matches('/');
+    if name.is_empty() || name.len() > SHM_NAME_MAX || name.contains('/') || name == "." || name == ".." {
+        return None;
+    }
+    Some(name)
+}
+
+/*
+ *   Looks up the object called name, creating it with mode create if that is given and
+ *   there is none. Returns the object and whether it was just created, or the errno: EEXIST
+ *   if exclusive is set and the object exists, ENOENT if it doesn't and create is None.
+ */
+pub fn shm_object(name: &str, create: Option<u32>, exclusive: bool) -> Result<(interface::RustRfc<ShmObject>, bool), i32> {
+    match SHM_OBJECT_TABLE.entry(name.to_string()) {
+        interface::RustHashEntry::Occupied(entry) => {
+            if create.is_some() && exclusive {
+                return Err(libc::EEXIST);
+            }
+            Ok((entry.get().clone(), false))
+        }
+        interface::RustHashEntry::Vacant(entry) => {
+            let mode = match create {
+                Some(mode) => mode,
+                None => return Err(libc::ENOENT),
+            };
+            let cname = CString::new(name).map_err(|_| libc::EINVAL)?;
+            let memfd = unsafe { libc::memfd_create(cname.as_ptr(), libc::MFD_CLOEXEC) };
+            if memfd < 0 {
+                return Err(interface::get_errno());
+            }
+            unsafe { libc::fchmod(memfd, 0o600) };
+            let object = interface::RustRfc::new(ShmObject {
+                memfd,
+                mode: interface::RustAtomicU32::new(mode & 0o7777),
+            });
+            entry.insert(object.clone());
+            Ok((object, true))
+        }
+    }
+}
+
+// Forgets the name, the object lives on in the fds and mappings made of it
+pub fn shm_remove(name: &str) -> bool {
+    SHM_OBJECT_TABLE.remove(name).is_some()
+}
+
+// Forgets the name if it still refers to object, for undoing a create that failed later on
+pub fn shm_remove_object(name: &str, object: &interface::RustRfc<ShmObject>) {
+    SHM_OBJECT_TABLE.remove_if(name, |_, found| interface::RustRfc::ptr_eq(found, object));
+}
+
+// Records that the host fd hostfd is an open of object
+pub fn shm_open_insert(hostfd: u64, object: interface::RustRfc<ShmObject>) {
+    SHM_OPEN_TABLE.insert(hostfd, object);
+}
+
+// The object the host fd of an open refers to
+pub fn shm_open_object(hostfd: u64) -> Option<interface::RustRfc<ShmObject>> {
+    SHM_OPEN_TABLE.get(&hostfd).map(|entry| entry.value().clone())
+}
+
+// Called once the open is closed, the object goes once nothing else holds it
+pub fn shm_open_remove(hostfd: u64) {
+    SHM_OPEN_TABLE.remove(&hostfd);
+}
+
+// A shm fd refers to a regular file in memory, which never blocks
+pub fn shm_poll_events(_underfd: u64, events: i16) -> i16 {
+    events & (libc::POLLIN | libc::POLLRDNORM | libc::POLLOUT | libc::POLLWRNORM)
+}
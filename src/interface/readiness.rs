@@ -0,0 +1,59 @@
+// Readiness of fds that are not backed by the kernel, shared by poll, select and epoll_wait.
+#![allow(dead_code)]
+
+use crate::interface;
+
+// Reports the poll revents of one object of an fd kind, given its underfd and the
+// requested events. Like Linux's f_op->poll, POLLERR/POLLHUP are reported unasked.
+pub type PollHandler = fn(u64, i16) -> i16;
+
+static POLL_HANDLER_TABLE: interface::RustLazyGlobal<interface::RustHashMap<u32, PollHandler>> =
+    interface::RustLazyGlobal::new(interface::RustHashMap::new);
+
+// Bumped whenever an in-memory object may have become ready, so waiters can sleep
+// until something changes instead of rechecking every fd in a loop
+static READINESS_SEQ: interface::RustLazyGlobal<interface::Mutex<u64>> =
+    interface::RustLazyGlobal::new(|| interface::Mutex::new(0));
+static READINESS_CV: interface::RustLazyGlobal<interface::Condvar> =
+    interface::RustLazyGlobal::new(interface::Condvar::new);
+
+pub fn register_poll_handler(fdkind: u32, handler: PollHandler) {
+    POLL_HANDLER_TABLE.insert(fdkind, handler);
+}
+
+// None means the fd kind has no handler, i.e. its readiness comes from the kernel
+pub fn poll_fdkind(fdkind: u32, underfd: u64, events: i16) -> Option<i16> {
+    let handler = *POLL_HANDLER_TABLE.get(&fdkind)?;
+    Some(handler(underfd, events))
+}
+
+pub fn has_poll_handler(fdkind: u32) -> bool {
+    POLL_HANDLER_TABLE.contains_key(&fdkind)
+}
+
+pub fn notify_readiness() {
+    *READINESS_SEQ.lock() += 1;
+    READINESS_CV.notify_all();
+}
+
+// Taken before checking the fds, then handed to wait_readiness_change
+pub fn readiness_seq() -> u64 {
+    *READINESS_SEQ.lock()
+}
+
+// Blocks until notify_readiness has been called since seen was taken, or until the
+// timeout runs out (None waits for as long as it takes)
+pub fn wait_readiness_change(seen: u64, timeout: Option<interface::RustDuration>) {
+    let deadline = timeout.map(|timeout| interface::RustInstant::now() + timeout);
+    let mut seq = READINESS_SEQ.lock();
+    while *seq == seen {
+        match deadline {
+            Some(deadline) => {
+                if READINESS_CV.wait_until(&mut seq, deadline).timed_out() {
+                    return;
+                }
+            }
+            None => READINESS_CV.wait(&mut seq),
+        }
+    }
+}
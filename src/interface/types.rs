@@ -55,6 +55,7 @@ pub struct StatData {
 }
 
 //R Limit for getrlimit system call
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 #[repr(C)]
 pub struct Rlimit {
     pub rlim_cur: u64,
@@ -98,6 +99,7 @@ pub struct SockaddrDummy {
     pub _sa_data: [u16; 14],
 }
 
+#[derive(Debug, Default, Copy, Clone)]
 #[repr(C)]
 pub struct TimeVal {
     pub tv_sec: i64,
@@ -110,6 +112,29 @@ pub struct ITimerVal {
     pub it_value: TimeVal,
 }
 
+//Resource usage reported by wait4, laid out as struct rusage in a wasm32 cage where long is 32 bits
+//None of it is accounted for cages yet so it all stays zero
+#[derive(Debug, Default, Copy, Clone)]
+#[repr(C)]
+pub struct Rusage {
+    pub ru_utime: TimeVal,
+    pub ru_stime: TimeVal,
+    pub ru_maxrss: i32,
+    pub ru_ixrss: i32,
+    pub ru_idrss: i32,
+    pub ru_isrss: i32,
+    pub ru_minflt: i32,
+    pub ru_majflt: i32,
+    pub ru_nswap: i32,
+    pub ru_inblock: i32,
+    pub ru_oublock: i32,
+    pub ru_msgsnd: i32,
+    pub ru_msgrcv: i32,
+    pub ru_nsignals: i32,
+    pub ru_nvcsw: i32,
+    pub ru_nivcsw: i32,
+}
+
 #[repr(C)]
 pub struct TimeSpec {
     pub tv_sec: i64,
@@ -152,6 +177,102 @@ pub struct ShmidsStruct {
     pub shm_nattch: u32,
 }
 
+// struct shminfo of shmctl(IPC_INFO), the unsigned longs being 32 bits in a wasm32 cage
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+pub struct ShminfoStruct {
+    pub shmmax: u32,
+    pub shmmin: u32,
+    pub shmmni: u32,
+    pub shmseg: u32,
+    pub shmall: u32,
+    pub __unused: [u32; 4],
+}
+
+// struct shm_info of shmctl(SHM_INFO), sizes counted in pages
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+pub struct ShmInfoStruct {
+    pub used_ids: i32,
+    pub shm_tot: u32,
+    pub shm_rss: u32,
+    pub shm_swp: u32,
+    pub swap_attempts: u32,
+    pub swap_successes: u32,
+}
+
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+pub struct MsqidsStruct {
+    pub msg_perm: IpcPermStruct,
+    pub msg_stime: isize,
+    pub msg_rtime: isize,
+    pub msg_ctime: isize,
+    pub msg_cbytes: u32,
+    pub msg_qnum: u32,
+    pub msg_qbytes: u32,
+    pub msg_lspid: u32,
+    pub msg_lrpid: u32,
+}
+
+// struct msginfo of msgctl(IPC_INFO/MSG_INFO)
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+pub struct MsginfoStruct {
+    pub msgpool: i32,
+    pub msgmap: i32,
+    pub msgmax: i32,
+    pub msgmnb: i32,
+    pub msgmni: i32,
+    pub msgssz: i32,
+    pub msgtql: i32,
+    pub msgseg: u16,
+}
+
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+pub struct SemidsStruct {
+    pub sem_perm: IpcPermStruct,
+    pub sem_otime: isize,
+    pub sem_ctime: isize,
+    pub sem_nsems: u32,
+    pub __unused3: u32,
+    pub __unused4: u32,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(C)]
+pub struct SembufStruct {
+    pub sem_num: u16,
+    pub sem_op: i16,
+    pub sem_flg: i16,
+}
+
+// struct seminfo of semctl(IPC_INFO/SEM_INFO)
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+pub struct SeminfoStruct {
+    pub semmap: i32,
+    pub semmni: i32,
+    pub semmns: i32,
+    pub semmnu: i32,
+    pub semmsl: i32,
+    pub semopm: i32,
+    pub semume: i32,
+    pub semusz: i32,
+    pub semvmx: i32,
+    pub semaem: i32,
+}
+
+// union semun, the optional last argument of semctl. Which member is meant depends on cmd.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub union SemunUnion {
+    pub val: i32,
+    pub buf: *mut SemidsStruct,
+    pub array: *mut u16,
+}
+
 pub type SigsetType = u64;
 
 pub type IovecStruct = libc::iovec;
@@ -365,6 +486,18 @@ pub fn get_shmidstruct<'a>(generic_argument: u64) -> Result<&'a mut ShmidsStruct
     ));
 }
 
+pub fn get_msqidstruct<'a>(generic_argument: u64) -> Result<&'a mut MsqidsStruct, i32> {
+    let pointer = generic_argument as *mut MsqidsStruct;
+    if !pointer.is_null() {
+        return Ok(unsafe { &mut *pointer });
+    }
+    return Err(syscall_error(
+        Errno::EFAULT,
+        "dispatcher",
+        "input data not valid",
+    ));
+}
+
 pub fn get_ioctlptrunion<'a>(generic_argument: u64) -> Result<&'a mut u8, i32> {
     let pointer = generic_argument as *mut u8;
     if !pointer.is_null() {
@@ -431,8 +564,17 @@ pub fn get_sockaddr(generic_argument: u64, addrlen: u32) -> Result<interface::Ge
                         "input length incorrect for family of sockaddr",
                     ));
                 }
-                let unix_ptr = pointer as *const interface::SockaddrUnix;
-                return Ok(interface::GenSockaddr::Unix(unsafe { *unix_ptr }));
+                // only addrlen bytes belong to the address, abstract names are not NUL
+                // terminated so whatever follows them must not be picked up
+                let mut unixaddr = interface::SockaddrUnix::default();
+                unsafe {
+                    std::ptr::copy_nonoverlapping(
+                        pointer as *const u8,
+                        &mut unixaddr as *mut interface::SockaddrUnix as *mut u8,
+                        addrlen as usize,
+                    );
+                }
+                return Ok(interface::GenSockaddr::Unix(unixaddr));
             }
             /*AF_INET*/
             2 => {
@@ -803,14 +945,484 @@ pub fn get_constsigsett<'a>(generic_argument: u64) -> Result<Option<&'a SigsetTy
     }
 }
 
-pub fn get_iovecstruct(generic_argument: u64) -> Result<*const interface::IovecStruct, i32> {
-    let data = generic_argument as *const interface::IovecStruct;
-    if !data.is_null() {
-        return Ok(data);
+// struct iovec as a wasm32 cage lays it out, with the base as an offset into its memory
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+pub struct WasmIovecStruct {
+    pub iov_base: u32,
+    pub iov_len: u32,
+}
+
+// Copies the cage's array of iovcnt iovecs at generic_argument (an offset into the cage's
+// memory) into host iovecs, translating each base by the start of that memory
+pub fn get_iovecstruct(start_address: u64, generic_argument: u64, iovcnt: i32) -> Result<Vec<interface::IovecStruct>, i32> {
+    if iovcnt < 0 || iovcnt > UIO_MAXIOV {
+        return Err(syscall_error(
+            Errno::EINVAL,
+            "dispatcher",
+            "iovcnt is out of range",
+        ));
     }
-    return Err(syscall_error(
-        Errno::EFAULT,
-        "dispatcher",
-        "input data not valid",
-    ));
+    if iovcnt == 0 {
+        return Ok(Vec::new());
+    }
+    if generic_argument == 0 {
+        return Err(syscall_error(
+            Errno::EFAULT,
+            "dispatcher",
+            "input data not valid",
+        ));
+    }
+    let wasmiovs = unsafe {
+        std::slice::from_raw_parts((start_address + generic_argument) as *const WasmIovecStruct, iovcnt as usize)
+    };
+    Ok(wasmiovs
+        .iter()
+        .map(|iov| interface::IovecStruct {
+            iov_base: (start_address + iov.iov_base as u64) as *mut c_void,
+            iov_len: iov.iov_len as usize,
+        })
+        .collect())
+}
+
+// struct msghdr as a wasm32 cage lays it out, with pointers as offsets into its memory
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+pub struct WasmMsghdr {
+    pub msg_name: u32,
+    pub msg_namelen: u32,
+    pub msg_iov: u32,
+    pub msg_iovlen: i32,
+    pub msg_control: u32,
+    pub msg_controllen: u32,
+    pub msg_flags: i32,
+}
+
+// a wasm32 struct cmsghdr is three 32 bit fields, and control messages are 4 byte aligned
+const WASM_CMSG_HDRLEN: usize = 12;
+// how much larger one control message can get in the host layout, header and padding
+const HOST_CMSG_GROWTH: usize = 8;
+
+// rounds len up to the 4 byte alignment of wasm32 structs
+fn wasm_align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+// A cage's msghdr translated for the host. It owns the iovecs and the control buffer hdr
+// points to, the control messages being converted between the wasm32 and host layouts.
+pub struct MsgHdrBuf {
+    pub hdr: libc::msghdr,
+    iovecs: Vec<interface::IovecStruct>,
+    control: Vec<u64>, // u64s so the host cmsghdrs are aligned
+}
+
+/*
+ *   Translates the cage's msghdr at generic_argument for sendmsg/recvmsg. The name and the
+ *   iovec buffers stay in the cage's memory. When sending, the control messages are converted
+ *   to the host layout; when receiving, a host control buffer is set aside that has room for
+ *   everything the cage's buffer can take, see copy_out_msghdr.
+ */
+pub fn get_msghdr(start_address: u64, generic_argument: u64, receiving: bool) -> Result<MsgHdrBuf, i32> {
+    if generic_argument == 0 {
+        return Err(syscall_error(
+            Errno::EFAULT,
+            "dispatcher",
+            "input data not valid",
+        ));
+    }
+    let wasmhdr = unsafe { *((start_address + generic_argument) as *const WasmMsghdr) };
+    if wasmhdr.msg_iovlen < 0 || wasmhdr.msg_iovlen > UIO_MAXIOV {
+        return Err(syscall_error(
+            Errno::EMSGSIZE,
+            "dispatcher",
+            "msg_iovlen is out of range",
+        ));
+    }
+    let iovecs = get_iovecstruct(start_address, wasmhdr.msg_iov as u64, wasmhdr.msg_iovlen)?;
+
+    let wasmcontrol: &[u8] = if wasmhdr.msg_control == 0 || wasmhdr.msg_controllen == 0 {
+        &[]
+    } else {
+        unsafe {
+            std::slice::from_raw_parts(
+                (start_address + wasmhdr.msg_control as u64) as *const u8,
+                wasmhdr.msg_controllen as usize,
+            )
+        }
+    };
+    let (mut control, controllen) = if receiving {
+        // at most one SCM_CREDENTIALS and one SCM_RIGHTS message come back
+        let controllen = if wasmcontrol.is_empty() { 0 } else { wasmcontrol.len() + 2 * HOST_CMSG_GROWTH };
+        (vec![0u64; (controllen + 7) / 8], controllen)
+    } else {
+        let hostcontrol = host_cmsgs(wasmcontrol)?;
+        let mut control = vec![0u64; (hostcontrol.len() + 7) / 8];
+        unsafe {
+            std::ptr::copy_nonoverlapping(hostcontrol.as_ptr(), control.as_mut_ptr() as *mut u8, hostcontrol.len());
+        }
+        (control, hostcontrol.len())
+    };
+
+    let mut hdr: libc::msghdr = unsafe { std::mem::zeroed() };
+    if wasmhdr.msg_name != 0 {
+        hdr.msg_name = (start_address + wasmhdr.msg_name as u64) as *mut c_void;
+        hdr.msg_namelen = wasmhdr.msg_namelen;
+    }
+    hdr.msg_iov = iovecs.as_ptr() as *mut interface::IovecStruct;
+    hdr.msg_iovlen = iovecs.len() as _;
+    if controllen > 0 {
+        hdr.msg_control = control.as_mut_ptr() as *mut c_void;
+        hdr.msg_controllen = controllen as _;
+    }
+    Ok(MsgHdrBuf { hdr, iovecs, control })
+}
+
+// Converts wasm32 control messages to the host layout
+fn host_cmsgs(wasmcontrol: &[u8]) -> Result<Vec<u8>, i32> {
+    let field = |at: usize| u32::from_ne_bytes([wasmcontrol[at], wasmcontrol[at + 1], wasmcontrol[at + 2], wasmcontrol[at + 3]]);
+    let hostheaderlen = unsafe { libc::CMSG_LEN(0) } as usize;
+    let mut hostcontrol = Vec::new();
+    let mut offset = 0;
+    while offset + WASM_CMSG_HDRLEN <= wasmcontrol.len() {
+        let cmsg_len = field(offset) as usize;
+        if cmsg_len < WASM_CMSG_HDRLEN || offset + cmsg_len > wasmcontrol.len() {
+            return Err(syscall_error(
+                Errno::EINVAL,
+                "dispatcher",
+                "a control message has a bad length",
+            ));
+        }
+        let data = &wasmcontrol[offset + WASM_CMSG_HDRLEN..offset + cmsg_len];
+        let mut header: libc::cmsghdr = unsafe { std::mem::zeroed() };
+        header.cmsg_len = unsafe { libc::CMSG_LEN(data.len() as u32) } as _;
+        header.cmsg_level = field(offset + 4) as i32;
+        header.cmsg_type = field(offset + 8) as i32;
+
+        let start = hostcontrol.len();
+        hostcontrol.resize(start + unsafe { libc::CMSG_SPACE(data.len() as u32) } as usize, 0);
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                &header as *const libc::cmsghdr as *const u8,
+                hostcontrol[start..].as_mut_ptr(),
+                size_of::<libc::cmsghdr>(),
+            );
+        }
+        hostcontrol[start + hostheaderlen..start + hostheaderlen + data.len()].copy_from_slice(data);
+        offset += wasm_align(cmsg_len);
+    }
+    Ok(hostcontrol)
+}
+
+/*
+ *   Copies the results of recvmsg back into the cage's msghdr: msg_namelen, msg_flags and the
+ *   control messages, converted to the wasm32 layout. What doesn't fit into the cage's control
+ *   buffer sets MSG_CTRUNC. The fds of an SCM_RIGHTS message that were cut off are returned so
+ *   the caller can close them again.
+ */
+pub fn copy_out_msghdr(start_address: u64, generic_argument: u64, msg: &MsgHdrBuf) -> Vec<i32> {
+    let wasmhdr = unsafe { &mut *((start_address + generic_argument) as *mut WasmMsghdr) };
+    wasmhdr.msg_namelen = msg.hdr.msg_namelen;
+    let mut flags = msg.hdr.msg_flags;
+
+    let capacity = if wasmhdr.msg_control == 0 { 0 } else { wasmhdr.msg_controllen as usize };
+    let wasmcontrol = (start_address + wasmhdr.msg_control as u64) as *mut u8;
+    let hostcontrol = if msg.hdr.msg_control.is_null() {
+        &[]
+    } else {
+        unsafe { std::slice::from_raw_parts(msg.control.as_ptr() as *const u8, msg.hdr.msg_controllen as usize) }
+    };
+    let hostheaderlen = unsafe { libc::CMSG_LEN(0) } as usize;
+    let mut cutoff = Vec::new();
+    let mut used = 0;
+    let mut offset = 0;
+    while offset + hostheaderlen <= hostcontrol.len() {
+        let header = unsafe { (hostcontrol[offset..].as_ptr() as *const libc::cmsghdr).read_unaligned() };
+        let cmsg_len = interface::rust_min(header.cmsg_len as usize, hostcontrol.len() - offset);
+        if cmsg_len < hostheaderlen {
+            break;
+        }
+        let data = &hostcontrol[offset + hostheaderlen..offset + cmsg_len];
+        offset += unsafe { libc::CMSG_SPACE((cmsg_len - hostheaderlen) as u32) } as usize;
+
+        let room = capacity.saturating_sub(used + WASM_CMSG_HDRLEN);
+        let is_rights = header.cmsg_level == libc::SOL_SOCKET && header.cmsg_type == libc::SCM_RIGHTS;
+        let fit = if is_rights {
+            let fit = interface::rust_min(room, data.len()) / size_of::<i32>() * size_of::<i32>();
+            for fd in data[fit..].chunks_exact(size_of::<i32>()) {
+                cutoff.push(i32::from_ne_bytes([fd[0], fd[1], fd[2], fd[3]]));
+            }
+            fit
+        } else if data.len() <= room {
+            data.len()
+        } else {
+            0
+        };
+        if fit < data.len() {
+            flags |= libc::MSG_CTRUNC;
+            if fit == 0 {
+                continue;
+            }
+        }
+
+        let wasmheader = [(WASM_CMSG_HDRLEN + fit) as u32, header.cmsg_level as u32, header.cmsg_type as u32];
+        unsafe {
+            std::ptr::copy_nonoverlapping(wasmheader.as_ptr() as *const u8, wasmcontrol.add(used), WASM_CMSG_HDRLEN);
+            std::ptr::copy_nonoverlapping(data.as_ptr(), wasmcontrol.add(used + WASM_CMSG_HDRLEN), fit);
+        }
+        used = interface::rust_min(used + wasm_align(WASM_CMSG_HDRLEN + fit), capacity);
+    }
+    wasmhdr.msg_controllen = used as u32;
+    wasmhdr.msg_flags = flags;
+    cutoff
+}
+
+// struct sock_fprog as a wasm32 cage lays it out, filter being an offset into its memory
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+pub struct WasmSockFprog {
+    pub len: u16,
+    pub _pad: u16,
+    pub filter: u32,
+}
+
+// Options the libc crate has no constant for
+const SO_MAX_PACING_RATE: i32 = 47;
+const SO_TXTIME: i32 = 61;
+const SO_RCVTIMEO_NEW: i32 = 66;
+const SO_SNDTIMEO_NEW: i32 = 67;
+
+// How a wasm32 cage lays out the value of a socket option compared to the host
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum SockoptLayout {
+    // an int, which is all the kernel reads of these options; a longer value is refused
+    Int,
+    // a struct made of fixed size fields only, a string, or bytes, all alike on both
+    Same,
+    // struct sock_fprog, whose filter is a pointer
+    Fprog,
+    // struct group_req, group_source_req and group_filter, which start with a u32 and a
+    // struct sockaddr_storage. Its unsigned long member aligns the sockaddr_storage to 8
+    // bytes on the host and to 4 in a cage, so the host has 4 bytes of padding before it.
+    GroupReq,
+}
+
+pub fn sockopt_layout(level: i32, optname: i32) -> SockoptLayout {
+    match (level, optname) {
+        (libc::SOL_SOCKET, libc::SO_ATTACH_FILTER | libc::SO_ATTACH_REUSEPORT_CBPF) => SockoptLayout::Fprog,
+        // struct linger, struct ucred and struct so_timestamping are ints; the cage's
+        // struct timeval has 64 bit fields like TimeVal; sock_txtime is a clockid_t and a u32
+        (
+            libc::SOL_SOCKET,
+            libc::SO_LINGER
+            | libc::SO_PEERCRED
+            | libc::SO_RCVTIMEO
+            | libc::SO_SNDTIMEO
+            | SO_RCVTIMEO_NEW
+            | SO_SNDTIMEO_NEW
+            | libc::SO_TIMESTAMPING
+            | SO_TXTIME
+            | SO_MAX_PACING_RATE
+            | libc::SO_BINDTODEVICE,
+        ) => SockoptLayout::Same,
+        // struct in_addr, ip_mreq, ip_mreqn, ip_mreq_source and ip_msfilter are all u32s
+        (
+            libc::IPPROTO_IP,
+            libc::IP_OPTIONS
+            | libc::IP_MULTICAST_IF
+            | libc::IP_ADD_MEMBERSHIP
+            | libc::IP_DROP_MEMBERSHIP
+            | libc::IP_ADD_SOURCE_MEMBERSHIP
+            | libc::IP_DROP_SOURCE_MEMBERSHIP
+            | libc::IP_BLOCK_SOURCE
+            | libc::IP_UNBLOCK_SOURCE
+            | libc::IP_MSFILTER,
+        ) => SockoptLayout::Same,
+        // struct ipv6_mreq and in6_pktinfo are an in6_addr and an int, the others are
+        // a sockaddr_in6 or extension headers as bytes
+        (
+            libc::IPPROTO_IPV6,
+            libc::IPV6_ADD_MEMBERSHIP
+            | libc::IPV6_DROP_MEMBERSHIP
+            | libc::IPV6_PKTINFO
+            | libc::IPV6_NEXTHOP
+            | libc::IPV6_HOPOPTS
+            | libc::IPV6_RTHDR
+            | libc::IPV6_DSTOPTS,
+        ) => SockoptLayout::Same,
+        (
+            libc::IPPROTO_IP | libc::IPPROTO_IPV6,
+            libc::MCAST_JOIN_GROUP
+            | libc::MCAST_LEAVE_GROUP
+            | libc::MCAST_JOIN_SOURCE_GROUP
+            | libc::MCAST_LEAVE_SOURCE_GROUP
+            | libc::MCAST_BLOCK_SOURCE
+            | libc::MCAST_UNBLOCK_SOURCE
+            | libc::MCAST_MSFILTER,
+        ) => SockoptLayout::GroupReq,
+        // struct tcp_info and tcp_repair_window are u8s, u32s and u64s, which wasm32
+        // aligns like the host; tcp_md5sig is a sockaddr_storage followed by u8s, a u16,
+        // an int and the key, with no padding on either; the rest are strings or bytes
+        (
+            libc::IPPROTO_TCP,
+            libc::TCP_INFO
+            | libc::TCP_CONGESTION
+            | libc::TCP_ULP
+            | libc::TCP_MD5SIG
+            | libc::TCP_MD5SIG_EXT
+            | libc::TCP_REPAIR_OPTIONS
+            | libc::TCP_REPAIR_WINDOW
+            | libc::TCP_FASTOPEN_KEY,
+        ) => SockoptLayout::Same,
+        _ => SockoptLayout::Int,
+    }
+}
+
+/*
+ *   Copies the optval of setsockopt out of the cage, translating the options whose layout
+ *   differs between wasm32 and the host (see sockopt_layout). An option that isn't known
+ *   to take anything but an int can't be given a longer value, as it may well be a struct
+ *   that would need translating too.
+ */
+pub fn get_sockoptval(start_address: u64, generic_argument: u64, optlen: u32, level: i32, optname: i32) -> Result<Vec<u8>, i32> {
+    if optlen == 0 {
+        return Ok(Vec::new());
+    }
+    if generic_argument == 0 {
+        return Err(syscall_error(
+            Errno::EFAULT,
+            "dispatcher",
+            "input data not valid",
+        ));
+    }
+    let wasmval = unsafe { std::slice::from_raw_parts((start_address + generic_argument) as *const u8, optlen as usize) };
+    match sockopt_layout(level, optname) {
+        SockoptLayout::Int => {
+            if wasmval.len() > size_of::<i32>() {
+                return Err(syscall_error(
+                    Errno::EINVAL,
+                    "setsockopt",
+                    "optlen is larger than the int the option takes",
+                ));
+            }
+            Ok(wasmval.to_vec())
+        }
+        SockoptLayout::Same => Ok(wasmval.to_vec()),
+        SockoptLayout::Fprog => {
+            if wasmval.len() < size_of::<WasmSockFprog>() {
+                return Err(syscall_error(
+                    Errno::EINVAL,
+                    "setsockopt",
+                    "optlen is too small for struct sock_fprog",
+                ));
+            }
+            let wasmprog = unsafe { (wasmval.as_ptr() as *const WasmSockFprog).read_unaligned() };
+            let prog = libc::sock_fprog {
+                len: wasmprog.len,
+                filter: (start_address + wasmprog.filter as u64) as *mut libc::sock_filter,
+            };
+            let hostval = unsafe {
+                std::slice::from_raw_parts(&prog as *const libc::sock_fprog as *const u8, size_of::<libc::sock_fprog>())
+            };
+            Ok(hostval.to_vec())
+        }
+        SockoptLayout::GroupReq => {
+            if wasmval.len() < size_of::<u32>() {
+                return Err(syscall_error(
+                    Errno::EINVAL,
+                    "setsockopt",
+                    "optlen is too small for struct group_req",
+                ));
+            }
+            let mut hostval = wasmval[..4].to_vec();
+            hostval.extend_from_slice(&[0; 4]);
+            hostval.extend_from_slice(&wasmval[4..]);
+            Ok(hostval)
+        }
+    }
+}
+
+// struct ifaddrs as a wasm32 cage lays it out, with pointers as offsets into its memory
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+pub struct WasmIfaddrs {
+    pub ifa_next: u32,
+    pub ifa_name: u32,
+    pub ifa_flags: u32,
+    pub ifa_addr: u32,
+    pub ifa_netmask: u32,
+    pub ifa_ifu: u32,
+    pub ifa_data: u32,
+}
+
+/*
+ *   Builds the ifaddrs list of getifaddrs in the count bytes of the cage's memory at
+ *   generic_argument: the list nodes come first, the first one being the head, followed by
+ *   the sockaddrs and the names they point to. Returns the number of nodes, so 0 is an empty
+ *   list, or ENOBUFS when the buffer is too small for all of it.
+ */
+pub fn copy_out_ifaddrs(start_address: u64, generic_argument: u64, count: usize, ifaddrs: &[interface::IfaddrEntry]) -> i32 {
+    let nodeslen = ifaddrs.len() * size_of::<WasmIfaddrs>();
+    let datalen: usize = ifaddrs
+        .iter()
+        .map(|ifa| {
+            [&ifa.addr, &ifa.netmask, &ifa.ifu_addr]
+                .iter()
+                .map(|addr| addr.as_ref().map_or(0, |addr| wasm_align(addr.len())))
+                .sum::<usize>()
+                + ifa.name.len()
+                + 1
+        })
+        .sum();
+    if nodeslen + datalen > count {
+        return syscall_error(
+            Errno::ENOBUFS,
+            "getifaddrs",
+            "the buffer is too small for the interface list",
+        );
+    }
+    if ifaddrs.is_empty() {
+        return 0;
+    }
+    if generic_argument == 0 {
+        return syscall_error(
+            Errno::EFAULT,
+            "getifaddrs",
+            "the buffer is not a valid address",
+        );
+    }
+
+    let buf = (start_address + generic_argument) as *mut u8;
+    // sockaddrs go first so they stay 4 byte aligned, the names are packed after them
+    let mut cursor = nodeslen;
+    let mut put = |bytes: &[u8], len: usize| -> u32 {
+        unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), buf.add(cursor), bytes.len()) };
+        let wasmptr = (generic_argument as usize + cursor) as u32;
+        cursor += len;
+        wasmptr
+    };
+    let mut nodes: Vec<WasmIfaddrs> = ifaddrs
+        .iter()
+        .map(|ifa| {
+            let mut put_addr = |addr: &Option<Vec<u8>>| addr.as_ref().map_or(0, |addr| put(addr, wasm_align(addr.len())));
+            WasmIfaddrs {
+                ifa_addr: put_addr(&ifa.addr),
+                ifa_netmask: put_addr(&ifa.netmask),
+                ifa_ifu: put_addr(&ifa.ifu_addr),
+                ifa_flags: ifa.flags,
+                ..Default::default()
+            }
+        })
+        .collect();
+    for (node, ifa) in nodes.iter_mut().zip(ifaddrs) {
+        let mut name = ifa.name.as_bytes().to_vec();
+        name.push(0);
+        node.ifa_name = put(&name, name.len());
+    }
+    for (i, node) in nodes.iter_mut().enumerate() {
+        if i + 1 < ifaddrs.len() {
+            node.ifa_next = (generic_argument as usize + (i + 1) * size_of::<WasmIfaddrs>()) as u32;
+        }
+        unsafe { (buf as *mut WasmIfaddrs).add(i).write_unaligned(*node) };
+    }
+    ifaddrs.len() as i32
 }
@@ -0,0 +1,873 @@
+// In-memory AF_UNIX sockets used for FDKIND_IMSOCK file descriptors.
+#![allow(dead_code)]
+
+use crate::fdtables::{self, FDTableEntry};
+use crate::interface;
+use crate::interface::errnos::{syscall_error, Errno};
+use crate::interface::SockaddrUnix;
+use std::collections::HashMap;
+use std::sync::Weak;
+
+// Linux's default net.core.rmem_default
+pub const UNIX_SOCK_BUFSIZE: usize = 212992;
+
+// bits of UnixSockState::shutdown, same as the kernel's sk_shutdown
+pub const RCV_SHUTDOWN: u8 = 1;
+pub const SEND_SHUTDOWN: u8 = 2;
+pub const SHUTDOWN_MASK: u8 = 3;
+
+// Every socket gets an id that is used as the underfd of its fdtables entries.
+pub static UNIX_SOCKET_TABLE: interface::RustLazyGlobal<
+    interface::RustHashMap<u64, interface::RustRfc<UnixSocket>>,
+> = interface::RustLazyGlobal::new(interface::RustHashMap::new);
+// bound names, mapping to the id of the socket that owns them
+static UNIX_NAME_TABLE: interface::RustLazyGlobal<interface::RustHashMap<UnixSockKey, u64>> =
+    interface::RustLazyGlobal::new(interface::RustHashMap::new);
+static NEXT_UNIX_SOCKET_ID: interface::RustAtomicU64 = interface::RustAtomicU64::new(0);
+static NEXT_AUTOBIND_ID: interface::RustAtomicU64 = interface::RustAtomicU64::new(0);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum UnixSockKey {
+    // a socket file in the sandbox, identified by its host (st_dev, st_ino) so that
+    // unlinking or renaming the file behaves the way it does in Linux
+    Inode(u64, u64),
+    Abstract(Vec<u8>),
+}
+
+#[derive(Debug, Clone)]
+pub struct UnixSockName {
+    pub key: UnixSockKey,
+    pub addr: SockaddrUnix, // as the cage passed it to bind, reported back by getsockname and friends
+}
+
+// Linux's SCM_MAX_FD, the most fds one SCM_RIGHTS message may carry
+pub const SCM_MAX_FD: usize = 253;
+
+// the credentials a message was sent with, reported by SCM_CREDENTIALS
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UnixCred {
+    pub pid: i32, // the sending cage's id
+    pub uid: u32,
+    pub gid: u32,
+}
+
+// An fd in flight in an SCM_RIGHTS message. Its fdtables entry is held so the file
+// stays open until the receiver installs it in its own table, or until the message
+// is dropped unread. Like Linux without its garbage collector, a socket that is in
+// flight on itself is never closed.
+pub struct InflightFd(FDTableEntry);
+
+impl InflightFd {
+    pub fn hold(entry: FDTableEntry) -> InflightFd {
+        fdtables::hold_fdentry(entry);
+        InflightFd(entry)
+    }
+
+    pub fn entry(&self) -> FDTableEntry {
+        self.0
+    }
+}
+
+impl Drop for InflightFd {
+    fn drop(&mut self) {
+        fdtables::release_fdentry(self.0);
+    }
+}
+
+// the ancillary data of one message
+#[derive(Default)]
+pub struct UnixAncillary {
+    pub creds: UnixCred,
+    pub rights: Vec<InflightFd>,
+}
+
+struct Segment {
+    data: Vec<u8>,
+    offset: usize,
+    from: SockaddrUnix,
+    creds: UnixCred,
+    rights: Vec<InflightFd>,
+}
+
+struct UnixSockState {
+    name: Option<UnixSockName>,
+    peer: Option<Weak<UnixSocket>>,
+    peer_addr: SockaddrUnix,
+    connected: bool,
+    listening: bool,
+    backlog: usize,
+    pending: interface::RustDeque<interface::RustRfc<UnixSocket>>, // connections waiting for accept
+    queue: interface::RustDeque<Segment>,
+    queued_bytes: usize,
+    rcvbuf: usize,
+    shutdown: u8,
+    closed: bool,
+    options: HashMap<i32, Vec<u8>>, // SOL_SOCKET options that are only stored and reported back
+    cred: UnixCred,                  // of the cage that created the socket, or started listening on it
+    peer_cred: Option<UnixCred>,     // reported by SO_PEERCRED
+}
+
+pub struct UnixSocket {
+    pub id: u64,
+    pub socktype: i32,
+    state: interface::Mutex<UnixSockState>,
+    cv: interface::Condvar, // notified whenever the queues or the connection state change
+}
+
+// Where recv puts what it takes: the caller's own buffer, or a Vec that only grows as data
+// is received, up to limit bytes, for callers that have no one buffer to offer
+pub trait RecvBuf {
+    fn limit(&self) -> usize;
+    fn put(&mut self, offset: usize, data: &[u8]);
+}
+
+impl RecvBuf for [u8] {
+    fn limit(&self) -> usize {
+        self.len()
+    }
+
+    fn put(&mut self, offset: usize, data: &[u8]) {
+        self[offset..offset + data.len()].copy_from_slice(data);
+    }
+}
+
+pub struct GrowingBuf {
+    pub data: Vec<u8>,
+    pub limit: usize,
+}
+
+impl RecvBuf for GrowingBuf {
+    fn limit(&self) -> usize {
+        self.limit
+    }
+
+    fn put(&mut self, offset: usize, data: &[u8]) {
+        self.data.truncate(offset);
+        self.data.extend_from_slice(data);
+    }
+}
+
+// the address reported for sockets that were never bound
+pub fn unnamed_unix_addr() -> SockaddrUnix {
+    SockaddrUnix {
+        sun_family: libc::AF_UNIX as u16,
+        sun_path: [0; 108],
+    }
+}
+
+pub fn register_unix_socket(sock: interface::RustRfc<UnixSocket>) {
+    UNIX_SOCKET_TABLE.insert(sock.id, sock);
+}
+
+pub fn get_unix_socket(id: u64) -> Option<interface::RustRfc<UnixSocket>> {
+    UNIX_SOCKET_TABLE.get(&id).map(|sock| sock.clone())
+}
+
+pub fn lookup_unix_name(key: &UnixSockKey) -> Option<interface::RustRfc<UnixSocket>> {
+    let id = *UNIX_NAME_TABLE.get(key)?;
+    get_unix_socket(id)
+}
+
+// called once the last fd referring to this socket is gone
+pub fn close_unix_socket(id: u64) {
+    if let Some((_, sock)) = UNIX_SOCKET_TABLE.remove(&id) {
+        sock.close();
+    }
+}
+
+// poll revents for the socket with the given id
+pub fn unix_socket_poll_events(id: u64, events: i16) -> i16 {
+    match get_unix_socket(id) {
+        Some(sock) => sock.poll_events(events),
+        None => libc::POLLNVAL,
+    }
+}
+
+// creates a pair of connected sockets, as socketpair() does
+pub fn new_unix_socketpair(
+    socktype: i32,
+    cred: UnixCred,
+) -> (interface::RustRfc<UnixSocket>, interface::RustRfc<UnixSocket>) {
+    let sock1 = UnixSocket::new(socktype);
+    let sock2 = UnixSocket::new(socktype);
+    for (sock, peer) in [(&sock1, &sock2), (&sock2, &sock1)] {
+        let mut state = sock.state.lock();
+        state.peer = Some(interface::RustRfc::downgrade(peer));
+        state.peer_addr = unnamed_unix_addr();
+        state.connected = true;
+        state.cred = cred;
+        state.peer_cred = Some(cred);
+    }
+    register_unix_socket(sock1.clone());
+    register_unix_socket(sock2.clone());
+    (sock1, sock2)
+}
+
+impl UnixSocket {
+    pub fn new(socktype: i32) -> interface::RustRfc<UnixSocket> {
+        interface::RustRfc::new(UnixSocket {
+            id: NEXT_UNIX_SOCKET_ID.fetch_add(1, interface::RustAtomicOrdering::Relaxed),
+            socktype,
+            state: interface::Mutex::new(UnixSockState {
+                name: None,
+                peer: None,
+                peer_addr: unnamed_unix_addr(),
+                connected: false,
+                listening: false,
+                backlog: 0,
+                pending: interface::RustDeque::new(),
+                queue: interface::RustDeque::new(),
+                queued_bytes: 0,
+                rcvbuf: UNIX_SOCK_BUFSIZE,
+                shutdown: 0,
+                closed: false,
+                options: HashMap::new(),
+                cred: UnixCred::default(),
+                peer_cred: None,
+            }),
+            cv: interface::Condvar::new(),
+        })
+    }
+
+    // wakes up blocked callers as well as anyone polling the socket
+    fn wake(&self) {
+        self.cv.notify_all();
+        interface::notify_readiness();
+    }
+
+    fn is_stream(&self) -> bool {
+        self.socktype == libc::SOCK_STREAM
+    }
+
+    fn peer(&self) -> Option<interface::RustRfc<UnixSocket>> {
+        self.state.lock().peer.as_ref().and_then(|peer| peer.upgrade())
+    }
+
+    pub fn sockname(&self) -> SockaddrUnix {
+        match &self.state.lock().name {
+            Some(name) => name.addr,
+            None => unnamed_unix_addr(),
+        }
+    }
+
+    pub fn peername(&self) -> Result<SockaddrUnix, i32> {
+        let state = self.state.lock();
+        if !state.connected {
+            return Err(syscall_error(Errno::ENOTCONN, "getpeername", "the socket is not connected"));
+        }
+        Ok(state.peer_addr)
+    }
+
+    pub fn is_bound(&self) -> bool {
+        self.state.lock().name.is_some()
+    }
+
+    pub fn is_listening(&self) -> bool {
+        self.state.lock().listening
+    }
+
+    pub fn bind(&self, name: UnixSockName) -> i32 {
+        let mut state = self.state.lock();
+        if state.name.is_some() {
+            return syscall_error(Errno::EINVAL, "bind", "the socket is already bound to an address");
+        }
+        match UNIX_NAME_TABLE.entry(name.key.clone()) {
+            interface::RustHashEntry::Occupied(_) => {
+                return syscall_error(Errno::EADDRINUSE, "bind", "the address is already in use");
+            }
+            interface::RustHashEntry::Vacant(entry) => {
+                entry.insert(self.id);
+            }
+        }
+        state.name = Some(name);
+        0
+    }
+
+    // binds to a fresh abstract name, as Linux does for listen/connect on an unbound socket
+    pub fn autobind(&self) -> i32 {
+        loop {
+            let id = NEXT_AUTOBIND_ID.fetch_add(1, interface::RustAtomicOrdering::Relaxed);
+            let abstractname = format!("{:05x}", id & 0xfffff).into_bytes();
+            let mut addr = unnamed_unix_addr();
+            addr.sun_path[1..1 + abstractname.len()].copy_from_slice(&abstractname);
+            let ret = self.bind(UnixSockName {
+                key: UnixSockKey::Abstract(abstractname),
+                addr,
+            });
+            if ret != -(Errno::EADDRINUSE as i32) {
+                return ret;
+            }
+        }
+    }
+
+    // the credentials SO_PEERCRED reports to whoever connects to or is paired with this socket
+    pub fn set_cred(&self, cred: UnixCred) {
+        self.state.lock().cred = cred;
+    }
+
+    pub fn listen(&self, backlog: i32) -> i32 {
+        if !self.is_stream() {
+            return syscall_error(Errno::EOPNOTSUPP, "listen", "the socket type does not support listen");
+        }
+        if !self.is_bound() {
+            let ret = self.autobind();
+            if ret < 0 {
+                return ret;
+            }
+        }
+        let mut state = self.state.lock();
+        if state.connected {
+            return syscall_error(Errno::EINVAL, "listen", "the socket is already connected");
+        }
+        state.listening = true;
+        state.backlog = backlog.clamp(0, libc::SOMAXCONN) as usize;
+        // a larger backlog may let blocked connect calls through
+        self.wake();
+        0
+    }
+
+    /*
+     *   Connects a stream socket to a listening one: the listener gets a new socket on its
+     *   accept queue which is the peer of this one.
+     */
+    pub fn connect_stream(&self, listener: &interface::RustRfc<UnixSocket>, nonblocking: bool) -> i32 {
+        if listener.socktype != self.socktype {
+            return syscall_error(Errno::EPROTOTYPE, "connect", "the socket types do not match");
+        }
+        let (localaddr, listening, cred) = {
+            let state = self.state.lock();
+            let localaddr = state.name.as_ref().map(|name| name.addr).unwrap_or_else(unnamed_unix_addr);
+            (localaddr, state.listening, state.cred)
+        };
+        if listening {
+            return syscall_error(Errno::EINVAL, "connect", "the socket is listening");
+        }
+        if self.state.lock().connected {
+            return syscall_error(Errno::EISCONN, "connect", "the socket is already connected");
+        }
+
+        let mut lstate = listener.state.lock();
+        loop {
+            if !lstate.listening || lstate.closed {
+                return syscall_error(Errno::ECONNREFUSED, "connect", "no one is listening on the remote address");
+            }
+            // like Linux, one more connection than the backlog is allowed to wait
+            if lstate.pending.len() <= lstate.backlog {
+                break;
+            }
+            if nonblocking {
+                return syscall_error(Errno::EAGAIN, "connect", "the listen backlog is full");
+            }
+            listener.cv.wait(&mut lstate);
+        }
+        let listenaddr = lstate.name.as_ref().map(|name| name.addr).unwrap_or_else(unnamed_unix_addr);
+
+        let server = UnixSocket::new(self.socktype);
+        {
+            let mut sstate = server.state.lock();
+            sstate.name = lstate.name.clone();
+            sstate.peer = Some(interface::RustRfc::downgrade(&self.self_rfc()));
+            sstate.peer_addr = localaddr;
+            sstate.connected = true;
+            sstate.cred = lstate.cred;
+            sstate.peer_cred = Some(cred);
+        }
+        {
+            let mut state = self.state.lock();
+            state.peer = Some(interface::RustRfc::downgrade(&server));
+            state.peer_addr = listenaddr;
+            state.connected = true;
+            state.peer_cred = Some(lstate.cred);
+        }
+        lstate.pending.push_back(server);
+        listener.wake();
+        0
+    }
+
+    // sets the default destination of a datagram socket
+    pub fn connect_dgram(&self, target: &interface::RustRfc<UnixSocket>) -> i32 {
+        if target.socktype != self.socktype {
+            return syscall_error(Errno::EPROTOTYPE, "connect", "the socket types do not match");
+        }
+        let targetaddr = target.sockname();
+        let mut state = self.state.lock();
+        state.peer = Some(interface::RustRfc::downgrade(target));
+        state.peer_addr = targetaddr;
+        state.connected = true;
+        0
+    }
+
+    pub fn accept(&self, nonblocking: bool) -> Result<interface::RustRfc<UnixSocket>, i32> {
+        if !self.is_stream() {
+            return Err(syscall_error(Errno::EOPNOTSUPP, "accept", "the socket type does not support accept"));
+        }
+        let mut state = self.state.lock();
+        loop {
+            if !state.listening {
+                return Err(syscall_error(Errno::EINVAL, "accept", "the socket is not listening"));
+            }
+            if let Some(server) = state.pending.pop_front() {
+                // there is room in the backlog again
+                self.wake();
+                drop(state);
+                register_unix_socket(server.clone());
+                return Ok(server);
+            }
+            if nonblocking {
+                return Err(syscall_error(Errno::EAGAIN, "accept", "there are no pending connections"));
+            }
+            self.cv.wait(&mut state);
+        }
+    }
+
+    pub fn shutdown(&self, how: i32) -> i32 {
+        let mode = match how {
+            libc::SHUT_RD => RCV_SHUTDOWN,
+            libc::SHUT_WR => SEND_SHUTDOWN,
+            libc::SHUT_RDWR => SHUTDOWN_MASK,
+            _ => return syscall_error(Errno::EINVAL, "shutdown", "how is not valid"),
+        };
+        {
+            let mut state = self.state.lock();
+            if !state.connected {
+                return syscall_error(Errno::ENOTCONN, "shutdown", "the socket is not connected");
+            }
+            state.shutdown |= mode;
+        }
+        self.wake();
+
+        // the other end of a stream stops receiving what we no longer send, and vice versa
+        if self.is_stream() {
+            if let Some(peer) = self.peer() {
+                let mut peer_mode = 0;
+                if mode & RCV_SHUTDOWN != 0 {
+                    peer_mode |= SEND_SHUTDOWN;
+                }
+                if mode & SEND_SHUTDOWN != 0 {
+                    peer_mode |= RCV_SHUTDOWN;
+                }
+                peer.state.lock().shutdown |= peer_mode;
+                peer.wake();
+            }
+        }
+        0
+    }
+
+    pub fn close(&self) {
+        let (peer, pending, name, queue) = {
+            let mut state = self.state.lock();
+            state.closed = true;
+            state.listening = false;
+            state.shutdown = SHUTDOWN_MASK;
+            state.queued_bytes = 0;
+            let pending: Vec<_> = state.pending.drain(..).collect();
+            // taken out so that fds in flight are released without holding our lock,
+            // as releasing one may close another socket
+            let queue = std::mem::take(&mut state.queue);
+            (state.peer.take().and_then(|peer| peer.upgrade()), pending, state.name.take(), queue)
+        };
+        self.wake();
+        drop(queue);
+
+        if let Some(name) = name {
+            UNIX_NAME_TABLE.remove_if(&name.key, |_, id| *id == self.id);
+        }
+        // connections that were never accepted are reset
+        for server in pending {
+            server.close();
+        }
+        if self.is_stream() {
+            if let Some(peer) = peer {
+                peer.state.lock().shutdown = SHUTDOWN_MASK;
+                peer.wake();
+            }
+        }
+    }
+
+    /*
+     *   Sends on a stream socket. Blocking sends only return once everything has been
+     *   queued, non-blocking ones send whatever fits. The fds of ancillary go with the
+     *   first byte sent and are released if nothing could be sent.
+     *   EPIPE is returned when the other end can't receive anymore, raising SIGPIPE is up to the caller.
+     */
+    pub fn send_stream(&self, buf: &[u8], mut ancillary: UnixAncillary, nonblocking: bool) -> i32 {
+        let peer = {
+            let state = self.state.lock();
+            if state.listening || !state.connected {
+                return syscall_error(Errno::ENOTCONN, "send", "the socket is not connected");
+            }
+            if state.shutdown & SEND_SHUTDOWN != 0 {
+                return syscall_error(Errno::EPIPE, "send", "the socket has been shut down for writing");
+            }
+            state.peer.as_ref().and_then(|peer| peer.upgrade())
+        };
+        let peer = match peer {
+            Some(peer) => peer,
+            None => return syscall_error(Errno::EPIPE, "send", "the peer has closed the connection"),
+        };
+        let localaddr = self.sockname();
+
+        let mut sent = 0;
+        let mut pstate = peer.state.lock();
+        loop {
+            if pstate.closed || pstate.shutdown & RCV_SHUTDOWN != 0 {
+                if sent > 0 {
+                    break;
+                }
+                return syscall_error(Errno::EPIPE, "send", "the peer has closed the connection");
+            }
+            if sent == buf.len() {
+                break;
+            }
+            let room = pstate.rcvbuf.saturating_sub(pstate.queued_bytes);
+            if room > 0 {
+                let chunk = interface::rust_min(room, buf.len() - sent);
+                pstate.queue.push_back(Segment {
+                    data: buf[sent..sent + chunk].to_vec(),
+                    offset: 0,
+                    from: localaddr,
+                    creds: ancillary.creds,
+                    rights: std::mem::take(&mut ancillary.rights),
+                });
+                pstate.queued_bytes += chunk;
+                sent += chunk;
+                peer.wake();
+                continue;
+            }
+            if nonblocking {
+                if sent > 0 {
+                    break;
+                }
+                return syscall_error(Errno::EAGAIN, "send", "the peer's receive buffer is full");
+            }
+            peer.cv.wait(&mut pstate);
+        }
+        sent as i32
+    }
+
+    // Sends one datagram along with its ancillary data, to target or else to the connected peer
+    pub fn send_dgram(
+        &self,
+        buf: &[u8],
+        ancillary: UnixAncillary,
+        target: Option<interface::RustRfc<UnixSocket>>,
+        nonblocking: bool,
+    ) -> i32 {
+        let (peer, connected) = {
+            let state = self.state.lock();
+            if state.shutdown & SEND_SHUTDOWN != 0 {
+                return syscall_error(Errno::EPIPE, "sendto", "the socket has been shut down for writing");
+            }
+            (state.peer.as_ref().and_then(|peer| peer.upgrade()), state.connected)
+        };
+        let target = match target {
+            Some(target) => target,
+            None if !connected => {
+                return syscall_error(Errno::ENOTCONN, "sendto", "the socket is not connected and no address was given");
+            }
+            None => match peer {
+                Some(peer) => peer,
+                None => return syscall_error(Errno::ECONNREFUSED, "sendto", "the peer socket has been closed"),
+            },
+        };
+        if target.socktype != self.socktype {
+            return syscall_error(Errno::EPROTOTYPE, "sendto", "the socket types do not match");
+        }
+        let localaddr = self.sockname();
+
+        let mut tstate = target.state.lock();
+        if buf.len() > tstate.rcvbuf {
+            return syscall_error(Errno::EMSGSIZE, "sendto", "the message is too long");
+        }
+        loop {
+            if tstate.closed {
+                return syscall_error(Errno::ECONNREFUSED, "sendto", "the peer socket has been closed");
+            }
+            if tstate.queued_bytes + buf.len() <= tstate.rcvbuf {
+                break;
+            }
+            if nonblocking {
+                return syscall_error(Errno::EAGAIN, "sendto", "the peer's receive buffer is full");
+            }
+            target.cv.wait(&mut tstate);
+        }
+        tstate.queue.push_back(Segment {
+            data: buf.to_vec(),
+            offset: 0,
+            from: localaddr,
+            creds: ancillary.creds,
+            rights: ancillary.rights,
+        });
+        tstate.queued_bytes += buf.len();
+        target.wake();
+        buf.len() as i32
+    }
+
+    /*
+     *   Receives into buf. Streams read across queued segments, datagrams return one message
+     *   and drop what doesn't fit (MSG_TRUNC reports its full length). The sender's address
+     *   is stored in from when given, the credentials and fds the data was sent with in
+     *   ancillary. Fds are released when there is no ancillary to take them.
+     *   Like Linux, a stream read stops after data that carried fds, and with SO_PASSCRED
+     *   it does not merge data from different senders.
+     */
+    pub fn recv<B: RecvBuf + ?Sized>(
+        &self,
+        buf: &mut B,
+        flags: i32,
+        nonblocking: bool,
+        from: Option<&mut SockaddrUnix>,
+        ancillary: Option<&mut UnixAncillary>,
+    ) -> i32 {
+        let peek = flags & libc::MSG_PEEK != 0;
+        let nonblocking = nonblocking || flags & libc::MSG_DONTWAIT != 0;
+        let waitall = flags & libc::MSG_WAITALL != 0 && !peek && self.is_stream();
+
+        let mut state = self.state.lock();
+        if state.listening {
+            return syscall_error(Errno::EINVAL, "recv", "the socket is listening");
+        }
+        if self.is_stream() && !state.connected {
+            return syscall_error(Errno::ENOTCONN, "recv", "the socket is not connected");
+        }
+        let passcred = int_option(&state.options, libc::SO_PASSCRED).map_or(false, |on| on != 0);
+
+        let mut copied = 0;
+        let mut fromaddr = None;
+        let mut creds = None;
+        let mut rights = Vec::new();
+        let mut boundary = false;
+        loop {
+            if !state.queue.is_empty() && buf.limit() > 0 {
+                if self.is_stream() {
+                    let mut index = 0;
+                    while copied < buf.limit() && index < state.queue.len() {
+                        let segment = &mut state.queue[index];
+                        if copied > 0 && (!segment.rights.is_empty() || passcred && creds != Some(segment.creds)) {
+                            boundary = true;
+                            break;
+                        }
+                        let avail = &segment.data[segment.offset..];
+                        let chunk = interface::rust_min(avail.len(), buf.limit() - copied);
+                        buf.put(copied, &avail[..chunk]);
+                        fromaddr = Some(segment.from);
+                        creds = Some(segment.creds);
+                        let carried_rights = !segment.rights.is_empty();
+                        rights.extend(take_rights(&mut segment.rights, peek));
+                        copied += chunk;
+                        if peek {
+                            index += 1;
+                        } else {
+                            segment.offset += chunk;
+                            if segment.offset == segment.data.len() {
+                                state.queue.pop_front();
+                            }
+                            state.queued_bytes -= chunk;
+                        }
+                        if carried_rights {
+                            boundary = true;
+                            break;
+                        }
+                    }
+                } else {
+                    let (length, chunk) = if peek {
+                        let segment = state.queue.front_mut().unwrap();
+                        let chunk = interface::rust_min(segment.data.len(), buf.limit());
+                        buf.put(0, &segment.data[..chunk]);
+                        fromaddr = Some(segment.from);
+                        creds = Some(segment.creds);
+                        rights = take_rights(&mut segment.rights, true);
+                        (segment.data.len(), chunk)
+                    } else {
+                        let segment = state.queue.pop_front().unwrap();
+                        state.queued_bytes -= segment.data.len();
+                        let chunk = interface::rust_min(segment.data.len(), buf.limit());
+                        buf.put(0, &segment.data[..chunk]);
+                        fromaddr = Some(segment.from);
+                        creds = Some(segment.creds);
+                        rights = segment.rights;
+                        (segment.data.len(), chunk)
+                    };
+                    copied = if flags & libc::MSG_TRUNC != 0 { length } else { chunk };
+                }
+                // senders may be waiting for room
+                self.wake();
+                if !waitall || copied == buf.limit() || boundary {
+                    break;
+                }
+            } else if buf.limit() == 0 && !state.queue.is_empty() {
+                break;
+            }
+
+            if state.shutdown & RCV_SHUTDOWN != 0 || copied > 0 && nonblocking {
+                break;
+            }
+            if nonblocking {
+                return syscall_error(Errno::EAGAIN, "recv", "there is no data available");
+            }
+            self.cv.wait(&mut state);
+        }
+        // fds that nobody takes may be the last reference to another socket
+        drop(state);
+
+        if let (Some(from), Some(fromaddr)) = (from, fromaddr) {
+            *from = fromaddr;
+        }
+        if let Some(ancillary) = ancillary {
+            ancillary.creds = creds.unwrap_or_default();
+            ancillary.rights = rights;
+        }
+        copied as i32
+    }
+
+    // whether received messages should report their credentials
+    pub fn passcred(&self) -> bool {
+        int_option(&self.state.lock().options, libc::SO_PASSCRED).map_or(false, |on| on != 0)
+    }
+
+    // number of bytes that can be read without blocking
+    pub fn get_read_avail(&self) -> usize {
+        let state = self.state.lock();
+        if self.is_stream() {
+            state.queued_bytes
+        } else {
+            state.queue.front().map_or(0, |segment| segment.data.len())
+        }
+    }
+
+    // the most one recv without MSG_WAITALL can return: what is queued, which the receive
+    // buffer bounds unless it was shrunk after the data arrived
+    pub fn max_recv_len(&self) -> usize {
+        let state = self.state.lock();
+        interface::rust_max(state.rcvbuf, state.queued_bytes)
+    }
+
+    // poll revents, with the same rules as Linux unix_poll
+    pub fn poll_events(&self, events: i16) -> i16 {
+        let (mut revents, peer, connected, listening) = {
+            let state = self.state.lock();
+            let mut revents = 0;
+            if !state.queue.is_empty() || !state.pending.is_empty() || state.shutdown & RCV_SHUTDOWN != 0 {
+                revents |= events & libc::POLLIN;
+            }
+            if state.shutdown == SHUTDOWN_MASK
+                || (self.is_stream() && !state.connected && !state.listening)
+            {
+                revents |= libc::POLLHUP;
+            }
+            (
+                revents,
+                state.peer.as_ref().and_then(|peer| peer.upgrade()),
+                state.connected,
+                state.listening,
+            )
+        };
+
+        let writable = match peer {
+            _ if listening => false,
+            Some(peer) => {
+                let pstate = peer.state.lock();
+                pstate.closed || pstate.queued_bytes < pstate.rcvbuf
+            }
+            None => !connected || !self.is_stream(),
+        };
+        if writable {
+            revents |= events & libc::POLLOUT;
+        }
+        revents
+    }
+
+    // Returns the value of a SOL_SOCKET option, laid out the way the C API has it
+    pub fn getsockopt(&self, level: i32, optname: i32) -> Result<Vec<u8>, i32> {
+        if level != libc::SOL_SOCKET {
+            return Err(syscall_error(Errno::ENOPROTOOPT, "getsockopt", "the option is not supported at this level"));
+        }
+        let state = self.state.lock();
+        let int = |value: i32| value.to_ne_bytes().to_vec();
+        Ok(match optname {
+            libc::SO_TYPE => int(self.socktype),
+            libc::SO_DOMAIN => int(libc::AF_UNIX),
+            libc::SO_PROTOCOL => int(0),
+            libc::SO_ERROR => int(0),
+            libc::SO_ACCEPTCONN => int(state.listening as i32),
+            libc::SO_RCVBUF => int(state.rcvbuf as i32),
+            libc::SO_SNDBUF => state.options.get(&optname).cloned().unwrap_or_else(|| int(UNIX_SOCK_BUFSIZE as i32)),
+            // like Linux, a socket without a peer reports pid 0 and uid/gid -1
+            libc::SO_PEERCRED => {
+                let cred = state.peer_cred.unwrap_or(UnixCred { pid: 0, uid: u32::MAX, gid: u32::MAX });
+                option_bytes(&libc::ucred { pid: cred.pid, uid: cred.uid, gid: cred.gid })
+            }
+            _ => state.options.get(&optname).cloned().unwrap_or_else(|| vec![0; option_len(optname)]),
+        })
+    }
+
+    // Sets a SOL_SOCKET option from its C layout, optval being at least as long as the option
+    pub fn setsockopt(&self, level: i32, optname: i32, optval: &[u8]) -> i32 {
+        if level != libc::SOL_SOCKET {
+            return syscall_error(Errno::ENOPROTOOPT, "setsockopt", "the option is not supported at this level");
+        }
+        let optlen = option_len(optname);
+        if optval.len() < optlen {
+            return syscall_error(Errno::EINVAL, "setsockopt", "optlen is too small for the option");
+        }
+        let value = &optval[..optlen];
+        let mut state = self.state.lock();
+        match optname {
+            libc::SO_TYPE | libc::SO_DOMAIN | libc::SO_PROTOCOL | libc::SO_ERROR | libc::SO_ACCEPTCONN
+            | libc::SO_PEERCRED => {
+                return syscall_error(Errno::ENOPROTOOPT, "setsockopt", "the option is read only");
+            }
+            // like Linux, the size is capped at rmem_max and then doubled to leave room
+            // for bookkeeping
+            libc::SO_RCVBUF => {
+                state.rcvbuf = int_value(value).clamp(1152, UNIX_SOCK_BUFSIZE as i32) as usize * 2;
+                drop(state);
+                self.wake();
+            }
+            libc::SO_SNDBUF => {
+                let sndbuf = interface::rust_max(int_value(value), 2304) * 2;
+                state.options.insert(optname, sndbuf.to_ne_bytes().to_vec());
+            }
+            _ => {
+                state.options.insert(optname, value.to_vec());
+            }
+        }
+        0
+    }
+
+    fn self_rfc(&self) -> interface::RustRfc<UnixSocket> {
+        get_unix_socket(self.id).expect("connecting socket must be registered")
+    }
+}
+
+// The fds of a segment for a receiver. A peek leaves them queued and hands out
+// holds of its own, as Linux installs duplicates when peeking.
+fn take_rights(rights: &mut Vec<InflightFd>, peek: bool) -> Vec<InflightFd> {
+    if peek {
+        rights.iter().map(|fd| InflightFd::hold(fd.entry())).collect()
+    } else {
+        std::mem::take(rights)
+    }
+}
+
+// the size of a SOL_SOCKET option's value, an int unless it is one of the struct valued ones
+fn option_len(optname: i32) -> usize {
+    match optname {
+        libc::SO_LINGER => std::mem::size_of::<libc::linger>(),
+        libc::SO_RCVTIMEO | libc::SO_SNDTIMEO => std::mem::size_of::<libc::timeval>(),
+        libc::SO_PEERCRED => std::mem::size_of::<libc::ucred>(),
+        _ => std::mem::size_of::<i32>(),
+    }
+}
+
+fn option_bytes<T>(value: &T) -> Vec<u8> {
+    unsafe { std::slice::from_raw_parts(value as *const T as *const u8, std::mem::size_of::<T>()) }.to_vec()
+}
+
+fn int_value(value: &[u8]) -> i32 {
+    i32::from_ne_bytes([value[0], value[1], value[2], value[3]])
+}
+
+fn int_option(options: &HashMap<i32, Vec<u8>>, optname: i32) -> Option<i32> {
+    options.get(&optname).map(|value| int_value(value))
+}
@@ -1,4 +1,5 @@
 #![allow(dead_code)]
+use crate::fdtables;
 use crate::interface;
 //going to get the datatypes and errnos from the cage file from now on
 pub use crate::interface::errnos::{syscall_error, Errno};
@@ -7,33 +8,69 @@ pub use crate::interface::types::{
     EpollEvent, IoctlPtrUnion, PipeArray, PollStruct,
 };
 
-use super::filesystem::normpath;
+use super::filesystem::{clean_lindroot, normpath};
 pub use super::syscalls::fs_constants::*;
 pub use super::syscalls::net_constants::*;
 pub use super::syscalls::sys_constants::*;
 
 pub use crate::interface::CAGE_TABLE;
 
+const FDKIND_KERNEL: u32 = 0;
+
+// An exited child that has not been reaped by its parent yet
+#[derive(Debug, Clone, Copy)]
+pub struct Zombie {
+    pub cageid: u64,
+    pub exit_code: i32,
+    pub rusage: interface::Rusage,
+}
+
+// The resource limits a cage starts out with. Fork and exec carry over whatever the cage set since
+pub fn default_rlimits() -> interface::RustHashMap<u64, interface::Rlimit> {
+    let rlimits = interface::RustHashMap::new();
+    let limit = |rlim_cur, rlim_max| interface::Rlimit { rlim_cur, rlim_max };
+    rlimits.insert(RLIMIT_NOFILE, limit(NOFILE_CUR, NOFILE_MAX));
+    rlimits.insert(RLIMIT_STACK, limit(STACK_CUR, STACK_MAX));
+    rlimits.insert(RLIMIT_FSIZE, limit(RLIM_INFINITY, RLIM_INFINITY));
+    rlimits.insert(RLIMIT_NPROC, limit(RLIM_INFINITY, RLIM_INFINITY));
+    rlimits.insert(RLIMIT_AS, limit(RLIM_INFINITY, RLIM_INFINITY));
+    rlimits.insert(RLIMIT_CORE, limit(CORE_CUR, CORE_MAX));
+    rlimits
+}
+
 #[derive(Debug)]
 pub struct Cage {
     pub cageid: u64,
     pub cwd: interface::RustLock<interface::RustRfc<interface::RustPathBuf>>,
+    pub lindroot: interface::RustLock<interface::RustRfc<interface::RustPathBuf>>, // host directory this cage's "/" maps to
     pub parent: u64,
     pub cancelstatus: interface::RustAtomicBool,
     pub getgid: interface::RustAtomicI32,
     pub getuid: interface::RustAtomicI32,
     pub getegid: interface::RustAtomicI32,
     pub geteuid: interface::RustAtomicI32,
+    pub getsgid: interface::RustAtomicI32, // saved set-group-ID
+    pub getsuid: interface::RustAtomicI32, // saved set-user-ID
+    pub groups: interface::RustLock<Vec<u32>>, // supplementary group IDs
+    pub umask: interface::RustAtomicU32, // permission bits cleared from newly created files
     pub rev_shm: interface::Mutex<Vec<(u32, i32)>>, //maps addr within cage to shmid
     pub mutex_table: interface::RustLock<Vec<Option<interface::RustRfc<interface::RawMutex>>>>,
     pub cv_table: interface::RustLock<Vec<Option<interface::RustRfc<interface::RawCondvar>>>>,
     pub sem_table: interface::RustHashMap<u32, interface::RustRfc<interface::RustSemaphore>>,
+    // sem_t handle -> named semaphore sem_open put there, and the sem_closes it takes to close
+    pub named_sem_table: interface::RustHashMap<u32, (interface::RustRfc<interface::NamedSemaphore>, u32)>,
     pub thread_table: interface::RustHashMap<u64, bool>,
     pub signalhandler: interface::RustHashMap<i32, interface::SigactionStruct>,
     pub sigset: interface::RustHashMap<u64, interface::RustAtomicU64>,
     pub pendingsigset: interface::RustHashMap<u64, interface::RustAtomicU64>,
     pub main_threadid: interface::RustAtomicU64,
     pub interval_timer: interface::IntervalTimer,
+    pub zombies: interface::Mutex<Vec<Zombie>>,
+    pub zombie_cv: interface::Condvar, // notified whenever a child becomes a zombie
+    pub child_num: interface::RustAtomicU64, // children forked and not yet exited
+    pub rlimits: interface::RustHashMap<u64, interface::Rlimit>, // keyed by RLIMIT_* resource
+    pub mapped_ranges: interface::Mutex<Vec<(usize, usize)>>, // [start, end) mmapped and not yet unmapped, checked against RLIMIT_AS
+    pub netns: interface::RustAtomicU64, // virtual network namespace, interface::HOST_NETNS for none
 }
 
 impl Cage {
@@ -43,6 +80,126 @@ impl Cage {
         *cwdbox = newwd;
     }
 
+    // the sandbox root is normally inherited from the parent cage, but the runtime
+    // may point an individual cage somewhere else before it starts running
+    pub fn changeroot(&self, newroot: &str) {
+        let newroot = interface::RustRfc::new(clean_lindroot(newroot));
+        let mut rootbox = self.lindroot.write();
+        *rootbox = newroot;
+    }
+
+    // like the sandbox root, the runtime may put a cage into a network namespace before it
+    // starts running, and the cages it forks or execs stay in it
+    pub fn changenetns(&self, nsid: u64) {
+        self.netns.store(nsid, interface::RustAtomicOrdering::Relaxed);
+    }
+
+    pub fn netns(&self) -> u64 {
+        self.netns.load(interface::RustAtomicOrdering::Relaxed)
+    }
+
+    // the soft limit of a resource, RLIM_INFINITY if we keep none for it
+    pub fn rlimit_cur(&self, resource: u64) -> u64 {
+        self.rlimits.get(&resource).map_or(RLIM_INFINITY, |limit| limit.rlim_cur)
+    }
+
+    // The credentials start out as -1 until the cage first asks for them (see getuid_syscall),
+    // and until then they are the DEFAULT_UID/DEFAULT_GID every cage runs as
+    fn credential(id: &interface::RustAtomicI32, default: u32) -> u32 {
+        match id.load(interface::RustAtomicOrdering::Relaxed) {
+            -1 => default,
+            id => id as u32,
+        }
+    }
+
+    pub fn uid(&self) -> u32 {
+        Self::credential(&self.getuid, DEFAULT_UID)
+    }
+
+    pub fn euid(&self) -> u32 {
+        Self::credential(&self.geteuid, DEFAULT_UID)
+    }
+
+    pub fn suid(&self) -> u32 {
+        Self::credential(&self.getsuid, DEFAULT_UID)
+    }
+
+    pub fn gid(&self) -> u32 {
+        Self::credential(&self.getgid, DEFAULT_GID)
+    }
+
+    pub fn egid(&self) -> u32 {
+        Self::credential(&self.getegid, DEFAULT_GID)
+    }
+
+    pub fn sgid(&self) -> u32 {
+        Self::credential(&self.getsgid, DEFAULT_GID)
+    }
+
+    pub fn in_group(&self, gid: u32, use_real_ids: bool) -> bool {
+        let primary = if use_real_ids { self.gid() } else { self.egid() };
+        primary == gid || self.groups.read().contains(&gid)
+    }
+
+    /*
+    *   Whether the cage may access an object owned by uid/gid with the permission bits of mode,
+    *   where want is made of R_OK, W_OK and X_OK. Like the kernel, only one class of bits is
+    *   looked at (owner, else group, else other), and root gets everything except executing
+    *   a file with no execute bit at all. access() checks with the real ids, the rest with the
+    *   effective ones.
+    */
+    pub fn permits(&self, uid: u32, gid: u32, mode: u32, want: i32, use_real_ids: bool) -> bool {
+        let want = (want & (libc::R_OK | libc::W_OK | libc::X_OK)) as u32;
+        let caller = if use_real_ids { self.uid() } else { self.euid() };
+        if caller == ROOT_UID {
+            let isdir = mode & libc::S_IFMT == libc::S_IFDIR;
+            return want & libc::X_OK as u32 == 0 || isdir || mode & 0o111 != 0;
+        }
+        let granted = if caller == uid {
+            (mode >> 6) & 7
+        } else if self.in_group(gid, use_real_ids) {
+            (mode >> 3) & 7
+        } else {
+            mode & 7
+        };
+        want & !granted == 0
+    }
+
+    // The same check for System V IPC objects, where both the owner and the creator count
+    pub fn ipc_permits(&self, perm: &interface::IpcPermStruct, want: i32) -> bool {
+        let euid = self.euid();
+        if euid == ROOT_UID {
+            return true;
+        }
+        let mode = perm.mode as u32;
+        let granted = if euid == perm.uid || euid == perm.cuid {
+            (mode >> 6) & 7
+        } else if self.in_group(perm.gid, false) || self.in_group(perm.cgid, false) {
+            (mode >> 3) & 7
+        } else {
+            mode & 7
+        };
+        (want as u32 & 7) & !granted == 0
+    }
+
+    // owners and root may change an IPC object's permissions or remove it
+    pub fn ipc_owns(&self, perm: &interface::IpcPermStruct) -> bool {
+        let euid = self.euid();
+        euid == ROOT_UID || euid == perm.uid || euid == perm.cuid
+    }
+
+    // maps a fresh kernel fd to the lowest free virtual fd, closing it again when
+    // RLIMIT_NOFILE (EMFILE) or the system wide fd limit (ENFILE) is hit
+    pub fn install_kernel_fd(&self, kernel_fd: i32, should_cloexec: bool, syscallname: &str) -> i32 {
+        match fdtables::get_unused_virtual_fd(self.cageid, FDKIND_KERNEL, kernel_fd as u64, should_cloexec, 0) {
+            Ok(virtual_fd) => virtual_fd as i32,
+            Err(e) => {
+                unsafe { libc::close(kernel_fd) };
+                interface::handle_errno(e as i32, syscallname)
+            }
+        }
+    }
+
     // function to signal all cvs in a cage when forcing exit
     pub fn signalcvs(&self) {
         let cvtable = self.cv_table.read();
@@ -55,7 +212,11 @@ impl Cage {
         }
     }
 
+    // delivers every signal in sigset to the thread and drops them from its pending set
     pub fn send_pending_signals(&self, sigset: interface::SigsetType, pthreadid: u64) {
+        if let Some(pendingset) = self.pendingsigset.get(&pthreadid) {
+            pendingset.fetch_and(!sigset, interface::RustAtomicOrdering::Relaxed);
+        }
         for signo in 1..SIGNAL_MAX {
             if interface::lind_sigismember(sigset, signo) {
                 interface::lind_threadkill(pthreadid, signo);
@@ -114,17 +114,83 @@ const SYNC_FILE_RANGE: i32 = 164;
 const WRITEV_SYSCALL: i32 = 170;
 
 const CLONE_SYSCALL: i32 = 171;
+const WAIT_SYSCALL: i32 = 172;
+const WAITPID_SYSCALL: i32 = 173;
+const WAIT4_SYSCALL: i32 = 174;
+const GETRLIMIT_SYSCALL: i32 = 175;
+const SETRLIMIT_SYSCALL: i32 = 176;
 
 const NANOSLEEP_TIME64_SYSCALL : i32 = 181;
 
+const SETUID_SYSCALL: i32 = 190;
+const SETEUID_SYSCALL: i32 = 191;
+const SETREUID_SYSCALL: i32 = 192;
+const SETRESUID_SYSCALL: i32 = 193;
+const GETRESUID_SYSCALL: i32 = 194;
+const SETGID_SYSCALL: i32 = 195;
+const SETEGID_SYSCALL: i32 = 196;
+const SETREGID_SYSCALL: i32 = 197;
+const SETRESGID_SYSCALL: i32 = 198;
+const GETRESGID_SYSCALL: i32 = 199;
+const GETGROUPS_SYSCALL: i32 = 200;
+const SETGROUPS_SYSCALL: i32 = 201;
+
+const CHOWN_SYSCALL: i32 = 202;
+const FCHOWN_SYSCALL: i32 = 203;
+const LCHOWN_SYSCALL: i32 = 204;
+const UMASK_SYSCALL: i32 = 205;
+
+const OPENAT_SYSCALL: i32 = 206;
+const MKDIRAT_SYSCALL: i32 = 207;
+const UNLINKAT_SYSCALL: i32 = 208;
+const RENAMEAT2_SYSCALL: i32 = 209;
+const FSTATAT_SYSCALL: i32 = 210;
+const LINKAT_SYSCALL: i32 = 211;
+const FCHMODAT_SYSCALL: i32 = 212;
+
+const SYMLINK_SYSCALL: i32 = 213;
+const READLINK_SYSCALL: i32 = 214;
+const LSTAT_SYSCALL: i32 = 215;
+const SYMLINKAT_SYSCALL: i32 = 216;
+const READLINKAT_SYSCALL: i32 = 217;
+
+const READV_SYSCALL: i32 = 218;
+const PREADV_SYSCALL: i32 = 219;
+const PWRITEV_SYSCALL: i32 = 220;
+const PREADV2_SYSCALL: i32 = 221;
+const PWRITEV2_SYSCALL: i32 = 222;
+
+const SENDMSG_SYSCALL: i32 = 223;
+const RECVMSG_SYSCALL: i32 = 224;
+const ACCEPT4_SYSCALL: i32 = 225;
+
+const SHM_OPEN_SYSCALL: i32 = 226;
+const SHM_UNLINK_SYSCALL: i32 = 227;
+
+const MSGGET_SYSCALL: i32 = 228;
+const MSGSND_SYSCALL: i32 = 229;
+const MSGRCV_SYSCALL: i32 = 230;
+const MSGCTL_SYSCALL: i32 = 231;
+
+const SEMGET_SYSCALL: i32 = 232;
+const SEMOP_SYSCALL: i32 = 233;
+const SEMTIMEDOP_SYSCALL: i32 = 234;
+const SEMCTL_SYSCALL: i32 = 235;
+
+const SEM_OPEN_SYSCALL: i32 = 236;
+const SEM_CLOSE_SYSCALL: i32 = 237;
+const SEM_UNLINK_SYSCALL: i32 = 238;
+
 use std::ffi::CString;
 use std::ffi::CStr;
 use super::cage::*;
-use super::syscalls::kernel_close;
+use super::syscalls::{epoll_poll_events, impipe_close, imshm_close, imsock_close, kernel_close};
+use super::filesystem::{clean_lindroot, clear_virtual_owners};
 
 const FDKIND_KERNEL: u32 = 0;
 const FDKIND_IMPIPE: u32 = 1;
 const FDKIND_IMSOCK: u32 = 2;
+const FDKIND_IMSHM: u32 = 3;
 
 use std::io::{Read, Write};
 use std::io;
@@ -157,9 +223,21 @@ pub extern "C" fn rustposix_thread_init(cageid: u64, signalflag: u64) {
             .insert(pthreadid, interface::RustAtomicU64::new(0));
     }
 
+    // signals sent before the main thread existed (and those carried over an exec) are parked at entry 0
+    let parkedsigs = match cage.pendingsigset.remove(&0) {
+        Some((_, pendingset)) => pendingset.load(interface::RustAtomicOrdering::Relaxed),
+        None => 0,
+    };
     cage.pendingsigset
-        .insert(pthreadid, interface::RustAtomicU64::new(0));
+        .insert(pthreadid, interface::RustAtomicU64::new(parkedsigs));
     interface::signalflag_set(signalflag);
+
+    // whatever isn't blocked can be delivered now, the rest waits for sigprocmask to unblock it
+    let mask = cage.sigset.get(&pthreadid).unwrap().load(interface::RustAtomicOrdering::Relaxed);
+    let deliverable = parkedsigs & !mask;
+    if deliverable != 0 {
+        cage.send_pending_signals(deliverable, pthreadid);
+    }
 }
 
 /// The `lind_syscall_api` function acts as the main dispatcher for handling system calls 
@@ -208,11 +286,74 @@ pub fn lind_syscall_api(
 
         WRITEV_SYSCALL => {
             let fd = arg1 as i32;
-            let iovec = (start_address + arg2) as *const interface::IovecStruct;
-            let iovcnt = arg3 as i32;
+            let iovecs = match interface::get_iovecstruct(start_address, arg2, arg3 as i32) {
+                Ok(iovecs) => iovecs,
+                Err(errno) => return errno,
+            };
+
+            interface::cagetable_getref(cageid)
+                .writev_syscall(fd, iovecs.as_ptr(), iovecs.len() as i32)
+        }
+
+        READV_SYSCALL => {
+            let fd = arg1 as i32;
+            let iovecs = match interface::get_iovecstruct(start_address, arg2, arg3 as i32) {
+                Ok(iovecs) => iovecs,
+                Err(errno) => return errno,
+            };
+
+            interface::cagetable_getref(cageid)
+                .readv_syscall(fd, iovecs.as_ptr(), iovecs.len() as i32)
+        }
+
+        PREADV_SYSCALL => {
+            let fd = arg1 as i32;
+            let iovecs = match interface::get_iovecstruct(start_address, arg2, arg3 as i32) {
+                Ok(iovecs) => iovecs,
+                Err(errno) => return errno,
+            };
+            let offset = arg4 as i64;
 
             interface::cagetable_getref(cageid)
-                .writev_syscall(fd, iovec, iovcnt)
+                .preadv_syscall(fd, iovecs.as_ptr(), iovecs.len() as i32, offset)
+        }
+
+        PWRITEV_SYSCALL => {
+            let fd = arg1 as i32;
+            let iovecs = match interface::get_iovecstruct(start_address, arg2, arg3 as i32) {
+                Ok(iovecs) => iovecs,
+                Err(errno) => return errno,
+            };
+            let offset = arg4 as i64;
+
+            interface::cagetable_getref(cageid)
+                .pwritev_syscall(fd, iovecs.as_ptr(), iovecs.len() as i32, offset)
+        }
+
+        PREADV2_SYSCALL => {
+            let fd = arg1 as i32;
+            let iovecs = match interface::get_iovecstruct(start_address, arg2, arg3 as i32) {
+                Ok(iovecs) => iovecs,
+                Err(errno) => return errno,
+            };
+            let offset = arg4 as i64;
+            let flags = arg5 as i32;
+
+            interface::cagetable_getref(cageid)
+                .preadv2_syscall(fd, iovecs.as_ptr(), iovecs.len() as i32, offset, flags)
+        }
+
+        PWRITEV2_SYSCALL => {
+            let fd = arg1 as i32;
+            let iovecs = match interface::get_iovecstruct(start_address, arg2, arg3 as i32) {
+                Ok(iovecs) => iovecs,
+                Err(errno) => return errno,
+            };
+            let offset = arg4 as i64;
+            let flags = arg5 as i32;
+
+            interface::cagetable_getref(cageid)
+                .pwritev2_syscall(fd, iovecs.as_ptr(), iovecs.len() as i32, offset, flags)
         }
 
         MUNMAP_SYSCALL => {
@@ -318,17 +459,18 @@ pub fn lind_syscall_api(
                 .bind_syscall(fd, localaddr)
         }
 
-        ACCEPT_SYSCALL => {
+        ACCEPT_SYSCALL | ACCEPT4_SYSCALL => {
             let mut addr = interface::GenSockaddr::V4(interface::SockaddrV4::default()); //value doesn't matter
             let nullity1 = interface::arg_nullity(arg2);
             let nullity2 = interface::arg_nullity(arg3);
+            let flags = if call_number == ACCEPT4_SYSCALL { arg4 as i32 } else { 0 };
 
             if nullity1 && nullity2 {
                 interface::cagetable_getref(cageid)
-                    .accept_syscall(arg1 as i32, &mut Some(&mut addr))
+                    .accept4_syscall(arg1 as i32, &mut Some(&mut addr), flags)
             } else if !(nullity1 || nullity2) {
                 let rv = interface::cagetable_getref(cageid)
-                    .accept_syscall(arg1 as i32, &mut Some(&mut addr));
+                    .accept4_syscall(arg1 as i32, &mut Some(&mut addr), flags);
                 if rv >= 0 {
                     interface::copy_out_sockaddr((start_address + arg2), (start_address + arg3), addr);
                 }
@@ -455,14 +597,9 @@ pub fn lind_syscall_api(
         
         DUP_SYSCALL => {
             let fd = arg1 as i32;
-            let fd2: Option<i32> = if arg1 <= i32::MAX as u64 {
-                Some(arg1 as i32)
-            } else {
-                None
-            };
 
             interface::cagetable_getref(cageid)
-                .dup_syscall(fd, fd2)
+                .dup_syscall(fd, None)
         }
 
         DUP2_SYSCALL => {
@@ -481,6 +618,188 @@ pub fn lind_syscall_api(
                 .fchmod_syscall(fd, mode)
         }
 
+        CHOWN_SYSCALL => {
+            let path_ptr = (start_address + arg1) as *const u8;
+            let path = unsafe {
+                CStr::from_ptr(path_ptr as *const i8).to_str().unwrap()
+            };
+
+            interface::cagetable_getref(cageid)
+                .chown_syscall(path, arg2 as u32, arg3 as u32)
+        }
+
+        LCHOWN_SYSCALL => {
+            let path_ptr = (start_address + arg1) as *const u8;
+            let path = unsafe {
+                CStr::from_ptr(path_ptr as *const i8).to_str().unwrap()
+            };
+
+            interface::cagetable_getref(cageid)
+                .lchown_syscall(path, arg2 as u32, arg3 as u32)
+        }
+
+        FCHOWN_SYSCALL => {
+            let fd = arg1 as i32;
+
+            interface::cagetable_getref(cageid)
+                .fchown_syscall(fd, arg2 as u32, arg3 as u32)
+        }
+
+        UMASK_SYSCALL => {
+            interface::cagetable_getref(cageid)
+                .umask_syscall(arg1 as u32) as i32
+        }
+
+        OPENAT_SYSCALL => {
+            let path = match interface::types::get_cstr(start_address + arg2) {
+                Ok(path_str) => path_str,
+                Err(_) => return -1,
+            };
+
+            interface::cagetable_getref(cageid)
+                .openat_syscall(arg1 as i32, path, arg3 as i32, arg4 as u32)
+        }
+
+        MKDIRAT_SYSCALL => {
+            let path = match interface::types::get_cstr(start_address + arg2) {
+                Ok(path_str) => path_str,
+                Err(_) => return -1,
+            };
+
+            interface::cagetable_getref(cageid)
+                .mkdirat_syscall(arg1 as i32, path, arg3 as u32)
+        }
+
+        UNLINKAT_SYSCALL => {
+            let path = match interface::types::get_cstr(start_address + arg2) {
+                Ok(path_str) => path_str,
+                Err(_) => return -1,
+            };
+
+            interface::cagetable_getref(cageid)
+                .unlinkat_syscall(arg1 as i32, path, arg3 as i32)
+        }
+
+        RENAMEAT2_SYSCALL => {
+            let oldpath = match interface::types::get_cstr(start_address + arg2) {
+                Ok(path_str) => path_str,
+                Err(_) => return -1,
+            };
+            let newpath = match interface::types::get_cstr(start_address + arg4) {
+                Ok(path_str) => path_str,
+                Err(_) => return -1,
+            };
+
+            interface::cagetable_getref(cageid)
+                .renameat2_syscall(arg1 as i32, oldpath, arg3 as i32, newpath, arg5 as u32)
+        }
+
+        FSTATAT_SYSCALL => {
+            let path = match interface::types::get_cstr(start_address + arg2) {
+                Ok(path_str) => path_str,
+                Err(_) => return -1,
+            };
+            let buf = match interface::get_statdatastruct(start_address + arg3) {
+                Ok(val) => val,
+                Err(errno) => {
+                    return errno;
+                }
+            };
+
+            interface::cagetable_getref(cageid)
+                .fstatat_syscall(arg1 as i32, path, buf, arg4 as i32)
+        }
+
+        LINKAT_SYSCALL => {
+            let oldpath = match interface::types::get_cstr(start_address + arg2) {
+                Ok(path_str) => path_str,
+                Err(_) => return -1,
+            };
+            let newpath = match interface::types::get_cstr(start_address + arg4) {
+                Ok(path_str) => path_str,
+                Err(_) => return -1,
+            };
+
+            interface::cagetable_getref(cageid)
+                .linkat_syscall(arg1 as i32, oldpath, arg3 as i32, newpath, arg5 as i32)
+        }
+
+        FCHMODAT_SYSCALL => {
+            let path = match interface::types::get_cstr(start_address + arg2) {
+                Ok(path_str) => path_str,
+                Err(_) => return -1,
+            };
+
+            interface::cagetable_getref(cageid)
+                .fchmodat_syscall(arg1 as i32, path, arg3 as u32, arg4 as i32)
+        }
+
+        SYMLINK_SYSCALL => {
+            let target = match interface::types::get_cstr(start_address + arg1) {
+                Ok(path_str) => path_str,
+                Err(_) => return -1,
+            };
+            let linkpath = match interface::types::get_cstr(start_address + arg2) {
+                Ok(path_str) => path_str,
+                Err(_) => return -1,
+            };
+
+            interface::cagetable_getref(cageid)
+                .symlink_syscall(target, linkpath)
+        }
+
+        READLINK_SYSCALL => {
+            let path = match interface::types::get_cstr(start_address + arg1) {
+                Ok(path_str) => path_str,
+                Err(_) => return -1,
+            };
+            let buf = (start_address + arg2) as *mut u8;
+
+            interface::cagetable_getref(cageid)
+                .readlink_syscall(path, buf, arg3 as usize)
+        }
+
+        LSTAT_SYSCALL => {
+            let path = match interface::types::get_cstr(start_address + arg1) {
+                Ok(path_str) => path_str,
+                Err(_) => return -1,
+            };
+            let buf = match interface::get_statdatastruct(start_address + arg2) {
+                Ok(val) => val,
+                Err(errno) => {
+                    return errno;
+                }
+            };
+
+            interface::cagetable_getref(cageid)
+                .lstat_syscall(path, buf)
+        }
+
+        SYMLINKAT_SYSCALL => {
+            let target = match interface::types::get_cstr(start_address + arg1) {
+                Ok(path_str) => path_str,
+                Err(_) => return -1,
+            };
+            let linkpath = match interface::types::get_cstr(start_address + arg3) {
+                Ok(path_str) => path_str,
+                Err(_) => return -1,
+            };
+
+            interface::cagetable_getref(cageid)
+                .symlinkat_syscall(target, arg2 as i32, linkpath)
+        }
+
+        READLINKAT_SYSCALL => {
+            let path = match interface::types::get_cstr(start_address + arg2) {
+                Ok(path_str) => path_str,
+                Err(_) => return -1,
+            };
+            let buf = (start_address + arg3) as *mut u8;
+
+            interface::cagetable_getref(cageid)
+                .readlinkat_syscall(arg1 as i32, path, buf, arg4 as usize)
+        }
+
         FXSTAT_SYSCALL => {
             let fd = arg1 as i32;
             let buf = interface::get_statdatastruct(start_address + arg2).unwrap();
@@ -636,6 +955,37 @@ pub fn lind_syscall_api(
             }
         }
 
+        SENDMSG_SYSCALL => {
+            let fd = arg1 as i32;
+            let msg = match interface::get_msghdr(start_address, arg2, false) {
+                Ok(msg) => msg,
+                Err(errno) => return errno,
+            };
+            let flags = arg3 as i32;
+
+            interface::cagetable_getref(cageid)
+                .sendmsg_syscall(fd, &msg.hdr, flags)
+        }
+
+        RECVMSG_SYSCALL => {
+            let fd = arg1 as i32;
+            let mut msg = match interface::get_msghdr(start_address, arg2, true) {
+                Ok(msg) => msg,
+                Err(errno) => return errno,
+            };
+            let flags = arg3 as i32;
+
+            let cage = interface::cagetable_getref(cageid);
+            let rv = cage.recvmsg_syscall(fd, &mut msg.hdr, flags);
+            if rv >= 0 {
+                // fds that fit in the host's control buffer but not in the cage's
+                for fd in interface::copy_out_msghdr(start_address, arg2, &msg) {
+                    cage.close_syscall(fd);
+                }
+            }
+            rv
+        }
+
         FLOCK_SYSCALL => {
             let virtual_fd = arg1 as i32;
             let operation = arg2 as i32;
@@ -662,6 +1012,19 @@ pub fn lind_syscall_api(
                 .shmat_syscall(shmid, shmaddr, shmflg)
         }
 
+        SHMCTL_SYSCALL => {
+            let shmid = arg1 as i32;
+            let cmd = arg2 as i32;
+            let buf = if interface::arg_nullity(arg3) {
+                None
+            } else {
+                Some(interface::get_shmidstruct(start_address + arg3).unwrap())
+            };
+
+            interface::cagetable_getref(cageid)
+                .shmctl_syscall(shmid, cmd, buf)
+        }
+
         SHMDT_SYSCALL => {
             let shmaddr = (start_address + arg1) as *mut u8;
             
@@ -669,6 +1032,158 @@ pub fn lind_syscall_api(
                 .shmdt_syscall(shmaddr)
         }
 
+        SHM_OPEN_SYSCALL => {
+            let name = match interface::types::get_cstr(start_address + arg1) {
+                Ok(name_str) => name_str,
+                Err(e) => return e,
+            };
+            let oflag = arg2 as i32;
+            let mode = arg3 as u32;
+
+            interface::cagetable_getref(cageid)
+                .shm_open_syscall(name, oflag, mode)
+        }
+
+        SHM_UNLINK_SYSCALL => {
+            let name = match interface::types::get_cstr(start_address + arg1) {
+                Ok(name_str) => name_str,
+                Err(e) => return e,
+            };
+
+            interface::cagetable_getref(cageid)
+                .shm_unlink_syscall(name)
+        }
+
+        MSGGET_SYSCALL => {
+            let key = arg1 as i32;
+            let msgflg = arg2 as i32;
+
+            interface::cagetable_getref(cageid)
+                .msgget_syscall(key, msgflg)
+        }
+
+        MSGSND_SYSCALL => {
+            let msqid = arg1 as i32;
+            if interface::arg_nullity(arg2) {
+                return syscall_error(Errno::EFAULT, "msgsnd", "msgp is not a valid address");
+            }
+            let msgp = (start_address + arg2) as *const u8;
+            let msgsz = arg3 as usize;
+            let msgflg = arg4 as i32;
+
+            interface::cagetable_getref(cageid)
+                .msgsnd_syscall(msqid, msgp, msgsz, msgflg)
+        }
+
+        MSGRCV_SYSCALL => {
+            let msqid = arg1 as i32;
+            if interface::arg_nullity(arg2) {
+                return syscall_error(Errno::EFAULT, "msgrcv", "msgp is not a valid address");
+            }
+            let msgp = (start_address + arg2) as *mut u8;
+            let msgsz = arg3 as usize;
+            let msgtyp = arg4 as i32;
+            let msgflg = arg5 as i32;
+
+            interface::cagetable_getref(cageid)
+                .msgrcv_syscall(msqid, msgp, msgsz, msgtyp, msgflg)
+        }
+
+        MSGCTL_SYSCALL => {
+            let msqid = arg1 as i32;
+            let cmd = arg2 as i32;
+            let buf = if interface::arg_nullity(arg3) {
+                None
+            } else {
+                Some(interface::get_msqidstruct(start_address + arg3).unwrap())
+            };
+
+            interface::cagetable_getref(cageid)
+                .msgctl_syscall(msqid, cmd, buf)
+        }
+
+        SEMGET_SYSCALL => {
+            let key = arg1 as i32;
+            let nsems = arg2 as i32;
+            let semflg = arg3 as i32;
+
+            interface::cagetable_getref(cageid)
+                .semget_syscall(key, nsems, semflg)
+        }
+
+        SEMOP_SYSCALL | SEMTIMEDOP_SYSCALL => {
+            let semid = arg1 as i32;
+            let nsops = arg3 as usize;
+            if nsops > 0 && interface::arg_nullity(arg2) {
+                return syscall_error(Errno::EFAULT, "semop", "sops is not a valid address");
+            }
+            let sops = if nsops == 0 {
+                &[][..]
+            } else {
+                unsafe { std::slice::from_raw_parts((start_address + arg2) as *const interface::SembufStruct, nsops) }
+            };
+            let timeout = if call_number == SEMOP_SYSCALL || interface::arg_nullity(arg4) {
+                None
+            } else {
+                let timespec = get_onearg!(interface::get_timespec(start_address + arg4));
+                if timespec.tv_sec < 0 || timespec.tv_nsec < 0 || timespec.tv_nsec >= 1000000000 {
+                    return syscall_error(Errno::EINVAL, "semtimedop", "timeout is not a valid time");
+                }
+                Some(interface::RustDuration::new(timespec.tv_sec as u64, timespec.tv_nsec as u32))
+            };
+
+            interface::cagetable_getref(cageid)
+                .semtimedop_syscall(semid, sops, timeout)
+        }
+
+        SEMCTL_SYSCALL => {
+            let semid = arg1 as i32;
+            let semnum = arg2 as i32;
+            let cmd = arg3 as i32;
+            // the union semun is passed by value, so it holds either val or a pointer
+            let arg = if cmd == libc::SETVAL {
+                interface::SemunUnion { val: arg4 as i32 }
+            } else if interface::arg_nullity(arg4) {
+                interface::SemunUnion { array: std::ptr::null_mut() }
+            } else {
+                interface::SemunUnion { array: (start_address + arg4) as *mut u16 }
+            };
+
+            interface::cagetable_getref(cageid)
+                .semctl_syscall(semid, semnum, cmd, arg)
+        }
+
+        SEM_OPEN_SYSCALL => {
+            let name = match interface::types::get_cstr(start_address + arg1) {
+                Ok(name_str) => name_str,
+                Err(e) => return e,
+            };
+            let oflag = arg2 as i32;
+            let mode = arg3 as u32;
+            let value = arg4 as u32;
+            let sem_handle = arg5 as u32;
+
+            interface::cagetable_getref(cageid)
+                .sem_open_syscall(name, oflag, mode, value, sem_handle)
+        }
+
+        SEM_CLOSE_SYSCALL => {
+            let sem_handle = arg1 as u32;
+
+            interface::cagetable_getref(cageid)
+                .sem_close_syscall(sem_handle)
+        }
+
+        SEM_UNLINK_SYSCALL => {
+            let name = match interface::types::get_cstr(start_address + arg1) {
+                Ok(name_str) => name_str,
+                Err(e) => return e,
+            };
+
+            interface::cagetable_getref(cageid)
+                .sem_unlink_syscall(name)
+        }
+
         MUTEX_DESTROY_SYSCALL => {
             let mutex_handle = arg1 as i32;
 
@@ -800,6 +1315,84 @@ pub fn lind_syscall_api(
                 .getegid_syscall()
         }
 
+        SETUID_SYSCALL => {
+            interface::cagetable_getref(cageid)
+                .setuid_syscall(arg1 as u32)
+        }
+
+        SETEUID_SYSCALL => {
+            interface::cagetable_getref(cageid)
+                .seteuid_syscall(arg1 as u32)
+        }
+
+        SETREUID_SYSCALL => {
+            interface::cagetable_getref(cageid)
+                .setreuid_syscall(arg1 as u32, arg2 as u32)
+        }
+
+        SETRESUID_SYSCALL => {
+            interface::cagetable_getref(cageid)
+                .setresuid_syscall(arg1 as u32, arg2 as u32, arg3 as u32)
+        }
+
+        GETRESUID_SYSCALL => {
+            let ruid = unsafe { &mut *((start_address + arg1) as *mut u32) };
+            let euid = unsafe { &mut *((start_address + arg2) as *mut u32) };
+            let suid = unsafe { &mut *((start_address + arg3) as *mut u32) };
+            interface::cagetable_getref(cageid)
+                .getresuid_syscall(ruid, euid, suid)
+        }
+
+        SETGID_SYSCALL => {
+            interface::cagetable_getref(cageid)
+                .setgid_syscall(arg1 as u32)
+        }
+
+        SETEGID_SYSCALL => {
+            interface::cagetable_getref(cageid)
+                .setegid_syscall(arg1 as u32)
+        }
+
+        SETREGID_SYSCALL => {
+            interface::cagetable_getref(cageid)
+                .setregid_syscall(arg1 as u32, arg2 as u32)
+        }
+
+        SETRESGID_SYSCALL => {
+            interface::cagetable_getref(cageid)
+                .setresgid_syscall(arg1 as u32, arg2 as u32, arg3 as u32)
+        }
+
+        GETRESGID_SYSCALL => {
+            let rgid = unsafe { &mut *((start_address + arg1) as *mut u32) };
+            let egid = unsafe { &mut *((start_address + arg2) as *mut u32) };
+            let sgid = unsafe { &mut *((start_address + arg3) as *mut u32) };
+            interface::cagetable_getref(cageid)
+                .getresgid_syscall(rgid, egid, sgid)
+        }
+
+        GETGROUPS_SYSCALL => {
+            let size = arg1 as i32;
+            let list = if size <= 0 {
+                &mut [][..]
+            } else {
+                unsafe { std::slice::from_raw_parts_mut((start_address + arg2) as *mut u32, size as usize) }
+            };
+            interface::cagetable_getref(cageid)
+                .getgroups_syscall(size, list)
+        }
+
+        SETGROUPS_SYSCALL => {
+            let size = arg1 as usize;
+            let list = if size == 0 {
+                &[][..]
+            } else {
+                unsafe { std::slice::from_raw_parts((start_address + arg2) as *const u32, size) }
+            };
+            interface::cagetable_getref(cageid)
+                .setgroups_syscall(list)
+        }
+
         EPOLL_CREATE_SYSCALL => {
             let size = arg1 as i32;
             
@@ -811,11 +1404,14 @@ pub fn lind_syscall_api(
             let virtual_fd = arg1 as i32;
             let level = arg2 as i32;
             let optname = arg3 as i32;
-            let optval = (start_address + arg4) as *mut u8;
-            let optlen = arg5 as u32;  
-            
+            let mut optval = match interface::get_sockoptval(start_address, arg4, arg5 as u32, level, optname) {
+                Ok(optval) => optval,
+                Err(errno) => return errno,
+            };
+            let optlen = optval.len() as u32;
+
             interface::cagetable_getref(cageid)
-                .setsockopt_syscall( virtual_fd, level, optname, optval, optlen)
+                .setsockopt_syscall( virtual_fd, level, optname, optval.as_mut_ptr(), optlen)
         }
 
         SHUTDOWN_SYSCALL => {
@@ -867,10 +1463,14 @@ pub fn lind_syscall_api(
         } 
 
         GETIFADDRS_SYSCALL => {
-            let buf = (start_address + arg1) as *mut u8;
             let count = arg2 as usize;
-            interface::cagetable_getref(cageid)
-                .getifaddrs_syscall(buf, count)
+            let mut ifaddrs = Vec::new();
+            let rv = interface::cagetable_getref(cageid)
+                .getifaddrs_syscall(&mut ifaddrs);
+            if rv < 0 {
+                return rv;
+            }
+            interface::copy_out_ifaddrs(start_address, arg1, count, &ifaddrs)
         }
 
         KILL_SYSCALL => {
@@ -946,11 +1546,26 @@ pub fn lind_syscall_api(
             let level = arg2 as i32;
             let optname = arg3 as i32;
 
-            let optval_ptr = (start_address + arg4) as *mut i32;
-            let optval = unsafe { &mut *optval_ptr };
+            if interface::arg_nullity(arg5) {
+                return syscall_error(
+                    Errno::EFAULT,
+                    "getsockopt",
+                    "optlen is not a valid address",
+                );
+            }
+            // the value is written straight into the cage, which won't do for a layout that differs
+            if interface::sockopt_layout(level, optname) == interface::SockoptLayout::GroupReq {
+                return syscall_error(
+                    Errno::EINVAL,
+                    "getsockopt",
+                    "the option's value has a different layout in the cage",
+                );
+            }
+            let optval = if interface::arg_nullity(arg4) { std::ptr::null_mut() } else { (start_address + arg4) as *mut u8 };
+            let optlen = unsafe { &mut *((start_address + arg5) as *mut u32) };
 
             interface::cagetable_getref(cageid)
-                .getsockopt_syscall(virtual_fd, level, optname, optval)
+                .getsockopt_syscall(virtual_fd, level, optname, optval, optlen)
         }
 
         SOCKETPAIR_SYSCALL => {
@@ -983,6 +1598,59 @@ pub fn lind_syscall_api(
                 .fork_syscall(id)
         }
 
+        WAIT_SYSCALL => {
+            let status = if interface::arg_nullity(arg1) {
+                None
+            } else {
+                Some(unsafe { &mut *((start_address + arg1) as *mut i32) })
+            };
+            interface::cagetable_getref(cageid)
+                .wait_syscall(status)
+        }
+
+        WAITPID_SYSCALL => {
+            let pid = arg1 as i32;
+            let status = if interface::arg_nullity(arg2) {
+                None
+            } else {
+                Some(unsafe { &mut *((start_address + arg2) as *mut i32) })
+            };
+            let options = arg3 as i32;
+            interface::cagetable_getref(cageid)
+                .waitpid_syscall(pid, status, options)
+        }
+
+        WAIT4_SYSCALL => {
+            let pid = arg1 as i32;
+            let status = if interface::arg_nullity(arg2) {
+                None
+            } else {
+                Some(unsafe { &mut *((start_address + arg2) as *mut i32) })
+            };
+            let options = arg3 as i32;
+            let rusage = if interface::arg_nullity(arg4) {
+                None
+            } else {
+                Some(unsafe { &mut *((start_address + arg4) as *mut interface::Rusage) })
+            };
+            interface::cagetable_getref(cageid)
+                .wait4_syscall(pid, status, options, rusage)
+        }
+
+        GETRLIMIT_SYSCALL => {
+            let resource = arg1 as u64;
+            let rlimit = unsafe { &mut *((start_address + arg2) as *mut interface::Rlimit) };
+            interface::cagetable_getref(cageid)
+                .getrlimit_syscall(resource, rlimit)
+        }
+
+        SETRLIMIT_SYSCALL => {
+            let resource = arg1 as u64;
+            let rlimit = unsafe { &*((start_address + arg2) as *const interface::Rlimit) };
+            interface::cagetable_getref(cageid)
+                .setrlimit_syscall(resource, rlimit)
+        }
+
         FUTEX_SYSCALL => {
             let uaddr = (start_address + arg1) as u64;
             let futex_op = arg2 as u32;
@@ -1050,7 +1718,7 @@ pub fn lindgetsighandler(cageid: u64, signo: i32) -> u32 {
     let cage = interface::cagetable_getref(cageid);
     let pthreadid = interface::get_pthreadid();
     let sigset = cage.sigset.get(&pthreadid).unwrap(); // these lock sigset dashmaps for concurrency
-    let pendingset = cage.sigset.get(&pthreadid).unwrap();
+    let pendingset = cage.pendingsigset.get(&pthreadid).unwrap();
 
     if !interface::lind_sigismember(sigset.load(interface::RustAtomicOrdering::Relaxed), signo) {
         return match cage.signalhandler.get(&signo) {
@@ -1060,8 +1728,8 @@ pub fn lindgetsighandler(cageid: u64, signo: i32) -> u32 {
             None => 0, // if we dont have a handler return 0
         };
     } else {
-        let mutpendingset = sigset.load(interface::RustAtomicOrdering::Relaxed);
-        sigset.store(
+        let mutpendingset = pendingset.load(interface::RustAtomicOrdering::Relaxed);
+        pendingset.store(
             interface::lind_sigaddset(mutpendingset, signo),
             interface::RustAtomicOrdering::Relaxed,
         );
@@ -1070,40 +1738,84 @@ pub fn lindgetsighandler(cageid: u64, signo: i32) -> u32 {
     }
 }
 
+#[no_mangle]
+pub fn lindsetcageroot(cageid: u64, lindroot: &str) {
+    let cage = interface::cagetable_getref(cageid);
+    cage.changeroot(lindroot);
+}
+
+// Puts the cage into a network namespace made with interface::netns_create, or back
+// onto the host network with interface::HOST_NETNS
+#[no_mangle]
+pub fn lindsetcagenetns(cageid: u64, nsid: u64) -> i32 {
+    if !interface::netns_exists(nsid) {
+        return syscall_error(Errno::EINVAL, "lindsetcagenetns", "there is no such network namespace");
+    }
+    let cage = interface::cagetable_getref(cageid);
+    cage.changenetns(nsid);
+    0
+}
+
 #[no_mangle]
 pub fn lindrustinit(verbosity: isize) {
+    lindrustinit_with_root(verbosity, DEFAULT_LIND_ROOT);
+}
+
+// Same as lindrustinit, but every cage created from here on (through fork/exec of
+// the init cage) is rooted at the given host directory instead of DEFAULT_LIND_ROOT
+#[no_mangle]
+pub fn lindrustinit_with_root(verbosity: isize, lindroot: &str) {
     let _ = interface::VERBOSE.set(verbosity); //assigned to suppress unused result warning
     interface::cagetable_init();
 
-    // TODO: needs to add close() that handling im-pipe
     fdtables::register_close_handlers(FDKIND_KERNEL, fdtables::NULL_FUNC, kernel_close);
+    fdtables::register_close_handlers(FDKIND_IMPIPE, fdtables::NULL_FUNC, impipe_close);
+    fdtables::register_close_handlers(FDKIND_IMSOCK, fdtables::NULL_FUNC, imsock_close);
+    fdtables::register_close_handlers(FDKIND_IMSHM, fdtables::NULL_FUNC, imshm_close);
+    // fds the kernel knows nothing about tell poll/select/epoll when they are ready
+    interface::register_poll_handler(FDKIND_IMPIPE, interface::pipe_poll_events);
+    interface::register_poll_handler(FDKIND_IMSOCK, interface::unix_socket_poll_events);
+    interface::register_poll_handler(FDKIND_IMSHM, interface::shm_poll_events);
+    interface::register_poll_handler(fdtables::FDT_KINDEPOLL, epoll_poll_events);
     
     let utilcage = Cage {
         cageid: 0,
         cwd: interface::RustLock::new(interface::RustRfc::new(interface::RustPathBuf::from("/"))),
+        lindroot: interface::RustLock::new(interface::RustRfc::new(clean_lindroot(lindroot))),
         parent: 0,
         cancelstatus: interface::RustAtomicBool::new(false),
         getgid: interface::RustAtomicI32::new(-1),
         getuid: interface::RustAtomicI32::new(-1),
         getegid: interface::RustAtomicI32::new(-1),
         geteuid: interface::RustAtomicI32::new(-1),
+        getsgid: interface::RustAtomicI32::new(-1),
+        getsuid: interface::RustAtomicI32::new(-1),
+        groups: interface::RustLock::new(vec![]),
+        umask: interface::RustAtomicU32::new(DEFAULT_UMASK),
         rev_shm: interface::Mutex::new(vec![]),
         mutex_table: interface::RustLock::new(vec![]),
         cv_table: interface::RustLock::new(vec![]),
         sem_table: interface::RustHashMap::new(),
+        named_sem_table: interface::RustHashMap::new(),
         thread_table: interface::RustHashMap::new(),
         signalhandler: interface::RustHashMap::new(),
         sigset: interface::RustHashMap::new(),
         pendingsigset: interface::RustHashMap::new(),
         main_threadid: interface::RustAtomicU64::new(0),
         interval_timer: interface::IntervalTimer::new(0),
+        zombies: interface::Mutex::new(vec![]),
+        zombie_cv: interface::Condvar::new(),
+        child_num: interface::RustAtomicU64::new(0),
+        rlimits: default_rlimits(),
+        mapped_ranges: interface::Mutex::new(vec![]),
+        netns: interface::RustAtomicU64::new(interface::HOST_NETNS),
     };
 
     interface::cagetable_insert(0, utilcage);
     fdtables::init_empty_cage(0);
     // Set the first 3 fd to STDIN / STDOUT / STDERR
     // STDIN
-    let dev_null = CString::new("/home/lind/lind_project/src/safeposix-rust/tmp/dev/null").unwrap();
+    let dev_null = CString::new(format!("{}/dev/null", clean_lindroot(lindroot).to_str().unwrap())).unwrap();
     unsafe {
         libc::open(dev_null.as_ptr(), libc::O_RDONLY);
         libc::open(dev_null.as_ptr(), libc::O_WRONLY);
@@ -1120,22 +1832,34 @@ pub fn lindrustinit(verbosity: isize) {
     let initcage = Cage {
         cageid: 1,
         cwd: interface::RustLock::new(interface::RustRfc::new(interface::RustPathBuf::from("/"))),
+        lindroot: interface::RustLock::new(interface::RustRfc::new(clean_lindroot(lindroot))),
         parent: 1,
         cancelstatus: interface::RustAtomicBool::new(false),
         getgid: interface::RustAtomicI32::new(-1),
         getuid: interface::RustAtomicI32::new(-1),
         getegid: interface::RustAtomicI32::new(-1),
         geteuid: interface::RustAtomicI32::new(-1),
+        getsgid: interface::RustAtomicI32::new(-1),
+        getsuid: interface::RustAtomicI32::new(-1),
+        groups: interface::RustLock::new(vec![]),
+        umask: interface::RustAtomicU32::new(DEFAULT_UMASK),
         rev_shm: interface::Mutex::new(vec![]),
         mutex_table: interface::RustLock::new(vec![]),
         cv_table: interface::RustLock::new(vec![]),
         sem_table: interface::RustHashMap::new(),
+        named_sem_table: interface::RustHashMap::new(),
         thread_table: interface::RustHashMap::new(),
         signalhandler: interface::RustHashMap::new(),
         sigset: interface::RustHashMap::new(),
         pendingsigset: interface::RustHashMap::new(),
         main_threadid: interface::RustAtomicU64::new(0),
         interval_timer: interface::IntervalTimer::new(1),
+        zombies: interface::Mutex::new(vec![]),
+        zombie_cv: interface::Condvar::new(),
+        child_num: interface::RustAtomicU64::new(0),
+        rlimits: default_rlimits(),
+        mapped_ranges: interface::Mutex::new(vec![]),
+        netns: interface::RustAtomicU64::new(interface::HOST_NETNS),
     };
     interface::cagetable_insert(1, initcage);
     fdtables::init_empty_cage(1);
@@ -1152,4 +1876,6 @@ pub fn lindrustinit(verbosity: isize) {
 #[no_mangle]
 pub fn lindrustfinalize() {
     interface::cagetable_clear();
+    // owners recorded for files without xattr support only last as long as lind runs
+    clear_virtual_owners();
 }
@@ -2,9 +2,13 @@
 #![allow(dead_code)]
 
 // use super::syscalls::fs_constants::*;
-// use super::syscalls::sys_constants::*;
+use super::syscalls::sys_constants::{DEFAULT_GID, DEFAULT_UID};
 use crate::interface;
 
+use std::ffi::{CString, OsString};
+use std::mem;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
 use super::cage::Cage;
 
 pub fn convpath(cpath: &str) ->
@@ -38,3 +42,463 @@ pub fn normpath(origp: interface::RustPathBuf, cage: &Cage) -> interface::RustPa
     }
     newp
 }
+
+// Every path a cage hands us is cage-visible.  Before it reaches the host kernel it
+// is normalized against the cage's cwd and then prefixed with the cage's sandbox root,
+// so that cages with different roots never see each other's trees.
+pub fn hostpath(origp: &str, cage: &Cage) -> String {
+    let relpath = normpath(convpath(origp), cage);
+    let root = cage.lindroot.read();
+    format!("{}{}", root.to_str().unwrap(), relpath.to_str().unwrap())
+}
+
+// Reverse of hostpath: turn a host path back into the path the cage sees.
+// Returns None if the host path does not live under the cage's sandbox root.
+pub fn strip_lindroot(hostp: &str, cage: &Cage) -> Option<interface::RustPathBuf> {
+    let root = cage.lindroot.read();
+    let rootstr = root.to_str().unwrap();
+    if !hostp.starts_with(rootstr) {
+        return None;
+    }
+    let rest = &hostp[rootstr.len()..];
+    if rest.is_empty() {
+        return Some(interface::RustPathBuf::from("/"));
+    }
+    if !rest.starts_with('/') {
+        // a sibling of the root which merely shares its prefix (e.g. /tmp2 vs /tmp)
+        return None;
+    }
+    Some(interface::RustPathBuf::from(rest))
+}
+
+// Sandbox roots are stored without a trailing slash so that joining a normalized
+// (always absolute) cage path onto them never produces a double slash.
+pub fn clean_lindroot(root: &str) -> interface::RustPathBuf {
+    let trimmed = root.trim_end_matches('/');
+    interface::RustPathBuf::from(trimmed)
+}
+
+// Joining the cage root onto a path and handing the result to the kernel is not enough
+// to keep a cage inside its root: a symlink inside the tree (say /escape -> /etc) would
+// be followed by the host kernel.  Path-taking syscalls therefore resolve the normalized
+// cage path relative to an O_PATH descriptor for the root with RESOLVE_BENEATH semantics,
+// so every lookup that would leave the root fails with EXDEV.
+//
+// Symlinks made by a cage are the one exception: their targets are stored as the cage gave
+// them, and the kernel refuses to follow an absolute one beneath the root, so those lookups
+// are retried with walk_beneath, which carries on from the root with the target.
+//
+// The *at() calls start a relative path at the directory their dirfd refers to instead. It
+// is looked up from that descriptor, not from a path of it that could be renamed meanwhile,
+// and ".." may lead up from it as far as the root but no further.
+
+const MAXSYMLINKS: usize = 40;
+
+// The result of a resolution: a descriptor for a directory (or, from resolve_beneath,
+// the resolved file itself) plus the name to hand to the *at() variant of a syscall.
+pub struct BeneathPath {
+    pub dirfd: i32,
+    pub name: CString,
+}
+
+impl BeneathPath {
+    // For calls without an *at() form (truncate, chmod, access).  The kernel reaches the
+    // file through the descriptor we already resolved, so no further lookup happens
+    // under the root.  Only meaningful for paths returned by resolve_beneath.
+    pub fn procpath(&self) -> CString {
+        procfd_path(self.dirfd)
+    }
+}
+
+impl Drop for BeneathPath {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.dirfd);
+        }
+    }
+}
+
+// Cage path made relative to the root, with "." standing for the root itself
+fn beneath_relpath(origp: &str, cage: &Cage) -> interface::RustPathBuf {
+    let abspath = normpath(convpath(origp), cage);
+    let relpath = abspath.strip_prefix("/").unwrap().to_path_buf();
+    if relpath.as_os_str().is_empty() {
+        interface::RustPathBuf::from(".")
+    } else {
+        relpath
+    }
+}
+
+// The path relative to where its lookup starts: the root, or dirfd for a relative path of
+// an *at() call. The ".." components of the latter are left for the lookup to follow, as
+// only the kernel knows what is above dirfd.
+fn beneath_relpath_at(dirfd: Option<i32>, origp: &str, cage: &Cage) -> interface::RustPathBuf {
+    if dirfd.is_none() || origp.starts_with('/') {
+        return beneath_relpath(origp, cage);
+    }
+    let relpath: interface::RustPathBuf = convpath(origp)
+        .components()
+        .filter(|comp| matches!(comp, interface::RustPathComponent::Normal(_) | interface::RustPathComponent::ParentDir))
+        .collect();
+    if relpath.as_os_str().is_empty() {
+        interface::RustPathBuf::from(".")
+    } else {
+        relpath
+    }
+}
+
+// Whether two descriptors refer to the same directory
+fn same_file(fd1: i32, fd2: i32) -> bool {
+    match (fstat_beneath(fd1), fstat_beneath(fd2)) {
+        (Ok(st1), Ok(st2)) => st1.st_dev == st2.st_dev && st1.st_ino == st2.st_ino,
+        _ => false,
+    }
+}
+
+fn open_lindroot(cage: &Cage) -> Result<i32, i32> {
+    let root = CString::new(cage.lindroot.read().to_str().unwrap()).unwrap();
+    let rootfd = unsafe { libc::open(root.as_ptr(), libc::O_PATH | libc::O_DIRECTORY | libc::O_CLOEXEC) };
+    if rootfd < 0 {
+        return Err(interface::get_errno());
+    }
+    Ok(rootfd)
+}
+
+// openat2(2) with RESOLVE_BENEATH from dirfd (the root, or the dirfd of an *at() call),
+// falling back to walking the path ourselves on kernels that predate it.  Errors are
+// returned as positive errnos for handle_errno.
+fn openat_beneath(rootfd: i32, dirfd: i32, relpath: &interface::RustPath, flags: i32, mode: u32) -> Result<i32, i32> {
+    let cpath = CString::new(relpath.as_os_str().as_bytes()).unwrap();
+    let mut how: libc::open_how = unsafe { mem::zeroed() };
+    how.flags = flags as u32 as u64;
+    // openat2 is strict about mode: it must be 0 unless a file may be created
+    if flags & libc::O_CREAT != 0 || flags & libc::O_TMPFILE == libc::O_TMPFILE {
+        how.mode = mode as u64;
+    }
+    how.resolve = libc::RESOLVE_BENEATH | libc::RESOLVE_NO_MAGICLINKS;
+
+    loop {
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_openat2,
+                dirfd,
+                cpath.as_ptr(),
+                &how as *const libc::open_how,
+                mem::size_of::<libc::open_how>(),
+            )
+        };
+        if fd >= 0 {
+            return Ok(fd as i32);
+        }
+        match interface::get_errno() {
+            libc::ENOSYS => return walk_beneath(rootfd, dirfd, relpath, flags, mode),
+            // possibly an absolute symlink made by a cage, which only the walk can follow,
+            // or a ".." leading up from the dirfd of an *at() call
+            libc::EXDEV => return walk_beneath(rootfd, dirfd, relpath, flags, mode),
+            // a concurrent rename or mount under the root; the kernel asks us to retry
+            libc::EAGAIN => continue,
+            errno => return Err(errno),
+        }
+    }
+}
+
+// Userspace stand-in for RESOLVE_BENEATH.  Every component is opened with O_NOFOLLOW
+// relative to the one before it and symlinks are expanded by hand, so the walk can
+// refuse ".." past the root (rootfd) and follow absolute targets from the root rather
+// than the host's "/".  A walk starting at the dirfd of an *at() call
+// goes up from it by ".." until it reaches the root.
+fn walk_beneath(rootfd: i32, dirfd: i32, relpath: &interface::RustPath, flags: i32, mode: u32) -> Result<i32, i32> {
+    let mut pending: interface::RustDeque<OsString> = relpath
+        .components()
+        .filter(|comp| matches!(comp, interface::RustPathComponent::Normal(_) | interface::RustPathComponent::ParentDir))
+        .map(|comp| comp.as_os_str().to_os_string())
+        .collect();
+
+    let startfd = unsafe { libc::fcntl(dirfd, libc::F_DUPFD_CLOEXEC, 0) };
+    if startfd < 0 {
+        return Err(interface::get_errno());
+    }
+    let mut dirstack = vec![startfd];
+    let mut links = 0;
+
+    let result = loop {
+        let curfd = *dirstack.last().unwrap();
+        let comp = match pending.pop_front() {
+            Some(comp) => comp,
+            // the path ended on a directory we already hold (e.g. "." or "a/..")
+            None => {
+                let fd = unsafe { libc::openat(curfd, b".\0".as_ptr() as *const libc::c_char, flags, mode) };
+                break if fd < 0 { Err(interface::get_errno()) } else { Ok(fd) };
+            }
+        };
+
+        if comp == ".." {
+            if dirstack.len() == 1 {
+                if same_file(curfd, rootfd) {
+                    break Err(libc::EXDEV);
+                }
+                let parentfd = unsafe {
+                    libc::openat(curfd, b"..\0".as_ptr() as *const libc::c_char, libc::O_PATH | libc::O_DIRECTORY | libc::O_CLOEXEC)
+                };
+                if parentfd < 0 {
+                    break Err(interface::get_errno());
+                }
+                // the host's "/" is its own parent, so a dirfd that isn't under the root ends here
+                if same_file(parentfd, curfd) {
+                    unsafe { libc::close(parentfd) };
+                    break Err(libc::EXDEV);
+                }
+                unsafe { libc::close(curfd) };
+                dirstack[0] = parentfd;
+                continue;
+            }
+            unsafe {
+                libc::close(dirstack.pop().unwrap());
+            }
+            continue;
+        }
+
+        let cname = CString::new(comp.as_bytes()).unwrap();
+        let last = pending.is_empty();
+        let mut st: libc::stat = unsafe { mem::zeroed() };
+        let statret = unsafe { libc::fstatat(curfd, cname.as_ptr(), &mut st, libc::AT_SYMLINK_NOFOLLOW) };
+        let islink = statret == 0 && (st.st_mode & libc::S_IFMT) == libc::S_IFLNK;
+
+        if islink && !(last && flags & libc::O_NOFOLLOW != 0) {
+            links += 1;
+            if links > MAXSYMLINKS {
+                break Err(libc::ELOOP);
+            }
+            let mut buf = vec![0u8; libc::PATH_MAX as usize];
+            let len = unsafe {
+                libc::readlinkat(curfd, cname.as_ptr(), buf.as_mut_ptr() as *mut libc::c_char, buf.len())
+            };
+            if len < 0 {
+                break Err(interface::get_errno());
+            }
+            buf.truncate(len as usize);
+            let target = interface::RustPathBuf::from(OsString::from_vec(buf));
+            if target.is_absolute() {
+                // a cage path: carry on from the root with the whole target
+                let restartfd = unsafe { libc::fcntl(rootfd, libc::F_DUPFD_CLOEXEC, 0) };
+                if restartfd < 0 {
+                    break Err(interface::get_errno());
+                }
+                for fd in dirstack.drain(..) {
+                    unsafe {
+                        libc::close(fd);
+                    }
+                }
+                dirstack.push(restartfd);
+            }
+            for tcomp in target.components().rev() {
+                if let interface::RustPathComponent::Normal(_) | interface::RustPathComponent::ParentDir = tcomp {
+                    pending.push_front(tcomp.as_os_str().to_os_string());
+                }
+            }
+            continue;
+        }
+
+        // O_NOFOLLOW here means a symlink swapped in after the fstatat above is
+        // refused rather than followed
+        if last {
+            let fd = unsafe { libc::openat(curfd, cname.as_ptr(), flags | libc::O_NOFOLLOW, mode) };
+            break if fd < 0 { Err(interface::get_errno()) } else { Ok(fd) };
+        }
+        let fd = unsafe {
+            libc::openat(
+                curfd,
+                cname.as_ptr(),
+                libc::O_PATH | libc::O_DIRECTORY | libc::O_NOFOLLOW | libc::O_CLOEXEC,
+            )
+        };
+        if fd < 0 {
+            break Err(interface::get_errno());
+        }
+        dirstack.push(fd);
+    };
+
+    for fd in dirstack {
+        unsafe {
+            libc::close(fd);
+        }
+    }
+    result
+}
+
+// Opens a cage path for real and returns the kernel fd
+pub fn open_beneath(origp: &str, cage: &Cage, oflag: i32, mode: u32) -> Result<i32, i32> {
+    open_beneath_at(None, origp, cage, oflag, mode)
+}
+
+// The *at() form of open_beneath, dirfd being the kernel fd a relative path starts at
+// (None for the cwd)
+pub fn open_beneath_at(dirfd: Option<i32>, origp: &str, cage: &Cage, oflag: i32, mode: u32) -> Result<i32, i32> {
+    let rootfd = open_lindroot(cage)?;
+    let relpath = beneath_relpath_at(dirfd, origp, cage);
+    let startfd = if origp.starts_with('/') { rootfd } else { dirfd.unwrap_or(rootfd) };
+    let ret = openat_beneath(rootfd, startfd, &relpath, oflag, mode);
+    unsafe {
+        libc::close(rootfd);
+    }
+    ret
+}
+
+// Resolves the whole path, following a trailing symlink, to an O_PATH descriptor
+// for the file itself (name is left empty)
+pub fn resolve_beneath(origp: &str, cage: &Cage) -> Result<BeneathPath, i32> {
+    resolve_beneath_at(None, origp, cage)
+}
+
+pub fn resolve_beneath_at(dirfd: Option<i32>, origp: &str, cage: &Cage) -> Result<BeneathPath, i32> {
+    let fd = open_beneath_at(dirfd, origp, cage, libc::O_PATH | libc::O_CLOEXEC, 0)?;
+    Ok(BeneathPath {
+        dirfd: fd,
+        name: CString::new("").unwrap(),
+    })
+}
+
+// Resolves all but the last component, which is returned by name and never followed.
+// This is what calls that create, remove or rename directory entries want.
+pub fn resolve_parent_beneath(origp: &str, cage: &Cage) -> Result<BeneathPath, i32> {
+    resolve_parent_beneath_at(None, origp, cage)
+}
+
+pub fn resolve_parent_beneath_at(dirfd: Option<i32>, origp: &str, cage: &Cage) -> Result<BeneathPath, i32> {
+    let relpath = beneath_relpath_at(dirfd, origp, cage);
+    // a path ending in "." or ".." names the directory it leads to
+    let (parent, name) = match relpath.file_name() {
+        Some(name) => (
+            relpath.parent().map(|p| p.to_path_buf()).unwrap_or_default(),
+            name.to_os_string(),
+        ),
+        None => (relpath.clone(), OsString::from(".")),
+    };
+    let parent = if parent.as_os_str().is_empty() {
+        interface::RustPathBuf::from(".")
+    } else {
+        parent
+    };
+
+    let rootfd = open_lindroot(cage)?;
+    let startfd = if origp.starts_with('/') { rootfd } else { dirfd.unwrap_or(rootfd) };
+    let ret = openat_beneath(rootfd, startfd, &parent, libc::O_PATH | libc::O_DIRECTORY | libc::O_CLOEXEC, 0);
+    unsafe {
+        libc::close(rootfd);
+    }
+    Ok(BeneathPath {
+        dirfd: ret?,
+        name: CString::new(name.as_bytes()).unwrap(),
+    })
+}
+
+// Reaches the file behind a kernel fd by path, which also works for O_PATH fds
+pub fn procfd_path(fd: i32) -> CString {
+    CString::new(format!("/proc/self/fd/{}", fd)).unwrap()
+}
+
+/*
+*   Every file under the root really belongs to whoever runs lind, so the owner a cage sees
+*   is kept apart from the host's. Owners set by chown, or by creating a file with ids other
+*   than the default ones, go in a user.lind.owner xattr that lives and dies with the file.
+*   Files that can't carry user xattrs (symlinks, fifos, sockets, devices, or files the host
+*   user can't write to) are tracked by inode in OWNER_TABLE instead, until their last link
+*   is removed.
+*   Files with no recorded owner belong to DEFAULT_UID/DEFAULT_GID if the host user owns
+*   them, and keep their host ids otherwise.
+*/
+const OWNER_XATTR: &[u8] = b"user.lind.owner\0";
+
+static OWNER_TABLE: interface::RustLazyGlobal<interface::RustHashMap<(u64, u64), (u32, u32)>> =
+    interface::RustLazyGlobal::new(interface::RustHashMap::new);
+
+fn owner_key(st: &libc::stat) -> (u64, u64) {
+    (st.st_dev as u64, st.st_ino as u64)
+}
+
+fn is_symlink(st: &libc::stat) -> bool {
+    st.st_mode & libc::S_IFMT == libc::S_IFLNK
+}
+
+pub fn virtual_owner(fd: i32, st: &libc::stat) -> (u32, u32) {
+    if let Some(owner) = OWNER_TABLE.get(&owner_key(st)) {
+        return *owner;
+    }
+    // getxattr would follow a symlink to its target
+    if !is_symlink(st) {
+        let mut ids = [0u32; 2];
+        let len = unsafe {
+            libc::getxattr(
+                procfd_path(fd).as_ptr(),
+                OWNER_XATTR.as_ptr() as *const libc::c_char,
+                ids.as_mut_ptr() as *mut libc::c_void,
+                mem::size_of_val(&ids),
+            )
+        };
+        if len == mem::size_of_val(&ids) as isize {
+            return (ids[0], ids[1]);
+        }
+    }
+    let uid = if st.st_uid == unsafe { libc::geteuid() } { DEFAULT_UID } else { st.st_uid };
+    let gid = if st.st_gid == unsafe { libc::getegid() } { DEFAULT_GID } else { st.st_gid };
+    (uid, gid)
+}
+
+pub fn set_virtual_owner(fd: i32, st: &libc::stat, uid: u32, gid: u32) -> Result<(), i32> {
+    if !is_symlink(st) {
+        let ids = [uid, gid];
+        let ret = unsafe {
+            libc::setxattr(
+                procfd_path(fd).as_ptr(),
+                OWNER_XATTR.as_ptr() as *const libc::c_char,
+                ids.as_ptr() as *const libc::c_void,
+                mem::size_of_val(&ids),
+                0,
+            )
+        };
+        if ret == 0 {
+            OWNER_TABLE.remove(&owner_key(st));
+            return Ok(());
+        }
+        match interface::get_errno() {
+            // the kernel only allows user xattrs on regular files and directories, and only
+            // lets a host user who may write to the file set them
+            libc::EPERM | libc::ENOTSUP | libc::EACCES => {}
+            errno => return Err(errno),
+        }
+    }
+    OWNER_TABLE.insert(owner_key(st), (uid, gid));
+    Ok(())
+}
+
+// Called with the stat of a name that was just removed, since the inode number may be
+// handed out again once its last link is gone
+pub fn forget_virtual_owner(st: &libc::stat) {
+    if st.st_nlink <= 1 {
+        OWNER_TABLE.remove(&owner_key(st));
+    }
+}
+
+pub fn clear_virtual_owners() {
+    OWNER_TABLE.clear();
+}
+
+// fstat() of an fd returned by the resolve functions above
+pub fn fstat_beneath(fd: i32) -> Result<libc::stat, i32> {
+    let mut st: libc::stat = unsafe { mem::zeroed() };
+    if unsafe { libc::fstat(fd, &mut st) } < 0 {
+        return Err(interface::get_errno());
+    }
+    Ok(st)
+}
+
+// Permission check against the cage's credentials for the file resolve_beneath returned
+pub fn cage_may_access(cage: &Cage, fd: i32, want: i32, use_real_ids: bool) -> Result<(), i32> {
+    let st = fstat_beneath(fd)?;
+    let (uid, gid) = virtual_owner(fd, &st);
+    if cage.permits(uid, gid, st.st_mode, want, use_real_ids) {
+        Ok(())
+    } else {
+        Err(libc::EACCES)
+    }
+}
@@ -1,5 +1,7 @@
 pub mod cage;
 pub mod dispatcher;
 pub mod filesystem;
+pub mod msg;
+pub mod sem;
 pub mod shm;
 pub mod syscalls;
@@ -0,0 +1,143 @@
+// System V message queue metadata
+#![allow(dead_code)]
+
+use crate::interface;
+
+use libc::*;
+use std::collections::VecDeque;
+
+pub static MSG_METADATA: interface::RustLazyGlobal<interface::RustRfc<MsgMetadata>> =
+    interface::RustLazyGlobal::new(|| interface::RustRfc::new(MsgMetadata::init_msg_metadata()));
+
+pub struct MsgMessage {
+    pub mtype: i32,
+    pub mtext: Vec<u8>,
+}
+
+pub struct MsgQueueState {
+    pub msqinfo: interface::MsqidsStruct,
+    pub key: i32,
+    pub messages: VecDeque<MsgMessage>,
+    pub removed: bool,
+}
+
+pub struct MsgQueue {
+    pub state: interface::Mutex<MsgQueueState>,
+    pub cv: interface::Condvar, // notified whenever a message comes or goes, or the queue is changed
+}
+
+impl MsgQueue {
+    pub fn new(key: i32, qbytes: u32, uid: u32, gid: u32, mode: u16) -> MsgQueue {
+        let time = interface::timestamp() as isize;
+        let permstruct = interface::IpcPermStruct {
+            __key: key,
+            uid,
+            gid,
+            cuid: uid,
+            cgid: gid,
+            mode,
+            __pad1: 0,
+            __seq: 0,
+            __pad2: 0,
+            __unused1: 0,
+            __unused2: 0,
+        };
+        let msqinfo = interface::MsqidsStruct {
+            msg_perm: permstruct,
+            msg_stime: 0,
+            msg_rtime: 0,
+            msg_ctime: time,
+            msg_cbytes: 0,
+            msg_qnum: 0,
+            msg_qbytes: qbytes,
+            msg_lspid: 0,
+            msg_lrpid: 0,
+        };
+
+        MsgQueue {
+            state: interface::Mutex::new(MsgQueueState {
+                msqinfo,
+                key,
+                messages: VecDeque::new(),
+                removed: false,
+            }),
+            cv: interface::Condvar::new(),
+        }
+    }
+
+    // IPC_RMID: the queue is gone at once, and cages blocked on it fail with EIDRM
+    pub fn mark_removed(&self) {
+        self.state.lock().removed = true;
+        self.cv.notify_all();
+    }
+}
+
+impl MsgQueueState {
+    // Whether a message of msgsz bytes fits. Like Linux, msg_qbytes also bounds the number of
+    // messages, so a flood of empty ones can't fill up memory.
+    pub fn has_room(&self, msgsz: usize) -> bool {
+        let qbytes = self.msqinfo.msg_qbytes as usize;
+        self.msqinfo.msg_cbytes as usize + msgsz <= qbytes && self.messages.len() < qbytes
+    }
+
+    /*
+     *   The position of the message msgrcv receives: the first one for a msgtyp of 0, the first
+     *   one of type msgtyp (or, with MSG_EXCEPT, of any other type) for a positive msgtyp, and
+     *   the first one of the lowest type up to -msgtyp for a negative one. With MSG_COPY,
+     *   msgtyp is the position itself.
+     */
+    pub fn find_message(&self, msgtyp: i32, msgflg: i32) -> Option<usize> {
+        if msgflg & MSG_COPY != 0 {
+            return if msgtyp >= 0 && (msgtyp as usize) < self.messages.len() {
+                Some(msgtyp as usize)
+            } else {
+                None
+            };
+        }
+        match msgtyp {
+            0 if self.messages.is_empty() => None,
+            0 => Some(0),
+            t if t > 0 && msgflg & MSG_EXCEPT != 0 => self.messages.iter().position(|msg| msg.mtype != t),
+            t if t > 0 => self.messages.iter().position(|msg| msg.mtype == t),
+            t => {
+                let limit = -(t as i64);
+                self.messages
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, msg)| msg.mtype as i64 <= limit)
+                    .min_by_key(|(_, msg)| msg.mtype)
+                    .map(|(index, _)| index)
+            }
+        }
+    }
+}
+
+pub struct MsgMetadata {
+    pub nextid: interface::RustAtomicI32,
+    pub msgkeyidtable: interface::RustHashMap<i32, i32>,
+    pub msgtable: interface::RustHashMap<i32, interface::RustRfc<MsgQueue>>,
+}
+
+impl MsgMetadata {
+    pub fn init_msg_metadata() -> MsgMetadata {
+        MsgMetadata {
+            nextid: interface::RustAtomicI32::new(1),
+            msgkeyidtable: interface::RustHashMap::new(),
+            msgtable: interface::RustHashMap::new(),
+        }
+    }
+
+    pub fn new_keyid(&self) -> i32 {
+        self.nextid
+            .fetch_add(1, interface::RustAtomicOrdering::Relaxed)
+    }
+
+    pub fn get_queue(&self, msqid: i32) -> Option<interface::RustRfc<MsgQueue>> {
+        self.msgtable.get(&msqid).map(|queue| queue.clone())
+    }
+
+    // the highest msqid in use, what IPC_INFO and MSG_INFO return
+    pub fn max_msqid(&self) -> i32 {
+        self.msgtable.iter().map(|entry| *entry.key()).max().unwrap_or(0)
+    }
+}
@@ -0,0 +1,191 @@
+// System V semaphore set metadata
+#![allow(dead_code)]
+
+use super::syscalls::fs_constants::*;
+use crate::interface;
+
+use libc::*;
+use std::collections::HashMap;
+
+pub static SEM_METADATA: interface::RustLazyGlobal<interface::RustRfc<SemMetadata>> =
+    interface::RustLazyGlobal::new(|| interface::RustRfc::new(SemMetadata::init_sem_metadata()));
+
+#[derive(Clone, Copy, Default)]
+pub struct Semaphore {
+    pub semval: i32,
+    pub sempid: u32,  // cage of the last semop on it
+    pub semncnt: u32, // semops waiting for it to increase
+    pub semzcnt: u32, // semops waiting for it to become zero
+}
+
+pub struct SemSetState {
+    pub seminfo: interface::SemidsStruct,
+    pub key: i32,
+    pub sems: Vec<Semaphore>,
+    // cageid -> what to add to each semaphore when that cage exits, kept by SEM_UNDO semops
+    pub undo: HashMap<u64, Vec<i32>>,
+    pub removed: bool,
+}
+
+pub struct SemSet {
+    pub state: interface::Mutex<SemSetState>,
+    pub cv: interface::Condvar, // notified whenever a semaphore changes, or the set is removed
+}
+
+// What became of a semop that was tried
+pub enum SemopResult {
+    Done,
+    Wait(usize), // the operation at this index can't proceed yet
+    OutOfRange,  // a semaphore would go above SEMVMX
+}
+
+impl SemSet {
+    pub fn new(key: i32, nsems: usize, uid: u32, gid: u32, mode: u16) -> SemSet {
+        let time = interface::timestamp() as isize;
+        let permstruct = interface::IpcPermStruct {
+            __key: key,
+            uid,
+            gid,
+            cuid: uid,
+            cgid: gid,
+            mode,
+            __pad1: 0,
+            __seq: 0,
+            __pad2: 0,
+            __unused1: 0,
+            __unused2: 0,
+        };
+        let seminfo = interface::SemidsStruct {
+            sem_perm: permstruct,
+            sem_otime: 0,
+            sem_ctime: time,
+            sem_nsems: nsems as u32,
+            __unused3: 0,
+            __unused4: 0,
+        };
+
+        SemSet {
+            state: interface::Mutex::new(SemSetState {
+                seminfo,
+                key,
+                sems: vec![Semaphore::default(); nsems],
+                undo: HashMap::new(),
+                removed: false,
+            }),
+            cv: interface::Condvar::new(),
+        }
+    }
+
+    // IPC_RMID: the set is gone at once, and cages blocked on it fail with EIDRM
+    pub fn mark_removed(&self) {
+        self.state.lock().removed = true;
+        self.cv.notify_all();
+    }
+}
+
+impl SemSetState {
+    /*
+     *   Applies all of sops or none of them. Operations are tried in order on a copy of the
+     *   values, so a later one sees what an earlier one did, as in Linux. On success the
+     *   adjustments of the SEM_UNDO operations are recorded for the cage.
+     */
+    pub fn try_semop(&mut self, sops: &[interface::SembufStruct], cageid: u64) -> SemopResult {
+        let mut values: Vec<i32> = self.sems.iter().map(|sem| sem.semval).collect();
+        for (index, sop) in sops.iter().enumerate() {
+            let value = &mut values[sop.sem_num as usize];
+            let op = sop.sem_op as i32;
+            if op == 0 {
+                if *value != 0 {
+                    return SemopResult::Wait(index);
+                }
+            } else if op < 0 && *value < -op {
+                return SemopResult::Wait(index);
+            } else if *value + op > SEMVMX as i32 {
+                return SemopResult::OutOfRange;
+            } else {
+                *value += op;
+            }
+        }
+
+        let nsems = self.sems.len();
+        for sop in sops.iter() {
+            let sem = &mut self.sems[sop.sem_num as usize];
+            sem.semval = values[sop.sem_num as usize];
+            sem.sempid = cageid as u32;
+            if sop.sem_flg as i32 & SEM_UNDO != 0 && sop.sem_op != 0 {
+                let adjustments = self.undo.entry(cageid).or_insert_with(|| vec![0; nsems]);
+                adjustments[sop.sem_num as usize] -= sop.sem_op as i32;
+            }
+        }
+        self.seminfo.sem_otime = interface::timestamp() as isize;
+        SemopResult::Done
+    }
+
+    // semncnt or semzcnt of the semaphore a waiting operation is waiting on
+    pub fn waiters(&mut self, sop: &interface::SembufStruct) -> &mut u32 {
+        let sem = &mut self.sems[sop.sem_num as usize];
+        if sop.sem_op == 0 {
+            &mut sem.semzcnt
+        } else {
+            &mut sem.semncnt
+        }
+    }
+
+    // SETVAL/SETALL make the recorded adjustments of a semaphore meaningless
+    pub fn clear_undo(&mut self, semnum: usize) {
+        for adjustments in self.undo.values_mut() {
+            adjustments[semnum] = 0;
+        }
+    }
+
+    /*
+     *   Rolls back the SEM_UNDO operations of an exiting cage. Like Linux, a value that would
+     *   go below zero is clamped to zero rather than waiting, and one above SEMVMX to SEMVMX.
+     *   Returns whether any semaphore changed.
+     */
+    pub fn undo_for_cage(&mut self, cageid: u64) -> bool {
+        let adjustments = match self.undo.remove(&cageid) {
+            Some(adjustments) => adjustments,
+            None => return false,
+        };
+        let mut changed = false;
+        for (sem, adjustment) in self.sems.iter_mut().zip(adjustments) {
+            if adjustment != 0 {
+                sem.semval = (sem.semval + adjustment).clamp(0, SEMVMX as i32);
+                sem.sempid = cageid as u32;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+pub struct SemMetadata {
+    pub nextid: interface::RustAtomicI32,
+    pub semkeyidtable: interface::RustHashMap<i32, i32>,
+    pub semtable: interface::RustHashMap<i32, interface::RustRfc<SemSet>>,
+}
+
+impl SemMetadata {
+    pub fn init_sem_metadata() -> SemMetadata {
+        SemMetadata {
+            nextid: interface::RustAtomicI32::new(1),
+            semkeyidtable: interface::RustHashMap::new(),
+            semtable: interface::RustHashMap::new(),
+        }
+    }
+
+    pub fn new_keyid(&self) -> i32 {
+        self.nextid
+            .fetch_add(1, interface::RustAtomicOrdering::Relaxed)
+    }
+
+    pub fn get_set(&self, semid: i32) -> Option<interface::RustRfc<SemSet>> {
+        self.semtable.get(&semid).map(|set| set.clone())
+    }
+
+    // the highest semid in use, what IPC_INFO and SEM_INFO return
+    pub fn max_semid(&self) -> i32 {
+        self.semtable.iter().map(|entry| *entry.key()).max().unwrap_or(0)
+    }
+}
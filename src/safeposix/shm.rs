@@ -78,6 +78,7 @@ impl ShmSegment {
         let fobjfdno = self.filebacking.as_fd_handle_raw_int();
         self.shminfo.shm_nattch += 1;
         self.shminfo.shm_atime = interface::timestamp() as isize;
+        self.shminfo.shm_lpid = cageid as u32;
 
         match self.attached_cages.entry(cageid) {
             interface::RustHashEntry::Occupied(mut occupied) => {
@@ -110,6 +111,7 @@ impl ShmSegment {
         );
         self.shminfo.shm_nattch -= 1;
         self.shminfo.shm_dtime = interface::timestamp() as isize;
+        self.shminfo.shm_lpid = cageid as u32;
         match self.attached_cages.entry(cageid) {
             interface::RustHashEntry::Occupied(mut occupied) => {
                 *occupied.get_mut() -= 1;
@@ -122,6 +124,15 @@ impl ShmSegment {
             }
         };
     }
+
+    // IPC_RMID: the segment goes away with its last detach. Its key is free for a new
+    // segment right away, like in Linux where it becomes IPC_PRIVATE.
+    pub fn mark_removed(&mut self) {
+        self.rmid = true;
+        self.shminfo.shm_perm.mode |= SHM_DEST as u16;
+        self.key = IPC_PRIVATE;
+        self.shminfo.shm_perm.__key = IPC_PRIVATE;
+    }
 }
 
 pub struct ShmMetadata {
@@ -143,4 +154,9 @@ impl ShmMetadata {
         self.nextid
             .fetch_add(1, interface::RustAtomicOrdering::Relaxed)
     }
+
+    // the highest shmid in use, what IPC_INFO and SHM_INFO return
+    pub fn max_shmid(&self) -> i32 {
+        self.shmtable.iter().map(|entry| *entry.key()).max().unwrap_or(0)
+    }
 }
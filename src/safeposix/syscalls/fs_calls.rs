@@ -13,8 +13,12 @@ use crate::safeposix::cage::Errno::EINVAL;
 use crate::safeposix::cage::*;
 use crate::safeposix::filesystem::convpath;
 use crate::safeposix::filesystem::normpath;
+use crate::safeposix::filesystem::{cage_may_access, BeneathPath, forget_virtual_owner, fstat_beneath, open_beneath, open_beneath_at, procfd_path, resolve_beneath, resolve_beneath_at, resolve_parent_beneath, resolve_parent_beneath_at, set_virtual_owner, virtual_owner};
+use crate::safeposix::filesystem::strip_lindroot;
+use crate::safeposix::msg::*;
+use crate::safeposix::sem::*;
 use crate::safeposix::shm::*;
-use crate::interface::ShmidsStruct;
+use crate::interface::{MsqidsStruct, SembufStruct, SemunUnion, ShmidsStruct};
 use crate::interface::StatData;
 
 use libc::*;
@@ -28,10 +32,15 @@ use std::mem;
 
 use crate::fdtables;
 
-static LIND_ROOT: &str = "/home/lind/lind_project/src/safeposix-rust/tmp";
-
 const FDKIND_KERNEL: u32 = 0;
 const FDKIND_IMPIPE: u32 = 1;
+const FDKIND_IMSOCK: u32 = 2;
+const FDKIND_IMSHM: u32 = 3;
+
+// POSIX shm objects are host memfds, so file calls on them go straight to the host fd
+fn is_host_fd(fdkind: u32) -> bool {
+    fdkind == FDKIND_KERNEL || fdkind == FDKIND_IMSHM
+}
 
 impl Cage {
     //------------------------------------OPEN SYSCALL------------------------------------
@@ -41,27 +50,102 @@ impl Cage {
     *   Then return virtual fd
     */
     pub fn open_syscall(&self, path: &str, oflag: i32, mode: u32) -> i32 {
+        self.open_at(None, path, oflag, mode)
+    }
 
-        // Convert data type from &str into *const i8
-        let relpath = normpath(convpath(path), self);
-        let relative_path = relpath.to_str().unwrap();
-        let full_path = format!("{}{}", LIND_ROOT, relative_path);
-        let c_path = CString::new(full_path).unwrap();
-
-        let kernel_fd = unsafe { libc::open(c_path.as_ptr(), oflag, mode) };
+    // open() and openat(), dirfd being the kernel fd a relative path starts at (None for the cwd)
+    fn open_at(&self, dirfd: Option<i32>, path: &str, oflag: i32, mode: u32) -> i32 {
+        // /dev/shm is not the host's, it holds the POSIX shm objects of the cages
+        if let Some(name) = self.devshm_name(dirfd, path) {
+            return self.open_shm_object(&name, oflag, mode, "open");
+        }
+        let creates = match self.open_permitted(dirfd, path, oflag) {
+            Ok(creates) => creates,
+            Err(errno) => return handle_errno(errno, "open"),
+        };
 
-        
-        if kernel_fd < 0 {
-            let errno = get_errno();
-            return handle_errno(errno, "open");
+        // Resolve the path beneath the cage's root, so symlinks can't lead outside of it
+        let kernel_fd = match open_beneath_at(dirfd, path, self, oflag, mode) {
+            Ok(fd) => fd,
+            Err(errno) => return handle_errno(errno, "open"),
+        };
+        if creates {
+            self.init_new_file(kernel_fd, mode);
         }
 
         let should_cloexec = (oflag & O_CLOEXEC) != 0;
 
-        match fdtables::get_unused_virtual_fd(self.cageid, FDKIND_KERNEL, kernel_fd as u64, should_cloexec, 0) {
-            Ok(virtual_fd) => return virtual_fd as i32,
-            Err(_) => return syscall_error(Errno::EMFILE, "open", "Too many files opened")
+        self.install_kernel_fd(kernel_fd, should_cloexec, "open")
+    }
+
+    /*
+    *   The host opens every file as whoever runs lind, so whether the cage's own credentials
+    *   allow the open is checked here: the access mode (and O_TRUNC) against an existing file,
+    *   or write and search permission on the parent directory for a file O_CREAT will make.
+    *   Paths that fail to resolve are left to the open itself to report.
+    *   Returns whether the open is going to create the file.
+    */
+    fn open_permitted(&self, dirfd: Option<i32>, path: &str, oflag: i32) -> Result<bool, i32> {
+        if oflag & O_PATH != 0 {
+            return Ok(false);
+        }
+        match resolve_beneath_at(dirfd, path, self) {
+            Ok(target) => {
+                if oflag & O_CREAT != 0 && oflag & O_EXCL != 0 {
+                    return Ok(false);
+                }
+                cage_may_access(self, target.dirfd, open_access_wanted(oflag), false).map(|_| false)
+            }
+            Err(libc::ENOENT) if oflag & O_CREAT != 0 => match resolve_parent_beneath_at(dirfd, path, self) {
+                Ok(parent) => cage_may_access(self, parent.dirfd, libc::W_OK | libc::X_OK, false).map(|_| true),
+                Err(_) => Ok(false),
+            },
+            Err(_) => Ok(false),
+        }
+    }
+
+    /*
+    *   A file the cage just created gets the mode it asked for less the cage's umask (the
+    *   host's umask is not the cage's business), and belongs to the cage's effective ids.
+    *   This is best effort, the file exists either way.
+    */
+    fn init_new_file(&self, fd: i32, mode: u32) {
+        let st = match fstat_beneath(fd) {
+            Ok(st) => st,
+            Err(_) => return,
+        };
+        // the owner goes first, while the file is still writable for its xattr
+        let owner = (self.euid(), self.egid());
+        if virtual_owner(fd, &st) != owner {
+            let _ = set_virtual_owner(fd, &st, owner.0, owner.1);
+        }
+        let newmode = mode & 0o7777 & !self.umask.load(interface::RustAtomicOrdering::Relaxed);
+        // a symlink's mode is always 0777 and can't be changed
+        if st.st_mode & S_IFMT != S_IFLNK && st.st_mode & 0o7777 != newmode {
+            unsafe { libc::chmod(procfd_path(fd).as_ptr(), newmode) };
+        }
+    }
+
+    // init_new_file for a directory entry made by mkdirat/mknodat/symlinkat
+    fn init_new_entry(&self, target: &BeneathPath, mode: u32) {
+        let fd = unsafe {
+            libc::openat(target.dirfd, target.name.as_ptr(), O_PATH | O_NOFOLLOW | O_CLOEXEC)
+        };
+        if fd >= 0 {
+            self.init_new_file(fd, mode);
+            unsafe { libc::close(fd) };
+        }
+    }
+
+    // Only the owner of a file, or root, may change its mode
+    fn check_owner(&self, fd: i32) -> Result<(), i32> {
+        let st = fstat_beneath(fd)?;
+        let (uid, _) = virtual_owner(fd, &st);
+        let euid = self.euid();
+        if euid != sys_constants::ROOT_UID && euid != uid {
+            return Err(libc::EPERM);
         }
+        Ok(())
     }
 
     //------------------MKDIR SYSCALL------------------
@@ -69,19 +153,23 @@ impl Cage {
     *   mkdir() will return 0 when success and -1 when fail 
     */
     pub fn mkdir_syscall(&self, path: &str, mode: u32) -> i32 {
-        // Convert data type from &str into *const i8
-        let relpath = normpath(convpath(path), self);
-        let relative_path = relpath.to_str().unwrap();
-        let full_path = format!("{}{}", LIND_ROOT, relative_path);
-        let c_path = CString::new(full_path).unwrap();
+        self.mkdir_at(None, path, mode)
+    }
+
+    fn mkdir_at(&self, dirfd: Option<i32>, path: &str, mode: u32) -> i32 {
+        let target = match resolve_parent_beneath_at(dirfd, path, self) {
+            Ok(target) => target,
+            Err(errno) => return handle_errno(errno, "mkdir"),
+        };
         
         let ret = unsafe {
-            libc::mkdir(c_path.as_ptr(), mode)
+            libc::mkdirat(target.dirfd, target.name.as_ptr(), mode)
         };
         if ret < 0 {
             let errno = get_errno();
             return handle_errno(errno, "mkdir");
         }
+        self.init_new_entry(&target, mode);
         ret
     }
 
@@ -90,18 +178,18 @@ impl Cage {
     *   mknod() will return 0 when success and -1 when fail 
     */
     pub fn mknod_syscall(&self, path: &str, mode: u32, dev: u64) -> i32 {
-        // Convert data type from &str into *const i8
-        let relpath = normpath(convpath(path), self);
-        let relative_path = relpath.to_str().unwrap();
-        let full_path = format!("{}{}", LIND_ROOT, relative_path);
-        let c_path = CString::new(full_path).unwrap();
+        let target = match resolve_parent_beneath(path, self) {
+            Ok(target) => target,
+            Err(errno) => return handle_errno(errno, "mknod"),
+        };
         let ret = unsafe {
-            libc::mknod(c_path.as_ptr(), mode, dev)
+            libc::mknodat(target.dirfd, target.name.as_ptr(), mode, dev)
         };
         if ret < 0 {
             let errno = get_errno();
             return handle_errno(errno, "mknod");
         }
+        self.init_new_entry(&target, mode);
         ret
     }
 
@@ -110,19 +198,22 @@ impl Cage {
     *   link() will return 0 when success and -1 when fail 
     */
     pub fn link_syscall(&self, oldpath: &str, newpath: &str) -> i32 {
-        // Convert data type from &str into *const i8
-        let rel_oldpath = normpath(convpath(oldpath), self);
-        let relative_oldpath = rel_oldpath.to_str().unwrap();
-        let full_oldpath = format!("{}{}", LIND_ROOT, relative_oldpath);
-        let old_cpath = CString::new(full_oldpath).unwrap();
+        self.link_at(None, oldpath, None, newpath)
+    }
 
-        let rel_newpath = normpath(convpath(newpath), self);
-        let relative_newpath = rel_newpath.to_str().unwrap();
-        let full_newpath = format!("{}{}", LIND_ROOT, relative_newpath);
-        let new_cpath = CString::new(full_newpath).unwrap();
+    fn link_at(&self, olddirfd: Option<i32>, oldpath: &str, newdirfd: Option<i32>, newpath: &str) -> i32 {
+        // Like link(2), a trailing symlink in oldpath is linked rather than followed
+        let oldtarget = match resolve_parent_beneath_at(olddirfd, oldpath, self) {
+            Ok(target) => target,
+            Err(errno) => return handle_errno(errno, "link"),
+        };
+        let newtarget = match resolve_parent_beneath_at(newdirfd, newpath, self) {
+            Ok(target) => target,
+            Err(errno) => return handle_errno(errno, "link"),
+        };
 
         let ret = unsafe {
-            libc::link(old_cpath.as_ptr(), new_cpath.as_ptr())
+            libc::linkat(oldtarget.dirfd, oldtarget.name.as_ptr(), newtarget.dirfd, newtarget.name.as_ptr(), 0)
         };
         if ret < 0 {
             let errno = get_errno();
@@ -131,25 +222,119 @@ impl Cage {
         ret
     }
 
+    //------------------------------------SYMLINK SYSCALL------------------------------------
+    /*
+    *   symlink() creates linkpath pointing at target, which is stored as it is given. Targets
+    *   are cage paths: an absolute one is found from the cage's root and not the host's.
+    *   symlink() will return 0 when success and -1 when fail 
+    */
+    pub fn symlink_syscall(&self, target: &str, linkpath: &str) -> i32 {
+        self.symlink_at(target, None, linkpath)
+    }
+
+    fn symlink_at(&self, target: &str, newdirfd: Option<i32>, linkpath: &str) -> i32 {
+        if target.is_empty() {
+            return syscall_error(Errno::ENOENT, "symlink", "target is an empty string");
+        }
+        let linktarget = match resolve_parent_beneath_at(newdirfd, linkpath, self) {
+            Ok(target) => target,
+            Err(errno) => return handle_errno(errno, "symlink"),
+        };
+        let stored = CString::new(target).unwrap();
+
+        let ret = unsafe {
+            libc::symlinkat(stored.as_ptr(), linktarget.dirfd, linktarget.name.as_ptr())
+        };
+        if ret < 0 {
+            let errno = get_errno();
+            return handle_errno(errno, "symlink");
+        }
+        self.init_new_entry(&linktarget, S_IRWXA);
+        ret
+    }
+
+    //------------------------------------READLINK SYSCALL------------------------------------
+    /*
+    *   readlink() places the target of the symlink at path in buf, exactly as it was stored.
+    *   Like Linux, the target is truncated to bufsize bytes and not null terminated.
+    *   readlink() will return the number of bytes placed in buf when success and -1 when fail 
+    */
+    pub fn readlink_syscall(&self, path: &str, buf: *mut u8, bufsize: usize) -> i32 {
+        self.readlink_at(None, path, buf, bufsize)
+    }
+
+    fn readlink_at(&self, dirfd: Option<i32>, path: &str, buf: *mut u8, bufsize: usize) -> i32 {
+        if bufsize == 0 {
+            return syscall_error(Errno::EINVAL, "readlink", "Invalid buffer size");
+        }
+        let target = match resolve_parent_beneath_at(dirfd, path, self) {
+            Ok(target) => target,
+            Err(errno) => return handle_errno(errno, "readlink"),
+        };
+        self.readlink_beneath(target.dirfd, &target.name, buf, bufsize, "readlink")
+    }
+
+    fn readlink_beneath(&self, dirfd: i32, name: &CStr, buf: *mut u8, bufsize: usize, syscallname: &str) -> i32 {
+        let target = match self.symlink_target(dirfd, name) {
+            Ok(target) => target,
+            Err(errno) => return handle_errno(errno, syscallname),
+        };
+
+        let copylen = target.len().min(bufsize);
+        unsafe {
+            ptr::copy(target.as_ptr(), buf, copylen);
+        }
+        copylen as i32
+    }
+
+    // The target of the symlink name in dirfd, as the cage stored it
+    fn symlink_target(&self, dirfd: i32, name: &CStr) -> Result<Vec<u8>, i32> {
+        let mut hostbuf = vec![0u8; PATH_MAX as usize];
+        let len = unsafe {
+            libc::readlinkat(dirfd, name.as_ptr(), hostbuf.as_mut_ptr() as *mut c_char, hostbuf.len())
+        };
+        if len < 0 {
+            return Err(get_errno());
+        }
+        hostbuf.truncate(len as usize);
+        Ok(hostbuf)
+    }
+
     //------------------------------------UNLINK SYSCALL------------------------------------
     /*
     *   unlink() will return 0 when success and -1 when fail 
     */
     pub fn unlink_syscall(&self, path: &str) -> i32 {
-        // let (path_c, _, _) = path.to_string().into_raw_parts();
-        let relpath = normpath(convpath(path), self);
-        let relative_path = relpath.to_str().unwrap();
-        let full_path = format!("{}{}", LIND_ROOT, relative_path);
-        let c_path = CString::new(full_path).unwrap();
+        self.unlink_at(None, path)
+    }
+
+    fn unlink_at(&self, dirfd: Option<i32>, path: &str) -> i32 {
+        if let Some(name) = self.devshm_name(dirfd, path) {
+            return match self.unlink_shm_object(&name) {
+                Ok(()) => 0,
+                Err(errno) => handle_errno(errno, "unlink"),
+            };
+        }
+        let target = match resolve_parent_beneath_at(dirfd, path, self) {
+            Ok(target) => target,
+            Err(errno) => return handle_errno(errno, "unlink"),
+        };
+        let mut st: libc::stat = unsafe { std::mem::zeroed() };
+        let statret = unsafe {
+            libc::fstatat(target.dirfd, target.name.as_ptr(), &mut st, AT_SYMLINK_NOFOLLOW)
+        };
 
         let ret = unsafe {
-            libc::unlink(c_path.as_ptr())
+            libc::unlinkat(target.dirfd, target.name.as_ptr(), 0)
         };
 
         if ret < 0 {
             let errno = get_errno();
             return handle_errno(errno, "unlink");
         }
+        if statret == 0 {
+            forget_virtual_owner(&st);
+        }
         ret
     }
 
@@ -158,22 +343,20 @@ impl Cage {
     *   creat() will return fd when success and -1 when fail 
     */
     pub fn creat_syscall(&self, path: &str, mode: u32) -> i32 {
-        // let c_path = CString::new(path).expect("CString::new failed");
-        let relpath = normpath(convpath(path), self);
-        let relative_path = relpath.to_str().unwrap();
-        let full_path = format!("{}{}", LIND_ROOT, relative_path);
-        let c_path = CString::new(full_path).unwrap();
-
-        let kernel_fd = unsafe {
-            libc::creat(c_path.as_ptr(), mode)
+        let creates = match self.open_permitted(None, path, O_CREAT | O_WRONLY | O_TRUNC) {
+            Ok(creates) => creates,
+            Err(errno) => return handle_errno(errno, "creat"),
         };
-        if kernel_fd < 0 {
-            let errno = get_errno();
-            return handle_errno(errno, "creat");
+        // creat() is open() with O_CREAT|O_WRONLY|O_TRUNC
+        let kernel_fd = match open_beneath(path, self, O_CREAT | O_WRONLY | O_TRUNC, mode) {
+            Ok(fd) => fd,
+            Err(errno) => return handle_errno(errno, "creat"),
+        };
+        if creates {
+            self.init_new_file(kernel_fd, mode);
         }
         
-        let virtual_fd = fdtables::get_unused_virtual_fd(self.cageid, FDKIND_KERNEL, kernel_fd as u64, false, 0).unwrap();
-        virtual_fd as i32
+        self.install_kernel_fd(kernel_fd, false, "creat")
     }
 
     //------------------------------------STAT SYSCALL------------------------------------
@@ -181,32 +364,60 @@ impl Cage {
     *   stat() will return 0 when success and -1 when fail 
     */
     pub fn stat_syscall(&self, path: &str, rposix_statbuf: &mut StatData) -> i32 {
-        let relpath = normpath(convpath(path), self);
-        let relative_path = relpath.to_str().unwrap();
-        let full_path = format!("{}{}", LIND_ROOT, relative_path);
-        let c_path = CString::new(full_path).unwrap();
+        self.stat_at(None, path, rposix_statbuf)
+    }
+
+    fn stat_at(&self, dirfd: Option<i32>, path: &str, rposix_statbuf: &mut StatData) -> i32 {
+        let target = match resolve_beneath_at(dirfd, path, self) {
+            Ok(target) => target,
+            Err(errno) => return handle_errno(errno, "stat"),
+        };
+        self.stat_kernel_fd(target.dirfd, rposix_statbuf, "stat")
+    }
+
+    //------------------------------------LSTAT SYSCALL------------------------------------
+    /*
+    *   lstat() is stat() on a trailing symlink itself rather than the file it points to
+    *   lstat() will return 0 when success and -1 when fail 
+    */
+    pub fn lstat_syscall(&self, path: &str, rposix_statbuf: &mut StatData) -> i32 {
+        self.lstat_at(None, path, rposix_statbuf)
+    }
+
+    fn lstat_at(&self, dirfd: Option<i32>, path: &str, rposix_statbuf: &mut StatData) -> i32 {
+        let kernel_fd = match open_beneath_at(dirfd, path, self, O_PATH | O_NOFOLLOW | O_CLOEXEC, 0) {
+            Ok(fd) => fd,
+            Err(errno) => return handle_errno(errno, "lstat"),
+        };
+        let ret = self.stat_kernel_fd(kernel_fd, rposix_statbuf, "lstat");
+        unsafe { libc::close(kernel_fd) };
+        ret
+    }
 
+    // fills in a StatData from the kernel fd, or an O_PATH fd from the resolve functions
+    fn stat_kernel_fd(&self, kernel_fd: i32, rposix_statbuf: &mut StatData, syscallname: &str) -> i32 {
         // Declare statbuf by ourselves 
         let mut libc_statbuf: stat = unsafe { std::mem::zeroed() };
         let libcret = unsafe {
-            libc::stat(c_path.as_ptr(), &mut libc_statbuf)
+            libc::fstat(kernel_fd, &mut libc_statbuf)
         };
         
         if libcret < 0 {
             let errno = get_errno();
-            return handle_errno(errno, "stat");
+            return handle_errno(errno, syscallname);
         }
         
         rposix_statbuf.st_blksize = libc_statbuf.st_blksize as i32;
         rposix_statbuf.st_blocks = libc_statbuf.st_blocks as u32;
         rposix_statbuf.st_dev = libc_statbuf.st_dev as u64;
-        rposix_statbuf.st_gid = libc_statbuf.st_gid;
+        let (st_uid, st_gid) = virtual_owner(kernel_fd, &libc_statbuf);
+        rposix_statbuf.st_gid = st_gid;
         rposix_statbuf.st_ino = libc_statbuf.st_ino as usize;
         rposix_statbuf.st_mode = libc_statbuf.st_mode as u32;
         rposix_statbuf.st_nlink = libc_statbuf.st_nlink as u32;
         rposix_statbuf.st_rdev = libc_statbuf.st_rdev as u64;
         rposix_statbuf.st_size = libc_statbuf.st_size as usize;
-        rposix_statbuf.st_uid = libc_statbuf.st_uid;
+        rposix_statbuf.st_uid = st_uid;
 
         libcret
     }
@@ -222,31 +433,45 @@ impl Cage {
             return syscall_error(Errno::EBADF, "fstat", "Bad File Descriptor");
         }
         let vfd = wrappedvfd.unwrap();
-
-        // Declare statbuf by ourselves 
-        let mut libc_statbuf: stat = unsafe { std::mem::zeroed() };
-        let libcret = unsafe {
-        libc::fstat(vfd.underfd as i32, &mut libc_statbuf)
-        };
-
-        if libcret < 0 {
-            let errno = get_errno();
-            return handle_errno(errno, "fstat");
+        if vfd.fdkind == FDKIND_IMPIPE {
+            *rposix_statbuf = StatData::default();
+            rposix_statbuf.st_mode = S_IFIFO | fs_constants::S_IRUSR | fs_constants::S_IWUSR;
+            rposix_statbuf.st_nlink = 1;
+            rposix_statbuf.st_ino = vfd.underfd as usize;
+            rposix_statbuf.st_blksize = interface::PIPE_BUF as i32;
+            rposix_statbuf.st_uid = self.euid();
+            rposix_statbuf.st_gid = self.egid();
+            return 0;
+        }
+        if vfd.fdkind == FDKIND_IMSOCK {
+            *rposix_statbuf = StatData::default();
+            rposix_statbuf.st_mode = S_IFSOCK | fs_constants::S_IRWXA;
+            rposix_statbuf.st_nlink = 1;
+            rposix_statbuf.st_ino = vfd.underfd as usize;
+            rposix_statbuf.st_blksize = 4096;
+            rposix_statbuf.st_uid = self.euid();
+            rposix_statbuf.st_gid = self.egid();
+            return 0;
+        }
+        // epoll fds live on an anonymous inode, which has no file type bits
+        if vfd.fdkind == fdtables::FDT_KINDEPOLL {
+            *rposix_statbuf = StatData::default();
+            rposix_statbuf.st_mode = fs_constants::S_IRUSR | fs_constants::S_IWUSR;
+            rposix_statbuf.st_nlink = 1;
+            rposix_statbuf.st_blksize = 4096;
+            rposix_statbuf.st_uid = self.euid();
+            rposix_statbuf.st_gid = self.egid();
+            return 0;
         }
-        
-        rposix_statbuf.st_blksize = libc_statbuf.st_blksize as i32;
-        rposix_statbuf.st_blocks = libc_statbuf.st_blocks as u32;
-        rposix_statbuf.st_dev = libc_statbuf.st_dev as u64;
-        rposix_statbuf.st_gid = libc_statbuf.st_gid;
-        rposix_statbuf.st_ino = libc_statbuf.st_ino as usize;
-        rposix_statbuf.st_mode = libc_statbuf.st_mode as u32;
-        rposix_statbuf.st_nlink = libc_statbuf.st_nlink as u32;
-        rposix_statbuf.st_rdev = libc_statbuf.st_rdev as u64;
-        rposix_statbuf.st_size = libc_statbuf.st_size as usize;
-        rposix_statbuf.st_uid = libc_statbuf.st_uid;
 
-        libcret
-        
+        let ret = self.stat_kernel_fd(vfd.underfd as i32, rposix_statbuf, "fstat");
+        // the memfd of a shm object is always 0600, the object has the mode the cage gave it
+        if ret == 0 && vfd.fdkind == FDKIND_IMSHM {
+            if let Some(object) = interface::shm_open_object(vfd.underfd) {
+                rposix_statbuf.st_mode = S_IFREG | object.mode();
+            }
+        }
+        ret
     }
 
     //------------------------------------STATFS SYSCALL------------------------------------
@@ -254,14 +479,14 @@ impl Cage {
     *   statfs() will return 0 when success and -1 when fail 
     */
     pub fn statfs_syscall(&self, path: &str, rposix_databuf: &mut FSData) -> i32 {
-        let relpath = normpath(convpath(path), self);
-        let relative_path = relpath.to_str().unwrap();
-        let full_path = format!("{}{}", LIND_ROOT, relative_path);
-        let c_path = CString::new(full_path).unwrap();
+        let target = match resolve_beneath(path, self) {
+            Ok(target) => target,
+            Err(errno) => return handle_errno(errno, "statfs"),
+        };
 
         let mut libc_databuf: statfs = unsafe { mem::zeroed() };
         let libcret = unsafe {
-            libc::statfs(c_path.as_ptr(), &mut libc_databuf)
+            libc::fstatfs(target.dirfd, &mut libc_databuf)
         };
 
         if libcret < 0 {
@@ -296,6 +521,24 @@ impl Cage {
             return syscall_error(Errno::EBADF, "fstatfs", "Bad File Descriptor");
         }
         let vfd = wrappedvfd.unwrap();
+        if !is_host_fd(vfd.fdkind) {
+            rposix_databuf.f_type = match vfd.fdkind {
+                FDKIND_IMPIPE => PIPEFS_MAGIC,
+                FDKIND_IMSOCK => SOCKFS_MAGIC,
+                _ => ANON_INODE_FS_MAGIC,
+            };
+            rposix_databuf.f_bsize = 4096;
+            rposix_databuf.f_blocks = 0;
+            rposix_databuf.f_bfree = 0;
+            rposix_databuf.f_bavail = 0;
+            rposix_databuf.f_files = 0;
+            rposix_databuf.f_ffiles = 0;
+            rposix_databuf.f_fsid = 0;
+            rposix_databuf.f_namelen = 255;
+            rposix_databuf.f_frsize = 4096;
+            rposix_databuf.f_spare = [0; 32];
+            return 0;
+        }
         let mut libc_databuf: statfs = unsafe { mem::zeroed() };
         let libcret = unsafe {
             libc::fstatfs(vfd.underfd as i32, &mut libc_databuf)
@@ -336,6 +579,19 @@ impl Cage {
         }
 
         let vfd = wrappedvfd.unwrap();
+        if vfd.fdkind == FDKIND_IMPIPE {
+            if interface::is_pipe_write_end(vfd.underfd) {
+                return syscall_error(Errno::EBADF, "read", "fd is not open for reading");
+            }
+            let pipe = interface::get_pipe(vfd.underfd).unwrap();
+            return pipe.read_from_pipe(readbuf, count, vfd.perfdinfo as i32 & O_NONBLOCK != 0);
+        }
+        if vfd.fdkind == FDKIND_IMSOCK {
+            return self.recv_syscall(virtual_fd, readbuf, count, 0);
+        }
+        if vfd.fdkind == fdtables::FDT_KINDEPOLL {
+            return syscall_error(Errno::EINVAL, "read", "fd is attached to an object which is unsuitable for reading");
+        }
         //kernel fd
         let ret = unsafe {
             libc::read(vfd.underfd as i32, readbuf as *mut c_void, count) as i32
@@ -360,6 +616,9 @@ impl Cage {
             return syscall_error(Errno::EBADF, "pread", "Bad File Descriptor");
         }
         let vfd = wrappedvfd.unwrap();
+        if !is_host_fd(vfd.fdkind) {
+            return syscall_error(Errno::ESPIPE, "pread", "Illegal seek");
+        }
         let ret = unsafe {
             libc::pread(vfd.underfd as i32, buf as *mut c_void, count, offset) as i32
         };
@@ -385,6 +644,19 @@ impl Cage {
         }
 
         let vfd = wrappedvfd.unwrap();
+        if vfd.fdkind == FDKIND_IMPIPE {
+            return self.write_to_impipe(vfd, buf, count, "write");
+        }
+        if vfd.fdkind == FDKIND_IMSOCK {
+            return self.send_syscall(virtual_fd, buf, count, 0);
+        }
+        if vfd.fdkind == fdtables::FDT_KINDEPOLL {
+            return syscall_error(Errno::EINVAL, "write", "fd is attached to an object which is unsuitable for writing");
+        }
+        let count = match self.fsize_allowance(vfd.underfd as i32, None, count, "write") {
+            Ok(count) => count,
+            Err(e) => return e,
+        };
         let ret = unsafe {
             libc::write(vfd.underfd as i32, buf as *const c_void, count) as i32
         };
@@ -408,6 +680,13 @@ impl Cage {
             return syscall_error(Errno::EBADF, "pwrite", "Bad File Descriptor");
         }
         let vfd = wrappedvfd.unwrap();
+        if !is_host_fd(vfd.fdkind) {
+            return syscall_error(Errno::ESPIPE, "pwrite", "Illegal seek");
+        }
+        let count = match self.fsize_allowance(vfd.underfd as i32, Some(offset), count, "pwrite") {
+            Ok(count) => count,
+            Err(e) => return e,
+        };
         let ret = unsafe {
             libc::pwrite(vfd.underfd as i32, buf as *const c_void, count, offset) as i32
         };
@@ -419,9 +698,15 @@ impl Cage {
         
     }
 
-    //------------------------------------WRITEV SYSCALL------------------------------------
-
-    pub fn writev_syscall(
+    //------------------------------------READV SYSCALL------------------------------------
+    /*
+    *   Get the kernel fd with provided virtual fd first
+    *   readv() is read() scattered over iovcnt buffers, which are filled in order
+    *   readv() will return:
+    *   - the number of bytes read is returned, success
+    *   - -1, fail 
+    */
+    pub fn readv_syscall(
         &self,
         virtual_fd: i32,
         iovec: *const interface::IovecStruct,
@@ -429,80 +714,322 @@ impl Cage {
     ) -> i32 {
         let wrappedvfd = fdtables::translate_virtual_fd(self.cageid, virtual_fd as u64);
         if wrappedvfd.is_err() {
-            return syscall_error(Errno::EBADF, "write", "Bad File Descriptor");
+            return syscall_error(Errno::EBADF, "readv", "Bad File Descriptor");
         }
-
         let vfd = wrappedvfd.unwrap();
+        let iovs = match iovec_slice(iovec, iovcnt, "readv") {
+            Ok(iovs) => iovs,
+            Err(e) => return e,
+        };
+        if !is_host_fd(vfd.fdkind) {
+            // one read, so a datagram lands as a single message and a pipe only
+            // blocks while it is empty, then scattered over the buffers. The read can't
+            // return more than the pipe or socket holds, whatever the iov_lens add up to.
+            let total = interface::rust_min(iovs.iter().map(|iov| iov.iov_len).sum(), self.inmem_read_max(&vfd));
+            let mut gathered = vec![0u8; total];
+            let ret = self.read_syscall(virtual_fd, gathered.as_mut_ptr(), total);
+            if ret > 0 {
+                scatter_iovecs(iovs, &gathered[..ret as usize]);
+            }
+            return ret;
+        }
         let ret = unsafe {
-            libc::writev(vfd.underfd as i32, iovec, iovcnt)
+            libc::readv(vfd.underfd as i32, iovs.as_ptr(), iovcnt)
         };
         if ret < 0 {
             let errno = get_errno();
-            return handle_errno(errno, "writev");
+            return handle_errno(errno, "readv");
         }
         return ret as i32;
     }
 
-    //------------------------------------LSEEK SYSCALL------------------------------------
+    //------------------------------------PREADV SYSCALL------------------------------------
     /*
     *   Get the kernel fd with provided virtual fd first
-    *   lseek() will return:
-    *   -  the resulting offset location as measured in bytes from the beginning of the file
+    *   preadv() is readv() from offset, leaving the file offset alone
+    *   preadv() will return:
+    *   - the number of bytes read is returned, success
     *   - -1, fail 
     */
-    pub fn lseek_syscall(&self, virtual_fd: i32, offset: isize, whence: i32) -> i32 {
+    pub fn preadv_syscall(
+        &self,
+        virtual_fd: i32,
+        iovec: *const interface::IovecStruct,
+        iovcnt: i32,
+        offset: i64,
+    ) -> i32 {
+        if offset < 0 {
+            return syscall_error(Errno::EINVAL, "preadv", "offset is negative");
+        }
+        self.preadv2_inner(virtual_fd, iovec, iovcnt, offset, 0, "preadv")
+    }
+
+    //------------------------------------PREADV2 SYSCALL------------------------------------
+    /*
+    *   Get the kernel fd with provided virtual fd first
+    *   preadv2() is preadv() with RWF_* flags, and an offset of -1 reading at the file
+    *   offset like readv()
+    *   preadv2() will return:
+    *   - the number of bytes read is returned, success
+    *   - -1, fail 
+    */
+    pub fn preadv2_syscall(
+        &self,
+        virtual_fd: i32,
+        iovec: *const interface::IovecStruct,
+        iovcnt: i32,
+        offset: i64,
+        flags: i32,
+    ) -> i32 {
+        self.preadv2_inner(virtual_fd, iovec, iovcnt, offset, flags, "preadv2")
+    }
+
+    fn preadv2_inner(
+        &self,
+        virtual_fd: i32,
+        iovec: *const interface::IovecStruct,
+        iovcnt: i32,
+        offset: i64,
+        flags: i32,
+        syscallname: &str,
+    ) -> i32 {
         let wrappedvfd = fdtables::translate_virtual_fd(self.cageid, virtual_fd as u64);
         if wrappedvfd.is_err() {
-            return syscall_error(Errno::EBADF, "lseek", "Bad File Descriptor");
+            return syscall_error(Errno::EBADF, syscallname, "Bad File Descriptor");
         }
         let vfd = wrappedvfd.unwrap();
+        let iovs = match iovec_slice(iovec, iovcnt, syscallname) {
+            Ok(iovs) => iovs,
+            Err(e) => return e,
+        };
+        if !is_host_fd(vfd.fdkind) {
+            if offset != -1 {
+                return syscall_error(Errno::ESPIPE, syscallname, "Illegal seek");
+            }
+            if flags != 0 {
+                return syscall_error(Errno::EOPNOTSUPP, syscallname, "flags are not supported on this file descriptor");
+            }
+            return self.readv_syscall(virtual_fd, iovec, iovcnt);
+        }
         let ret = unsafe {
-            libc::lseek(vfd.underfd as i32, offset as i64, whence) as i32
+            libc::preadv2(vfd.underfd as i32, iovs.as_ptr(), iovcnt, offset, flags)
         };
         if ret < 0 {
             let errno = get_errno();
-            return handle_errno(errno, "lseek");
+            return handle_errno(errno, syscallname);
         }
-        return ret;
-        
+        return ret as i32;
     }
 
-    //------------------------------------ACCESS SYSCALL------------------------------------
+    //------------------------------------WRITEV SYSCALL------------------------------------
+
+    pub fn writev_syscall(
+        &self,
+        virtual_fd: i32,
+        iovec: *const interface::IovecStruct,
+        iovcnt: i32,
+    ) -> i32 {
+        let wrappedvfd = fdtables::translate_virtual_fd(self.cageid, virtual_fd as u64);
+        if wrappedvfd.is_err() {
+            return syscall_error(Errno::EBADF, "write", "Bad File Descriptor");
+        }
+
+        let vfd = wrappedvfd.unwrap();
+        if vfd.fdkind == fdtables::FDT_KINDEPOLL {
+            return syscall_error(Errno::EINVAL, "writev", "fd is attached to an object which is unsuitable for writing");
+        }
+        if vfd.fdkind == FDKIND_IMPIPE {
+            if iovcnt < 0 {
+                return syscall_error(Errno::EINVAL, "writev", "iovcnt is negative");
+            }
+            let iovs = unsafe { std::slice::from_raw_parts(iovec, iovcnt as usize) };
+            let mut total = 0;
+            for iov in iovs {
+                let ret = self.write_to_impipe(vfd, iov.iov_base as *const u8, iov.iov_len, "writev");
+                if ret < 0 {
+                    // errors are only reported when nothing has been written yet
+                    return if total > 0 { total } else { ret };
+                }
+                total += ret;
+                if (ret as usize) < iov.iov_len {
+                    break;
+                }
+            }
+            return total;
+        }
+        if vfd.fdkind == FDKIND_IMSOCK {
+            if iovcnt < 0 {
+                return syscall_error(Errno::EINVAL, "writev", "iovcnt is negative");
+            }
+            // gathered into one send so a datagram socket gets a single message
+            let iovs = unsafe { std::slice::from_raw_parts(iovec, iovcnt as usize) };
+            let mut gathered = Vec::new();
+            for iov in iovs {
+                gathered.extend_from_slice(unsafe { std::slice::from_raw_parts(iov.iov_base as *const u8, iov.iov_len) });
+            }
+            return self.send_syscall(virtual_fd, gathered.as_ptr(), gathered.len(), 0);
+        }
+        self.pwritev2_inner(virtual_fd, iovec, iovcnt, -1, 0, "writev")
+    }
+
+    //------------------------------------PWRITEV SYSCALL------------------------------------
     /*
-    *   access() will return 0 when sucess, -1 when fail 
+    *   Get the kernel fd with provided virtual fd first
+    *   pwritev() is writev() at offset, leaving the file offset alone
+    *   pwritev() will return:
+    *   - the number of bytes written is returned, success
+    *   - -1, fail 
     */
-    pub fn access_syscall(&self, path: &str, amode: i32) -> i32 {
-        let relpath = normpath(convpath(path), self);
-        let relative_path = relpath.to_str().unwrap();
-        let full_path = format!("{}{}", LIND_ROOT, relative_path);
-        let c_path = CString::new(full_path).unwrap();
-        let ret = unsafe {
-            libc::access(c_path.as_ptr(), amode)
-        };
-        if ret < 0 {
-            let errno = get_errno();
-            return handle_errno(errno, "access");
+    pub fn pwritev_syscall(
+        &self,
+        virtual_fd: i32,
+        iovec: *const interface::IovecStruct,
+        iovcnt: i32,
+        offset: i64,
+    ) -> i32 {
+        if offset < 0 {
+            return syscall_error(Errno::EINVAL, "pwritev", "offset is negative");
         }
-        ret
+        self.pwritev2_inner(virtual_fd, iovec, iovcnt, offset, 0, "pwritev")
     }
 
-    //------------------------------------FCHDIR SYSCALL------------------------------------
+    //------------------------------------PWRITEV2 SYSCALL------------------------------------
     /*
     *   Get the kernel fd with provided virtual fd first
-    *   fchdir() will return 0 when sucess, -1 when fail 
+    *   pwritev2() is pwritev() with RWF_* flags, and an offset of -1 writing at the file
+    *   offset like writev()
+    *   pwritev2() will return:
+    *   - the number of bytes written is returned, success
+    *   - -1, fail 
     */
-    pub fn fchdir_syscall(&self, virtual_fd: i32) -> i32 {
+    pub fn pwritev2_syscall(
+        &self,
+        virtual_fd: i32,
+        iovec: *const interface::IovecStruct,
+        iovcnt: i32,
+        offset: i64,
+        flags: i32,
+    ) -> i32 {
+        self.pwritev2_inner(virtual_fd, iovec, iovcnt, offset, flags, "pwritev2")
+    }
+
+    fn pwritev2_inner(
+        &self,
+        virtual_fd: i32,
+        iovec: *const interface::IovecStruct,
+        iovcnt: i32,
+        offset: i64,
+        flags: i32,
+        syscallname: &str,
+    ) -> i32 {
         let wrappedvfd = fdtables::translate_virtual_fd(self.cageid, virtual_fd as u64);
         if wrappedvfd.is_err() {
-            return syscall_error(Errno::EBADF, "fchdir", "Bad File Descriptor");
+            return syscall_error(Errno::EBADF, syscallname, "Bad File Descriptor");
         }
         let vfd = wrappedvfd.unwrap();
-        let ret = unsafe {
-            libc::fchdir(vfd.underfd as i32)
+        let iovs = match iovec_slice(iovec, iovcnt, syscallname) {
+            Ok(iovs) => iovs,
+            Err(e) => return e,
         };
-        if ret < 0 {
-            let errno = get_errno();
-            return handle_errno(errno, "fchdir");
+        if !is_host_fd(vfd.fdkind) {
+            if offset != -1 {
+                return syscall_error(Errno::ESPIPE, syscallname, "Illegal seek");
+            }
+            if flags != 0 {
+                return syscall_error(Errno::EOPNOTSUPP, syscallname, "flags are not supported on this file descriptor");
+            }
+            return self.writev_syscall(virtual_fd, iovec, iovcnt);
+        }
+
+        let total = iovs.iter().map(|iov| iov.iov_len).sum();
+        let position = if offset == -1 || flags & RWF_APPEND != 0 { None } else { Some(offset) };
+        let allowed = match self.fsize_allowance(vfd.underfd as i32, position, total, syscallname) {
+            Ok(count) => count,
+            Err(e) => return e,
+        };
+        let iovs = limit_iovecs(iovs, allowed);
+        let ret = unsafe {
+            libc::pwritev2(vfd.underfd as i32, iovs.as_ptr(), iovs.len() as i32, offset, flags)
+        };
+        if ret < 0 {
+            let errno = get_errno();
+            return handle_errno(errno, syscallname);
+        }
+        return ret as i32;
+    }
+
+    //------------------------------------LSEEK SYSCALL------------------------------------
+    /*
+    *   Get the kernel fd with provided virtual fd first
+    *   lseek() will return:
+    *   -  the resulting offset location as measured in bytes from the beginning of the file
+    *   - -1, fail 
+    */
+    pub fn lseek_syscall(&self, virtual_fd: i32, offset: isize, whence: i32) -> i32 {
+        let wrappedvfd = fdtables::translate_virtual_fd(self.cageid, virtual_fd as u64);
+        if wrappedvfd.is_err() {
+            return syscall_error(Errno::EBADF, "lseek", "Bad File Descriptor");
+        }
+        let vfd = wrappedvfd.unwrap();
+        if !is_host_fd(vfd.fdkind) {
+            return syscall_error(Errno::ESPIPE, "lseek", "Illegal seek");
+        }
+        let ret = unsafe {
+            libc::lseek(vfd.underfd as i32, offset as i64, whence) as i32
+        };
+        if ret < 0 {
+            let errno = get_errno();
+            return handle_errno(errno, "lseek");
+        }
+        return ret;
+        
+    }
+
+    //------------------------------------ACCESS SYSCALL------------------------------------
+    /*
+    *   access() will return 0 when sucess, -1 when fail 
+    */
+    pub fn access_syscall(&self, path: &str, amode: i32) -> i32 {
+        let target = match resolve_beneath(path, self) {
+            Ok(target) => target,
+            Err(errno) => return handle_errno(errno, "access"),
+        };
+        // access() answers for the real ids, F_OK only asks whether the file exists
+        if amode != libc::F_OK {
+            if let Err(errno) = cage_may_access(self, target.dirfd, amode, true) {
+                return handle_errno(errno, "access");
+            }
+        }
+        let ret = unsafe {
+            libc::access(target.procpath().as_ptr(), amode)
+        };
+        if ret < 0 {
+            let errno = get_errno();
+            return handle_errno(errno, "access");
+        }
+        ret
+    }
+
+    //------------------------------------FCHDIR SYSCALL------------------------------------
+    /*
+    *   Get the kernel fd with provided virtual fd first
+    *   fchdir() will return 0 when sucess, -1 when fail 
+    */
+    pub fn fchdir_syscall(&self, virtual_fd: i32) -> i32 {
+        let wrappedvfd = fdtables::translate_virtual_fd(self.cageid, virtual_fd as u64);
+        if wrappedvfd.is_err() {
+            return syscall_error(Errno::EBADF, "fchdir", "Bad File Descriptor");
+        }
+        let vfd = wrappedvfd.unwrap();
+        if vfd.fdkind != FDKIND_KERNEL {
+            return syscall_error(Errno::ENOTDIR, "fchdir", "file descriptor does not refer to a directory");
+        }
+        let ret = unsafe {
+            libc::fchdir(vfd.underfd as i32)
+        };
+        if ret < 0 {
+            let errno = get_errno();
+            return handle_errno(errno, "fchdir");
         }
 
         // Get the working directory in native
@@ -517,8 +1044,10 @@ impl Cage {
         let cwdcstr = unsafe { CStr::from_ptr(buf.as_ptr() as *const i8) };
         let cwd = cwdcstr.to_str().unwrap();
         // Update RawPOSIX working directory
-        let raw_path = &cwd[LIND_ROOT.len()..];
-        let true_path = normpath(convpath(raw_path), self);
+        let true_path = match strip_lindroot(cwd, self) {
+            Some(raw_path) => normpath(raw_path, self),
+            None => return syscall_error(Errno::EACCES, "fchdir", "Directory is outside of the cage's root"),
+        };
         let mut cwd_container = self.cwd.write();
         *cwd_container = interface::RustRfc::new(true_path);
 
@@ -532,13 +1061,13 @@ impl Cage {
     *   chdir() will return 0 when sucess, -1 when fail 
     */
     pub fn chdir_syscall(&self, path: &str) -> i32 {
-        let relpath = normpath(convpath(path), self);
-        let relative_path = relpath.to_str().unwrap();
-        let full_path = format!("{}{}", LIND_ROOT, relative_path);
-        let c_path = CString::new(full_path).unwrap();
+        let target = match resolve_beneath(path, self) {
+            Ok(target) => target,
+            Err(errno) => return handle_errno(errno, "chdir"),
+        };
 
         let ret = unsafe {
-            libc::chdir(c_path.as_ptr())
+            libc::fchdir(target.dirfd)
         };
 
         if ret < 0 {
@@ -561,7 +1090,7 @@ impl Cage {
     *   Mapping a new virtual fd and kernel fd that libc::dup returned
     *   Then return virtual fd
     */
-    pub fn dup_syscall(&self, virtual_fd: i32, _start_desc: Option<i32>) -> i32 {
+    pub fn dup_syscall(&self, virtual_fd: i32, start_desc: Option<i32>) -> i32 {
         if virtual_fd < 0 {
             return syscall_error(Errno::EBADF, "dup", "Bad File Descriptor");
         }
@@ -570,21 +1099,46 @@ impl Cage {
             return syscall_error(Errno::EBADF, "dup", "Bad File Descriptor");
         }
         let vfd = wrappedvfd.unwrap();
+        // the new fd is the lowest unused one from start_desc on, fcntl's F_DUPFD passes it
+        let start_desc = start_desc.unwrap_or(0) as u64;
+        // in-memory fds are shared by reference, fdtables keeps count of the duplicates
+        if vfd.fdkind != FDKIND_KERNEL {
+            return match fdtables::get_unused_virtual_fd_from(self.cageid, start_desc, vfd.fdkind, vfd.underfd, false, vfd.perfdinfo) {
+                Ok(ret_virtualfd) => ret_virtualfd as i32,
+                Err(e) => handle_errno(e as i32, "dup"),
+            };
+        }
         let ret_kernelfd = unsafe{ libc::dup(vfd.underfd as i32) };
-        let ret_virtualfd = fdtables::get_unused_virtual_fd(self.cageid, vfd.fdkind, ret_kernelfd as u64, false, 0).unwrap();
-        return ret_virtualfd as i32;
+        if ret_kernelfd < 0 {
+            let errno = get_errno();
+            return handle_errno(errno, "dup");
+        }
+        match fdtables::get_unused_virtual_fd_from(self.cageid, start_desc, FDKIND_KERNEL, ret_kernelfd as u64, false, 0) {
+            Ok(ret_virtualfd) => ret_virtualfd as i32,
+            Err(e) => {
+                unsafe { libc::close(ret_kernelfd) };
+                handle_errno(e as i32, "dup")
+            }
+        }
         
     }
 
     /* 
     */
     pub fn dup2_syscall(&self, old_virtualfd: i32, new_virtualfd: i32) -> i32 {
-        if old_virtualfd < 0 || new_virtualfd < 0 {
-            return syscall_error(Errno::EBADF, "dup", "Bad File Descriptor");
+        if old_virtualfd < 0 || new_virtualfd < 0 || new_virtualfd as u64 >= fdtables::get_fd_limit(self.cageid) {
+            return syscall_error(Errno::EBADF, "dup2", "Bad File Descriptor");
         }
 
         match fdtables::translate_virtual_fd(self.cageid, old_virtualfd as u64) {
             Ok(old_vfd) => {
+                if old_virtualfd == new_virtualfd {
+                    return new_virtualfd;
+                }
+                if old_vfd.fdkind != FDKIND_KERNEL {
+                    let _ = fdtables::get_specific_virtual_fd(self.cageid, new_virtualfd as u64, old_vfd.fdkind, old_vfd.underfd, false, old_vfd.perfdinfo).unwrap();
+                    return new_virtualfd;
+                }
                 let new_kernelfd = unsafe {
                     libc::dup(old_vfd.underfd as i32)
                 };
@@ -672,12 +1226,29 @@ impl Cage {
                     return syscall_error(Errno::EBADF, "fcntl", "Bad File Descriptor");
                 }
                 let vfd = wrappedvfd.unwrap();
-                if cmd == libc::F_DUPFD {
-                    match arg {
-                        n if n < 0 => return syscall_error(Errno::EINVAL, "fcntl", "op is F_DUPFD and arg is negative or is greater than the maximum allowable value"),
-                        0..=1024 => return self.dup2_syscall(virtual_fd, arg),
-                        _ => return syscall_error(Errno::EMFILE, "fcntl", "op is F_DUPFD and the per-process limit on the number of open file descriptors has been reached")
+                if cmd == libc::F_DUPFD || cmd == libc::F_DUPFD_CLOEXEC {
+                    if arg < 0 || arg as u64 >= fdtables::get_fd_limit(self.cageid) {
+                        return syscall_error(Errno::EINVAL, "fcntl", "op is F_DUPFD and arg is negative or is greater than the maximum allowable value");
+                    }
+                    let new_virtualfd = self.dup_syscall(virtual_fd, Some(arg));
+                    if new_virtualfd >= 0 && cmd == libc::F_DUPFD_CLOEXEC {
+                        let _ = fdtables::set_cloexec(self.cageid, new_virtualfd as u64, true);
+                        // F_GETFD on a kernel fd asks the host
+                        if vfd.fdkind == FDKIND_KERNEL {
+                            let newvfd = fdtables::translate_virtual_fd(self.cageid, new_virtualfd as u64).unwrap();
+                            unsafe { libc::fcntl(newvfd.underfd as i32, F_SETFD, FD_CLOEXEC) };
+                        }
                     }
+                    return new_virtualfd;
+                }
+                // the close-on-exec flag of a shm fd is the cage's, the rest is the memfd's
+                let inmem = match vfd.fdkind {
+                    FDKIND_KERNEL => false,
+                    FDKIND_IMSHM => cmd == F_GETFD || cmd == F_SETFD,
+                    _ => true,
+                };
+                if inmem {
+                    return self.fcntl_inmem(virtual_fd, vfd, cmd, arg);
                 }
                 let ret = unsafe { libc::fcntl(vfd.underfd as i32, cmd, arg) };
                 if ret < 0 {
@@ -704,6 +1275,9 @@ impl Cage {
             return syscall_error(Errno::EBADF, "ioctl", "Bad File Descriptor");
         }
         let vfd = wrappedvfd.unwrap();
+        if !is_host_fd(vfd.fdkind) {
+            return self.ioctl_inmem(virtual_fd, vfd, request, ptrunion);
+        }
         let ret = unsafe { libc::ioctl(vfd.underfd as i32, request, ptrunion as *mut c_void) };
         if ret < 0 {
             let errno = get_errno();
@@ -719,12 +1293,19 @@ impl Cage {
     *   chmod() will return 0 when success and -1 when fail 
     */
     pub fn chmod_syscall(&self, path: &str, mode: u32) -> i32 {
-        let relpath = normpath(convpath(path), self);
-        let relative_path = relpath.to_str().unwrap();
-        let full_path = format!("{}{}", LIND_ROOT, relative_path);
-        let c_path = CString::new(full_path).unwrap();
+        self.chmod_at(None, path, mode)
+    }
+
+    fn chmod_at(&self, dirfd: Option<i32>, path: &str, mode: u32) -> i32 {
+        let target = match resolve_beneath_at(dirfd, path, self) {
+            Ok(target) => target,
+            Err(errno) => return handle_errno(errno, "chmod"),
+        };
+        if let Err(errno) = self.check_owner(target.dirfd) {
+            return handle_errno(errno, "chmod");
+        }
         let ret = unsafe {
-            libc::chmod(c_path.as_ptr(), mode)
+            libc::chmod(target.procpath().as_ptr(), mode)
         };
         if ret < 0 {
             let errno = get_errno();
@@ -744,6 +1325,19 @@ impl Cage {
             return syscall_error(Errno::EBADF, "fchmod", "Bad File Descriptor");
         }
         let vfd = wrappedvfd.unwrap();
+        // in-memory fds have no host inode, so there is nothing to change
+        if !is_host_fd(vfd.fdkind) {
+            return 0;
+        }
+        if let Err(errno) = self.check_owner(vfd.underfd as i32) {
+            return handle_errno(errno, "fchmod");
+        }
+        if vfd.fdkind == FDKIND_IMSHM {
+            if let Some(object) = interface::shm_open_object(vfd.underfd) {
+                object.set_mode(mode);
+                return 0;
+            }
+        }
         let ret = unsafe {
             libc::fchmod(vfd.underfd as i32, mode)
         };
@@ -755,6 +1349,109 @@ impl Cage {
         
     }
 
+    //------------------------------------CHOWN SYSCALL------------------------------------
+    /*
+    *   chown() changes the owner and group the cage sees for a file, following a trailing
+    *   symlink. The host file stays with whoever runs lind, the new ids are recorded with
+    *   set_virtual_owner and reported back by stat. An id of -1 is left as it is.
+    *   chown() will return 0 when success, -1 (EPERM) when the caller may not make the change
+    */
+    pub fn chown_syscall(&self, path: &str, uid: u32, gid: u32) -> i32 {
+        let target = match resolve_beneath(path, self) {
+            Ok(target) => target,
+            Err(errno) => return handle_errno(errno, "chown"),
+        };
+        match self.chown_fd(target.dirfd, uid, gid) {
+            Ok(()) => 0,
+            Err(errno) => handle_errno(errno, "chown"),
+        }
+    }
+
+    //------------------------------------LCHOWN SYSCALL------------------------------------
+    /*
+    *   lchown() is chown() on a symlink itself rather than on its target
+    */
+    pub fn lchown_syscall(&self, path: &str, uid: u32, gid: u32) -> i32 {
+        let fd = match open_beneath(path, self, O_PATH | O_NOFOLLOW | O_CLOEXEC, 0) {
+            Ok(fd) => fd,
+            Err(errno) => return handle_errno(errno, "lchown"),
+        };
+        let ret = self.chown_fd(fd, uid, gid);
+        unsafe { libc::close(fd) };
+        match ret {
+            Ok(()) => 0,
+            Err(errno) => handle_errno(errno, "lchown"),
+        }
+    }
+
+    //------------------------------------FCHOWN SYSCALL------------------------------------
+    /*
+    *   Get the kernel fd with provided virtual fd first
+    *   fchown() will return 0 when sucess, -1 when fail
+    */
+    pub fn fchown_syscall(&self, virtual_fd: i32, uid: u32, gid: u32) -> i32 {
+        let wrappedvfd = fdtables::translate_virtual_fd(self.cageid, virtual_fd as u64);
+        if wrappedvfd.is_err() {
+            return syscall_error(Errno::EBADF, "fchown", "Bad File Descriptor");
+        }
+        let vfd = wrappedvfd.unwrap();
+        // in-memory fds have no host inode, so there is nothing to record
+        if !is_host_fd(vfd.fdkind) {
+            return 0;
+        }
+        if let Err(errno) = self.chown_fd(vfd.underfd as i32, uid, gid) {
+            return handle_errno(errno, "fchown");
+        }
+        // a shm object keeps its set-ID bits on the object, chown_fd only saw its 0600 memfd
+        if vfd.fdkind == FDKIND_IMSHM && self.euid() != sys_constants::ROOT_UID && (uid != u32::MAX || gid != u32::MAX) {
+            if let Some(object) = interface::shm_open_object(vfd.underfd) {
+                object.set_mode(object.mode() & !(libc::S_ISUID | libc::S_ISGID));
+            }
+        }
+        0
+    }
+
+    /*
+    *   The permission rules are those of Linux: root may do anything, the owner may move
+    *   the file to one of its own groups, and nobody else may change anything. A change
+    *   made by anyone but root clears the set-user-ID and set-group-ID bits of a regular file.
+    */
+    fn chown_fd(&self, fd: i32, uid: u32, gid: u32) -> Result<(), i32> {
+        let st = fstat_beneath(fd)?;
+        let (olduid, oldgid) = virtual_owner(fd, &st);
+        let euid = self.euid();
+        let isroot = euid == sys_constants::ROOT_UID;
+        if !isroot {
+            if uid != u32::MAX && (euid != olduid || uid != olduid) {
+                return Err(libc::EPERM);
+            }
+            if gid != u32::MAX && (euid != olduid || (gid != oldgid && !self.in_group(gid, false))) {
+                return Err(libc::EPERM);
+            }
+        }
+        if uid == u32::MAX && gid == u32::MAX {
+            return Ok(());
+        }
+        let newuid = if uid == u32::MAX { olduid } else { uid };
+        let newgid = if gid == u32::MAX { oldgid } else { gid };
+        set_virtual_owner(fd, &st, newuid, newgid)?;
+
+        let setid = st.st_mode & (libc::S_ISUID | libc::S_ISGID);
+        if !isroot && setid != 0 && st.st_mode & libc::S_IFMT == libc::S_IFREG {
+            unsafe { libc::chmod(procfd_path(fd).as_ptr(), st.st_mode & 0o7777 & !setid) };
+        }
+        Ok(())
+    }
+
+    //------------------------------------UMASK SYSCALL------------------------------------
+    /*
+    *   umask() sets the permission bits cleared from files the cage creates from now on
+    *   umask() always succeeds and returns the previous mask
+    */
+    pub fn umask_syscall(&self, mask: u32) -> u32 {
+        self.umask.swap(mask & 0o777, interface::RustAtomicOrdering::Relaxed)
+    }
+
     //------------------------------------MMAP SYSCALL------------------------------------
     /*
     *   Get the kernel fd with provided virtual fd first
@@ -771,17 +1468,53 @@ impl Cage {
         virtual_fd: i32,
         off: i64,
     ) -> i32 {
+        // RLIMIT_AS is checked against the ranges this cage has mapped through mmap. A
+        // MAP_FIXED mapping replaces whatever it lands on, which is counted only once.
+        let limit = self.rlimit_cur(sys_constants::RLIMIT_AS);
+        let mut ranges = self.mapped_ranges.lock();
+        if limit != sys_constants::RLIM_INFINITY {
+            let (start, end) = page_range(addr as usize, len);
+            let mut added = end - start;
+            if flags & MAP_FIXED != 0 {
+                added -= covered_bytes(&ranges, start, end);
+            }
+            if covered_bytes(&ranges, 0, usize::MAX).saturating_add(added) as u64 > limit {
+                return syscall_error(Errno::ENOMEM, "mmap", "mapping would exceed RLIMIT_AS");
+            }
+        }
+        let ret = self.mmap_inner(addr, len, prot, flags, virtual_fd, off);
+        if ret >= 0 {
+            let (start, end) = page_range(ret as usize, len);
+            remove_range(&mut ranges, start, end);
+            ranges.push((start, end));
+        }
+        // the returned address is truncated to 32 bits, like Linux only -4095..-1 are errors
+        (ret & 0xffffffff) as i32
+    }
+
+    // The host address of the mapping, or a negative errno
+    fn mmap_inner(
+        &self,
+        addr: *mut u8,
+        len: usize,
+        prot: i32,
+        flags: i32,
+        virtual_fd: i32,
+        off: i64,
+    ) -> i64 {
         if virtual_fd != -1 {
             match fdtables::translate_virtual_fd(self.cageid, virtual_fd as u64) {
                 Ok(kernel_fd) => {
+                    if !is_host_fd(kernel_fd.fdkind) {
+                        return syscall_error(Errno::ENODEV, "mmap", "file descriptor does not support memory mapping") as i64;
+                    }
                     let ret = unsafe {
-                        ((libc::mmap(addr as *mut c_void, len, prot, flags, kernel_fd.underfd as i32, off) as i64) 
-                            & 0xffffffff) as i32
+                        libc::mmap(addr as *mut c_void, len, prot, flags, kernel_fd.underfd as i32, off) as i64
                     };
                     return ret;
                 },
                 Err(_e) => {
-                    return syscall_error(Errno::EBADF, "mmap", "Bad File Descriptor");
+                    return syscall_error(Errno::EBADF, "mmap", "Bad File Descriptor") as i64;
                 }
             }
         } else {
@@ -791,9 +1524,9 @@ impl Cage {
             };
             // Check if mmap failed and return the appropriate error if so
             if ret == -1 {
-                return syscall_error(Errno::EINVAL, "mmap", "mmap failed with invalid flags");
+                return syscall_error(Errno::EINVAL, "mmap", "mmap failed with invalid flags") as i64;
             }
-            return (ret & 0xffffffff) as i32;
+            return ret;
         }
     }
 
@@ -811,6 +1544,8 @@ impl Cage {
             let errno = get_errno();
             return handle_errno(errno, "munmap");
         }
+        let (start, end) = page_range(addr as usize, len);
+        remove_range(&mut self.mapped_ranges.lock(), start, end);
         ret
     }
 
@@ -825,6 +1560,10 @@ impl Cage {
             return syscall_error(Errno::EBADF, "flock", "Bad File Descriptor");
         }
         let vfd = wrappedvfd.unwrap();
+        // nothing outside the sandbox can contend for a lock on an in-memory fd
+        if !is_host_fd(vfd.fdkind) {
+            return 0;
+        }
         let ret = unsafe {
             libc::flock(vfd.underfd as i32, operation)
         };
@@ -841,16 +1580,20 @@ impl Cage {
     *   rmdir() will return 0 when sucess, -1 when fail 
     */
     pub fn rmdir_syscall(&self, path: &str) -> i32 {
+        self.rmdir_at(None, path)
+    }
+
+    fn rmdir_at(&self, dirfd: Option<i32>, path: &str) -> i32 {
         match path {
             "" => return syscall_error(Errno::ENOENT, "rmdir", "A directory component in pathname does not exist"),
             "/" => return syscall_error(Errno::EBUSY, "rmdir", "pathname is currently in use by the system or some process that prevents its removal"),
             _ => {
-                let relpath = normpath(convpath(path), self);
-                let relative_path = relpath.to_str().unwrap();
-                let full_path = format!("{}{}", LIND_ROOT, relative_path);
-                let c_path = CString::new(full_path).unwrap();
+                let target = match resolve_parent_beneath_at(dirfd, path, self) {
+                    Ok(target) => target,
+                    Err(errno) => return handle_errno(errno, "rmdir"),
+                };
                 let ret = unsafe {
-                    libc::rmdir(c_path.as_ptr())
+                    libc::unlinkat(target.dirfd, target.name.as_ptr(), AT_REMOVEDIR)
                 };
                 if ret < 0 {
                     let errno = get_errno();
@@ -867,142 +1610,425 @@ impl Cage {
     *   rename() will return 0 when sucess, -1 when fail 
     */
     pub fn rename_syscall(&self, oldpath: &str, newpath: &str) -> i32 {
-        let rel_oldpath = normpath(convpath(oldpath), self);
-        let relative_oldpath = rel_oldpath.to_str().unwrap();
-        let full_oldpath = format!("{}{}", LIND_ROOT, relative_oldpath);
-        let old_cpath = CString::new(full_oldpath).unwrap();
+        self.rename_beneath(None, oldpath, None, newpath, 0, "rename")
+    }
+
+    // rename() and renameat2(), flags being the RENAME_* flags of renameat2
+    fn rename_beneath(&self, olddirfd: Option<i32>, oldpath: &str, newdirfd: Option<i32>, newpath: &str, flags: u32, syscallname: &str) -> i32 {
+        let oldtarget = match resolve_parent_beneath_at(olddirfd, oldpath, self) {
+            Ok(target) => target,
+            Err(errno) => return handle_errno(errno, syscallname),
+        };
+        let newtarget = match resolve_parent_beneath_at(newdirfd, newpath, self) {
+            Ok(target) => target,
+            Err(errno) => return handle_errno(errno, syscallname),
+        };
 
-        let rel_newpath = normpath(convpath(newpath), self);
-        let relative_newpath = rel_newpath.to_str().unwrap();
-        let full_newpath = format!("{}{}", LIND_ROOT, relative_newpath);
-        let new_cpath = CString::new(full_newpath).unwrap();
+        // a file renamed over loses that link just like unlink() would take it,
+        // while RENAME_EXCHANGE keeps both files around
+        let mut st: libc::stat = unsafe { std::mem::zeroed() };
+        let statret = if flags & RENAME_EXCHANGE == 0 {
+            unsafe { libc::fstatat(newtarget.dirfd, newtarget.name.as_ptr(), &mut st, AT_SYMLINK_NOFOLLOW) }
+        } else {
+            -1
+        };
 
         let ret = unsafe {
-            libc::rename(old_cpath.as_ptr(), new_cpath.as_ptr())
+            libc::renameat2(oldtarget.dirfd, oldtarget.name.as_ptr(), newtarget.dirfd, newtarget.name.as_ptr(), flags)
         };
         if ret < 0 {
             let errno = get_errno();
-            return handle_errno(errno, "rename");
+            return handle_errno(errno, syscallname);
+        }
+        if statret == 0 {
+            forget_virtual_owner(&st);
         }
         ret
     }
 
-    //------------------------------------FSYNC SYSCALL------------------------------------
     /*
-    *   Get the kernel fd with provided virtual fd first
-    *   fsync() will return 0 when sucess, -1 when fail 
+    *   The *at() calls resolve a relative path from the directory dirfd refers to instead
+    *   of the cwd. The lookup starts at the directory's kernel fd itself, so it still finds
+    *   the directory after a rename, and stays beneath the cage's root like any other.
+    *   Returns that kernel fd, or None for absolute paths and AT_FDCWD.
     */
-    pub fn fsync_syscall(&self, virtual_fd: i32) -> i32 {
-        let wrappedvfd = fdtables::translate_virtual_fd(self.cageid, virtual_fd as u64);
-        if wrappedvfd.is_err() {
-            return syscall_error(Errno::EBADF, "fsync", "Bad File Descriptor");
+    fn at_dir(&self, dirfd: i32, path: &str) -> Result<Option<i32>, i32> {
+        if path.is_empty() {
+            return Err(libc::ENOENT);
         }
-        let vfd = wrappedvfd.unwrap();
-        let ret = unsafe {
-            libc::fsync(vfd.underfd as i32)
+        if path.starts_with('/') || dirfd == AT_FDCWD {
+            return Ok(None);
+        }
+        let vfd = match fdtables::translate_virtual_fd(self.cageid, dirfd as u64) {
+            Ok(vfd) => vfd,
+            Err(_) => return Err(libc::EBADF),
         };
-        if ret < 0 {
-            let errno = get_errno();
-            return handle_errno(errno, "fsync");
+        if vfd.fdkind != FDKIND_KERNEL {
+            return Err(libc::ENOTDIR);
         }
-        return ret;
-        
+        let st = fstat_beneath(vfd.underfd as i32)?;
+        if st.st_mode & S_IFMT != S_IFDIR {
+            return Err(libc::ENOTDIR);
+        }
+        Ok(Some(vfd.underfd as i32))
     }
 
-    //------------------------------------FDATASYNC SYSCALL------------------------------------
+    //------------------------------------OPENAT SYSCALL------------------------------------
     /*
-    *   Get the kernel fd with provided virtual fd first
-    *   fdatasync() will return 0 when sucess, -1 when fail 
+    *   openat() is open() with path relative to dirfd
+    *   openat() will return a file descriptor when success and -1 when fail 
     */
-    pub fn fdatasync_syscall(&self, virtual_fd: i32) -> i32 {
-        let wrappedvfd = fdtables::translate_virtual_fd(self.cageid, virtual_fd as u64);
-        if wrappedvfd.is_err() {
-            return syscall_error(Errno::EBADF, "fdatasync", "Bad File Descriptor");
-        }
-        let vfd = wrappedvfd.unwrap();
-        let ret = unsafe {
-            libc::fdatasync(vfd.underfd as i32)
-        };
-        if ret < 0 {
-            let errno = get_errno();
-            return handle_errno(errno, "fdatasync");
+    pub fn openat_syscall(&self, dirfd: i32, path: &str, oflag: i32, mode: u32) -> i32 {
+        match self.at_dir(dirfd, path) {
+            Ok(dir) => self.open_at(dir, path, oflag, mode),
+            Err(errno) => handle_errno(errno, "openat"),
         }
-        return ret;
-        
     }
 
-    //------------------------------------SYNC_FILE_RANGE SYSCALL------------------------------------
+    //------------------------------------MKDIRAT SYSCALL------------------------------------
     /*
-    *   Get the kernel fd with provided virtual fd first
-    *   sync_file_range() will return 0 when sucess, -1 when fail 
+    *   mkdirat() is mkdir() with path relative to dirfd
+    *   mkdirat() will return 0 when success and -1 when fail 
     */
-    pub fn sync_file_range_syscall(
-        &self,
-        virtual_fd: i32,
-        offset: isize,
-        nbytes: isize,
-        flags: u32,
-    ) -> i32 {
-        let wrappedvfd = fdtables::translate_virtual_fd(self.cageid, virtual_fd as u64);
-        if wrappedvfd.is_err() {
-            return syscall_error(Errno::EBADF, "sync", "Bad File Descriptor");
-        }
-        let vfd = wrappedvfd.unwrap();
-        let ret = unsafe {
-            libc::sync_file_range(vfd.underfd as i32, offset as i64, nbytes as i64, flags)
-        };
-        if ret < 0 {
-            let errno = get_errno();
-            return handle_errno(errno, "sync_file_range");
+    pub fn mkdirat_syscall(&self, dirfd: i32, path: &str, mode: u32) -> i32 {
+        match self.at_dir(dirfd, path) {
+            Ok(dir) => self.mkdir_at(dir, path, mode),
+            Err(errno) => handle_errno(errno, "mkdirat"),
         }
-        ret
     }
 
-    //------------------FTRUNCATE SYSCALL------------------
+    //------------------------------------UNLINKAT SYSCALL------------------------------------
     /*
-    *   Get the kernel fd with provided virtual fd first
-    *   ftruncate() will return 0 when sucess, -1 when fail 
+    *   unlinkat() is unlink(), or rmdir() with AT_REMOVEDIR, with path relative to dirfd
+    *   unlinkat() will return 0 when success and -1 when fail 
     */
-    pub fn ftruncate_syscall(&self, virtual_fd: i32, length: isize) -> i32 {
-        let wrappedvfd = fdtables::translate_virtual_fd(self.cageid, virtual_fd as u64);
-        if wrappedvfd.is_err() {
-            return syscall_error(Errno::EBADF, "ftruncate", "Bad File Descriptor");
+    pub fn unlinkat_syscall(&self, dirfd: i32, path: &str, flags: i32) -> i32 {
+        if flags & !AT_REMOVEDIR != 0 {
+            return syscall_error(Errno::EINVAL, "unlinkat", "Invalid flags");
         }
-        let vfd = wrappedvfd.unwrap();
-        let ret = unsafe {
-            libc::ftruncate(vfd.underfd as i32, length as i64)
+        let dir = match self.at_dir(dirfd, path) {
+            Ok(dir) => dir,
+            Err(errno) => return handle_errno(errno, "unlinkat"),
         };
-        if ret < 0 {
-            let errno = get_errno();
-            return handle_errno(errno, "ftruncate");
+        if flags & AT_REMOVEDIR != 0 {
+            self.rmdir_at(dir, path)
+        } else {
+            self.unlink_at(dir, path)
         }
-        ret
     }
 
-    //------------------TRUNCATE SYSCALL------------------
+    //------------------------------------RENAMEAT2 SYSCALL------------------------------------
     /*
-    *   truncate() will return 0 when sucess, -1 when fail 
+    *   renameat2() is rename() with both paths relative to their own dirfd.
+    *   RENAME_NOREPLACE fails with EEXIST instead of replacing newpath,
+    *   RENAME_EXCHANGE atomically swaps the two paths, which must both exist.
+    *   renameat2() will return 0 when success and -1 when fail 
     */
-    pub fn truncate_syscall(&self, path: &str, length: isize) -> i32 {
-        let relpath = normpath(convpath(path), self);
-        let relative_path = relpath.to_str().unwrap();
-        let full_path = format!("{}{}", LIND_ROOT, relative_path);
-        let c_path = CString::new(full_path).unwrap();
-        let ret = unsafe {
-            libc::truncate(c_path.as_ptr(), length as i64)
-        };
-        if ret < 0 {
-            let errno = get_errno();
-            return handle_errno(errno, "truncate");
+    pub fn renameat2_syscall(&self, olddirfd: i32, oldpath: &str, newdirfd: i32, newpath: &str, flags: u32) -> i32 {
+        if flags & !(RENAME_NOREPLACE | RENAME_EXCHANGE) != 0 {
+            return syscall_error(Errno::EINVAL, "renameat2", "Invalid flags");
         }
-        ret
+        if flags & RENAME_NOREPLACE != 0 && flags & RENAME_EXCHANGE != 0 {
+            return syscall_error(Errno::EINVAL, "renameat2", "RENAME_NOREPLACE and RENAME_EXCHANGE are mutually exclusive");
+        }
+        let olddir = match self.at_dir(olddirfd, oldpath) {
+            Ok(dir) => dir,
+            Err(errno) => return handle_errno(errno, "renameat2"),
+        };
+        let newdir = match self.at_dir(newdirfd, newpath) {
+            Ok(dir) => dir,
+            Err(errno) => return handle_errno(errno, "renameat2"),
+        };
+        self.rename_beneath(olddir, oldpath, newdir, newpath, flags, "renameat2")
     }
 
-    //------------------PIPE SYSCALL------------------
+    //------------------------------------FSTATAT SYSCALL------------------------------------
     /*
-    *   When using the rust libc, a pipe file descriptor (pipefd) is typically an array 
-    *   containing two integers. This array is populated by the pipe system call, with one 
-    *   integer for the read end and the other for the write end.
+    *   fstatat() is stat() with path relative to dirfd. AT_SYMLINK_NOFOLLOW stats a
+    *   trailing symlink itself, and AT_EMPTY_PATH with an empty path stats dirfd
+    *   (which need not be a directory then) like fstat().
+    *   fstatat() will return 0 when success and -1 when fail 
+    */
+    pub fn fstatat_syscall(&self, dirfd: i32, path: &str, rposix_statbuf: &mut StatData, flags: i32) -> i32 {
+        if flags & !(AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH | AT_NO_AUTOMOUNT) != 0 {
+            return syscall_error(Errno::EINVAL, "fstatat", "Invalid flags");
+        }
+        if path.is_empty() && flags & AT_EMPTY_PATH != 0 {
+            if dirfd == AT_FDCWD {
+                return self.stat_syscall(".", rposix_statbuf);
+            }
+            return self.fstat_syscall(dirfd, rposix_statbuf);
+        }
+        let dir = match self.at_dir(dirfd, path) {
+            Ok(dir) => dir,
+            Err(errno) => return handle_errno(errno, "fstatat"),
+        };
+
+        if flags & AT_SYMLINK_NOFOLLOW != 0 {
+            self.lstat_at(dir, path, rposix_statbuf)
+        } else {
+            self.stat_at(dir, path, rposix_statbuf)
+        }
+    }
+
+    //------------------------------------LINKAT SYSCALL------------------------------------
+    /*
+    *   linkat() is link() with both paths relative to their own dirfd.
+    *   AT_SYMLINK_FOLLOW links the file a trailing symlink in oldpath points to instead of
+    *   the symlink, and AT_EMPTY_PATH with an empty oldpath links the file olddirfd refers to.
+    *   linkat() will return 0 when success and -1 when fail 
+    */
+    pub fn linkat_syscall(&self, olddirfd: i32, oldpath: &str, newdirfd: i32, newpath: &str, flags: i32) -> i32 {
+        if flags & !(AT_SYMLINK_FOLLOW | AT_EMPTY_PATH) != 0 {
+            return syscall_error(Errno::EINVAL, "linkat", "Invalid flags");
+        }
+        let newdir = match self.at_dir(newdirfd, newpath) {
+            Ok(dir) => dir,
+            Err(errno) => return handle_errno(errno, "linkat"),
+        };
+
+        if oldpath.is_empty() && flags & AT_EMPTY_PATH != 0 {
+            let vfd = match fdtables::translate_virtual_fd(self.cageid, olddirfd as u64) {
+                Ok(vfd) => vfd,
+                Err(_) => return syscall_error(Errno::EBADF, "linkat", "Bad File Descriptor"),
+            };
+            // in-memory pipes and sockets have no inode a name could be given to
+            if vfd.fdkind != FDKIND_KERNEL {
+                return syscall_error(Errno::ENOENT, "linkat", "file descriptor has no file to link");
+            }
+            return self.link_fd(vfd.underfd as i32, newdir, newpath);
+        }
+
+        let olddir = match self.at_dir(olddirfd, oldpath) {
+            Ok(dir) => dir,
+            Err(errno) => return handle_errno(errno, "linkat"),
+        };
+        if flags & AT_SYMLINK_FOLLOW == 0 {
+            return self.link_at(olddir, oldpath, newdir, newpath);
+        }
+        let oldtarget = match resolve_beneath_at(olddir, oldpath, self) {
+            Ok(target) => target,
+            Err(errno) => return handle_errno(errno, "linkat"),
+        };
+        self.link_fd(oldtarget.dirfd, newdir, newpath)
+    }
+
+    // Gives the file a kernel fd refers to the name newpath. Going through /proc/self/fd
+    // with AT_SYMLINK_FOLLOW works for any fd, where AT_EMPTY_PATH would need privileges.
+    fn link_fd(&self, kernel_fd: i32, newdirfd: Option<i32>, newpath: &str) -> i32 {
+        let newtarget = match resolve_parent_beneath_at(newdirfd, newpath, self) {
+            Ok(target) => target,
+            Err(errno) => return handle_errno(errno, "linkat"),
+        };
+        let ret = unsafe {
+            libc::linkat(AT_FDCWD, procfd_path(kernel_fd).as_ptr(), newtarget.dirfd, newtarget.name.as_ptr(), AT_SYMLINK_FOLLOW)
+        };
+        if ret < 0 {
+            let errno = get_errno();
+            return handle_errno(errno, "linkat");
+        }
+        ret
+    }
+
+    //------------------------------------FCHMODAT SYSCALL------------------------------------
+    /*
+    *   fchmodat() is chmod() with path relative to dirfd. Symlinks have no mode of their
+    *   own on Linux, so with AT_SYMLINK_NOFOLLOW a trailing symlink fails with EOPNOTSUPP.
+    *   fchmodat() will return 0 when success and -1 when fail 
+    */
+    pub fn fchmodat_syscall(&self, dirfd: i32, path: &str, mode: u32, flags: i32) -> i32 {
+        if flags & !AT_SYMLINK_NOFOLLOW != 0 {
+            return syscall_error(Errno::EINVAL, "fchmodat", "Invalid flags");
+        }
+        let dir = match self.at_dir(dirfd, path) {
+            Ok(dir) => dir,
+            Err(errno) => return handle_errno(errno, "fchmodat"),
+        };
+        if flags & AT_SYMLINK_NOFOLLOW == 0 {
+            return self.chmod_at(dir, path, mode);
+        }
+
+        let kernel_fd = match open_beneath_at(dir, path, self, O_PATH | O_NOFOLLOW | O_CLOEXEC, 0) {
+            Ok(fd) => fd,
+            Err(errno) => return handle_errno(errno, "fchmodat"),
+        };
+        let islink = fstat_beneath(kernel_fd).map(|st| st.st_mode & S_IFMT == S_IFLNK);
+        unsafe { libc::close(kernel_fd) };
+        match islink {
+            Ok(true) => syscall_error(Errno::EOPNOTSUPP, "fchmodat", "symbolic links have no mode to change"),
+            Ok(false) => self.chmod_at(dir, path, mode),
+            Err(errno) => handle_errno(errno, "fchmodat"),
+        }
+    }
+
+    //------------------------------------SYMLINKAT SYSCALL------------------------------------
+    /*
+    *   symlinkat() is symlink() with linkpath relative to newdirfd. The target itself is
+    *   stored as it is, a relative one is resolved against the link's directory later on.
+    *   symlinkat() will return 0 when success and -1 when fail 
+    */
+    pub fn symlinkat_syscall(&self, target: &str, newdirfd: i32, linkpath: &str) -> i32 {
+        match self.at_dir(newdirfd, linkpath) {
+            Ok(dir) => self.symlink_at(target, dir, linkpath),
+            Err(errno) => handle_errno(errno, "symlinkat"),
+        }
+    }
+
+    //------------------------------------READLINKAT SYSCALL------------------------------------
+    /*
+    *   readlinkat() is readlink() with path relative to dirfd. An empty path reads the
+    *   symlink dirfd refers to, which has to be opened with O_PATH | O_NOFOLLOW.
+    *   readlinkat() will return the number of bytes placed in buf when success and -1 when fail 
+    */
+    pub fn readlinkat_syscall(&self, dirfd: i32, path: &str, buf: *mut u8, bufsize: usize) -> i32 {
+        if !path.is_empty() {
+            return match self.at_dir(dirfd, path) {
+                Ok(dir) => self.readlink_at(dir, path, buf, bufsize),
+                Err(errno) => handle_errno(errno, "readlinkat"),
+            };
+        }
+        if bufsize == 0 {
+            return syscall_error(Errno::EINVAL, "readlinkat", "Invalid buffer size");
+        }
+        let vfd = match fdtables::translate_virtual_fd(self.cageid, dirfd as u64) {
+            Ok(vfd) => vfd,
+            Err(_) => return syscall_error(Errno::EBADF, "readlinkat", "Bad File Descriptor"),
+        };
+        if vfd.fdkind != FDKIND_KERNEL {
+            return syscall_error(Errno::ENOENT, "readlinkat", "file descriptor does not refer to a symlink");
+        }
+        let empty = CString::new("").unwrap();
+        self.readlink_beneath(vfd.underfd as i32, &empty, buf, bufsize, "readlinkat")
+    }
+
+    //------------------------------------FSYNC SYSCALL------------------------------------
+    /*
+    *   Get the kernel fd with provided virtual fd first
+    *   fsync() will return 0 when sucess, -1 when fail 
+    */
+    pub fn fsync_syscall(&self, virtual_fd: i32) -> i32 {
+        let wrappedvfd = fdtables::translate_virtual_fd(self.cageid, virtual_fd as u64);
+        if wrappedvfd.is_err() {
+            return syscall_error(Errno::EBADF, "fsync", "Bad File Descriptor");
+        }
+        let vfd = wrappedvfd.unwrap();
+        if !is_host_fd(vfd.fdkind) {
+            return syscall_error(Errno::EINVAL, "fsync", "file descriptor does not support synchronization");
+        }
+        let ret = unsafe {
+            libc::fsync(vfd.underfd as i32)
+        };
+        if ret < 0 {
+            let errno = get_errno();
+            return handle_errno(errno, "fsync");
+        }
+        return ret;
+        
+    }
+
+    //------------------------------------FDATASYNC SYSCALL------------------------------------
+    /*
+    *   Get the kernel fd with provided virtual fd first
+    *   fdatasync() will return 0 when sucess, -1 when fail 
+    */
+    pub fn fdatasync_syscall(&self, virtual_fd: i32) -> i32 {
+        let wrappedvfd = fdtables::translate_virtual_fd(self.cageid, virtual_fd as u64);
+        if wrappedvfd.is_err() {
+            return syscall_error(Errno::EBADF, "fdatasync", "Bad File Descriptor");
+        }
+        let vfd = wrappedvfd.unwrap();
+        if !is_host_fd(vfd.fdkind) {
+            return syscall_error(Errno::EINVAL, "fdatasync", "file descriptor does not support synchronization");
+        }
+        let ret = unsafe {
+            libc::fdatasync(vfd.underfd as i32)
+        };
+        if ret < 0 {
+            let errno = get_errno();
+            return handle_errno(errno, "fdatasync");
+        }
+        return ret;
+        
+    }
+
+    //------------------------------------SYNC_FILE_RANGE SYSCALL------------------------------------
+    /*
+    *   Get the kernel fd with provided virtual fd first
+    *   sync_file_range() will return 0 when sucess, -1 when fail 
+    */
+    pub fn sync_file_range_syscall(
+        &self,
+        virtual_fd: i32,
+        offset: isize,
+        nbytes: isize,
+        flags: u32,
+    ) -> i32 {
+        let wrappedvfd = fdtables::translate_virtual_fd(self.cageid, virtual_fd as u64);
+        if wrappedvfd.is_err() {
+            return syscall_error(Errno::EBADF, "sync", "Bad File Descriptor");
+        }
+        let vfd = wrappedvfd.unwrap();
+        if !is_host_fd(vfd.fdkind) {
+            return syscall_error(Errno::ESPIPE, "sync_file_range", "file descriptor refers to a pipe");
+        }
+        let ret = unsafe {
+            libc::sync_file_range(vfd.underfd as i32, offset as i64, nbytes as i64, flags)
+        };
+        if ret < 0 {
+            let errno = get_errno();
+            return handle_errno(errno, "sync_file_range");
+        }
+        ret
+    }
+
+    //------------------FTRUNCATE SYSCALL------------------
+    /*
+    *   Get the kernel fd with provided virtual fd first
+    *   ftruncate() will return 0 when sucess, -1 when fail 
+    */
+    pub fn ftruncate_syscall(&self, virtual_fd: i32, length: isize) -> i32 {
+        let wrappedvfd = fdtables::translate_virtual_fd(self.cageid, virtual_fd as u64);
+        if wrappedvfd.is_err() {
+            return syscall_error(Errno::EBADF, "ftruncate", "Bad File Descriptor");
+        }
+        let vfd = wrappedvfd.unwrap();
+        if !is_host_fd(vfd.fdkind) {
+            return syscall_error(Errno::EINVAL, "ftruncate", "file descriptor does not refer to a regular file");
+        }
+        let ret = unsafe {
+            libc::ftruncate(vfd.underfd as i32, length as i64)
+        };
+        if ret < 0 {
+            let errno = get_errno();
+            return handle_errno(errno, "ftruncate");
+        }
+        ret
+    }
+
+    //------------------TRUNCATE SYSCALL------------------
+    /*
+    *   truncate() will return 0 when sucess, -1 when fail 
+    */
+    pub fn truncate_syscall(&self, path: &str, length: isize) -> i32 {
+        let target = match resolve_beneath(path, self) {
+            Ok(target) => target,
+            Err(errno) => return handle_errno(errno, "truncate"),
+        };
+        let ret = unsafe {
+            libc::truncate(target.procpath().as_ptr(), length as i64)
+        };
+        if ret < 0 {
+            let errno = get_errno();
+            return handle_errno(errno, "truncate");
+        }
+        ret
+    }
+
+    //------------------PIPE SYSCALL------------------
+    /*
+    *   When using the rust libc, a pipe file descriptor (pipefd) is typically an array 
+    *   containing two integers. This array is populated by the pipe system call, with one 
+    *   integer for the read end and the other for the write end.
     *
     *   pipe() will return 0 when sucess, -1 when fail 
     */
@@ -1011,22 +2037,146 @@ impl Cage {
     }
 
     pub fn pipe2_syscall(&self, pipefd: &mut PipeArray, flags: i32) -> i32 {
-        let mut kernel_fds:[i32; 2] = [0; 2];
-            
-        let ret = unsafe { libc::pipe2(kernel_fds.as_mut_ptr() as *mut i32, flags as i32) };
-        if ret < 0 {
-            let errno = get_errno();
-            return handle_errno(errno, "pipe2");
+        if flags & !(O_NONBLOCK | O_CLOEXEC) != 0 {
+            return syscall_error(Errno::EINVAL, "pipe2", "Invalid value in flags");
         }
 
         let should_cloexec = if flags & O_CLOEXEC != 0 {
             true
         } else { false };
 
-        pipefd.readfd = fdtables::get_unused_virtual_fd(self.cageid, FDKIND_KERNEL, kernel_fds[0] as u64, should_cloexec, 0).unwrap() as i32;
-        pipefd.writefd = fdtables::get_unused_virtual_fd(self.cageid, FDKIND_KERNEL, kernel_fds[1] as u64, should_cloexec, 0).unwrap() as i32;
+        // both ends live in memory, the per-fd info keeps the status flags that
+        // fcntl(F_GETFL) reports and that read/write consult for O_NONBLOCK
+        let (readid, writeid) = interface::new_pipe(interface::PIPE_CAPACITY);
+        let readinfo = (O_RDONLY | (flags & O_NONBLOCK)) as u64;
+        let writeinfo = (O_WRONLY | (flags & O_NONBLOCK)) as u64;
 
-        return ret;
+        let readfd = match fdtables::get_unused_virtual_fd(self.cageid, FDKIND_IMPIPE, readid, should_cloexec, readinfo) {
+            Ok(fd) => fd,
+            Err(e) => {
+                interface::close_pipe_end(readid);
+                interface::close_pipe_end(writeid);
+                return handle_errno(e as i32, "pipe2");
+            }
+        };
+        let writefd = match fdtables::get_unused_virtual_fd(self.cageid, FDKIND_IMPIPE, writeid, should_cloexec, writeinfo) {
+            Ok(fd) => fd,
+            Err(e) => {
+                let _ = fdtables::close_virtualfd(self.cageid, readfd);
+                interface::close_pipe_end(writeid);
+                return handle_errno(e as i32, "pipe2");
+            }
+        };
+
+        pipefd.readfd = readfd as i32;
+        pipefd.writefd = writefd as i32;
+        0
+    }
+
+    /*
+    *   How many of count bytes a write at offset (the file position when None) may put in a
+    *   regular file under RLIMIT_FSIZE. Like Linux, a write crossing the limit is cut short,
+    *   and one starting at or past it fails with EFBIG and raises SIGXFSZ.
+    */
+    fn fsize_allowance(&self, kernel_fd: i32, offset: Option<i64>, count: usize, syscallname: &str) -> Result<usize, i32> {
+        let limit = self.rlimit_cur(sys_constants::RLIMIT_FSIZE);
+        if limit == sys_constants::RLIM_INFINITY || count == 0 {
+            return Ok(count);
+        }
+        let mut statbuf: libc::stat = unsafe { std::mem::zeroed() };
+        if unsafe { libc::fstat(kernel_fd, &mut statbuf) } < 0
+            || statbuf.st_mode & libc::S_IFMT != libc::S_IFREG
+        {
+            return Ok(count);
+        }
+        let position = match offset {
+            Some(offset) => offset,
+            None if unsafe { libc::fcntl(kernel_fd, libc::F_GETFL) } & O_APPEND != 0 => statbuf.st_size,
+            None => unsafe { libc::lseek(kernel_fd, 0, libc::SEEK_CUR) },
+        };
+        if position < 0 {
+            return Ok(count);
+        }
+        if position as u64 >= limit {
+            interface::lind_kill_from_id(self.cageid, libc::SIGXFSZ);
+            return Err(syscall_error(Errno::EFBIG, syscallname, "file size limit exceeded"));
+        }
+        Ok(count.min((limit - position as u64) as usize))
+    }
+
+    // write() on the write end of an in-memory pipe, raising SIGPIPE when nobody can read it
+    fn write_to_impipe(&self, vfd: fdtables::FDTableEntry, buf: *const u8, count: usize, syscallname: &str) -> i32 {
+        if !interface::is_pipe_write_end(vfd.underfd) {
+            return syscall_error(Errno::EBADF, syscallname, "fd is not open for writing");
+        }
+        let pipe = interface::get_pipe(vfd.underfd).unwrap();
+        let ret = pipe.write_to_pipe(buf, count, vfd.perfdinfo as i32 & O_NONBLOCK != 0);
+        if ret == -(Errno::EPIPE as i32) {
+            interface::lind_kill_from_id(self.cageid, libc::SIGPIPE);
+        }
+        ret
+    }
+
+    // The most one read of an in-memory pipe or socket can return
+    fn inmem_read_max(&self, vfd: &fdtables::FDTableEntry) -> usize {
+        match vfd.fdkind {
+            FDKIND_IMPIPE => interface::PIPE_CAPACITY,
+            FDKIND_IMSOCK => interface::get_unix_socket(vfd.underfd).map_or(0, |sock| sock.max_recv_len()),
+            _ => 0,
+        }
+    }
+
+    // fcntl() commands that make sense on an in-memory pipe, socket or epoll fd
+    fn fcntl_inmem(&self, virtual_fd: i32, vfd: fdtables::FDTableEntry, cmd: i32, arg: i32) -> i32 {
+        match cmd {
+            F_GETFD => {
+                if vfd.should_cloexec { FD_CLOEXEC } else { 0 }
+            }
+            F_SETFD => {
+                let _ = fdtables::set_cloexec(self.cageid, virtual_fd as u64, arg & FD_CLOEXEC != 0);
+                0
+            }
+            F_GETFL => vfd.perfdinfo as i32,
+            F_SETFL => {
+                // the access mode is fixed, only O_NONBLOCK can be toggled
+                let newinfo = (vfd.perfdinfo as i32 & !O_NONBLOCK) | (arg & O_NONBLOCK);
+                let _ = fdtables::set_perfdinfo(self.cageid, virtual_fd as u64, newinfo as u64);
+                0
+            }
+            F_GETPIPE_SZ if vfd.fdkind == FDKIND_IMPIPE => interface::PIPE_CAPACITY as i32,
+            _ => syscall_error(Errno::EINVAL, "fcntl", "op is not supported on this fd"),
+        }
+    }
+
+    // ioctl() requests that make sense on an in-memory pipe, socket or epoll fd
+    fn ioctl_inmem(&self, virtual_fd: i32, vfd: fdtables::FDTableEntry, request: u64, ptrunion: *mut u8) -> i32 {
+        if ptrunion.is_null() {
+            return syscall_error(Errno::EFAULT, "ioctl", "argp is not a valid pointer");
+        }
+        match request {
+            FIONREAD if vfd.fdkind != fdtables::FDT_KINDEPOLL => {
+                let avail = if vfd.fdkind == FDKIND_IMSOCK {
+                    interface::get_unix_socket(vfd.underfd).unwrap().get_read_avail()
+                } else if interface::is_pipe_write_end(vfd.underfd) {
+                    0
+                } else {
+                    interface::get_pipe(vfd.underfd).unwrap().get_read_avail()
+                };
+                unsafe { *(ptrunion as *mut i32) = avail as i32 };
+                0
+            }
+            FIONBIO => {
+                let nonblocking = unsafe { *(ptrunion as *const i32) } != 0;
+                let newinfo = if nonblocking {
+                    vfd.perfdinfo as i32 | O_NONBLOCK
+                } else {
+                    vfd.perfdinfo as i32 & !O_NONBLOCK
+                };
+                let _ = fdtables::set_perfdinfo(self.cageid, virtual_fd as u64, newinfo as u64);
+                0
+            }
+            _ => syscall_error(Errno::ENOTTY, "ioctl", "request is not supported on this fd"),
+        }
     }
 
     //------------------GETDENTS SYSCALL------------------
@@ -1043,6 +2193,9 @@ impl Cage {
             return syscall_error(Errno::EBADF, "getdents", "Bad File Descriptor");
         }
         let vfd = wrappedvfd.unwrap();
+        if vfd.fdkind != FDKIND_KERNEL {
+            return syscall_error(Errno::ENOTDIR, "getdents", "file descriptor does not refer to a directory");
+        }
         let ret = unsafe { libc::syscall(libc::SYS_getdents as c_long, vfd.underfd as i32, buf as *mut c_void, nbytes) as i32 };
         if ret < 0 {
             let errno = get_errno();
@@ -1114,185 +2267,967 @@ impl Cage {
                 }
             }
         }
-        None
+        None
+    }
+
+    //------------------SHMGET SYSCALL------------------
+
+    /*
+     *   shmget() returns the id of the segment with key, creating it with IPC_CREAT. A key of
+     *   IPC_PRIVATE always creates a new segment, which no other shmget call can find.
+     */
+    pub fn shmget_syscall(&self, key: i32, size: usize, shmflg: i32) -> i32 {
+        let metadata = &SHM_METADATA;
+        if key == IPC_PRIVATE {
+            return self.new_shm_segment_id(key, size, shmflg);
+        }
+        let shmid: i32;
+
+        match metadata.shmkeyidtable.entry(key) {
+            interface::RustHashEntry::Occupied(occupied) => {
+                if (IPC_CREAT | IPC_EXCL) == (shmflg & (IPC_CREAT | IPC_EXCL)) {
+                    return syscall_error(
+                        Errno::EEXIST,
+                        "shmget",
+                        "key already exists and IPC_CREAT and IPC_EXCL were used",
+                    );
+                }
+                shmid = *occupied.get();
+                // the permission bits in shmflg are what the caller wants from the segment
+                let want = (shmflg >> 6 | shmflg >> 3 | shmflg) & 7;
+                if let Some(segment) = metadata.shmtable.get(&shmid) {
+                    if !self.ipc_permits(&segment.shminfo.shm_perm, want) {
+                        return syscall_error(Errno::EACCES, "shmget", "permission denied for the segment");
+                    }
+                    if size > segment.size {
+                        return syscall_error(Errno::EINVAL, "shmget", "the segment is smaller than size");
+                    }
+                }
+            }
+            interface::RustHashEntry::Vacant(vacant) => {
+                if 0 == (shmflg & IPC_CREAT) {
+                    return syscall_error(
+                        Errno::ENOENT,
+                        "shmget",
+                        "tried to use a key that did not exist, and IPC_CREAT was not specified",
+                    );
+                }
+
+                shmid = self.new_shm_segment_id(key, size, shmflg);
+                if shmid < 0 {
+                    return shmid;
+                }
+                vacant.insert(shmid);
+            }
+        };
+        shmid // return the shmid
+    }
+
+    // creates a segment for shmget, owned by the caller's effective ids
+    fn new_shm_segment_id(&self, key: i32, size: usize, shmflg: i32) -> i32 {
+        let metadata = &SHM_METADATA;
+        if (size as u32) < SHMMIN || (size as u32) > SHMMAX {
+            return syscall_error(
+                Errno::EINVAL,
+                "shmget",
+                "Size is less than SHMMIN or more than SHMMAX",
+            );
+        }
+        if metadata.shmtable.len() >= SHMMNI as usize {
+            return syscall_error(Errno::ENOSPC, "shmget", "all possible shared memory ids are in use");
+        }
+
+        let shmid = metadata.new_keyid();
+        let mode = (shmflg & 0x1FF) as u16; // mode is 9 least signficant bits of shmflag
+
+        let segment = new_shm_segment(
+            key,
+            size,
+            self.cageid as u32,
+            self.euid(),
+            self.egid(),
+            mode,
+        );
+        metadata.shmtable.insert(shmid, segment);
+        shmid
+    }
+
+    //------------------SHMAT SYSCALL------------------
+
+    pub fn shmat_syscall(&self, shmid: i32, shmaddr: *mut u8, shmflg: i32) -> i32 {
+        let metadata = &SHM_METADATA;
+        let prot: i32;
+        if let Some(mut segment) = metadata.shmtable.get_mut(&shmid) {
+            if 0 != (shmflg & fs_constants::SHM_RDONLY) {
+                prot = PROT_READ;
+            } else {
+                prot = PROT_READ | PROT_WRITE;
+            }
+            let want = if prot & PROT_WRITE != 0 { libc::R_OK | libc::W_OK } else { libc::R_OK };
+            if !self.ipc_permits(&segment.shminfo.shm_perm, want) {
+                return syscall_error(Errno::EACCES, "shmat", "permission denied for the segment");
+            }
+            let mut rev_shm = self.rev_shm.lock();
+            rev_shm.push((shmaddr as u32, shmid));
+            drop(rev_shm);
+
+            // update semaphores
+            if !segment.semaphor_offsets.is_empty() {
+                // lets just look at the first cage in the set, since we only need to grab the ref from one
+                if let Some(cageid) = segment
+                    .attached_cages
+                    .clone()
+                    .into_read_only()
+                    .keys()
+                    .next()
+                {
+                    let cage2 = interface::cagetable_getref(*cageid);
+                    let cage2_rev_shm = cage2.rev_shm.lock();
+                    let addrs = Self::rev_shm_find_addrs_by_shmid(&cage2_rev_shm, shmid); // find all the addresses assoc. with shmid
+                    for offset in segment.semaphor_offsets.iter() {
+                        let sementry = cage2.sem_table.get(&(addrs[0] + *offset)).unwrap().clone(); //add  semaphors into semtable at addr + offsets
+                        self.sem_table.insert(shmaddr as u32 + *offset, sementry);
+                    }
+                }
+            }
+
+            segment.map_shm(shmaddr, prot, self.cageid)
+        } else {
+            syscall_error(Errno::EINVAL, "shmat", "Invalid shmid value")
+        }
+    }
+
+    //------------------SHMDT SYSCALL------------------
+
+    pub fn shmdt_syscall(&self, shmaddr: *mut u8) -> i32 {
+        let metadata = &SHM_METADATA;
+        let mut rm = false;
+        let mut rev_shm = self.rev_shm.lock();
+        let rev_shm_index = Self::rev_shm_find_index_by_addr(&rev_shm, shmaddr as u32);
+
+        if let Some(index) = rev_shm_index {
+            let shmid = rev_shm[index].1;
+            match metadata.shmtable.entry(shmid) {
+                interface::RustHashEntry::Occupied(mut occupied) => {
+                    let segment = occupied.get_mut();
+
+                    // update semaphores
+                    for offset in segment.semaphor_offsets.iter() {
+                        self.sem_table.remove(&(shmaddr as u32 + *offset));
+                    }
+
+                    segment.unmap_shm(shmaddr, self.cageid);
+                    let (start, end) = page_range(shmaddr as usize, segment.size);
+                    remove_range(&mut self.mapped_ranges.lock(), start, end);
+
+                    if segment.rmid && segment.shminfo.shm_nattch == 0 {
+                        rm = true;
+                    }
+                    rev_shm.swap_remove(index);
+
+                    if rm {
+                        let key = segment.key;
+                        occupied.remove_entry();
+                        metadata.shmkeyidtable.remove(&key);
+                    }
+
+                    return shmid; //NaCl relies on this non-posix behavior of returning the shmid on success
+                }
+                interface::RustHashEntry::Vacant(_) => {
+                    panic!("Inode not created for some reason");
+                }
+            };
+        } else {
+            return syscall_error(
+                Errno::EINVAL,
+                "shmdt",
+                "No shared memory segment at shmaddr",
+            );
+        }
+    }
+
+    //------------------SHMCTL SYSCALL------------------
+
+    /*
+     *   shmctl() supports
+     *       - IPC_STAT: copies the segment's shmid_ds into buf, needs read permission
+     *       - IPC_SET: takes the owner uid/gid and the permission bits from buf, owner only
+     *       - IPC_RMID: marks the segment to be removed once the last cage detaches, owner only
+     *       - SHM_LOCK/SHM_UNLOCK: sets or clears SHM_LOCKED, owner only
+     *       - IPC_INFO/SHM_INFO: fill buf with struct shminfo/struct shm_info instead, and
+     *           return the highest shmid in use rather than 0
+     */
+    pub fn shmctl_syscall(&self, shmid: i32, cmd: i32, buf: Option<&mut ShmidsStruct>) -> i32 {
+        let metadata = &SHM_METADATA;
+
+        if cmd == IPC_INFO || cmd == SHM_INFO {
+            let buf = match buf {
+                Some(buf) => buf as *mut ShmidsStruct,
+                None => return syscall_error(Errno::EFAULT, "shmctl", "buf is not a valid address"),
+            };
+            if cmd == IPC_INFO {
+                let info = interface::ShminfoStruct {
+                    shmmax: SHMMAX,
+                    shmmin: SHMMIN,
+                    shmmni: SHMMNI,
+                    shmseg: SHMSEG,
+                    shmall: SHMALL,
+                    __unused: [0; 4],
+                };
+                unsafe { (buf as *mut interface::ShminfoStruct).write_unaligned(info) };
+            } else {
+                let pagesize = SHM_PAGESIZE as usize;
+                let pages: usize = metadata.shmtable.iter().map(|segment| (segment.size + pagesize - 1) / pagesize).sum();
+                let info = interface::ShmInfoStruct {
+                    used_ids: metadata.shmtable.len() as i32,
+                    shm_tot: pages as u32,
+                    shm_rss: pages as u32,
+                    shm_swp: 0,
+                    swap_attempts: 0,
+                    swap_successes: 0,
+                };
+                unsafe { (buf as *mut interface::ShmInfoStruct).write_unaligned(info) };
+            }
+            return metadata.max_shmid();
+        }
+
+        if let Some(mut segment) = metadata.shmtable.get_mut(&shmid) {
+            match cmd {
+                IPC_STAT => {
+                    if !self.ipc_permits(&segment.shminfo.shm_perm, libc::R_OK) {
+                        return syscall_error(Errno::EACCES, "shmctl", "no read permission for the segment");
+                    }
+                    match buf {
+                        Some(buf) => *buf = segment.shminfo,
+                        None => return syscall_error(Errno::EFAULT, "shmctl", "buf is not a valid address"),
+                    }
+                }
+                IPC_SET => {
+                    let buf = match buf {
+                        Some(buf) => buf,
+                        None => return syscall_error(Errno::EFAULT, "shmctl", "buf is not a valid address"),
+                    };
+                    if !self.ipc_owns(&segment.shminfo.shm_perm) {
+                        return syscall_error(Errno::EPERM, "shmctl", "only the owner or creator may change the segment");
+                    }
+                    let perm = &mut segment.shminfo.shm_perm;
+                    perm.uid = buf.shm_perm.uid;
+                    perm.gid = buf.shm_perm.gid;
+                    perm.mode = (perm.mode & !0o777) | (buf.shm_perm.mode & 0o777);
+                    segment.shminfo.shm_ctime = interface::timestamp() as isize;
+                }
+                IPC_RMID => {
+                    if !self.ipc_owns(&segment.shminfo.shm_perm) {
+                        return syscall_error(Errno::EPERM, "shmctl", "only the owner or creator may remove the segment");
+                    }
+                    let key = segment.key;
+                    segment.mark_removed();
+                    metadata.shmkeyidtable.remove(&key);
+                    if segment.shminfo.shm_nattch == 0 {
+                        drop(segment);
+                        metadata.shmtable.remove(&shmid);
+                    }
+                }
+                SHM_LOCK | SHM_UNLOCK => {
+                    if !self.ipc_owns(&segment.shminfo.shm_perm) {
+                        return syscall_error(Errno::EPERM, "shmctl", "only the owner or creator may lock the segment");
+                    }
+                    if cmd == SHM_LOCK {
+                        segment.shminfo.shm_perm.mode |= SHM_LOCKED as u16;
+                    } else {
+                        segment.shminfo.shm_perm.mode &= !(SHM_LOCKED as u16);
+                    }
+                    segment.shminfo.shm_ctime = interface::timestamp() as isize;
+                }
+                _ => {
+                    return syscall_error(
+                        Errno::EINVAL,
+                        "shmctl",
+                        "Arguments provided do not match implemented parameters",
+                    );
+                }
+            }
+        } else {
+            return syscall_error(Errno::EINVAL, "shmctl", "Invalid identifier");
+        }
+
+        0 //shmctl has succeeded!
+    }
+
+    //------------------SHM_OPEN SYSCALL------------------
+
+    /*
+     *   shm_open() opens the POSIX shared memory object called name, which is also reachable
+     *   as /dev/shm/name through open(). A new object is empty until ftruncate gives it a size,
+     *   and every cage mapping it MAP_SHARED sees the same memory.
+     *   Returns the new fd, which always has close-on-exec set, or -1 on failure.
+     */
+    pub fn shm_open_syscall(&self, name: &str, oflag: i32, mode: u32) -> i32 {
+        let name = match interface::shm_object_name(name) {
+            Some(name) => name,
+            None => return syscall_error(Errno::EINVAL, "shm_open", "name is not a valid shared memory object name"),
+        };
+        self.open_shm_object(name, oflag | O_CLOEXEC, mode, "shm_open")
+    }
+
+    //------------------SHM_UNLINK SYSCALL------------------
+
+    /*
+     *   shm_unlink() removes the name of a POSIX shared memory object. The memory stays around
+     *   for the fds and mappings that still refer to it, but shm_open can't find it anymore.
+     *   Returns 0 on success and -1 on failure.
+     */
+    pub fn shm_unlink_syscall(&self, name: &str) -> i32 {
+        let name = match interface::shm_object_name(name) {
+            Some(name) => name,
+            None => return syscall_error(Errno::EINVAL, "shm_unlink", "name is not a valid shared memory object name"),
+        };
+        match self.unlink_shm_object(name) {
+            Ok(()) => 0,
+            // glibc reports the sticky bit of /dev/shm getting in the way as EACCES
+            Err(libc::EPERM) => syscall_error(Errno::EACCES, "shm_unlink", "only the owner may remove the object"),
+            Err(errno) => handle_errno(errno, "shm_unlink"),
+        }
+    }
+
+    // The shm object name of a path that lies directly in /dev/shm. A path relative to the
+    // dirfd of an *at() call is a host file like any other.
+    fn devshm_name(&self, dirfd: Option<i32>, path: &str) -> Option<String> {
+        if dirfd.is_some() && !path.starts_with('/') {
+            return None;
+        }
+        let normed = normpath(convpath(path), self);
+        let name = normed.strip_prefix("/dev/shm").ok()?.to_str()?;
+        interface::shm_object_name(name).map(|name| name.to_string())
+    }
+
+    // Like open() of a file, the open that creates an object is let through whatever its
+    // mode, which only applies to the opens after it
+    fn open_shm_object(&self, name: &str, oflag: i32, mode: u32, syscallname: &str) -> i32 {
+        let create = if oflag & O_CREAT != 0 {
+            Some(mode & 0o7777 & !self.umask.load(interface::RustAtomicOrdering::Relaxed))
+        } else {
+            None
+        };
+        let (object, created) = match interface::shm_object(name, create, oflag & O_EXCL != 0) {
+            Ok(found) => found,
+            Err(errno) => return handle_errno(errno, syscallname),
+        };
+        let ret = self.open_shm_fd(&object, created, oflag);
+        if ret.is_err() && created {
+            interface::shm_remove_object(name, &object);
+        }
+        match ret {
+            Ok(virtual_fd) => virtual_fd,
+            Err(errno) => handle_errno(errno, syscallname),
+        }
+    }
+
+    fn open_shm_fd(&self, object: &interface::RustRfc<interface::ShmObject>, created: bool, oflag: i32) -> Result<i32, i32> {
+        let st = fstat_beneath(object.fd())?;
+        if created {
+            set_virtual_owner(object.fd(), &st, self.euid(), self.egid())?;
+        } else {
+            let (uid, gid) = virtual_owner(object.fd(), &st);
+            if !self.permits(uid, gid, S_IFREG | object.mode(), open_access_wanted(oflag), false) {
+                return Err(libc::EACCES);
+            }
+        }
+
+        // a host fd of its own, so the open has its own offset and access mode
+        let hostfd = unsafe {
+            libc::open(procfd_path(object.fd()).as_ptr(), (oflag & (O_ACCMODE | O_TRUNC)) | O_CLOEXEC)
+        };
+        if hostfd < 0 {
+            return Err(get_errno());
+        }
+        interface::shm_open_insert(hostfd as u64, object.clone());
+        match fdtables::get_unused_virtual_fd(self.cageid, FDKIND_IMSHM, hostfd as u64, oflag & O_CLOEXEC != 0, 0) {
+            Ok(virtual_fd) => Ok(virtual_fd as i32),
+            Err(e) => {
+                interface::shm_open_remove(hostfd as u64);
+                unsafe { libc::close(hostfd) };
+                Err(e as i32)
+            }
+        }
+    }
+
+    // /dev/shm is sticky, so only the owner of an object (or root) may remove it
+    fn unlink_shm_object(&self, name: &str) -> Result<(), i32> {
+        let (object, _) = interface::shm_object(name, None, false)?;
+        self.check_owner(object.fd())?;
+        interface::shm_remove(name);
+        Ok(())
+    }
+
+    //------------------MSGGET SYSCALL------------------
+
+    /*
+     *   msgget() returns the id of the message queue with key, creating it with IPC_CREAT.
+     *   A key of IPC_PRIVATE always creates a new queue, which no other msgget call can find.
+     */
+    pub fn msgget_syscall(&self, key: i32, msgflg: i32) -> i32 {
+        let metadata = &MSG_METADATA;
+        if key == IPC_PRIVATE {
+            return self.new_msg_queue_id(key, msgflg);
+        }
+
+        match metadata.msgkeyidtable.entry(key) {
+            interface::RustHashEntry::Occupied(occupied) => {
+                if (IPC_CREAT | IPC_EXCL) == (msgflg & (IPC_CREAT | IPC_EXCL)) {
+                    return syscall_error(
+                        Errno::EEXIST,
+                        "msgget",
+                        "key already exists and IPC_CREAT and IPC_EXCL were used",
+                    );
+                }
+                let msqid = *occupied.get();
+                // the permission bits in msgflg are what the caller wants from the queue
+                let want = (msgflg >> 6 | msgflg >> 3 | msgflg) & 7;
+                if let Some(queue) = metadata.get_queue(msqid) {
+                    if !self.ipc_permits(&queue.state.lock().msqinfo.msg_perm, want) {
+                        return syscall_error(Errno::EACCES, "msgget", "permission denied for the queue");
+                    }
+                }
+                msqid
+            }
+            interface::RustHashEntry::Vacant(vacant) => {
+                if 0 == (msgflg & IPC_CREAT) {
+                    return syscall_error(
+                        Errno::ENOENT,
+                        "msgget",
+                        "tried to use a key that did not exist, and IPC_CREAT was not specified",
+                    );
+                }
+                let msqid = self.new_msg_queue_id(key, msgflg);
+                if msqid < 0 {
+                    return msqid;
+                }
+                vacant.insert(msqid);
+                msqid
+            }
+        }
+    }
+
+    // creates a queue for msgget, owned by the caller's effective ids
+    fn new_msg_queue_id(&self, key: i32, msgflg: i32) -> i32 {
+        let metadata = &MSG_METADATA;
+        if metadata.msgtable.len() >= MSGMNI as usize {
+            return syscall_error(Errno::ENOSPC, "msgget", "all possible message queue ids are in use");
+        }
+        let msqid = metadata.new_keyid();
+        let mode = (msgflg & 0o777) as u16;
+        let queue = MsgQueue::new(key, MSGMNB, self.euid(), self.egid(), mode);
+        metadata.msgtable.insert(msqid, interface::RustRfc::new(queue));
+        msqid
+    }
+
+    //------------------MSGSND SYSCALL------------------
+
+    /*
+     *   msgsnd() appends the message at msgp, an mtype followed by msgsz bytes of text, to the
+     *   queue. While the queue is full it blocks, or fails with EAGAIN under IPC_NOWAIT.
+     *   Returns 0 on success and -1 on failure.
+     */
+    pub fn msgsnd_syscall(&self, msqid: i32, msgp: *const u8, msgsz: usize, msgflg: i32) -> i32 {
+        if msgsz > MSGMAX as usize {
+            return syscall_error(Errno::EINVAL, "msgsnd", "msgsz is larger than MSGMAX");
+        }
+        let mtype = unsafe { (msgp as *const i32).read_unaligned() };
+        if mtype < 1 {
+            return syscall_error(Errno::EINVAL, "msgsnd", "mtype must be positive");
+        }
+        let queue = match MSG_METADATA.get_queue(msqid) {
+            Some(queue) => queue,
+            None => return syscall_error(Errno::EINVAL, "msgsnd", "Invalid identifier"),
+        };
+        let mtext = unsafe { std::slice::from_raw_parts(msgp.add(MSG_MTYPE_SIZE), msgsz) }.to_vec();
+
+        let mut state = queue.state.lock();
+        if !self.ipc_permits(&state.msqinfo.msg_perm, libc::W_OK) {
+            return syscall_error(Errno::EACCES, "msgsnd", "no write permission for the queue");
+        }
+        loop {
+            if state.removed {
+                return syscall_error(Errno::EIDRM, "msgsnd", "the queue was removed");
+            }
+            if state.has_room(msgsz) {
+                break;
+            }
+            if msgflg & IPC_NOWAIT != 0 {
+                return syscall_error(Errno::EAGAIN, "msgsnd", "the queue is full");
+            }
+            queue.cv.wait(&mut state);
+        }
+
+        state.messages.push_back(MsgMessage { mtype, mtext });
+        state.msqinfo.msg_qnum += 1;
+        state.msqinfo.msg_cbytes += msgsz as u32;
+        state.msqinfo.msg_stime = interface::timestamp() as isize;
+        state.msqinfo.msg_lspid = self.cageid as u32;
+        queue.cv.notify_all();
+        0
+    }
+
+    //------------------MSGRCV SYSCALL------------------
+
+    /*
+     *   msgrcv() takes a message picked by msgtyp off the queue (see MsgQueueState::find_message)
+     *   and stores its mtype and up to msgsz bytes of its text at msgp. A longer message fails
+     *   with E2BIG and stays queued, unless MSG_NOERROR has it truncated. With no such message
+     *   it blocks, or fails with ENOMSG under IPC_NOWAIT. MSG_COPY leaves the message queued.
+     *   Returns the number of bytes of text received, or -1 on failure.
+     */
+    pub fn msgrcv_syscall(&self, msqid: i32, msgp: *mut u8, msgsz: usize, msgtyp: i32, msgflg: i32) -> i32 {
+        if msgflg & MSG_COPY != 0 && (msgflg & IPC_NOWAIT == 0 || msgflg & MSG_EXCEPT != 0) {
+            return syscall_error(Errno::EINVAL, "msgrcv", "MSG_COPY needs IPC_NOWAIT and excludes MSG_EXCEPT");
+        }
+        let queue = match MSG_METADATA.get_queue(msqid) {
+            Some(queue) => queue,
+            None => return syscall_error(Errno::EINVAL, "msgrcv", "Invalid identifier"),
+        };
+
+        let mut state = queue.state.lock();
+        if !self.ipc_permits(&state.msqinfo.msg_perm, libc::R_OK) {
+            return syscall_error(Errno::EACCES, "msgrcv", "no read permission for the queue");
+        }
+        let index = loop {
+            if state.removed {
+                return syscall_error(Errno::EIDRM, "msgrcv", "the queue was removed");
+            }
+            if let Some(index) = state.find_message(msgtyp, msgflg) {
+                break index;
+            }
+            if msgflg & IPC_NOWAIT != 0 {
+                return syscall_error(Errno::ENOMSG, "msgrcv", "there is no message of the requested type");
+            }
+            queue.cv.wait(&mut state);
+        };
+
+        let message = &state.messages[index];
+        if message.mtext.len() > msgsz && msgflg & MSG_NOERROR == 0 {
+            return syscall_error(Errno::EBIG, "msgrcv", "the message is longer than msgsz");
+        }
+        let copied = message.mtext.len().min(msgsz);
+        unsafe {
+            (msgp as *mut i32).write_unaligned(message.mtype);
+            std::ptr::copy_nonoverlapping(message.mtext.as_ptr(), msgp.add(MSG_MTYPE_SIZE), copied);
+        }
+        if msgflg & MSG_COPY == 0 {
+            let message = state.messages.remove(index).unwrap();
+            state.msqinfo.msg_qnum -= 1;
+            state.msqinfo.msg_cbytes -= message.mtext.len() as u32;
+            state.msqinfo.msg_rtime = interface::timestamp() as isize;
+            state.msqinfo.msg_lrpid = self.cageid as u32;
+            queue.cv.notify_all();
+        }
+        copied as i32
+    }
+
+    //------------------MSGCTL SYSCALL------------------
+
+    /*
+     *   msgctl() supports
+     *       - IPC_STAT: copies the queue's msqid_ds into buf, needs read permission
+     *       - IPC_SET: takes the owner uid/gid, the permission bits and msg_qbytes from buf,
+     *           owner only, and only root may raise msg_qbytes above MSGMNB
+     *       - IPC_RMID: removes the queue at once, owner only
+     *       - IPC_INFO/MSG_INFO: fill buf with struct msginfo instead, and return the highest
+     *           msqid in use rather than 0
+     */
+    pub fn msgctl_syscall(&self, msqid: i32, cmd: i32, buf: Option<&mut MsqidsStruct>) -> i32 {
+        let metadata = &MSG_METADATA;
+
+        if cmd == IPC_INFO || cmd == MSG_INFO {
+            let buf = match buf {
+                Some(buf) => buf as *mut MsqidsStruct,
+                None => return syscall_error(Errno::EFAULT, "msgctl", "buf is not a valid address"),
+            };
+            let mut info = interface::MsginfoStruct {
+                msgpool: MSGPOOL as i32,
+                msgmap: MSGMNB as i32,
+                msgmax: MSGMAX as i32,
+                msgmnb: MSGMNB as i32,
+                msgmni: MSGMNI as i32,
+                msgssz: MSGSSZ as i32,
+                msgtql: MSGTQL as i32,
+                msgseg: MSGSEG,
+            };
+            // MSG_INFO reports what is in use in the place of some of the limits
+            if cmd == MSG_INFO {
+                let (mut messages, mut bytes) = (0, 0);
+                for queue in metadata.msgtable.iter() {
+                    let state = queue.state.lock();
+                    messages += state.msqinfo.msg_qnum;
+                    bytes += state.msqinfo.msg_cbytes;
+                }
+                info.msgpool = metadata.msgtable.len() as i32;
+                info.msgmap = messages as i32;
+                info.msgtql = bytes as i32;
+            }
+            unsafe { (buf as *mut interface::MsginfoStruct).write_unaligned(info) };
+            return metadata.max_msqid();
+        }
+
+        let queue = match metadata.get_queue(msqid) {
+            Some(queue) => queue,
+            None => return syscall_error(Errno::EINVAL, "msgctl", "Invalid identifier"),
+        };
+        let mut state = queue.state.lock();
+        match cmd {
+            IPC_STAT => {
+                if !self.ipc_permits(&state.msqinfo.msg_perm, libc::R_OK) {
+                    return syscall_error(Errno::EACCES, "msgctl", "no read permission for the queue");
+                }
+                match buf {
+                    Some(buf) => *buf = state.msqinfo,
+                    None => return syscall_error(Errno::EFAULT, "msgctl", "buf is not a valid address"),
+                }
+            }
+            IPC_SET => {
+                let buf = match buf {
+                    Some(buf) => buf,
+                    None => return syscall_error(Errno::EFAULT, "msgctl", "buf is not a valid address"),
+                };
+                if !self.ipc_owns(&state.msqinfo.msg_perm) {
+                    return syscall_error(Errno::EPERM, "msgctl", "only the owner or creator may change the queue");
+                }
+                if buf.msg_qbytes > MSGMNB && self.euid() != sys_constants::ROOT_UID {
+                    return syscall_error(Errno::EPERM, "msgctl", "only root may raise msg_qbytes above MSGMNB");
+                }
+                let perm = &mut state.msqinfo.msg_perm;
+                perm.uid = buf.msg_perm.uid;
+                perm.gid = buf.msg_perm.gid;
+                perm.mode = (perm.mode & !0o777) | (buf.msg_perm.mode & 0o777);
+                state.msqinfo.msg_qbytes = buf.msg_qbytes;
+                state.msqinfo.msg_ctime = interface::timestamp() as isize;
+                // senders may fit now
+                queue.cv.notify_all();
+            }
+            IPC_RMID => {
+                if !self.ipc_owns(&state.msqinfo.msg_perm) {
+                    return syscall_error(Errno::EPERM, "msgctl", "only the owner or creator may remove the queue");
+                }
+                let key = state.key;
+                drop(state);
+                if key != IPC_PRIVATE {
+                    metadata.msgkeyidtable.remove(&key);
+                }
+                metadata.msgtable.remove(&msqid);
+                queue.mark_removed();
+            }
+            _ => {
+                return syscall_error(
+                    Errno::EINVAL,
+                    "msgctl",
+                    "Arguments provided do not match implemented parameters",
+                );
+            }
+        }
+
+        0 //msgctl has succeeded!
     }
 
-    //------------------SHMGET SYSCALL------------------
+    //------------------SEMGET SYSCALL------------------
 
-    pub fn shmget_syscall(&self, key: i32, size: usize, shmflg: i32) -> i32 {
+    /*
+     *   semget() returns the id of the set of nsems semaphores with key, creating it with
+     *   IPC_CREAT. A key of IPC_PRIVATE always creates a new set, which no other semget call
+     *   can find. The semaphores of a new set start out at 0.
+     */
+    pub fn semget_syscall(&self, key: i32, nsems: i32, semflg: i32) -> i32 {
+        let metadata = &SEM_METADATA;
+        if nsems < 0 || nsems as u32 > SEMMSL {
+            return syscall_error(Errno::EINVAL, "semget", "nsems is less than 0 or greater than SEMMSL");
+        }
         if key == IPC_PRIVATE {
-            return syscall_error(Errno::ENOENT, "shmget", "IPC_PRIVATE not implemented");
+            return self.new_sem_set_id(key, nsems, semflg);
         }
-        let shmid: i32;
-        let metadata = &SHM_METADATA;
 
-        match metadata.shmkeyidtable.entry(key) {
+        match metadata.semkeyidtable.entry(key) {
             interface::RustHashEntry::Occupied(occupied) => {
-                if (IPC_CREAT | IPC_EXCL) == (shmflg & (IPC_CREAT | IPC_EXCL)) {
+                if (IPC_CREAT | IPC_EXCL) == (semflg & (IPC_CREAT | IPC_EXCL)) {
                     return syscall_error(
                         Errno::EEXIST,
-                        "shmget",
+                        "semget",
                         "key already exists and IPC_CREAT and IPC_EXCL were used",
                     );
                 }
-                shmid = *occupied.get();
+                let semid = *occupied.get();
+                // the permission bits in semflg are what the caller wants from the set
+                let want = (semflg >> 6 | semflg >> 3 | semflg) & 7;
+                if let Some(set) = metadata.get_set(semid) {
+                    let state = set.state.lock();
+                    if !self.ipc_permits(&state.seminfo.sem_perm, want) {
+                        return syscall_error(Errno::EACCES, "semget", "permission denied for the set");
+                    }
+                    if nsems as usize > state.sems.len() {
+                        return syscall_error(Errno::EINVAL, "semget", "the set has fewer than nsems semaphores");
+                    }
+                }
+                semid
             }
             interface::RustHashEntry::Vacant(vacant) => {
-                if 0 == (shmflg & IPC_CREAT) {
+                if 0 == (semflg & IPC_CREAT) {
                     return syscall_error(
                         Errno::ENOENT,
-                        "shmget",
+                        "semget",
                         "tried to use a key that did not exist, and IPC_CREAT was not specified",
                     );
                 }
-
-                if (size as u32) < SHMMIN || (size as u32) > SHMMAX {
-                    return syscall_error(
-                        Errno::EINVAL,
-                        "shmget",
-                        "Size is less than SHMMIN or more than SHMMAX",
-                    );
+                let semid = self.new_sem_set_id(key, nsems, semflg);
+                if semid < 0 {
+                    return semid;
                 }
-
-                shmid = metadata.new_keyid();
-                vacant.insert(shmid);
-                let mode = (shmflg & 0x1FF) as u16; // mode is 9 least signficant bits of shmflag, even if we dont really do anything with them
-
-                let segment = new_shm_segment(
-                    key,
-                    size,
-                    self.cageid as u32,
-                    DEFAULT_UID,
-                    DEFAULT_GID,
-                    mode,
-                );
-                metadata.shmtable.insert(shmid, segment);
+                vacant.insert(semid);
+                semid
             }
-        };
-        shmid // return the shmid
+        }
     }
 
-    //------------------SHMAT SYSCALL------------------
-
-    pub fn shmat_syscall(&self, shmid: i32, shmaddr: *mut u8, shmflg: i32) -> i32 {
-        let metadata = &SHM_METADATA;
-        let prot: i32;
-        if let Some(mut segment) = metadata.shmtable.get_mut(&shmid) {
-            if 0 != (shmflg & fs_constants::SHM_RDONLY) {
-                prot = PROT_READ;
-            } else {
-                prot = PROT_READ | PROT_WRITE;
-            }
-            let mut rev_shm = self.rev_shm.lock();
-            rev_shm.push((shmaddr as u32, shmid));
-            drop(rev_shm);
-
-            // update semaphores
-            if !segment.semaphor_offsets.is_empty() {
-                // lets just look at the first cage in the set, since we only need to grab the ref from one
-                if let Some(cageid) = segment
-                    .attached_cages
-                    .clone()
-                    .into_read_only()
-                    .keys()
-                    .next()
-                {
-                    let cage2 = interface::cagetable_getref(*cageid);
-                    let cage2_rev_shm = cage2.rev_shm.lock();
-                    let addrs = Self::rev_shm_find_addrs_by_shmid(&cage2_rev_shm, shmid); // find all the addresses assoc. with shmid
-                    for offset in segment.semaphor_offsets.iter() {
-                        let sementry = cage2.sem_table.get(&(addrs[0] + *offset)).unwrap().clone(); //add  semaphors into semtable at addr + offsets
-                        self.sem_table.insert(shmaddr as u32 + *offset, sementry);
-                    }
-                }
-            }
-
-            segment.map_shm(shmaddr, prot, self.cageid)
-        } else {
-            syscall_error(Errno::EINVAL, "shmat", "Invalid shmid value")
+    // creates a set for semget, owned by the caller's effective ids
+    fn new_sem_set_id(&self, key: i32, nsems: i32, semflg: i32) -> i32 {
+        let metadata = &SEM_METADATA;
+        if nsems == 0 {
+            return syscall_error(Errno::EINVAL, "semget", "a new set needs at least one semaphore");
+        }
+        if metadata.semtable.len() >= SEMMNI as usize {
+            return syscall_error(Errno::ENOSPC, "semget", "all possible semaphore set ids are in use");
         }
+        let semid = metadata.new_keyid();
+        let mode = (semflg & 0o777) as u16;
+        let set = SemSet::new(key, nsems as usize, self.euid(), self.egid(), mode);
+        metadata.semtable.insert(semid, interface::RustRfc::new(set));
+        semid
     }
 
-    //------------------SHMDT SYSCALL------------------
-
-    pub fn shmdt_syscall(&self, shmaddr: *mut u8) -> i32 {
-        let metadata = &SHM_METADATA;
-        let mut rm = false;
-        let mut rev_shm = self.rev_shm.lock();
-        let rev_shm_index = Self::rev_shm_find_index_by_addr(&rev_shm, shmaddr as u32);
-
-        if let Some(index) = rev_shm_index {
-            let shmid = rev_shm[index].1;
-            match metadata.shmtable.entry(shmid) {
-                interface::RustHashEntry::Occupied(mut occupied) => {
-                    let segment = occupied.get_mut();
+    //------------------SEMOP SYSCALL------------------
 
-                    // update semaphores
-                    for offset in segment.semaphor_offsets.iter() {
-                        self.sem_table.remove(&(shmaddr as u32 + *offset));
-                    }
+    /*
+     *   semop() performs all of sops on the set at once, waiting for as long as any of them
+     *   can't proceed (see SemSetState::try_semop), or failing with EAGAIN if that operation
+     *   has IPC_NOWAIT. Operations with SEM_UNDO are rolled back when the cage exits.
+     *   Returns 0 on success and -1 on failure.
+     */
+    pub fn semop_syscall(&self, semid: i32, sops: &[SembufStruct]) -> i32 {
+        self.semtimedop_syscall(semid, sops, None)
+    }
 
-                    segment.unmap_shm(shmaddr, self.cageid);
+    //------------------SEMTIMEDOP SYSCALL------------------
 
-                    if segment.rmid && segment.shminfo.shm_nattch == 0 {
-                        rm = true;
-                    }
-                    rev_shm.swap_remove(index);
+    /*
+     *   semtimedop() is semop() that fails with EAGAIN once timeout has passed without the
+     *   operations going through. A timeout of None waits for as long as it takes.
+     */
+    pub fn semtimedop_syscall(&self, semid: i32, sops: &[SembufStruct], timeout: Option<interface::RustDuration>) -> i32 {
+        if sops.is_empty() {
+            return syscall_error(Errno::EINVAL, "semop", "nsops is 0");
+        }
+        if sops.len() > SEMOPM as usize {
+            return syscall_error(Errno::EBIG, "semop", "nsops is greater than SEMOPM");
+        }
+        let set = match SEM_METADATA.get_set(semid) {
+            Some(set) => set,
+            None => return syscall_error(Errno::EINVAL, "semop", "Invalid identifier"),
+        };
+        let deadline = timeout.map(|timeout| interface::RustInstant::now() + timeout);
 
-                    if rm {
-                        let key = segment.key;
-                        occupied.remove_entry();
-                        metadata.shmkeyidtable.remove(&key);
-                    }
+        let mut state = set.state.lock();
+        if sops.iter().any(|sop| sop.sem_num as usize >= state.sems.len()) {
+            return syscall_error(Errno::EFBIG, "semop", "sem_num is out of range for the set");
+        }
+        // operations that only wait for zero need read permission, the others alter the set
+        let want = if sops.iter().any(|sop| sop.sem_op != 0) { libc::W_OK } else { libc::R_OK };
+        if !self.ipc_permits(&state.seminfo.sem_perm, want) {
+            return syscall_error(Errno::EACCES, "semop", "permission denied for the set");
+        }
 
-                    return shmid; //NaCl relies on this non-posix behavior of returning the shmid on success
+        loop {
+            if state.removed {
+                return syscall_error(Errno::EIDRM, "semop", "the set was removed");
+            }
+            let index = match state.try_semop(sops, self.cageid) {
+                SemopResult::Done => {
+                    set.cv.notify_all();
+                    return 0;
                 }
-                interface::RustHashEntry::Vacant(_) => {
-                    panic!("Inode not created for some reason");
+                SemopResult::OutOfRange => {
+                    return syscall_error(Errno::ERANGE, "semop", "a semaphore would exceed SEMVMX");
                 }
+                SemopResult::Wait(index) => index,
             };
-        } else {
-            return syscall_error(
-                Errno::EINVAL,
-                "shmdt",
-                "No shared memory segment at shmaddr",
-            );
+            if sops[index].sem_flg as i32 & IPC_NOWAIT != 0 {
+                return syscall_error(Errno::EAGAIN, "semop", "the operation would block");
+            }
+
+            *state.waiters(&sops[index]) += 1;
+            let timed_out = match deadline {
+                Some(deadline) => set.cv.wait_until(&mut state, deadline).timed_out(),
+                None => {
+                    set.cv.wait(&mut state);
+                    false
+                }
+            };
+            *state.waiters(&sops[index]) -= 1;
+            if timed_out {
+                return syscall_error(Errno::EAGAIN, "semtimedop", "the time limit was reached");
+            }
         }
     }
 
-    //------------------SHMCTL SYSCALL------------------
+    //------------------SEMCTL SYSCALL------------------
 
-    pub fn shmctl_syscall(&self, shmid: i32, cmd: i32, buf: Option<&mut ShmidsStruct>) -> i32 {
-        let metadata = &SHM_METADATA;
+    /*
+     *   semctl() supports
+     *       - GETVAL/GETPID/GETNCNT/GETZCNT: return semval/sempid/semncnt/semzcnt of semaphore
+     *           semnum, needs read permission
+     *       - GETALL: stores every semval into arg.array, needs read permission
+     *       - SETVAL/SETALL: set semaphore semnum to arg.val, or every semaphore from
+     *           arg.array, and forget the SEM_UNDO adjustments made to them, needs write
+     *           permission
+     *       - IPC_STAT: copies the set's semid_ds into arg.buf, needs read permission
+     *       - IPC_SET: takes the owner uid/gid and the permission bits from arg.buf, owner only
+     *       - IPC_RMID: removes the set at once, owner only
+     *       - IPC_INFO/SEM_INFO: fill arg.buf with struct seminfo instead, and return the
+     *           highest semid in use
+     */
+    pub fn semctl_syscall(&self, semid: i32, semnum: i32, cmd: i32, arg: SemunUnion) -> i32 {
+        let metadata = &SEM_METADATA;
 
-        if let Some(mut segment) = metadata.shmtable.get_mut(&shmid) {
-            match cmd {
-                IPC_STAT => {
-                    *buf.unwrap() = segment.shminfo;
+        if cmd == IPC_INFO || cmd == SEM_INFO {
+            let buf = unsafe { arg.buf } as *mut interface::SeminfoStruct;
+            if buf.is_null() {
+                return syscall_error(Errno::EFAULT, "semctl", "arg.buf is not a valid address");
+            }
+            let mut info = interface::SeminfoStruct {
+                semmap: SEMMNS as i32,
+                semmni: SEMMNI as i32,
+                semmns: SEMMNS as i32,
+                semmnu: SEMMNS as i32,
+                semmsl: SEMMSL as i32,
+                semopm: SEMOPM as i32,
+                semume: SEMUME as i32,
+                semusz: SEMUSZ as i32,
+                semvmx: SEMVMX as i32,
+                semaem: SEMAEM as i32,
+            };
+            // SEM_INFO reports the sets and semaphores in use in the place of some of the limits
+            if cmd == SEM_INFO {
+                info.semusz = metadata.semtable.len() as i32;
+                info.semaem = metadata.semtable.iter().map(|set| set.state.lock().sems.len() as i32).sum();
+            }
+            unsafe { buf.write_unaligned(info) };
+            return metadata.max_semid();
+        }
+
+        let set = match metadata.get_set(semid) {
+            Some(set) => set,
+            None => return syscall_error(Errno::EINVAL, "semctl", "Invalid identifier"),
+        };
+        let mut state = set.state.lock();
+        let nsems = state.sems.len();
+        let want = match cmd {
+            SETVAL | SETALL => libc::W_OK,
+            IPC_SET | IPC_RMID => 0,
+            _ => libc::R_OK,
+        };
+        if !self.ipc_permits(&state.seminfo.sem_perm, want) {
+            return syscall_error(Errno::EACCES, "semctl", "permission denied for the set");
+        }
+        if matches!(cmd, GETVAL | GETPID | GETNCNT | GETZCNT | SETVAL) && (semnum < 0 || semnum as usize >= nsems) {
+            return syscall_error(Errno::EINVAL, "semctl", "semnum is out of range for the set");
+        }
+
+        match cmd {
+            GETVAL => state.sems[semnum as usize].semval,
+            GETPID => state.sems[semnum as usize].sempid as i32,
+            GETNCNT => state.sems[semnum as usize].semncnt as i32,
+            GETZCNT => state.sems[semnum as usize].semzcnt as i32,
+            GETALL => {
+                let array = unsafe { arg.array };
+                if array.is_null() {
+                    return syscall_error(Errno::EFAULT, "semctl", "arg.array is not a valid address");
                 }
-                IPC_RMID => {
-                    segment.rmid = true;
-                    segment.shminfo.shm_perm.mode |= SHM_DEST as u16;
-                    if segment.shminfo.shm_nattch == 0 {
-                        let key = segment.key;
-                        drop(segment);
-                        metadata.shmtable.remove(&shmid);
-                        metadata.shmkeyidtable.remove(&key);
-                    }
+                for (index, sem) in state.sems.iter().enumerate() {
+                    unsafe { array.add(index).write_unaligned(sem.semval as u16) };
                 }
-                _ => {
-                    return syscall_error(
-                        Errno::EINVAL,
-                        "shmctl",
-                        "Arguments provided do not match implemented parameters",
-                    );
+                0
+            }
+            SETVAL => {
+                let val = unsafe { arg.val };
+                if val < 0 || val as u32 > SEMVMX {
+                    return syscall_error(Errno::ERANGE, "semctl", "val is less than 0 or greater than SEMVMX");
                 }
+                let sem = &mut state.sems[semnum as usize];
+                sem.semval = val;
+                sem.sempid = self.cageid as u32;
+                state.clear_undo(semnum as usize);
+                state.seminfo.sem_ctime = interface::timestamp() as isize;
+                set.cv.notify_all();
+                0
             }
-        } else {
-            return syscall_error(Errno::EINVAL, "shmctl", "Invalid identifier");
+            SETALL => {
+                let array = unsafe { arg.array };
+                if array.is_null() {
+                    return syscall_error(Errno::EFAULT, "semctl", "arg.array is not a valid address");
+                }
+                let values: Vec<u16> = (0..nsems).map(|index| unsafe { array.add(index).read_unaligned() }).collect();
+                if values.iter().any(|val| *val as u32 > SEMVMX) {
+                    return syscall_error(Errno::ERANGE, "semctl", "a value is greater than SEMVMX");
+                }
+                for (index, val) in values.into_iter().enumerate() {
+                    let sem = &mut state.sems[index];
+                    sem.semval = val as i32;
+                    sem.sempid = self.cageid as u32;
+                    state.clear_undo(index);
+                }
+                state.seminfo.sem_ctime = interface::timestamp() as isize;
+                set.cv.notify_all();
+                0
+            }
+            IPC_STAT => {
+                let buf = unsafe { arg.buf };
+                if buf.is_null() {
+                    return syscall_error(Errno::EFAULT, "semctl", "arg.buf is not a valid address");
+                }
+                unsafe { buf.write_unaligned(state.seminfo) };
+                0
+            }
+            IPC_SET => {
+                let buf = unsafe { arg.buf };
+                if buf.is_null() {
+                    return syscall_error(Errno::EFAULT, "semctl", "arg.buf is not a valid address");
+                }
+                if !self.ipc_owns(&state.seminfo.sem_perm) {
+                    return syscall_error(Errno::EPERM, "semctl", "only the owner or creator may change the set");
+                }
+                let newinfo = unsafe { buf.read_unaligned() };
+                let perm = &mut state.seminfo.sem_perm;
+                perm.uid = newinfo.sem_perm.uid;
+                perm.gid = newinfo.sem_perm.gid;
+                perm.mode = (perm.mode & !0o777) | (newinfo.sem_perm.mode & 0o777);
+                state.seminfo.sem_ctime = interface::timestamp() as isize;
+                0
+            }
+            IPC_RMID => {
+                if !self.ipc_owns(&state.seminfo.sem_perm) {
+                    return syscall_error(Errno::EPERM, "semctl", "only the owner or creator may remove the set");
+                }
+                let key = state.key;
+                drop(state);
+                if key != IPC_PRIVATE {
+                    metadata.semkeyidtable.remove(&key);
+                }
+                metadata.semtable.remove(&semid);
+                set.mark_removed();
+                0
+            }
+            _ => syscall_error(
+                Errno::EINVAL,
+                "semctl",
+                "Arguments provided do not match implemented parameters",
+            ),
         }
-
-        0 //shmctl has succeeded!
     }
 
     //------------------MUTEX SYSCALLS------------------
@@ -1878,6 +3813,103 @@ impl Cage {
         return 0;
     }
 
+    //------------------SEM_OPEN SYSCALL------------------
+
+    /*
+     *   sem_open() opens the named semaphore called name, creating it with the given mode and
+     *   value if O_CREAT is set and there is none. The semaphore is put at sem_handle, the
+     *   sem_t the cage set aside for it, so the other semaphore calls work on it. Opening a
+     *   semaphore the cage already has open returns the handle it is at instead, and each
+     *   open then takes a sem_close of its own.
+     *   Returns the handle on success and -1 on failure.
+     */
+    pub fn sem_open_syscall(&self, name: &str, oflag: i32, mode: u32, value: u32, sem_handle: u32) -> i32 {
+        let name = match interface::shm_object_name(name) {
+            Some(name) => name,
+            None => return syscall_error(Errno::EINVAL, "sem_open", "name is not a valid semaphore name"),
+        };
+        if oflag & O_CREAT != 0 && value > SEM_VALUE_MAX {
+            return syscall_error(Errno::EINVAL, "sem_open", "value exceeds SEM_VALUE_MAX");
+        }
+
+        let create = if oflag & O_CREAT != 0 {
+            let newmode = mode & 0o777 & !self.umask.load(interface::RustAtomicOrdering::Relaxed);
+            Some(interface::NamedSemaphore::new(value, self.euid(), self.egid(), newmode))
+        } else {
+            None
+        };
+        let (namedsem, created) = match interface::named_sem(name, create, oflag & O_EXCL != 0) {
+            Ok(found) => found,
+            Err(errno) => return handle_errno(errno, "sem_open"),
+        };
+        // a semaphore is waited on and posted to alike, so opening one takes both
+        if !created && !self.permits(namedsem.uid, namedsem.gid, namedsem.mode, R_OK | W_OK, false) {
+            return syscall_error(Errno::EACCES, "sem_open", "permission to the semaphore is denied");
+        }
+
+        for mut entry in self.named_sem_table.iter_mut() {
+            if interface::RustRfc::ptr_eq(&entry.value().0, &namedsem) {
+                entry.value_mut().1 += 1;
+                return *entry.key() as i32;
+            }
+        }
+        if self.sem_table.contains_key(&sem_handle) {
+            return syscall_error(Errno::EINVAL, "sem_open", "sem is already a semaphore");
+        }
+        self.sem_table.insert(sem_handle, namedsem.sem.clone());
+        self.named_sem_table.insert(sem_handle, (namedsem, 1));
+        sem_handle as i32
+    }
+
+    //------------------SEM_CLOSE SYSCALL------------------
+
+    /*
+     *   sem_close() undoes one sem_open of the named semaphore at sem_handle. Once all of the
+     *   cage's opens are undone the handle no longer refers to the semaphore, which itself is
+     *   only gone when it is unlinked and no cage has it open anymore.
+     *   Returns 0 on success and -1 on failure.
+     */
+    pub fn sem_close_syscall(&self, sem_handle: u32) -> i32 {
+        let lastclose = match self.named_sem_table.get_mut(&sem_handle) {
+            Some(mut entry) => {
+                entry.1 -= 1;
+                entry.1 == 0
+            }
+            None => return syscall_error(Errno::EINVAL, "sem_close", "sem is not a named semaphore"),
+        };
+        if lastclose {
+            self.named_sem_table.remove(&sem_handle);
+            self.sem_table.remove(&sem_handle);
+        }
+        0
+    }
+
+    //------------------SEM_UNLINK SYSCALL------------------
+
+    /*
+     *   sem_unlink() removes the name of a named semaphore. Cages that have it open keep using
+     *   it, but sem_open can't find it anymore and creates a new semaphore under the name.
+     *   Returns 0 on success and -1 on failure.
+     */
+    pub fn sem_unlink_syscall(&self, name: &str) -> i32 {
+        // glibc looks an invalid name up all the same, and doesn't find it
+        let name = match interface::shm_object_name(name) {
+            Some(name) => name,
+            None => return syscall_error(Errno::ENOENT, "sem_unlink", "no semaphore has that name"),
+        };
+        let namedsem = match interface::named_sem(name, None, false) {
+            Ok((namedsem, _)) => namedsem,
+            Err(errno) => return handle_errno(errno, "sem_unlink"),
+        };
+        // the names live in the sticky /dev/shm, so only the owner (or root) may remove one
+        let euid = self.euid();
+        if euid != sys_constants::ROOT_UID && euid != namedsem.uid {
+            return syscall_error(Errno::EACCES, "sem_unlink", "only the owner may remove the semaphore");
+        }
+        interface::named_sem_remove(name);
+        0
+    }
+
     // We're directly patching in the libc futex call for experimentation with lind-wasm
     // this should allow us to use the nptl data structures such as mutexes and condvars directly
     // as opposed to lind-nacl's individual implementations
@@ -1904,8 +3936,117 @@ impl Cage {
     }
 }
 
+// The permissions an open with oflag needs on an existing file
+fn open_access_wanted(oflag: i32) -> i32 {
+    let mut want = match oflag & O_ACCMODE {
+        O_RDONLY => libc::R_OK,
+        O_WRONLY => libc::W_OK,
+        _ => libc::R_OK | libc::W_OK,
+    };
+    if oflag & O_TRUNC != 0 {
+        want |= libc::W_OK;
+    }
+    want
+}
+
 pub fn kernel_close(fdentry: fdtables::FDTableEntry, _count: u64) {
+    // a socket bound in a network namespace gives its port back
+    interface::netns_release(fdentry.underfd);
+    let _ret = unsafe {
+        libc::close(fdentry.underfd as i32)
+    };
+}
+
+// Called once no cage holds an fd for this pipe end anymore: closing the last
+// write end gives readers EOF, closing the last read end makes writes fail with EPIPE
+pub fn impipe_close(fdentry: fdtables::FDTableEntry, _count: u64) {
+    interface::close_pipe_end(fdentry.underfd);
+}
+
+// Called once no cage holds an fd for this socket anymore: its name is released and
+// a connected stream peer sees EOF
+pub fn imsock_close(fdentry: fdtables::FDTableEntry, _count: u64) {
+    interface::close_unix_socket(fdentry.underfd);
+}
+
+// Called once no cage holds an fd for this open of a shm object anymore
+pub fn imshm_close(fdentry: fdtables::FDTableEntry, _count: u64) {
+    interface::shm_open_remove(fdentry.underfd);
     let _ret = unsafe {
         libc::close(fdentry.underfd as i32)
     };
 }
+
+// The whole pages a mapping of len bytes at addr takes up, as [start, end)
+fn page_range(addr: usize, len: usize) -> (usize, usize) {
+    let start = addr & !(PAGESIZE - 1);
+    let end = addr.saturating_add(len).saturating_add(PAGESIZE - 1) & !(PAGESIZE - 1);
+    (start, end)
+}
+
+// How much of [start, end) the counted ranges of a cage cover
+fn covered_bytes(ranges: &[(usize, usize)], start: usize, end: usize) -> usize {
+    ranges.iter().map(|&(rstart, rend)| rend.min(end).saturating_sub(rstart.max(start))).sum()
+}
+
+// Takes [start, end) out of the counted ranges, splitting those it only partly covers
+fn remove_range(ranges: &mut Vec<(usize, usize)>, start: usize, end: usize) {
+    let mut kept = Vec::with_capacity(ranges.len() + 1);
+    for &(rstart, rend) in ranges.iter() {
+        if rend <= start || rstart >= end {
+            kept.push((rstart, rend));
+            continue;
+        }
+        if rstart < start {
+            kept.push((rstart, start));
+        }
+        if rend > end {
+            kept.push((end, rend));
+        }
+    }
+    *ranges = kept;
+}
+
+// The iovecs handed to the vectored I/O calls, checked the way Linux does
+fn iovec_slice<'a>(iovec: *const interface::IovecStruct, iovcnt: i32, syscallname: &str) -> Result<&'a [interface::IovecStruct], i32> {
+    if iovcnt < 0 || iovcnt > libc::UIO_MAXIOV {
+        return Err(syscall_error(Errno::EINVAL, syscallname, "iovcnt is out of range"));
+    }
+    if iovcnt == 0 {
+        return Ok(&[]);
+    }
+    if iovec.is_null() {
+        return Err(syscall_error(Errno::EFAULT, syscallname, "iovec is not a valid address"));
+    }
+    Ok(unsafe { std::slice::from_raw_parts(iovec, iovcnt as usize) })
+}
+
+// Copies data into the buffers of iovs in order, as far as it goes
+pub(crate) fn scatter_iovecs(iovs: &[interface::IovecStruct], data: &[u8]) {
+    let mut copied = 0;
+    for iov in iovs {
+        if copied == data.len() {
+            break;
+        }
+        let len = iov.iov_len.min(data.len() - copied);
+        unsafe {
+            ptr::copy_nonoverlapping(data[copied..].as_ptr(), iov.iov_base as *mut u8, len);
+        }
+        copied += len;
+    }
+}
+
+// The first count bytes of iovs, for writes cut short by RLIMIT_FSIZE
+fn limit_iovecs(iovs: &[interface::IovecStruct], count: usize) -> Vec<interface::IovecStruct> {
+    let mut left = count;
+    let mut limited = Vec::new();
+    for iov in iovs {
+        if left == 0 {
+            break;
+        }
+        let len = iov.iov_len.min(left);
+        limited.push(interface::IovecStruct { iov_base: iov.iov_base, iov_len: len });
+        left -= len;
+    }
+    limited
+}
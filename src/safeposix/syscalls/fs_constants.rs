@@ -42,8 +42,21 @@ use crate::interface;
 
 // pub const DEFAULTTIME: u64 = 1323630836;
 
+// Host directory used as "/" for cages when the runtime does not pick one at init
+pub const DEFAULT_LIND_ROOT: &str = "/home/lind/lind_project/src/safeposix-rust/tmp";
+
+// f_type reported by fstatfs for in-memory pipes, same as Linux pipefs
+pub const PIPEFS_MAGIC: u64 = 0x50495045;
+// and for in-memory unix domain sockets, same as Linux sockfs
+pub const SOCKFS_MAGIC: u64 = 0x534F434B;
+// and for epoll fds, same as Linux anon_inodefs
+pub const ANON_INODE_FS_MAGIC: u64 = 0x09041934;
+
 //Standard flag combinations
 pub const S_IRWXA: u32 = 0o777;
+
+// umask a cage starts with, the usual one for a login shell
+pub const DEFAULT_UMASK: u32 = 0o022;
 pub const S_IRWXU: u32 = 0o700;
 pub const S_IRUSR: u32 = 0o400;
 pub const S_IWUSR: u32 = 0o200;
@@ -121,6 +134,9 @@ pub const S_IXOTH: u32 = 0o001;
 // pub const IPC_SET: i32 = 1;
 // pub const IPC_STAT: i32 = 2;
 
+// shmctl commands libc only has for some targets
+pub const SHM_INFO: i32 = 14;
+
 pub const SHM_DEST: i32 = 0o1000;
 pub const SHM_LOCKED: i32 = 0o2000;
 pub const SHM_HUGETLB: i32 = 0o4000;
@@ -137,6 +153,31 @@ pub const SHMMNI: u32 = 4096;
 pub const SHMMAX: u32 = 4278190079; // (ULONG_MAX - (1UL << 24))
 pub const SHMALL: u32 = 4278190079; // (ULONG_MAX - (1UL << 24));
 pub const SHMSEG: u32 = SHMMNI;
+// wasm pages are 64KiB, but shm_info counts the 4KiB pages Linux does
+pub const SHM_PAGESIZE: u32 = 4096;
+// mmap and munmap work in whole host pages
+pub const PAGESIZE: usize = 4096;
+
+// message queue limits, the Linux defaults
+pub const MSGMAX: u32 = 8192; // largest message
+pub const MSGMNB: u32 = 16384; // msg_qbytes of a new queue
+pub const MSGMNI: u32 = 32000;
+pub const MSGSSZ: u32 = 16;
+pub const MSGPOOL: u32 = MSGMNI * MSGMNB / 1024;
+pub const MSGTQL: u32 = MSGMNB;
+pub const MSGSEG: u16 = 0xffff;
+// the mtype that starts a message is the cage's long
+pub const MSG_MTYPE_SIZE: usize = 4;
+
+// semaphore set limits, the Linux defaults
+pub const SEMMSL: u32 = 32000; // semaphores per set
+pub const SEMMNS: u32 = 1024000000;
+pub const SEMOPM: u32 = 500; // operations per semop
+pub const SEMMNI: u32 = 32000;
+pub const SEMVMX: u32 = 32767; // largest semaphore value
+pub const SEMAEM: u32 = SEMVMX;
+pub const SEMUME: u32 = SEMOPM;
+pub const SEMUSZ: u32 = 20;
 
 pub const SEM_VALUE_MAX: u32 = 2147483647;
 
@@ -12,7 +12,6 @@ use std::collections::HashMap;
 use std::convert::TryInto;
 use dashmap::mapref::entry;
 use parking_lot::Mutex;
-use lazy_static::lazy_static;
 use std::io::{Read, Write};
 use std::io;
 use std::mem::size_of;
@@ -22,28 +21,192 @@ use std::ffi::CStr;
 use std::sync::Arc;
 
 use crate::safeposix::filesystem::normpath;
+use crate::safeposix::filesystem::hostpath;
+use crate::safeposix::filesystem::{resolve_beneath, resolve_parent_beneath};
+use super::fs_calls::scatter_iovecs;
 
 use libc::*;
 use std::{os::fd::RawFd, ptr};
 use bit_set::BitSet;
 
-static LIND_ROOT: &str = "/home/lind/lind_project/src/safeposix-rust/tmp/";
 const FDKIND_KERNEL: u32 = 0;
 const FDKIND_IMPIPE: u32 = 1;
 const FDKIND_IMSOCK: u32 = 2;
 
-lazy_static! {
-    // A hashmap used to store epoll mapping relationships 
-    // <virtual_epfd <kernel_fd, virtual_fd>> 
-    static ref REAL_EPOLL_MAP: Mutex<HashMap<u64, HashMap<i32, u64>>> = Mutex::new(HashMap::new());
+// In-memory fds cannot wake up a host poll/select/epoll_wait. While only in-memory fds are
+// watched we sleep until one of them changes state, when kernel fds are watched as well the
+// host call only blocks for short slices so the in-memory ones get rechecked in between
+const INMEM_POLL_SLICE: RustDuration = RustDuration::from_millis(1);
+
+// poll-style timeouts are in milliseconds and negative means wait forever
+fn poll_timeout_duration(timeout: i32) -> Option<RustDuration> {
+    if timeout < 0 {
+        None
+    } else {
+        Some(RustDuration::from_millis(timeout as u64))
+    }
+}
+
+fn duration_to_poll_timeout(dur: Option<RustDuration>) -> i32 {
+    match dur {
+        // round up so a partial millisecond does not turn into a busy loop
+        Some(dur) => ((dur.as_micros() + 999) / 1000) as i32,
+        None => -1,
+    }
+}
+
+// poll revents for an fd whose kind registered a readiness handler
+fn virtual_poll_events(entry: &FDTableEntry, events: i16) -> i16 {
+    interface::poll_fdkind(entry.fdkind, entry.underfd, events).unwrap_or(libc::POLLNVAL)
+}
+
+/*
+ *   The wait shared by poll, select and epoll_wait. Each round the caller calls begin_round,
+ *   checks its in-memory fds, runs the host call with kernel_timeout and then goes around
+ *   again for as long as keep_waiting says so.
+ */
+struct ReadinessWait {
+    start: RustInstant,
+    timeout: Option<RustDuration>,
+    has_virtual: bool,
+    has_kernel: bool,
+    seq: u64,
+}
+
+impl ReadinessWait {
+    fn new(timeout: Option<RustDuration>, has_virtual: bool, has_kernel: bool) -> ReadinessWait {
+        ReadinessWait {
+            start: interface::starttimer(),
+            timeout,
+            has_virtual,
+            has_kernel,
+            seq: 0,
+        }
+    }
+
+    // taken before the in-memory fds are checked, so no change can slip by unnoticed
+    fn begin_round(&mut self) {
+        self.seq = interface::readiness_seq();
+    }
+
+    fn remaining(&self) -> Option<RustDuration> {
+        self.timeout.map(|timeout| timeout.saturating_sub(self.start.elapsed()))
+    }
+
+    // how long the host call may block this round, None meaning forever
+    fn kernel_timeout(&self, virtual_ready: bool) -> Option<RustDuration> {
+        if virtual_ready || (self.has_virtual && !self.has_kernel) {
+            Some(RustDuration::ZERO)
+        } else if self.has_virtual {
+            Some(self.remaining().map_or(INMEM_POLL_SLICE, |remaining| remaining.min(INMEM_POLL_SLICE)))
+        } else {
+            self.remaining()
+        }
+    }
+
+    fn keep_waiting(&self, nready: usize) -> bool {
+        if nready > 0 || !self.has_virtual {
+            return false;
+        }
+        let remaining = self.remaining();
+        if remaining == Some(RustDuration::ZERO) {
+            return false;
+        }
+        if !self.has_kernel {
+            interface::wait_readiness_change(self.seq, remaining);
+        }
+        true
+    }
+}
+
+// An epoll fd can hold kernel fds, and nothing tells us when those become ready. A wait
+// that involves one has to keep asking, like when kernel fds are watched directly
+fn is_epoll_fd(entry: &FDTableEntry) -> bool {
+    entry.fdkind == fdtables::FDT_KINDEPOLL
+}
+
+// level-triggered events of a non-kernel fd registered with an epoll instance
+fn inmem_epoll_revents(fdkind: u32, event: &fdtables::epoll_event) -> u32 {
+    let wanted = event.events | (libc::EPOLLERR | libc::EPOLLHUP) as u32;
+    interface::poll_fdkind(fdkind, event.u64, event.events as i16).unwrap_or(libc::POLLNVAL) as u32 & wanted
+}
+
+/*
+ *   Readiness handler of FDT_KINDEPOLL, so an epoll fd can be polled, selected or put in
+ *   another epoll instance. As in Linux it is readable while any fd in it has an event.
+ */
+pub fn epoll_poll_events(entrynum: u64, events: i16) -> i16 {
+    let registrations = match fdtables::get_virtual_epoll_wait_data_by_entrynum(entrynum) {
+        Ok(registrations) => registrations,
+        Err(_) => return libc::POLLNVAL,
+    };
+    if events & libc::POLLIN == 0 {
+        return 0;
+    }
+    let inmem_ready = registrations
+        .iter()
+        .filter(|(fdkind, _)| **fdkind != FDKIND_KERNEL)
+        .any(|(fdkind, fdmap)| fdmap.values().any(|event| inmem_epoll_revents(*fdkind, event) != 0));
+    let kernel_ready = registrations.contains_key(&FDKIND_KERNEL) && {
+        let underfds = fdtables::epoll_get_underfd_hashmap_by_entrynum(entrynum).unwrap();
+        let mut kernel_poll = pollfd { fd: underfds[&FDKIND_KERNEL] as i32, events: libc::POLLIN, revents: 0 };
+        unsafe { libc::poll(&mut kernel_poll, 1, 0) > 0 }
+    };
+    if inmem_ready || kernel_ready {
+        libc::POLLIN
+    } else {
+        0
+    }
+}
+
+fn imsock_nonblocking(vfd: &FDTableEntry) -> bool {
+    vfd.perfdinfo as i32 & O_NONBLOCK != 0
+}
+
+// What a sun_path names: abstract names start with a NUL byte, anything else is a path
+enum UnixAddrName {
+    Abstract(Vec<u8>),
+    Path(String),
+}
+
+// Returns the address as given along with its name, None meaning the address is empty
+fn parse_unix_addr(addr: &GenSockaddr, syscallname: &str) -> Result<(SockaddrUnix, Option<UnixAddrName>), i32> {
+    let unixaddr = match addr {
+        GenSockaddr::Unix(unixaddr) if unixaddr.sun_family == libc::AF_UNIX as u16 => *unixaddr,
+        _ => return Err(syscall_error(Errno::EINVAL, syscallname, "address is not an AF_UNIX address")),
+    };
+    let sun_path = &unixaddr.sun_path;
+    if sun_path[0] == 0 {
+        // the address length is not kept around, so trailing NULs are not part of the name
+        let end = sun_path.iter().rposition(|&byte| byte != 0).map_or(0, |last| last + 1);
+        if end == 0 {
+            return Ok((unixaddr, None));
+        }
+        return Ok((unixaddr, Some(UnixAddrName::Abstract(sun_path[1..end].to_vec()))));
+    }
+    let end = sun_path.iter().position(|&byte| byte == 0).unwrap_or(sun_path.len());
+    match std::str::from_utf8(&sun_path[..end]) {
+        Ok(path) => Ok((unixaddr, Some(UnixAddrName::Path(path.to_string())))),
+        Err(_) => Err(syscall_error(Errno::EINVAL, syscallname, "socket path is not valid")),
+    }
 }
 
 impl Cage {
     /* 
      *   Mapping a new virtual fd and kernel fd that libc::socket returned
      *   Then return virtual fd
+     *
+     *   AF_UNIX sockets are created in memory (FDKIND_IMSOCK) so cages talking to each 
+     *   other never go through host sockets
+     *
+     *   SOCK_CLOEXEC is kept in the virtual fd so that exec closes it, SOCK_NONBLOCK goes
+     *   to the kernel along with the type
      */
     pub fn socket_syscall(&self, domain: i32, socktype: i32, protocol: i32) -> i32 {
+        if domain == libc::AF_UNIX {
+            return self.socket_imsock(socktype, protocol);
+        }
+
         let kernel_fd = unsafe { libc::socket(domain, socktype, protocol) };
         /*
             get_unused_virtual_fd(cageid,realfd,is_cloexec,optionalinfo) -> Result<virtualfd, EMFILE>
@@ -53,7 +216,7 @@ impl Cage {
             return handle_errno(errno, "socket");
         }
 
-        return fdtables::get_unused_virtual_fd(self.cageid, FDKIND_KERNEL, kernel_fd as u64, false, 0).unwrap() as i32;
+        return self.install_kernel_fd(kernel_fd, socktype & libc::SOCK_CLOEXEC != 0, "socket");
     }
 
     /* 
@@ -69,6 +232,25 @@ impl Cage {
             return syscall_error(Errno::EBADF, "bind", "Bad File Descriptor");
         }
         let vfd = wrappedvfd.unwrap();
+        if vfd.fdkind == FDKIND_IMSOCK {
+            return self.bind_imsock(vfd, addr);
+        }
+        if vfd.fdkind != FDKIND_KERNEL {
+            return syscall_error(Errno::ENOTSOCK, "bind", "file descriptor is not a socket");
+        }
+
+        // in a network namespace the host socket binds the host port picked for the cage port
+        let nsid = self.netns();
+        let innetns = nsid != interface::HOST_NETNS && !matches!(addr, GenSockaddr::Unix(_));
+        let socktype = if innetns { kernel_socktype(vfd.underfd) } else { 0 };
+        let mut hostaddr = *addr;
+        if innetns {
+            match interface::netns_reserve_port(nsid, vfd.underfd, socktype, u16::from_be(addr.port())) {
+                Ok(hostport) => hostaddr.set_port(hostport.to_be()),
+                Err(errno) => return errno,
+            }
+        }
+        let addr = &hostaddr;
 
         let mut new_addr = SockaddrUnix::default();
 
@@ -82,9 +264,9 @@ impl Cage {
                 size_of::<SockaddrV4>(),
             ),
             GenSockaddr::Unix(addrrefu) => {
-                // Convert sun_path to LIND_ROOT path
+                // Convert sun_path to a path under the cage's root
                 let original_path = unsafe { CStr::from_ptr(addrrefu.sun_path.as_ptr() as *const i8).to_str().unwrap() };
-                let lind_path = format!("{}{}", LIND_ROOT, &original_path[..]); // Skip the initial '/' in original path
+                let lind_path = hostpath(original_path, self);
 
                 // Ensure the length of lind_path does not exceed sun_path capacity
                 if lind_path.len() >= addrrefu.sun_path.len() {
@@ -118,8 +300,12 @@ impl Cage {
         let ret = unsafe { libc::bind(vfd.underfd as i32, finalsockaddr, addrlen as u32) };
         if ret < 0 {
             let errno = get_errno();
+            interface::netns_release(vfd.underfd);
             return handle_errno(errno, "bind");
         }
+        if innetns {
+            interface::netns_bound(nsid, vfd.underfd, socktype, kernel_local_port(vfd.underfd));
+        }
         ret
     }
 
@@ -133,6 +319,18 @@ impl Cage {
             return syscall_error(Errno::EBADF, "connect", "Bad File Descriptor");
         }
         let vfd = wrappedvfd.unwrap();
+        if vfd.fdkind == FDKIND_IMSOCK {
+            return self.connect_imsock(vfd, addr);
+        }
+        if vfd.fdkind != FDKIND_KERNEL {
+            return syscall_error(Errno::ENOTSOCK, "connect", "file descriptor is not a socket");
+        }
+
+        let hostaddr = match self.netns_host_addr(vfd.underfd, addr) {
+            Some(hostaddr) => hostaddr,
+            None => return syscall_error(Errno::ECONNREFUSED, "connect", "nothing is bound to the port in the network namespace"),
+        };
+        let addr = &hostaddr;
 
         let mut new_addr = SockaddrUnix::default();
 
@@ -146,9 +344,9 @@ impl Cage {
                 size_of::<SockaddrV4>(),
             ),
             GenSockaddr::Unix(addrrefu) => {
-                // Convert sun_path to LIND_ROOT path
+                // Convert sun_path to a path under the cage's root
                 let original_path = unsafe { CStr::from_ptr(addrrefu.sun_path.as_ptr() as *const i8).to_str().unwrap() };
-                let lind_path = format!("{}{}", LIND_ROOT, &original_path[..]); // Skip the initial '/' in original path
+                let lind_path = hostpath(original_path, self);
 
                 // Ensure the length of lind_path does not exceed sun_path capacity
                 if lind_path.len() >= addrrefu.sun_path.len() {
@@ -203,6 +401,24 @@ impl Cage {
             return syscall_error(Errno::EBADF, "sendto", "Bad File Descriptor");
         }
         let vfd = wrappedvfd.unwrap();
+        if vfd.fdkind == FDKIND_IMSOCK {
+            return self.send_imsock(vfd, buf, buflen, flags, Some(dest_addr), None, "sendto");
+        }
+        if vfd.fdkind != FDKIND_KERNEL {
+            return syscall_error(Errno::ENOTSOCK, "sendto", "file descriptor is not a socket");
+        }
+
+        // like a datagram to a closed port, one to an unbound port of the namespace is dropped,
+        // while a connected stream socket ignores dest_addr anyway
+        let hostaddr = if self.netns() == interface::HOST_NETNS || kernel_socktype(vfd.underfd) == libc::SOCK_STREAM {
+            *dest_addr
+        } else {
+            match self.netns_host_addr(vfd.underfd, dest_addr) {
+                Some(hostaddr) => hostaddr,
+                None => return buflen as i32,
+            }
+        };
+        let dest_addr = &hostaddr;
 
         let (finalsockaddr, addrlen) = match dest_addr {
             GenSockaddr::V6(addrref6) => (
@@ -214,9 +430,9 @@ impl Cage {
                 size_of::<SockaddrV4>(),
             ),
             GenSockaddr::Unix(addrrefu) => {
-                // Convert sun_path to LIND_ROOT path
+                // Convert sun_path to a path under the cage's root
                 let original_path = unsafe { CStr::from_ptr(addrrefu.sun_path.as_ptr() as *const i8).to_str().unwrap() };
-                let lind_path = format!("{}{}", LIND_ROOT, &original_path[..]); // Skip the initial '/' in original path
+                let lind_path = hostpath(original_path, self);
 
                 // Ensure the length of lind_path does not exceed sun_path capacity
                 if lind_path.len() >= addrrefu.sun_path.len() {
@@ -281,6 +497,12 @@ impl Cage {
             return syscall_error(Errno::EBADF, "send", "Bad File Descriptor");
         }
         let vfd = wrappedvfd.unwrap();
+        if vfd.fdkind == FDKIND_IMSOCK {
+            return self.send_imsock(vfd, buf, buflen, flags, None, None, "send");
+        }
+        if vfd.fdkind != FDKIND_KERNEL {
+            return syscall_error(Errno::ENOTSOCK, "send", "file descriptor is not a socket");
+        }
 
         let ret = unsafe { libc::send(vfd.underfd as i32, buf as *const c_void, buflen, flags) as i32};
         if ret < 0 {
@@ -311,6 +533,12 @@ impl Cage {
             return syscall_error(Errno::EBADF, "recvfrom", "Bad File Descriptor");
         }
         let vfd = wrappedvfd.unwrap();
+        if vfd.fdkind == FDKIND_IMSOCK {
+            return self.recv_imsock(vfd, buf, buflen, flags, addr.as_deref_mut(), None);
+        }
+        if vfd.fdkind != FDKIND_KERNEL {
+            return syscall_error(Errno::ENOTSOCK, "recvfrom", "file descriptor is not a socket");
+        }
 
         let (finalsockaddr, mut addrlen) = match addr {
             Some(GenSockaddr::V6(ref mut addrref6)) => (
@@ -334,6 +562,9 @@ impl Cage {
             let errno = get_errno();
             return handle_errno(errno, "recvfrom");
         }
+        if let Some(addr) = addr.as_deref_mut() {
+            self.netns_cage_addr(vfd.underfd, addr, false);
+        }
         ret
     }
 
@@ -351,6 +582,12 @@ impl Cage {
             return syscall_error(Errno::EBADF, "recv", "Bad File Descriptor");
         }
         let vfd = wrappedvfd.unwrap();
+        if vfd.fdkind == FDKIND_IMSOCK {
+            return self.recv_imsock(vfd, buf, len, flags, None, None);
+        }
+        if vfd.fdkind != FDKIND_KERNEL {
+            return syscall_error(Errno::ENOTSOCK, "recv", "file descriptor is not a socket");
+        }
 
         let ret = unsafe { libc::recv(vfd.underfd as i32, buf as *mut c_void, len, flags) as i32 };
         if ret < 0 {
@@ -360,6 +597,108 @@ impl Cage {
         ret
     }
 
+    /*
+     *   sendmsg() sends the data of the iovecs in msg as one message, to msg_name if it is set.
+     *   On in-memory AF_UNIX sockets the control messages are handled here: the fds of an
+     *   SCM_RIGHTS message are held until the receiving cage installs them in its own fdtable,
+     *   and SCM_CREDENTIALS states the sender's cage id and uid/gid, which have to be its own
+     *   unless it is root. Kernel sockets hand msg to the kernel as it is, except for a
+     *   loopback msg_name, which names a port of the cage's network namespace like in sendto.
+     *   sendmsg() will return the number of bytes sent, and -1 when fail
+     */
+    pub fn sendmsg_syscall(&self, virtual_fd: i32, msg: *const libc::msghdr, flags: i32) -> i32 {
+        let wrappedvfd = fdtables::translate_virtual_fd(self.cageid, virtual_fd as u64);
+        if wrappedvfd.is_err() {
+            return syscall_error(Errno::EBADF, "sendmsg", "Bad File Descriptor");
+        }
+        let vfd = wrappedvfd.unwrap();
+        if msg.is_null() {
+            return syscall_error(Errno::EFAULT, "sendmsg", "msg is not a valid address");
+        }
+        if vfd.fdkind == FDKIND_IMSOCK {
+            return self.sendmsg_imsock(vfd, unsafe { &*msg }, flags);
+        }
+        if vfd.fdkind != FDKIND_KERNEL {
+            return syscall_error(Errno::ENOTSOCK, "sendmsg", "file descriptor is not a socket");
+        }
+
+        // like sendto, a datagram to an unbound port of the namespace is dropped
+        let msgref = unsafe { &*msg };
+        let mut hostmsg = *msgref;
+        let mut hostaddr = None;
+        if self.netns() != interface::HOST_NETNS && kernel_socktype(vfd.underfd) != libc::SOCK_STREAM {
+            if let Some(addr) = msg_inet_addr(msgref.msg_name, msgref.msg_namelen) {
+                match self.netns_host_addr(vfd.underfd, &addr) {
+                    Some(addr) => hostaddr = Some(addr),
+                    None => {
+                        return match msghdr_iovecs(msgref, "sendmsg") {
+                            Ok(iovs) => iovs.iter().map(|iov| iov.iov_len).sum::<usize>() as i32,
+                            Err(e) => e,
+                        };
+                    }
+                }
+            }
+        }
+        match hostaddr {
+            Some(GenSockaddr::V4(ref mut addr)) => hostmsg.msg_name = addr as *mut SockaddrV4 as *mut c_void,
+            Some(GenSockaddr::V6(ref mut addr)) => hostmsg.msg_name = addr as *mut SockaddrV6 as *mut c_void,
+            _ => {}
+        }
+
+        let ret = unsafe { libc::sendmsg(vfd.underfd as i32, &hostmsg, flags) as i32 };
+        if ret < 0 {
+            let errno = get_errno();
+            return handle_errno(errno, "sendmsg");
+        }
+        ret
+    }
+
+    /*
+     *   recvmsg() scatters one message into the iovecs of msg and stores its sender in msg_name.
+     *   On in-memory AF_UNIX sockets the fds of an SCM_RIGHTS message become new virtual fds of
+     *   this cage, close-on-exec with MSG_CMSG_CLOEXEC, and with SO_PASSCRED set SCM_CREDENTIALS
+     *   reports the sender's cage id and uid/gid. Fds that don't fit in msg_control are closed
+     *   and MSG_CTRUNC is set, as in Linux. Kernel sockets hand msg to the kernel as it is, and
+     *   a loopback sender in msg_name is given the port of the cage's namespace like in recvfrom.
+     *   recvmsg() will return
+     *       - Success: the length of the message in bytes
+     *       - No messages are available to be received and the
+     *           peer has performed an orderly shutdown: 0
+     *       - Fail: -1
+     */
+    pub fn recvmsg_syscall(&self, virtual_fd: i32, msg: *mut libc::msghdr, flags: i32) -> i32 {
+        let wrappedvfd = fdtables::translate_virtual_fd(self.cageid, virtual_fd as u64);
+        if wrappedvfd.is_err() {
+            return syscall_error(Errno::EBADF, "recvmsg", "Bad File Descriptor");
+        }
+        let vfd = wrappedvfd.unwrap();
+        if msg.is_null() {
+            return syscall_error(Errno::EFAULT, "recvmsg", "msg is not a valid address");
+        }
+        if vfd.fdkind == FDKIND_IMSOCK {
+            return self.recvmsg_imsock(vfd, unsafe { &mut *msg }, flags);
+        }
+        if vfd.fdkind != FDKIND_KERNEL {
+            return syscall_error(Errno::ENOTSOCK, "recvmsg", "file descriptor is not a socket");
+        }
+
+        let namecapacity = unsafe { (*msg).msg_namelen };
+        let ret = unsafe { libc::recvmsg(vfd.underfd as i32, msg, flags) as i32 };
+        if ret < 0 {
+            let errno = get_errno();
+            return handle_errno(errno, "recvmsg");
+        }
+        let msgref = unsafe { &mut *msg };
+        // a sender address cut short by msg_namelen is left as the kernel wrote it
+        if msgref.msg_namelen <= namecapacity {
+            if let Some(mut addr) = msg_inet_addr(msgref.msg_name, msgref.msg_namelen) {
+                self.netns_cage_addr(vfd.underfd, &mut addr, false);
+                put_msg_inet_addr(msgref.msg_name, &addr);
+            }
+        }
+        ret
+    }
+
     /*  
      *   Get the kernel fd with provided virtual fd first
      *   listen() will return 0 when success and -1 when fail
@@ -370,6 +709,15 @@ impl Cage {
             return syscall_error(Errno::EBADF, "listen", "Bad File Descriptor");
         }
         let vfd = wrappedvfd.unwrap();
+        if vfd.fdkind == FDKIND_IMSOCK {
+            let sock = interface::get_unix_socket(vfd.underfd).unwrap();
+            // like Linux, whoever connects from now on sees the credentials of the listening cage
+            sock.set_cred(self.unix_creds());
+            return sock.listen(backlog);
+        }
+        if vfd.fdkind != FDKIND_KERNEL {
+            return syscall_error(Errno::ENOTSOCK, "listen", "file descriptor is not a socket");
+        }
 
         let ret = unsafe { libc::listen(vfd.underfd as i32, backlog) };
         if ret < 0 {
@@ -389,6 +737,12 @@ impl Cage {
             return syscall_error(Errno::EBADF, "shutdown", "Bad File Descriptor");
         }
         let vfd = wrappedvfd.unwrap();
+        if vfd.fdkind == FDKIND_IMSOCK {
+            return interface::get_unix_socket(vfd.underfd).unwrap().shutdown(how);
+        }
+        if vfd.fdkind != FDKIND_KERNEL {
+            return syscall_error(Errno::ENOTSOCK, "shutdown", "file descriptor is not a socket");
+        }
 
         let ret = unsafe { libc::shutdown(vfd.underfd as i32, how) };
 
@@ -414,12 +768,35 @@ impl Cage {
         &self,
         virtual_fd: i32,
         addr: &mut Option<&mut GenSockaddr>,
+    ) -> i32 {
+        self.accept4_syscall(virtual_fd, addr, 0)
+    }
+
+    /*
+    *   accept4() is accept() with SOCK_NONBLOCK and SOCK_CLOEXEC flags for the new socket.
+    *   SOCK_CLOEXEC is kept in the virtual fd so that exec closes it, SOCK_NONBLOCK goes
+    *   to the kernel, or into the fd's status flags for an in-memory socket
+    */
+    pub fn accept4_syscall(
+        &self,
+        virtual_fd: i32,
+        addr: &mut Option<&mut GenSockaddr>,
+        flags: i32,
     ) -> i32 {
         let wrappedvfd = fdtables::translate_virtual_fd(self.cageid, virtual_fd as u64);
         if wrappedvfd.is_err() {
             return syscall_error(Errno::EBADF, "accept", "Bad File Descriptor");
         }
         let vfd = wrappedvfd.unwrap();
+        if flags & !(libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC) != 0 {
+            return syscall_error(Errno::EINVAL, "accept", "flags is not valid");
+        }
+        if vfd.fdkind == FDKIND_IMSOCK {
+            return self.accept_imsock(vfd, addr.as_deref_mut(), flags);
+        }
+        if vfd.fdkind != FDKIND_KERNEL {
+            return syscall_error(Errno::ENOTSOCK, "accept", "file descriptor is not a socket");
+        }
 
         let (finalsockaddr, mut addrlen) = match addr {
             Some(GenSockaddr::V6(ref mut addrref6)) => (
@@ -437,7 +814,7 @@ impl Cage {
             None => (std::ptr::null::<libc::sockaddr>() as *mut libc::sockaddr, 0),
         };
 
-        let ret_kernelfd = unsafe { libc::accept(vfd.underfd as i32, finalsockaddr, &mut addrlen as *mut u32) };
+        let ret_kernelfd = unsafe { libc::accept4(vfd.underfd as i32, finalsockaddr, &mut addrlen as *mut u32, flags) };
 
         if ret_kernelfd < 0 {
             let errno = get_errno();
@@ -450,13 +827,16 @@ impl Cage {
 
         if let Some(sockaddr) = addr {
             if let GenSockaddr::Unix(ref mut sockaddr_unix) = sockaddr{
+                let lind_root = self.lindroot.read().to_str().unwrap().to_string();
                 unsafe {
-                    if std::slice::from_raw_parts(sockaddr_unix.sun_path.as_ptr() as *const u8, LIND_ROOT.len()) == LIND_ROOT.as_bytes() {
-                        // Move ptr to exclue LIND_ROOT
-                        let new_path_ptr = sockaddr_unix.sun_path.as_ptr().add(LIND_ROOT.len());
+                    // a root longer than sun_path can't be a prefix of it
+                    if lind_root.len() <= sockaddr_unix.sun_path.len()
+                        && std::slice::from_raw_parts(sockaddr_unix.sun_path.as_ptr() as *const u8, lind_root.len()) == lind_root.as_bytes() {
+                        // Move ptr to exclue the cage's root
+                        let new_path_ptr = sockaddr_unix.sun_path.as_ptr().add(lind_root.len());
                 
                         // sun_path in RawPOSIX will always be 108
-                        let new_path_len = 108 - LIND_ROOT.len();
+                        let new_path_len = sockaddr_unix.sun_path.len() - lind_root.len();
                 
                         let mut temp_path = vec![0u8; sockaddr_unix.sun_path.len()];
                 
@@ -469,12 +849,12 @@ impl Cage {
                         std::ptr::copy_nonoverlapping(temp_path.as_ptr(), sockaddr_unix.sun_path.as_mut_ptr(), new_path_len);
                     }
                 }
+            } else {
+                self.netns_cage_addr(vfd.underfd, sockaddr, false);
             }
         }
 
-        let ret_virtualfd = fdtables::get_unused_virtual_fd(self.cageid, FDKIND_KERNEL, ret_kernelfd as u64, false, 0).unwrap();
-        
-        ret_virtualfd as i32
+        self.install_kernel_fd(ret_kernelfd, flags & libc::SOCK_CLOEXEC != 0, "accept")
     }
     
     /* 
@@ -482,8 +862,8 @@ impl Cage {
     *   Specifically, kernel fds are passed to the underlying libc select, while impipe and imsock fds would be processed by the 
     *   in-memory system. Afterward, the results are combined and consolidated accordingly.
     *
-    *   (Note: Currently, only kernel fds are supported. The implementation for in-memory pipes is commented out and will require 
-    *   further integration and testing once in-memory pipe support is added.)
+    *   (Note: While any in-memory pipe or socket is being watched, the libc select only blocks for short slices so they 
+    *   can be rechecked in between.)
     *
     *   select() will return:
     *       - the total number of bits that are set in readfds, writefds, errorfds
@@ -498,17 +878,8 @@ impl Cage {
         mut errorfds: Option<&mut fd_set>,
         rposix_timeout: Option<RustDuration>,
     ) -> i32 {
-        let mut timeout;
-        if rposix_timeout.is_none() {
-            timeout = libc::timeval { 
-                tv_sec: 0, 
-                tv_usec: 0,
-            };
-        } else {
-            timeout = libc::timeval { 
-                tv_sec: rposix_timeout.unwrap().as_secs() as i64, 
-                tv_usec: rposix_timeout.unwrap().subsec_micros() as i64,
-            };
+        if nfds < 0 {
+            return syscall_error(Errno::EINVAL, "select", "nfds is negative");
         }
 
         let orfds = readfds.as_mut().map(|fds| &mut **fds);
@@ -519,14 +890,26 @@ impl Cage {
         fdkindset.insert(FDKIND_KERNEL);
 
         let (selectbittables, unparsedtables, mappingtable) 
-            = fdtables::prepare_bitmasks_for_select(
+            = match fdtables::prepare_bitmasks_for_select(
                 self.cageid, 
                 nfds as u64, 
                 orfds.copied(), 
                 owfds.copied(), 
                 oefds.copied(), 
-                &fdkindset)
-                .unwrap();
+                &fdkindset) {
+                Ok(tables) => tables,
+                Err(_) => return syscall_error(Errno::EINVAL, "select", "nfds exceeds the open files limit"),
+            };
+
+        // Fds that are not handed to the kernel are checked through their readiness handler. 
+        // None of them have exceptional conditions, so only the read and write sets matter
+        let inmem_fds = |table: &HashMap<u32, HashSet<FDTableEntry>>| -> Vec<FDTableEntry> {
+            table.values().flat_map(|entries| entries.iter().copied()).collect()
+        };
+        let inmem_reads = inmem_fds(&unparsedtables[0]);
+        let inmem_writes = inmem_fds(&unparsedtables[1]);
+        let has_inmem = !inmem_reads.is_empty() || !inmem_writes.is_empty();
+        let has_epoll = inmem_reads.iter().chain(inmem_writes.iter()).any(is_epoll_fd);
         
         // ------ libc select() ------
         // In select, each fd_set is allowed to contain empty values, as it’s possible for the user to input a mixture of pure 
@@ -534,86 +917,120 @@ impl Cage {
         // handle both types of descriptors properly. The goal here is to ensure that each fd_set (read, write, error) is correctly 
         // initialized. To handle cases where selectbittables does not contain an entry at the expected index or where it doesn’t 
         // include a FDKIND_KERNEL entry, the code assigns a default value with an initialized fd_set and an nfd of 0.
-        let (readnfd, mut real_readfds) = selectbittables
+        let (readnfd, kernel_readfds) = selectbittables
             .get(0)
             .and_then(|table| table.get(&FDKIND_KERNEL).cloned())
             .unwrap_or((0, fdtables::_init_fd_set()));
-        let (writenfd, mut real_writefds) = selectbittables
+        let (writenfd, kernel_writefds) = selectbittables
             .get(1)
             .and_then(|table| table.get(&FDKIND_KERNEL).cloned())
             .unwrap_or((0, fdtables::_init_fd_set()));
-        let (errornfd, mut real_errorfds) = selectbittables
+        let (errornfd, kernel_errorfds) = selectbittables
             .get(2)
             .and_then(|table| table.get(&FDKIND_KERNEL).cloned())
             .unwrap_or((0, fdtables::_init_fd_set()));
         
-        let mut realnewnfds = readnfd.max(writenfd).max(errornfd);
+        let realnewnfds = readnfd.max(writenfd).max(errornfd);
+
+        let mut wait = ReadinessWait::new(rposix_timeout, has_inmem, realnewnfds > 0 || has_epoll);
+        loop {
+            wait.begin_round();
+            let mut unreal_read = HashSet::new();
+            let mut unreal_write = HashSet::new();
+            for entry in &inmem_reads {
+                if virtual_poll_events(entry, libc::POLLIN) & (libc::POLLIN | libc::POLLHUP | libc::POLLERR) != 0 {
+                    unreal_read.insert(*mappingtable.get(&(entry.fdkind, entry.underfd)).unwrap());
+                }
+            }
+            for entry in &inmem_writes {
+                if virtual_poll_events(entry, libc::POLLOUT) & (libc::POLLOUT | libc::POLLERR) != 0 {
+                    unreal_write.insert(*mappingtable.get(&(entry.fdkind, entry.underfd)).unwrap());
+                }
+            }
 
+            // the kernel sets are consumed by select, so every round starts from a fresh copy
+            let mut real_readfds = kernel_readfds;
+            let mut real_writefds = kernel_writefds;
+            let mut real_errorfds = kernel_errorfds;
+
+            let kernel_wait = wait.kernel_timeout(!unreal_read.is_empty() || !unreal_write.is_empty());
+            let mut timeout = kernel_wait.map(|dur| libc::timeval {
+                tv_sec: dur.as_secs() as i64,
+                tv_usec: dur.subsec_micros() as i64,
+            });
+            let timeoutptr = match timeout.as_mut() {
+                Some(timeout) => timeout as *mut timeval,
+                None => ptr::null_mut(),
+            };
 
-        // Ensured that null_mut is used if the Option is None for fd_set parameters.
-        let ret = unsafe { 
-            libc::select(
-                realnewnfds as i32, 
-                &mut real_readfds as *mut _,
-                &mut real_writefds as *mut _,
-                &mut real_errorfds as *mut _,
-                &mut timeout as *mut timeval)
-        };
+            // Ensured that null_mut is used if the Option is None for fd_set parameters.
+            let ret = unsafe { 
+                libc::select(
+                    realnewnfds as i32, 
+                    &mut real_readfds as *mut _,
+                    &mut real_writefds as *mut _,
+                    &mut real_errorfds as *mut _,
+                    timeoutptr)
+            };
 
-        if ret < 0 {
-            let errno = get_errno();
-            return handle_errno(errno, "select");
-        }
+            if ret < 0 {
+                let errno = get_errno();
+                return handle_errno(errno, "select");
+            }
 
-        let mut unreal_read = HashSet::new();
-        let mut unreal_write = HashSet::new();
+            if wait.keep_waiting(ret as usize + unreal_read.len() + unreal_write.len()) {
+                continue;
+            }
 
-        // Revert result
-        let (read_flags, read_result) = fdtables::get_one_virtual_bitmask_from_select_result(
-            FDKIND_KERNEL, 
-            realnewnfds as u64, 
-            Some(real_readfds), 
-            unreal_read, 
-            None, 
-            &mappingtable
-        );
-    
-        if let Some(readfds) = readfds.as_mut() {
-            **readfds = read_result.unwrap();
-        }
-    
-        let (write_flags, write_result) = fdtables::get_one_virtual_bitmask_from_select_result(
-            FDKIND_KERNEL, 
-            realnewnfds as u64, 
-            Some(real_writefds), 
-            unreal_write, 
-            None, 
-            &mappingtable
-        );
-    
-        if let Some(writefds) = writefds.as_mut() {
-            **writefds = write_result.unwrap();
-        }
-    
-        let (error_flags, error_result) = fdtables::get_one_virtual_bitmask_from_select_result(
-            FDKIND_KERNEL, 
-            realnewnfds as u64, 
-            Some(real_errorfds), 
-            HashSet::new(), // Assuming there are no unreal errorsets
-            None, 
-            &mappingtable
-        );
-    
-        if let Some(errorfds) = errorfds.as_mut() {
-            **errorfds = error_result.unwrap();
-        }
+            // Revert result
+            let (read_flags, read_result) = fdtables::get_one_virtual_bitmask_from_select_result(
+                FDKIND_KERNEL, 
+                realnewnfds as u64, 
+                Some(real_readfds), 
+                unreal_read, 
+                None, 
+                &mappingtable
+            );
+        
+            if let Some(readfds) = readfds.as_mut() {
+                **readfds = read_result.unwrap();
+            }
+        
+            let (write_flags, write_result) = fdtables::get_one_virtual_bitmask_from_select_result(
+                FDKIND_KERNEL, 
+                realnewnfds as u64, 
+                Some(real_writefds), 
+                unreal_write, 
+                None, 
+                &mappingtable
+            );
+        
+            if let Some(writefds) = writefds.as_mut() {
+                **writefds = write_result.unwrap();
+            }
+        
+            let (error_flags, error_result) = fdtables::get_one_virtual_bitmask_from_select_result(
+                FDKIND_KERNEL, 
+                realnewnfds as u64, 
+                Some(real_errorfds), 
+                HashSet::new(), // Assuming there are no unreal errorsets
+                None, 
+                &mappingtable
+            );
+        
+            if let Some(errorfds) = errorfds.as_mut() {
+                **errorfds = error_result.unwrap();
+            }
 
-        // The total number of descriptors ready
-        (read_flags + write_flags + error_flags) as i32
+            // The total number of descriptors ready
+            return (read_flags + write_flags + error_flags) as i32;
+        }
     }
 
     /*  
      *   Get the kernel fd with provided virtual fd first
+     *   optlen holds the size of the optval buffer and is set to the size of the value
+     *   stored there, values that don't fit are cut short as in Linux
      *   getsockopt() will return 0 when success and -1 when fail
      */
     pub fn getsockopt_syscall(
@@ -621,17 +1038,33 @@ impl Cage {
         virtual_fd: i32,
         level: i32,
         optname: i32,
-        optval: &mut i32,
+        optval: *mut u8,
+        optlen: &mut u32,
     ) -> i32 {
         let wrappedvfd = fdtables::translate_virtual_fd(self.cageid, virtual_fd as u64);
         if wrappedvfd.is_err() {
             return syscall_error(Errno::EBADF, "getsockopt", "Bad File Descriptor");
         }
         let vfd = wrappedvfd.unwrap();
+        if optval.is_null() && *optlen > 0 {
+            return syscall_error(Errno::EFAULT, "getsockopt", "optval is not a valid address");
+        }
+        if vfd.fdkind == FDKIND_IMSOCK {
+            return match interface::get_unix_socket(vfd.underfd).unwrap().getsockopt(level, optname) {
+                Ok(value) => {
+                    let copylen = interface::rust_min(*optlen as usize, value.len());
+                    unsafe { ptr::copy_nonoverlapping(value.as_ptr(), optval, copylen) };
+                    *optlen = copylen as u32;
+                    0
+                }
+                Err(errno) => errno,
+            };
+        }
+        if vfd.fdkind != FDKIND_KERNEL {
+            return syscall_error(Errno::ENOTSOCK, "getsockopt", "file descriptor is not a socket");
+        }
 
-        let mut optlen: socklen_t = 4;
-
-        let ret = unsafe { libc::getsockopt(vfd.underfd as i32, level, optname, optval as *mut c_int as *mut c_void, &mut optlen as *mut socklen_t) };
+        let ret = unsafe { libc::getsockopt(vfd.underfd as i32, level, optname, optval as *mut c_void, optlen as *mut socklen_t) };
         if ret < 0 {
             let errno = get_errno();
             return handle_errno(errno, "getsockopt");
@@ -642,6 +1075,7 @@ impl Cage {
 
     /*  
      *   Get the kernel fd with provided virtual fd first
+     *   optval points to optlen bytes laid out the way the host expects the option
      *   setsockopt() will return 0 when success and -1 when fail
      */
     pub fn setsockopt_syscall(
@@ -657,6 +1091,16 @@ impl Cage {
             return syscall_error(Errno::EBADF, "setsockopt", "Bad File Descriptor");
         }
         let vfd = wrappedvfd.unwrap();
+        if optval.is_null() && optlen > 0 {
+            return syscall_error(Errno::EFAULT, "setsockopt", "optval is not a valid address");
+        }
+        if vfd.fdkind == FDKIND_IMSOCK {
+            let value: &[u8] = if optlen == 0 { &[] } else { unsafe { std::slice::from_raw_parts(optval, optlen as usize) } };
+            return interface::get_unix_socket(vfd.underfd).unwrap().setsockopt(level, optname, value);
+        }
+        if vfd.fdkind != FDKIND_KERNEL {
+            return syscall_error(Errno::ENOTSOCK, "setsockopt", "file descriptor is not a socket");
+        }
 
         let ret = unsafe { 
             libc::setsockopt(vfd.underfd as i32, level, optname, optval as *mut c_void, optlen)
@@ -682,6 +1126,20 @@ impl Cage {
             return syscall_error(Errno::EBADF, "getpeername", "Bad File Descriptor");
         }
         let vfd = wrappedvfd.unwrap();
+        if vfd.fdkind == FDKIND_IMSOCK {
+            return match interface::get_unix_socket(vfd.underfd).unwrap().peername() {
+                Ok(peeraddr) => {
+                    if let Some(address) = address.as_deref_mut() {
+                        *address = GenSockaddr::Unix(peeraddr);
+                    }
+                    0
+                }
+                Err(errno) => errno,
+            };
+        }
+        if vfd.fdkind != FDKIND_KERNEL {
+            return syscall_error(Errno::ENOTSOCK, "getpeername", "file descriptor is not a socket");
+        }
         
         let (finalsockaddr, mut addrlen) = match address {
             Some(GenSockaddr::V6(ref mut addrref6)) => (
@@ -708,13 +1166,16 @@ impl Cage {
 
         if let Some(sockaddr) = address {
             if let GenSockaddr::Unix(ref mut sockaddr_unix) = sockaddr{
+                let lind_root = self.lindroot.read().to_str().unwrap().to_string();
                 unsafe {
-                    if std::slice::from_raw_parts(sockaddr_unix.sun_path.as_ptr() as *const u8, LIND_ROOT.len()) == LIND_ROOT.as_bytes() {
-                        // Move ptr to exclue LIND_ROOT
-                        let new_path_ptr = sockaddr_unix.sun_path.as_ptr().add(LIND_ROOT.len());
+                    // a root longer than sun_path can't be a prefix of it
+                    if lind_root.len() <= sockaddr_unix.sun_path.len()
+                        && std::slice::from_raw_parts(sockaddr_unix.sun_path.as_ptr() as *const u8, lind_root.len()) == lind_root.as_bytes() {
+                        // Move ptr to exclue the cage's root
+                        let new_path_ptr = sockaddr_unix.sun_path.as_ptr().add(lind_root.len());
                 
                         // sun_path in RawPOSIX will always be 108
-                        let new_path_len = 108 - LIND_ROOT.len();
+                        let new_path_len = sockaddr_unix.sun_path.len() - lind_root.len();
                 
                         let mut temp_path = vec![0u8; sockaddr_unix.sun_path.len()];
                 
@@ -727,6 +1188,8 @@ impl Cage {
                         std::ptr::copy_nonoverlapping(temp_path.as_ptr(), sockaddr_unix.sun_path.as_mut_ptr(), new_path_len);
                     }
                 }
+            } else {
+                self.netns_cage_addr(vfd.underfd, sockaddr, false);
             }
         }
 
@@ -747,6 +1210,15 @@ impl Cage {
             return syscall_error(Errno::EBADF, "getsockname", "Bad File Descriptor");
         }
         let vfd = wrappedvfd.unwrap();
+        if vfd.fdkind == FDKIND_IMSOCK {
+            if let Some(address) = address.as_deref_mut() {
+                *address = GenSockaddr::Unix(interface::get_unix_socket(vfd.underfd).unwrap().sockname());
+            }
+            return 0;
+        }
+        if vfd.fdkind != FDKIND_KERNEL {
+            return syscall_error(Errno::ENOTSOCK, "getsockname", "file descriptor is not a socket");
+        }
 
         let (finalsockaddr, mut _addrlen) = match address {
             Some(GenSockaddr::V6(ref mut addrref6)) => (
@@ -771,6 +1243,9 @@ impl Cage {
             let errno = get_errno();
             return handle_errno(errno, "getsockname");
         }
+        if let Some(address) = address.as_deref_mut() {
+            self.netns_cage_addr(vfd.underfd, address, true);
+        }
 
         ret
     }
@@ -808,57 +1283,70 @@ impl Cage {
         _nfds: u64,
         timeout: i32,
     ) -> i32 {
-
-        let mut virfdvec = HashSet::new();
-
-        for vpoll in &mut *virtual_fds {
-            let vfd = vpoll.fd as u64;
-            virfdvec.insert(vfd);
-        }
-
-        let (allhashmap, _mappingtable) = fdtables::convert_virtualfds_for_poll(self.cageid, virfdvec);
-
-        let mut libc_nfds = 0;
+        // Kernel fds are polled by the kernel, the others through their readiness handler. kernel_index
+        // remembers which PollStruct each libc pollfd was built from
         let mut libc_pollfds: Vec<pollfd> = Vec::new();
-        for (fd_kind, fdtuple) in allhashmap {
-            match fd_kind {
-                FDKIND_KERNEL => {
-                    for (virtfd, _entry) in fdtuple {
-                        if let Some(vpollstruct) = virtual_fds.iter().find(|&ps| ps.fd == virtfd as i32) {
-                            // Convert PollStruct to libc::pollfd
-                            let libcpollstruct = self.convert_to_libc_pollfd(vpollstruct);
-                            libc_pollfds.push(libcpollstruct);
-                            libc_nfds = libc_nfds + 1;
-                        }
+        let mut kernel_index: Vec<usize> = Vec::new();
+        let mut entries: Vec<Option<FDTableEntry>> = Vec::with_capacity(virtual_fds.len());
+        let mut has_inmem = false;
+        let mut has_epoll = false;
+        for (index, vpoll) in virtual_fds.iter().enumerate() {
+            // negative fds are ignored, as in Linux
+            let entry = if vpoll.fd < 0 {
+                None
+            } else {
+                fdtables::translate_virtual_fd(self.cageid, vpoll.fd as u64).ok()
+            };
+            if let Some(entry) = entry {
+                match entry.fdkind {
+                    FDKIND_KERNEL => {
+                        let mut libcpollstruct = self.convert_to_libc_pollfd(vpoll);
+                        libcpollstruct.fd = entry.underfd as i32;
+                        libc_pollfds.push(libcpollstruct);
+                        kernel_index.push(index);
                     }
-                    if libc_nfds != 0 {
-                        let ret = unsafe { libc::poll(libc_pollfds.as_mut_ptr(), libc_nfds as u64, timeout) };
-                        if ret < 0 {
-                            let errno = get_errno();
-                            return handle_errno(errno, "poll");
-                        }
-                        // Convert back to PollStruct
-                        for (i, libcpoll) in libc_pollfds.iter().enumerate() {
-                            if let Some(rposix_poll) = virtual_fds.get_mut(i) {
-                                rposix_poll.revents = libcpoll.revents;
-                            }
-                        }
-                        
-                        return ret;
+                    _ => {
+                        has_inmem = true;
+                        has_epoll |= is_epoll_fd(&entry);
                     }
-                },
-                _ => {
-                    /*TODO 
-                        Need to confirm the error num (we could add fdkind specific error..? eg: EFDKIND)
-                    */
-                    return syscall_error(Errno::EBADFD, "poll", "Invalid fdkind");
                 }
             }
+            entries.push(entry);
         }
 
-        // TODO: Return check...?
-        0
-        
+        let mut wait = ReadinessWait::new(poll_timeout_duration(timeout), has_inmem, !libc_pollfds.is_empty() || has_epoll);
+        loop {
+            wait.begin_round();
+            let mut virtual_ready = false;
+            for (vpoll, entry) in virtual_fds.iter_mut().zip(entries.iter()) {
+                vpoll.revents = match entry {
+                    Some(entry) if entry.fdkind == FDKIND_KERNEL => 0,
+                    Some(entry) => virtual_poll_events(entry, vpoll.events),
+                    None if vpoll.fd >= 0 => libc::POLLNVAL,
+                    None => 0,
+                };
+                virtual_ready |= vpoll.revents != 0;
+            }
+
+            let kernel_timeout = duration_to_poll_timeout(wait.kernel_timeout(virtual_ready));
+            for libcpoll in libc_pollfds.iter_mut() {
+                libcpoll.revents = 0;
+            }
+            let ret = unsafe { libc::poll(libc_pollfds.as_mut_ptr(), libc_pollfds.len() as u64, kernel_timeout) };
+            if ret < 0 {
+                let errno = get_errno();
+                return handle_errno(errno, "poll");
+            }
+            // Convert back to PollStruct
+            for (libcpoll, index) in libc_pollfds.iter().zip(kernel_index.iter()) {
+                virtual_fds[*index].revents = libcpoll.revents;
+            }
+
+            let nready = virtual_fds.iter().filter(|vpoll| vpoll.revents != 0).count();
+            if !wait.keep_waiting(nready) {
+                return nready as i32;
+            }
+        }
     }
 
     /* POLL()
@@ -887,27 +1375,30 @@ impl Cage {
     *   Then the processing workflow in RawPOSIX is:
     *
     *   epoll_create:
-    *   When epoll_create is called, we use epoll_create_helper to create a virtual epfd.
-    *   Add this virtual epfd to the global mapping table.
+    *   fdtables::epoll_create_empty gives us a virtual epfd of kind FDT_KINDEPOLL. A kernel 
+    *   epoll instance is created next to it and attached as the FDKIND_KERNEL underfd, so 
+    *   dup/fork share the instance and it is closed along with the last epfd referring to it.
     *
     *   epoll_ctl:
-    *   (Use try_epoll_ctl to handle the association between the virtual epfd and the 
-    *   events with the file descriptors.) This step involves updating the global table 
-    *   with the appropriate mappings.
+    *   Every registration is recorded through fdtables::virtualize_epoll_ctl, keyed by fdkind 
+    *   and virtual fd, with the underfd kept in the event data. Kernel fds are additionally 
+    *   registered with the kernel instance, using the virtual fd as the event data.
     *
     *   epoll_wait:
-    *   When epoll_wait is called, you need to convert the virtual epfd to the real epfd.
-    *   Call libc::epoll_wait to perform the actual wait operation on the real epfd.
-    *   Convert the resulting real events back to the virtual events using the mapping in 
-    *   the global table.
+    *   The in-memory fds are checked through their readiness handlers, the kernel ones with 
+    *   libc::epoll_wait on the kernel instance, which already reports virtual fds.
+    *
+    *   An epoll fd may itself be registered with another epoll instance. fdtables refuses 
+    *   loops and nesting deeper than EP_MAX_NESTS with ELOOP, and epoll_poll_events reports 
+    *   the inner instance as readable while anything in it is ready.
     */
 
     /*  
-     *   Mapping a new virtual fd and kernel fd that libc::epoll_create returned
+     *   Creates a virtual epfd backed by a kernel epoll instance
      *   Then return virtual fd
      */
     pub fn epoll_create_syscall(&self, size: i32) -> i32 {
-        // Create the kernel instance
+        // Create the kernel instance, this also rejects a size that is not positive
         let kernel_fd = unsafe { libc::epoll_create(size) };
         
         if kernel_fd < 0 {
@@ -915,18 +1406,22 @@ impl Cage {
             return handle_errno(errno, "epoll_create");
         }
 
-        // Get the virtual epfd
-        let virtual_epfd = fdtables::get_unused_virtual_fd(self.cageid, FDKIND_KERNEL, kernel_fd as u64, false, 0).unwrap();
+        let virtual_epfd = match fdtables::epoll_create_empty(self.cageid, false) {
+            Ok(virtual_epfd) => virtual_epfd,
+            Err(e) => {
+                unsafe { libc::close(kernel_fd) };
+                return handle_errno(e as i32, "epoll_create");
+            }
+        };
+        fdtables::epoll_add_underfd(self.cageid, virtual_epfd, FDKIND_KERNEL, kernel_fd as u64).unwrap();
+        // an epoll fd is open for reading and writing, as in Linux
+        let _ = fdtables::set_perfdinfo(self.cageid, virtual_epfd, O_RDWR as u64);
 
-        // We don't need to update mapping table at now
-        // Return virtual epfd
         virtual_epfd as i32
-        
     }
 
     /*  
-    *   Translate before calling, and updating the glocal mapping tables according to 
-    *   the op. 
+    *   Translate before calling, kernel fds are registered with the kernel instance as well
     *   epoll_ctl() will return 0 when success and -1 when fail
     */
     pub fn epoll_ctl_syscall(
@@ -939,53 +1434,92 @@ impl Cage {
         let wrappedepfd = fdtables::translate_virtual_fd(self.cageid, virtual_epfd as u64);
         let wrappedvfd = fdtables::translate_virtual_fd(self.cageid, virtual_fd as u64);
         if wrappedvfd.is_err() || wrappedepfd.is_err() {
-            return syscall_error(Errno::EBADF, "epoll", "Bad File Descriptor");
+            return syscall_error(Errno::EBADF, "epoll_ctl", "Bad File Descriptor");
         }
 
         let vepfd = wrappedepfd.unwrap();
         let vfd = wrappedvfd.unwrap();
-        // EpollEvent conversion
-        let event = epollevent.events;
-        let mut epoll_event = epoll_event {
-            events: event,
-            u64: vfd.underfd as u64,
+        if vepfd.fdkind != fdtables::FDT_KINDEPOLL {
+            return syscall_error(Errno::EINVAL, "epoll_ctl", "epfd is not an epoll file descriptor");
+        }
+        if virtual_epfd == virtual_fd {
+            return syscall_error(Errno::EINVAL, "epoll_ctl", "fd is the same as epfd");
+        }
+
+        if op == libc::EPOLL_CTL_ADD {
+            self.epoll_drop_stale_registration(virtual_epfd, virtual_fd, &vfd);
+        }
+
+        let event = fdtables::epoll_event {
+            events: epollevent.events,
+            u64: vfd.underfd,
         };
+        let previous = self.epoll_registration(virtual_epfd, virtual_fd, vfd.fdkind);
+        if let Err(e) = fdtables::virtualize_epoll_ctl(self.cageid, virtual_epfd as u64, op, virtual_fd as u64, event.clone()) {
+            return handle_errno(e as i32, "epoll_ctl");
+        }
 
-        let ret = unsafe { libc::epoll_ctl(vepfd.underfd as i32, op, vfd.underfd as i32, &mut epoll_event) };
-        if ret < 0 {
-            let errno = get_errno();
-            return handle_errno(errno, "epoll_ctl");
+        if vfd.fdkind == FDKIND_KERNEL {
+            let kernel_epfd = self.epoll_kernel_fd(virtual_epfd);
+            let mut epoll_event = epoll_event {
+                events: epollevent.events,
+                u64: virtual_fd as u64,
+            };
+            let ret = unsafe { libc::epoll_ctl(kernel_epfd, op, vfd.underfd as i32, &mut epoll_event) };
+            if ret < 0 {
+                let errno = get_errno();
+                // the kernel refused, so put the registration back the way it was
+                let (undo_op, undo_event) = match (op, previous) {
+                    (libc::EPOLL_CTL_DEL, Some(previous)) => (libc::EPOLL_CTL_ADD, previous),
+                    (libc::EPOLL_CTL_MOD, Some(previous)) => (libc::EPOLL_CTL_MOD, previous),
+                    _ => (libc::EPOLL_CTL_DEL, event),
+                };
+                let _ = fdtables::virtualize_epoll_ctl(self.cageid, virtual_epfd as u64, undo_op, virtual_fd as u64, undo_event);
+                return handle_errno(errno, "epoll_ctl");
+            }
         }
+        // an epoll_wait on this instance rereads its registrations
+        interface::notify_readiness();
+        0
+    }
 
-        // Update the virtual list -- but we only handle the non-real fd case
-        //  try_epoll_ctl will directly return a real fd in libc case
-        //  - maybe we could create a new mapping table to handle the mapping relationship..?
-        //      ceate inside the fdtable interface? or we could handle inside rawposix..?
-        
-        // Update the mapping table for epoll
-        if op == libc::EPOLL_CTL_DEL {
-            let mut epollmapping = REAL_EPOLL_MAP.lock();
-            if let Some(fdmap) = epollmapping.get_mut(&(vepfd.underfd as u64)) {
-                if fdmap.remove(&(vfd.underfd as i32)).is_some() {
-                    if fdmap.is_empty() {
-                        epollmapping.remove(&(vepfd.underfd as u64));
-                    }
-                    return ret;
+    // the kernel epoll instance attached to an epfd by epoll_create
+    fn epoll_kernel_fd(&self, virtual_epfd: i32) -> i32 {
+        let underfds = fdtables::epoll_get_underfd_hashmap(self.cageid, virtual_epfd as u64).unwrap();
+        underfds[&FDKIND_KERNEL] as i32
+    }
+
+    // the registration of virtual_fd as an fd of this kind, if there is one
+    fn epoll_registration(&self, virtual_epfd: i32, virtual_fd: i32, fdkind: u32) -> Option<fdtables::epoll_event> {
+        let registrations = fdtables::get_virtual_epoll_wait_data(self.cageid, virtual_epfd as u64).ok()?;
+        registrations.get(&fdkind)?.get(&(virtual_fd as u64)).cloned()
+    }
+
+    // A registration goes away once the last fd referring to its file is closed. One made
+    // through an fd that was closed while a dup of it stays open is left behind, and is
+    // skipped (see epoll_registration_is_live) until the fd number is reused by another file.
+    fn epoll_drop_stale_registration(&self, virtual_epfd: i32, virtual_fd: i32, vfd: &FDTableEntry) {
+        if let Some(event) = self.epoll_registration(virtual_epfd, virtual_fd, vfd.fdkind) {
+            if event.u64 != vfd.underfd {
+                let _ = fdtables::virtualize_epoll_ctl(self.cageid, virtual_epfd as u64, libc::EPOLL_CTL_DEL, virtual_fd as u64, event.clone());
+                if vfd.fdkind == FDKIND_KERNEL {
+                    // the old host fd is still open through that dup, and still in the kernel instance
+                    let kernel_epfd = self.epoll_kernel_fd(virtual_epfd);
+                    unsafe { libc::epoll_ctl(kernel_epfd, libc::EPOLL_CTL_DEL, event.u64 as i32, std::ptr::null_mut()) };
                 }
             }
-        } else {
-            let mut epollmapping = REAL_EPOLL_MAP.lock();
-            epollmapping.entry(vepfd.underfd as u64).or_insert_with(HashMap::new).insert(vfd.underfd as i32, virtual_fd as u64);
-            return ret;
         }
+    }
 
-        // [TODO]
-        // should be op not support
-        -1
+    fn epoll_registration_is_live(&self, fdkind: u32, virtual_fd: u64, event: &fdtables::epoll_event) -> bool {
+        match fdtables::translate_virtual_fd(self.cageid, virtual_fd) {
+            Ok(entry) => entry.fdkind == fdkind && entry.underfd == event.u64,
+            Err(_) => false,
+        }
     }
 
     /*  
-     *   Get the kernel fd with provided virtual fd first, and then convert back to virtual
+     *   Check the in-memory registrations and wait on the kernel instance for the rest
      *   epoll_wait() will return:
      *       1. the number of file descriptors ready for the requested I/O
      *       2. 0, if none
@@ -1003,36 +1537,76 @@ impl Cage {
             return syscall_error(Errno::EBADF, "epoll_wait", "Bad File Descriptor");
         }
         let vepfd = wrappedepfd.unwrap();
+        if vepfd.fdkind != fdtables::FDT_KINDEPOLL {
+            return syscall_error(Errno::EINVAL, "epoll_wait", "epfd is not an epoll file descriptor");
+        }
+        if maxevents <= 0 {
+            return syscall_error(Errno::EINVAL, "epoll_wait", "maxevents is less than or equal to zero");
+        }
+        let kernel_epfd = self.epoll_kernel_fd(virtual_epfd);
         
-        let mut kernel_events: Vec<epoll_event> = Vec::with_capacity(maxevents as usize);
-
         // Should always be null value before we call libc::epoll_wait
-        kernel_events.push(
-            epoll_event {
-                events: 0,
-                u64: 0,
+        let mut kernel_events: Vec<epoll_event> = vec![epoll_event { events: 0, u64: 0 }; maxevents as usize];
+
+        let mut wait = ReadinessWait::new(poll_timeout_duration(timeout), false, false);
+        loop {
+            // Reread every round, so registrations another thread made meanwhile are picked up. 
+            // With nothing registered we sleep like for in-memory fds, epoll_ctl wakes us up
+            let registrations = fdtables::get_virtual_epoll_wait_data(self.cageid, virtual_epfd as u64).unwrap_or_default();
+            wait.has_kernel = registrations.contains_key(&FDKIND_KERNEL) || registrations.contains_key(&fdtables::FDT_KINDEPOLL);
+            wait.has_virtual = !registrations.contains_key(&FDKIND_KERNEL) || registrations.len() > 1;
+            wait.begin_round();
+
+            // level-triggered readiness of the in-memory fds registered with this instance
+            let mut nready = 0;
+            for (fdkind, fdmap) in registrations.iter().filter(|(fdkind, _)| **fdkind != FDKIND_KERNEL) {
+                for (virtualfd, event) in fdmap.iter() {
+                    if nready == maxevents as usize {
+                        break;
+                    }
+                    if !self.epoll_registration_is_live(*fdkind, *virtualfd, event) {
+                        continue;
+                    }
+                    let revents = inmem_epoll_revents(*fdkind, event);
+                    if revents != 0 {
+                        events[nready].fd = *virtualfd as i32;
+                        events[nready].events = revents;
+                        nready += 1;
+                    }
+                }
             }
-        );
 
-        let ret = unsafe { libc::epoll_wait(vepfd.underfd as i32, kernel_events.as_mut_ptr(), maxevents, timeout as i32) };
-        if ret < 0 {
-            let errno = get_errno();
-            return handle_errno(errno, "epoll_wait");
-        }
-
-        // Convert back to rustposix's data structure
-        // Loop over virtual_epollfd to find corresponding mapping relationship between kernel fd and virtual fd
-        for i in 0..ret as usize {
+            let kernel_timeout = duration_to_poll_timeout(wait.kernel_timeout(nready > 0));
+            let kernel_max = maxevents - nready as i32;
+            let ret = if kernel_max > 0 {
+                unsafe { libc::epoll_wait(kernel_epfd, kernel_events.as_mut_ptr(), kernel_max, kernel_timeout) }
+            } else {
+                0
+            };
+            if ret < 0 {
+                let errno = get_errno();
+                return handle_errno(errno, "epoll_wait");
+            }
 
-            let ret_kernelfd = kernel_events[i].u64;
-            let epollmapping = REAL_EPOLL_MAP.lock();
-            let ret_virtualfd = epollmapping.get(&(vepfd.underfd as u64)).and_then(|kernel_map| kernel_map.get(&(ret_kernelfd as i32)).copied());
+            // The kernel instance reports virtual fds already, drop the ones whose 
+            // registration belongs to an fd that has been closed since
+            let kernel_registrations = registrations.get(&FDKIND_KERNEL);
+            for kernel_event in &kernel_events[..ret as usize] {
+                let virtualfd = kernel_event.u64;
+                let live = kernel_registrations
+                    .and_then(|fdmap| fdmap.get(&virtualfd))
+                    .map_or(false, |event| self.epoll_registration_is_live(FDKIND_KERNEL, virtualfd, event));
+                if live {
+                    events[nready].fd = virtualfd as i32;
+                    events[nready].events = kernel_event.events;
+                    nready += 1;
+                }
+            }
 
-            events[i].fd = ret_virtualfd.unwrap() as i32;
-            events[i].events = kernel_events[i].events;
+            if !wait.keep_waiting(nready) {
+                return nready as i32;
+            }
         }
-
-        ret
     }
 
     /*  
@@ -1045,6 +1619,9 @@ impl Cage {
         protocol: i32,
         virtual_socket_vector: &mut SockPair,
     ) -> i32 {
+        if domain == libc::AF_UNIX {
+            return self.socketpair_imsock(type_, protocol, virtual_socket_vector);
+        }
 
         let mut kernel_socket_vector: [i32; 2] = [0, 0];
 
@@ -1056,18 +1633,29 @@ impl Cage {
 
         let ksv_1 = kernel_socket_vector[0];
         let ksv_2 = kernel_socket_vector[1];
-        let vsv_1 = fdtables::get_unused_virtual_fd(self.cageid, FDKIND_KERNEL, ksv_1 as u64, false, 0).unwrap();
-        let vsv_2 = fdtables::get_unused_virtual_fd(self.cageid, FDKIND_KERNEL, ksv_2 as u64, false, 0).unwrap();
-        virtual_socket_vector.sock1 = vsv_1 as i32;
-        virtual_socket_vector.sock2 = vsv_2 as i32;
+        let should_cloexec = type_ & libc::SOCK_CLOEXEC != 0;
+        let vsv_1 = self.install_kernel_fd(ksv_1, should_cloexec, "socketpair");
+        if vsv_1 < 0 {
+            unsafe { libc::close(ksv_2) };
+            return vsv_1;
+        }
+        let vsv_2 = self.install_kernel_fd(ksv_2, should_cloexec, "socketpair");
+        if vsv_2 < 0 {
+            let _ = fdtables::close_virtualfd(self.cageid, vsv_1 as u64);
+            return vsv_2;
+        }
+        virtual_socket_vector.sock1 = vsv_1;
+        virtual_socket_vector.sock2 = vsv_2;
         return 0;
     }
 
     /*
-    *   Get result back from libc::getifaddrs and fill the content of name field into a buf 
-    *   as rustposix, so that i don’t need to change the dispatcher interface
+    *   Get result back from libc::getifaddrs and collect the interface addresses the cage may
+    *   see, named as the interface policy says (see interface::set_interface_policy). Laying
+    *   them out in the cage's memory is up to the caller, see interface::copy_out_ifaddrs.
+    *   Addresses of families other than AF_INET, AF_INET6 and AF_PACKET are left out.
     */
-    pub fn getifaddrs_syscall(&self, buf: *mut u8, count: usize) -> i32 {
+    pub fn getifaddrs_syscall(&self, ifaddrs: &mut Vec<interface::IfaddrEntry>) -> i32 {
         let mut ifaddr: *mut ifaddrs = ptr::null_mut();
 
         unsafe {
@@ -1076,33 +1664,569 @@ impl Cage {
                 return handle_errno(errno, "getifaddrs");
             }
             let mut ifa = ifaddr;
-            let mut offset = 0;
             while !ifa.is_null() {
                 let ifa_ref = &*ifa;
-                let name_cstr = CStr::from_ptr(ifa_ref.ifa_name);
-                let name_bytes = name_cstr.to_bytes();
-
-                // Check if there's enough space in the buffer
-                if offset + name_bytes.len() + 1 > count {
-                    println!("Buffer size exceeded");
-                    freeifaddrs(ifaddr);
-                    return -1;
+                let hostname = CStr::from_ptr(ifa_ref.ifa_name).to_string_lossy();
+                if let Some(name) = interface::cage_interface_name(&hostname) {
+                    ifaddrs.push(interface::IfaddrEntry {
+                        name,
+                        flags: ifa_ref.ifa_flags,
+                        addr: sockaddr_bytes(ifa_ref.ifa_addr),
+                        netmask: sockaddr_bytes(ifa_ref.ifa_netmask),
+                        ifu_addr: sockaddr_bytes(ifa_ref.ifa_ifu),
+                    });
                 }
-
-                let name_vec = name_bytes.to_vec();
-                fill(buf.add(offset), name_vec.len(), &name_vec);
-
-                // Add a null terminator to separate names
-                *buf.add(offset + name_vec.len()) = 0;
-                offset += name_vec.len() + 1; // Move offset past the name and null terminator
-            
                 ifa = ifa_ref.ifa_next;
-            
             }
             freeifaddrs(ifaddr);
-            0
         }
+        0
+    }
+
+    /* NETNS
+    *   A cage in a network namespace (see interface::netns) sees the ports of its namespace
+    *   rather than the host's. Its kernel AF_INET/AF_INET6 sockets are bound to host ports,
+    *   and loopback addresses are translated between the two on the way in and out.
+    *
+    *   The host address to hand to the kernel for addr, the address of a peer. A loopback
+    *   address is only reachable in the namespace, so None is returned if nothing is bound
+    *   to its port there.
+    */
+    fn netns_host_addr(&self, hostfd: u64, addr: &GenSockaddr) -> Option<GenSockaddr> {
+        let nsid = self.netns();
+        if nsid == interface::HOST_NETNS || matches!(addr, GenSockaddr::Unix(_)) || !addr.addr().is_loopback() {
+            return Some(*addr);
+        }
+        let hostport = interface::netns_host_port(nsid, kernel_socktype(hostfd), u16::from_be(addr.port()))?;
+        let mut hostaddr = *addr;
+        hostaddr.set_port(hostport.to_be());
+        Some(hostaddr)
+    }
+
+    // Turns the host address of a socket into the one the cage knows it by, its own when
+    // local is set and that of a peer otherwise
+    fn netns_cage_addr(&self, hostfd: u64, addr: &mut GenSockaddr, local: bool) {
+        let nsid = self.netns();
+        if nsid == interface::HOST_NETNS || matches!(addr, GenSockaddr::Unix(_)) {
+            return;
+        }
+        if !local && !addr.addr().is_loopback() {
+            return;
+        }
+        if let Some(cageport) = interface::netns_cage_port(nsid, kernel_socktype(hostfd), u16::from_be(addr.port())) {
+            addr.set_port(cageport.to_be());
+        }
+    }
+
+    /* IMSOCK
+    *   AF_UNIX sockets are implemented in memory (see interface::unixsock). The fd's underfd
+    *   is the socket id and perfdinfo keeps its status flags, like in-memory pipes. Sockets
+    *   bound to a path still get a socket file in the cage's filesystem: the host inode of
+    *   that file is what names the socket, so connect has to be able to find and write to it.
+    */
+    fn socket_imsock(&self, socktype: i32, protocol: i32) -> i32 {
+        let basetype = socktype & !(libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC);
+        if basetype != libc::SOCK_STREAM && basetype != libc::SOCK_DGRAM {
+            return syscall_error(Errno::ESOCKTNOSUPPORT, "socket", "socket type is not supported for AF_UNIX");
+        }
+        if protocol != 0 && protocol != libc::PF_UNIX {
+            return syscall_error(Errno::EPROTONOSUPPORT, "socket", "protocol is not supported for AF_UNIX");
+        }
+
+        let sock = interface::UnixSocket::new(basetype);
+        sock.set_cred(self.unix_creds());
+        interface::register_unix_socket(sock.clone());
+        let perfdinfo = (O_RDWR | if socktype & libc::SOCK_NONBLOCK != 0 { O_NONBLOCK } else { 0 }) as u64;
+        match fdtables::get_unused_virtual_fd(self.cageid, FDKIND_IMSOCK, sock.id, socktype & libc::SOCK_CLOEXEC != 0, perfdinfo) {
+            Ok(virtualfd) => virtualfd as i32,
+            Err(e) => {
+                interface::close_unix_socket(sock.id);
+                handle_errno(e as i32, "socket")
+            }
+        }
+    }
+
+    fn socketpair_imsock(&self, socktype: i32, protocol: i32, virtual_socket_vector: &mut SockPair) -> i32 {
+        let basetype = socktype & !(libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC);
+        if basetype != libc::SOCK_STREAM && basetype != libc::SOCK_DGRAM {
+            return syscall_error(Errno::ESOCKTNOSUPPORT, "socketpair", "socket type is not supported for AF_UNIX");
+        }
+        if protocol != 0 && protocol != libc::PF_UNIX {
+            return syscall_error(Errno::EPROTONOSUPPORT, "socketpair", "protocol is not supported for AF_UNIX");
+        }
+
+        let (sock1, sock2) = interface::new_unix_socketpair(basetype, self.unix_creds());
+        let should_cloexec = socktype & libc::SOCK_CLOEXEC != 0;
+        let perfdinfo = (O_RDWR | if socktype & libc::SOCK_NONBLOCK != 0 { O_NONBLOCK } else { 0 }) as u64;
+        let vsv_1 = match fdtables::get_unused_virtual_fd(self.cageid, FDKIND_IMSOCK, sock1.id, should_cloexec, perfdinfo) {
+            Ok(virtualfd) => virtualfd,
+            Err(e) => {
+                interface::close_unix_socket(sock1.id);
+                interface::close_unix_socket(sock2.id);
+                return handle_errno(e as i32, "socketpair");
+            }
+        };
+        let vsv_2 = match fdtables::get_unused_virtual_fd(self.cageid, FDKIND_IMSOCK, sock2.id, should_cloexec, perfdinfo) {
+            Ok(virtualfd) => virtualfd,
+            Err(e) => {
+                let _ = fdtables::close_virtualfd(self.cageid, vsv_1);
+                interface::close_unix_socket(sock2.id);
+                return handle_errno(e as i32, "socketpair");
+            }
+        };
+        virtual_socket_vector.sock1 = vsv_1 as i32;
+        virtual_socket_vector.sock2 = vsv_2 as i32;
+        0
+    }
+
+    fn bind_imsock(&self, vfd: FDTableEntry, addr: &GenSockaddr) -> i32 {
+        let sock = interface::get_unix_socket(vfd.underfd).unwrap();
+        let (unixaddr, name) = match parse_unix_addr(addr, "bind") {
+            Ok(parsed) => parsed,
+            Err(errno) => return errno,
+        };
+        if sock.is_bound() {
+            return syscall_error(Errno::EINVAL, "bind", "the socket is already bound to an address");
+        }
+
+        let path = match name {
+            None => return sock.autobind(),
+            Some(UnixAddrName::Abstract(abstractname)) => {
+                return sock.bind(interface::UnixSockName {
+                    key: interface::UnixSockKey::Abstract(abstractname),
+                    addr: unixaddr,
+                });
+            }
+            Some(UnixAddrName::Path(path)) => path,
+        };
+
+        // like Linux, binding creates the socket file and fails if anything is in the way
+        let target = match resolve_parent_beneath(&path, self) {
+            Ok(target) => target,
+            Err(errno) => return handle_errno(errno, "bind"),
+        };
+        let ret = unsafe { libc::mknodat(target.dirfd, target.name.as_ptr(), S_IFSOCK | 0o777, 0) };
+        if ret < 0 {
+            let errno = get_errno();
+            if errno == EEXIST {
+                return syscall_error(Errno::EADDRINUSE, "bind", "the address is already in use");
+            }
+            return handle_errno(errno, "bind");
+        }
+        let mut statbuf: libc::stat = unsafe { std::mem::zeroed() };
+        if unsafe { libc::fstatat(target.dirfd, target.name.as_ptr(), &mut statbuf, AT_SYMLINK_NOFOLLOW) } < 0 {
+            let errno = get_errno();
+            unsafe { libc::unlinkat(target.dirfd, target.name.as_ptr(), 0) };
+            return handle_errno(errno, "bind");
+        }
+
+        let ret = sock.bind(interface::UnixSockName {
+            key: interface::UnixSockKey::Inode(statbuf.st_dev, statbuf.st_ino),
+            addr: unixaddr,
+        });
+        if ret < 0 {
+            unsafe { libc::unlinkat(target.dirfd, target.name.as_ptr(), 0) };
+        }
+        ret
+    }
+
+    // finds the socket an address refers to, for connect and sendto
+    fn lookup_imsock(&self, addr: &GenSockaddr, syscallname: &str) -> Result<interface::RustRfc<interface::UnixSocket>, i32> {
+        let key = match parse_unix_addr(addr, syscallname)? {
+            (_, None) => return Err(syscall_error(Errno::EINVAL, syscallname, "the address is empty")),
+            (_, Some(UnixAddrName::Abstract(abstractname))) => interface::UnixSockKey::Abstract(abstractname),
+            (_, Some(UnixAddrName::Path(path))) => {
+                let target = resolve_beneath(&path, self).map_err(|errno| handle_errno(errno, syscallname))?;
+                let mut statbuf: libc::stat = unsafe { std::mem::zeroed() };
+                if unsafe { libc::fstat(target.dirfd, &mut statbuf) } < 0 {
+                    return Err(handle_errno(get_errno(), syscallname));
+                }
+                if statbuf.st_mode & S_IFMT != S_IFSOCK {
+                    return Err(syscall_error(Errno::ECONNREFUSED, syscallname, "the path is not a socket"));
+                }
+                // connecting needs write permission on the socket file
+                if unsafe { libc::access(target.procpath().as_ptr(), W_OK) } < 0 {
+                    return Err(handle_errno(get_errno(), syscallname));
+                }
+                interface::UnixSockKey::Inode(statbuf.st_dev, statbuf.st_ino)
+            }
+        };
+        interface::lookup_unix_name(&key)
+            .ok_or_else(|| syscall_error(Errno::ECONNREFUSED, syscallname, "no socket is bound to the address"))
+    }
+
+    fn connect_imsock(&self, vfd: FDTableEntry, addr: &GenSockaddr) -> i32 {
+        let sock = interface::get_unix_socket(vfd.underfd).unwrap();
+        let target = match self.lookup_imsock(addr, "connect") {
+            Ok(target) => target,
+            Err(errno) => return errno,
+        };
+        if sock.socktype == libc::SOCK_STREAM {
+            sock.connect_stream(&target, imsock_nonblocking(&vfd))
+        } else {
+            sock.connect_dgram(&target)
+        }
+    }
+
+    fn send_imsock(
+        &self,
+        vfd: FDTableEntry,
+        buf: *const u8,
+        buflen: usize,
+        flags: i32,
+        dest_addr: Option<&GenSockaddr>,
+        ancillary: Option<UnixAncillary>,
+        syscallname: &str,
+    ) -> i32 {
+        let sock = interface::get_unix_socket(vfd.underfd).unwrap();
+        let ancillary = ancillary.unwrap_or_else(|| UnixAncillary { creds: self.unix_creds(), rights: Vec::new() });
+        let nonblocking = imsock_nonblocking(&vfd) || flags & libc::MSG_DONTWAIT != 0;
+        let data: &[u8] = if buflen == 0 { &[] } else { unsafe { std::slice::from_raw_parts(buf, buflen) } };
+
+        let ret = if sock.socktype == libc::SOCK_STREAM {
+            if dest_addr.is_some() {
+                // same as Linux, a stream socket can only send to its peer
+                return match sock.peername() {
+                    Ok(_) => syscall_error(Errno::EISCONN, syscallname, "the socket is already connected"),
+                    Err(_) => syscall_error(Errno::EOPNOTSUPP, syscallname, "stream sockets must be connected to send"),
+                };
+            }
+            sock.send_stream(data, ancillary, nonblocking)
+        } else {
+            let target = match dest_addr {
+                Some(addr) => match self.lookup_imsock(addr, syscallname) {
+                    Ok(target) => Some(target),
+                    Err(errno) => return errno,
+                },
+                None => None,
+            };
+            sock.send_dgram(data, ancillary, target, nonblocking)
+        };
+
+        if ret == -(Errno::EPIPE as i32) && flags & libc::MSG_NOSIGNAL == 0 {
+            interface::lind_kill_from_id(self.cageid, libc::SIGPIPE);
+        }
+        ret
+    }
+
+    fn recv_imsock(
+        &self,
+        vfd: FDTableEntry,
+        buf: *mut u8,
+        buflen: usize,
+        flags: i32,
+        addr: Option<&mut GenSockaddr>,
+        ancillary: Option<&mut UnixAncillary>,
+    ) -> i32 {
+        let sock = interface::get_unix_socket(vfd.underfd).unwrap();
+        let data: &mut [u8] = if buflen == 0 { &mut [] } else { unsafe { std::slice::from_raw_parts_mut(buf, buflen) } };
+        let mut fromaddr = interface::unnamed_unix_addr();
+        let ret = sock.recv(data, flags, imsock_nonblocking(&vfd), Some(&mut fromaddr), ancillary);
+        if ret >= 0 {
+            if let Some(addr) = addr {
+                *addr = GenSockaddr::Unix(fromaddr);
+            }
+        }
+        ret
+    }
+
+    // the credentials this cage's messages carry, which are its real ids as in Linux
+    fn unix_creds(&self) -> UnixCred {
+        UnixCred { pid: self.cageid as i32, uid: self.uid(), gid: self.gid() }
+    }
+
+    fn sendmsg_imsock(&self, vfd: FDTableEntry, msg: &libc::msghdr, flags: i32) -> i32 {
+        let iovs = match msghdr_iovecs(msg, "sendmsg") {
+            Ok(iovs) => iovs,
+            Err(e) => return e,
+        };
+        // gathered into one send so a datagram socket gets a single message
+        let mut gathered = Vec::new();
+        for iov in iovs.iter().filter(|iov| iov.iov_len > 0) {
+            gathered.extend_from_slice(unsafe { std::slice::from_raw_parts(iov.iov_base as *const u8, iov.iov_len) });
+        }
+        let dest_addr = if msg.msg_name.is_null() || msg.msg_namelen == 0 {
+            None
+        } else {
+            match interface::get_sockaddr(msg.msg_name as u64, msg.msg_namelen) {
+                Ok(addr) => Some(addr),
+                Err(e) => return e,
+            }
+        };
+        let ancillary = match self.unix_ancillary(msg) {
+            Ok(ancillary) => ancillary,
+            Err(e) => return e,
+        };
+        self.send_imsock(vfd, gathered.as_ptr(), gathered.len(), flags, dest_addr.as_ref(), Some(ancillary), "sendmsg")
+    }
+
+    /*
+     *   Reads the control messages of msg into the ancillary data of a message on an in-memory
+     *   AF_UNIX socket. The fds of SCM_RIGHTS are held from here on, dropping the result
+     *   releases them again. Control messages of other levels are ignored, as in Linux.
+     */
+    fn unix_ancillary(&self, msg: &libc::msghdr) -> Result<UnixAncillary, i32> {
+        let mut ancillary = UnixAncillary { creds: self.unix_creds(), rights: Vec::new() };
+        if msg.msg_controllen == 0 {
+            return Ok(ancillary);
+        }
+        if msg.msg_control.is_null() {
+            return Err(syscall_error(Errno::EFAULT, "sendmsg", "msg_control is not a valid address"));
+        }
+        let control_end = msg.msg_control as usize + msg.msg_controllen as usize;
+        let header_len = unsafe { libc::CMSG_LEN(0) } as usize;
+
+        let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(msg) };
+        while !cmsg.is_null() {
+            let cmsg_len = unsafe { (*cmsg).cmsg_len } as usize;
+            if cmsg_len < header_len || cmsg as usize + cmsg_len > control_end {
+                return Err(syscall_error(Errno::EINVAL, "sendmsg", "a control message has a bad length"));
+            }
+            let data = unsafe { libc::CMSG_DATA(cmsg) };
+            let datalen = cmsg_len - header_len;
+            if unsafe { (*cmsg).cmsg_level } == libc::SOL_SOCKET {
+                match unsafe { (*cmsg).cmsg_type } {
+                    libc::SCM_RIGHTS => {
+                        let count = datalen / size_of::<i32>();
+                        if ancillary.rights.len() + count > interface::SCM_MAX_FD {
+                            return Err(syscall_error(Errno::EINVAL, "sendmsg", "too many fds in SCM_RIGHTS"));
+                        }
+                        for index in 0..count {
+                            let fd = unsafe { (data as *const i32).add(index).read_unaligned() };
+                            match fdtables::translate_virtual_fd(self.cageid, fd as u64) {
+                                Ok(entry) => ancillary.rights.push(InflightFd::hold(entry)),
+                                Err(_) => return Err(syscall_error(Errno::EBADF, "sendmsg", "SCM_RIGHTS holds an fd that is not open")),
+                            }
+                        }
+                    }
+                    libc::SCM_CREDENTIALS => {
+                        if datalen != size_of::<libc::ucred>() {
+                            return Err(syscall_error(Errno::EINVAL, "sendmsg", "SCM_CREDENTIALS has a bad length"));
+                        }
+                        let cred = unsafe { (data as *const libc::ucred).read_unaligned() };
+                        let isroot = self.euid() == sys_constants::ROOT_UID;
+                        let own_pid = cred.pid == self.cageid as i32;
+                        let own_uid = [self.uid(), self.euid(), self.suid()].contains(&cred.uid);
+                        let own_gid = [self.gid(), self.egid(), self.sgid()].contains(&cred.gid);
+                        if !isroot && !(own_pid && own_uid && own_gid) {
+                            return Err(syscall_error(Errno::EPERM, "sendmsg", "only root may send credentials other than its own"));
+                        }
+                        ancillary.creds = UnixCred { pid: cred.pid, uid: cred.uid, gid: cred.gid };
+                    }
+                    _ => {
+                        return Err(syscall_error(Errno::EINVAL, "sendmsg", "the control message type is not supported"));
+                    }
+                }
+            }
+            cmsg = unsafe { libc::CMSG_NXTHDR(msg, cmsg) };
+        }
+        Ok(ancillary)
+    }
+
+    fn recvmsg_imsock(&self, vfd: FDTableEntry, msg: &mut libc::msghdr, flags: i32) -> i32 {
+        let iovs = match msghdr_iovecs(msg, "recvmsg") {
+            Ok(iovs) => iovs,
+            Err(e) => return e,
+        };
+        let sock = interface::get_unix_socket(vfd.underfd).unwrap();
+        let buflen: usize = iovs.iter().map(|iov| iov.iov_len).sum();
+        // the buffer grows with what is received rather than the iov_lens the cage claims
+        let mut data = GrowingBuf { data: Vec::new(), limit: buflen };
+        let mut fromaddr = interface::unnamed_unix_addr();
+        let mut ancillary = UnixAncillary::default();
+        // always asks for the full length so a datagram that didn't fit shows in msg_flags
+        let ret = sock.recv(&mut data, flags | libc::MSG_TRUNC, imsock_nonblocking(&vfd), Some(&mut fromaddr), Some(&mut ancillary));
+        if ret < 0 {
+            return ret;
+        }
+        let length = ret as usize;
+        let received = interface::rust_min(length, buflen);
+        scatter_iovecs(iovs, &data.data[..received]);
+
+        msg.msg_flags = 0;
+        if length > buflen {
+            msg.msg_flags |= libc::MSG_TRUNC;
+        }
+        if !msg.msg_name.is_null() {
+            let addrlen = unix_addr_len(&fromaddr);
+            let copylen = interface::rust_min(msg.msg_namelen as usize, addrlen);
+            unsafe {
+                std::ptr::copy_nonoverlapping(&fromaddr as *const SockaddrUnix as *const u8, msg.msg_name as *mut u8, copylen);
+            }
+            msg.msg_namelen = addrlen as u32;
+        }
+        self.put_unix_ancillary(msg, sock.passcred(), ancillary, flags & libc::MSG_CMSG_CLOEXEC != 0);
+
+        if flags & libc::MSG_TRUNC != 0 { length as i32 } else { received as i32 }
+    }
+
+    /*
+     *   Fills msg_control with the ancillary data of a received message. The fds are installed
+     *   as new virtual fds of this cage before the holds on them end. Those that don't fit in
+     *   msg_control, or that the cage has no room for, are released and MSG_CTRUNC is set.
+     */
+    fn put_unix_ancillary(&self, msg: &mut libc::msghdr, passcred: bool, ancillary: UnixAncillary, cloexec: bool) {
+        let capacity = if msg.msg_control.is_null() { 0 } else { msg.msg_controllen as usize };
+        let mut used = 0;
+
+        if passcred {
+            let cred = libc::ucred { pid: ancillary.creds.pid, uid: ancillary.creds.uid, gid: ancillary.creds.gid };
+            let bytes = unsafe {
+                std::slice::from_raw_parts(&cred as *const libc::ucred as *const u8, size_of::<libc::ucred>())
+            };
+            if !put_cmsg(msg.msg_control as *mut u8, capacity, &mut used, libc::SCM_CREDENTIALS, bytes) {
+                msg.msg_flags |= libc::MSG_CTRUNC;
+            }
+        }
+
+        if !ancillary.rights.is_empty() {
+            let room = (capacity - used).saturating_sub(unsafe { libc::CMSG_LEN(0) } as usize) / size_of::<i32>();
+            let mut fds: Vec<i32> = Vec::new();
+            for inflight in ancillary.rights.iter().take(room) {
+                let entry = inflight.entry();
+                match fdtables::get_unused_virtual_fd(self.cageid, entry.fdkind, entry.underfd, cloexec, entry.perfdinfo) {
+                    Ok(virtualfd) => fds.push(virtualfd as i32),
+                    Err(_) => break,
+                }
+            }
+            if fds.len() < ancillary.rights.len() {
+                msg.msg_flags |= libc::MSG_CTRUNC;
+            }
+            if !fds.is_empty() {
+                let bytes = unsafe { std::slice::from_raw_parts(fds.as_ptr() as *const u8, fds.len() * size_of::<i32>()) };
+                put_cmsg(msg.msg_control as *mut u8, capacity, &mut used, libc::SCM_RIGHTS, bytes);
+            }
+        }
+        msg.msg_controllen = used as _;
     }
 
+    fn accept_imsock(&self, vfd: FDTableEntry, addr: Option<&mut GenSockaddr>, flags: i32) -> i32 {
+        let sock = interface::get_unix_socket(vfd.underfd).unwrap();
+        let server = match sock.accept(imsock_nonblocking(&vfd)) {
+            Ok(server) => server,
+            Err(errno) => return errno,
+        };
+        let peeraddr = server.peername().unwrap_or_else(|_| interface::unnamed_unix_addr());
+        let perfdinfo = (O_RDWR | if flags & libc::SOCK_NONBLOCK != 0 { O_NONBLOCK } else { 0 }) as u64;
+        match fdtables::get_unused_virtual_fd(self.cageid, FDKIND_IMSOCK, server.id, flags & libc::SOCK_CLOEXEC != 0, perfdinfo) {
+            Ok(virtualfd) => {
+                if let Some(addr) = addr {
+                    *addr = GenSockaddr::Unix(peeraddr);
+                }
+                virtualfd as i32
+            }
+            Err(e) => {
+                interface::close_unix_socket(server.id);
+                handle_errno(e as i32, "accept")
+            }
+        }
+    }
 }
 
+// The iovecs of a msghdr, with the same limit on their count as Linux
+fn msghdr_iovecs<'a>(msg: &'a libc::msghdr, syscallname: &str) -> Result<&'a [IovecStruct], i32> {
+    if msg.msg_iovlen > libc::UIO_MAXIOV as usize {
+        return Err(syscall_error(Errno::EMSGSIZE, syscallname, "msg_iovlen is too large"));
+    }
+    if msg.msg_iovlen == 0 {
+        return Ok(&[]);
+    }
+    if msg.msg_iov.is_null() {
+        return Err(syscall_error(Errno::EFAULT, syscallname, "msg_iov is not a valid address"));
+    }
+    Ok(unsafe { std::slice::from_raw_parts(msg.msg_iov, msg.msg_iovlen as usize) })
+}
+
+// The AF_INET/AF_INET6 address at msg_name, the only ones a network namespace translates
+fn msg_inet_addr(name: *const c_void, namelen: u32) -> Option<GenSockaddr> {
+    if name.is_null() || (namelen as usize) < size_of::<u16>() {
+        return None;
+    }
+    let family = unsafe { (name as *const u16).read_unaligned() } as i32;
+    if family == libc::AF_INET && namelen as usize >= size_of::<SockaddrV4>() {
+        return Some(GenSockaddr::V4(unsafe { (name as *const SockaddrV4).read_unaligned() }));
+    }
+    if family == libc::AF_INET6 && namelen as usize >= size_of::<SockaddrV6>() {
+        return Some(GenSockaddr::V6(unsafe { (name as *const SockaddrV6).read_unaligned() }));
+    }
+    None
+}
+
+fn put_msg_inet_addr(name: *mut c_void, addr: &GenSockaddr) {
+    match addr {
+        GenSockaddr::V4(addr) => unsafe { (name as *mut SockaddrV4).write_unaligned(*addr) },
+        GenSockaddr::V6(addr) => unsafe { (name as *mut SockaddrV6).write_unaligned(*addr) },
+        GenSockaddr::Unix(_) => {}
+    }
+}
+
+// The length Linux reports for a unix socket address, 0 for a sender that was never bound
+fn unix_addr_len(addr: &SockaddrUnix) -> usize {
+    let pathlen = if addr.sun_path[0] != 0 {
+        let nul = addr.sun_path.iter().position(|c| *c == 0).unwrap_or(addr.sun_path.len() - 1);
+        nul + 1
+    } else {
+        match addr.sun_path.iter().rposition(|c| *c != 0) {
+            Some(last) => last + 1,
+            None => return 0,
+        }
+    };
+    size_of::<u16>() + pathlen
+}
+
+// Appends one SOL_SOCKET control message at used if it fits into the capacity bytes of control
+fn put_cmsg(control: *mut u8, capacity: usize, used: &mut usize, cmsg_type: i32, data: &[u8]) -> bool {
+    let cmsg_len = unsafe { libc::CMSG_LEN(data.len() as u32) } as usize;
+    if *used + cmsg_len > capacity {
+        return false;
+    }
+    let mut header: libc::cmsghdr = unsafe { std::mem::zeroed() };
+    header.cmsg_len = cmsg_len as _;
+    header.cmsg_level = libc::SOL_SOCKET;
+    header.cmsg_type = cmsg_type;
+    unsafe {
+        let cmsg = control.add(*used);
+        (cmsg as *mut libc::cmsghdr).write_unaligned(header);
+        let header_len = libc::CMSG_LEN(0) as usize;
+        std::ptr::copy_nonoverlapping(data.as_ptr(), cmsg.add(header_len), data.len());
+    }
+    let space = unsafe { libc::CMSG_SPACE(data.len() as u32) } as usize;
+    *used = interface::rust_min(*used + space, capacity);
+    true
+}
+
+// The bytes of a sockaddr of getifaddrs, None for a null address or an unknown family
+fn sockaddr_bytes(addr: *const libc::sockaddr) -> Option<Vec<u8>> {
+    if addr.is_null() {
+        return None;
+    }
+    let len = match unsafe { (*addr).sa_family } as i32 {
+        libc::AF_INET => size_of::<libc::sockaddr_in>(),
+        libc::AF_INET6 => size_of::<libc::sockaddr_in6>(),
+        libc::AF_PACKET => size_of::<libc::sockaddr_ll>(),
+        _ => return None,
+    };
+    Some(unsafe { std::slice::from_raw_parts(addr as *const u8, len) }.to_vec())
+}
+
+// SOCK_STREAM, SOCK_DGRAM... of a kernel socket
+fn kernel_socktype(hostfd: u64) -> i32 {
+    let mut socktype: i32 = 0;
+    let mut optlen = size_of::<i32>() as socklen_t;
+    unsafe {
+        libc::getsockopt(hostfd as i32, libc::SOL_SOCKET, libc::SO_TYPE, &mut socktype as *mut i32 as *mut c_void, &mut optlen);
+    }
+    socktype
+}
+
+// The port a kernel AF_INET/AF_INET6 socket is bound to, in host byte order
+fn kernel_local_port(hostfd: u64) -> u16 {
+    let mut addr: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let mut addrlen = size_of::<libc::sockaddr_storage>() as socklen_t;
+    unsafe {
+        libc::getsockname(hostfd as i32, &mut addr as *mut libc::sockaddr_storage as *mut libc::sockaddr, &mut addrlen);
+    }
+    // sin_port and sin6_port are both right after the family
+    let addr_in = unsafe { &*(&addr as *const libc::sockaddr_storage as *const libc::sockaddr_in) };
+    u16::from_be(addr_in.sin_port)
+}
@@ -8,6 +8,7 @@ use super::sys_constants::*;
 use crate::interface;
 use crate::safeposix::cage;
 use crate::safeposix::cage::*;
+use crate::safeposix::sem::*;
 use crate::safeposix::shm::*;
 
 use crate::fdtables;
@@ -30,6 +31,7 @@ impl Cage {
                     let segment = occupied.get_mut();
                     segment.shminfo.shm_nattch -= 1;
                     segment.shminfo.shm_dtime = interface::timestamp() as isize;
+                    segment.shminfo.shm_lpid = self.cageid as u32;
                     segment.attached_cages.remove(&self.cageid);
 
                     if segment.rmid && segment.shminfo.shm_nattch == 0 {
@@ -45,7 +47,33 @@ impl Cage {
         }
     }
 
+    // Rolls back the SEM_UNDO semops of the cage when it exits. A cage that execs keeps
+    // its adjustments, under the new cageid.
+    fn undo_semops(&self, exec_cageid: Option<u64>) {
+        for set in SEM_METADATA.semtable.iter() {
+            let mut state = set.state.lock();
+            match exec_cageid {
+                Some(newcageid) => {
+                    if let Some(adjustments) = state.undo.remove(&self.cageid) {
+                        state.undo.insert(newcageid, adjustments);
+                    }
+                }
+                None => {
+                    if state.undo_for_cage(self.cageid) {
+                        set.cv.notify_all();
+                    }
+                }
+            }
+        }
+    }
+
     pub fn fork_syscall(&self, child_cageid: u64) -> i32 {
+        // every cage runs as the same user, so RLIMIT_NPROC caps the number of cages
+        let nproc = self.rlimit_cur(sys_constants::RLIMIT_NPROC);
+        if nproc != sys_constants::RLIM_INFINITY && interface::cagetable_count() as u64 >= nproc {
+            return syscall_error(Errno::EAGAIN, "fork", "RLIMIT_NPROC has been reached");
+        }
+
         // Modify the fdtable manually 
         fdtables::copy_fdtable_for_cage(self.cageid, child_cageid).unwrap();
         
@@ -111,21 +139,12 @@ impl Cage {
         // we do this because we haven't established a thread for the cage yet, and dont have a threadid to store it at
         // this way the child can initialize the sigset properly when it establishes its own mainthreadid
         let newsigset = interface::RustHashMap::new();
-        if !interface::RUSTPOSIX_TESTSUITE.load(interface::RustAtomicOrdering::Relaxed) {
-            // we don't add these for the test suite
-            // BUG: Signals are commented out until we add them to lind-wasm
-            // let mainsigsetatomic = self
-            //     .sigset
-            //     .get(
-            //         &self
-            //             .main_threadid
-            //             .load(interface::RustAtomicOrdering::Relaxed),
-            //     )
-            //     .unwrap();
-            // let mainsigset = interface::RustAtomicU64::new(
-            //     mainsigsetatomic.load(interface::RustAtomicOrdering::Relaxed),
-            // );
-            // newsigset.insert(0, mainsigset);
+        let mainthreadid = self.main_threadid.load(interface::RustAtomicOrdering::Relaxed);
+        if let Some(mainsigsetatomic) = self.sigset.get(&mainthreadid) {
+            let mainsigset = interface::RustAtomicU64::new(
+                mainsigsetatomic.load(interface::RustAtomicOrdering::Relaxed),
+            );
+            newsigset.insert(0, mainsigset);
         }
 
         /*
@@ -144,6 +163,7 @@ impl Cage {
         let cageobj = Cage {
             cageid: child_cageid,
             cwd: interface::RustLock::new(self.cwd.read().clone()),
+            lindroot: interface::RustLock::new(self.lindroot.read().clone()),
             parent: self.cageid,
             cancelstatus: interface::RustAtomicBool::new(false),
             // This happens because self.getgid tries to copy atomic value which does not implement "Copy" trait; self.getgid.load returns i32.
@@ -159,16 +179,34 @@ impl Cage {
             geteuid: interface::RustAtomicI32::new(
                 self.geteuid.load(interface::RustAtomicOrdering::Relaxed),
             ),
+            getsgid: interface::RustAtomicI32::new(
+                self.getsgid.load(interface::RustAtomicOrdering::Relaxed),
+            ),
+            getsuid: interface::RustAtomicI32::new(
+                self.getsuid.load(interface::RustAtomicOrdering::Relaxed),
+            ),
+            groups: interface::RustLock::new(self.groups.read().clone()),
+            umask: interface::RustAtomicU32::new(
+                self.umask.load(interface::RustAtomicOrdering::Relaxed),
+            ),
             rev_shm: interface::Mutex::new((*self.rev_shm.lock()).clone()),
             mutex_table: interface::RustLock::new(new_mutex_table),
             cv_table: interface::RustLock::new(new_cv_table),
             sem_table: new_semtable,
+            // a forked cage has the named semaphores of its parent open too
+            named_sem_table: self.named_sem_table.clone(),
             thread_table: interface::RustHashMap::new(),
             signalhandler: self.signalhandler.clone(),
             sigset: newsigset,
             pendingsigset: interface::RustHashMap::new(),
             main_threadid: interface::RustAtomicU64::new(0),
             interval_timer: interface::IntervalTimer::new(child_cageid),
+            zombies: interface::Mutex::new(vec![]),
+            zombie_cv: interface::Condvar::new(),
+            child_num: interface::RustAtomicU64::new(0),
+            rlimits: self.rlimits.clone(),
+            mapped_ranges: interface::Mutex::new((*self.mapped_ranges.lock()).clone()),
+            netns: interface::RustAtomicU64::new(self.netns()),
         };
 
         let shmtable = &SHM_METADATA.shmtable;
@@ -182,6 +220,8 @@ impl Cage {
             shment.attached_cages.insert(child_cageid, childrefs);
         }
 
+        // the parent can now wait on this child
+        self.child_num.fetch_add(1, interface::RustAtomicOrdering::SeqCst);
         interface::cagetable_insert(child_cageid, cageobj);
 
         0
@@ -198,53 +238,92 @@ impl Cage {
         // Delete the original one
         let _newfdtable = fdtables::remove_cage_from_fdtable(self.cageid);
 
-        interface::cagetable_remove(self.cageid);
-
         self.unmap_shm_mappings();
+        self.undo_semops(Some(child_cageid));
 
         // we grab the parent cages main threads sigset and store it at 0
         // this way the child can initialize the sigset properly when it establishes its own mainthreadid
         let newsigset = interface::RustHashMap::new();
-        if !interface::RUSTPOSIX_TESTSUITE.load(interface::RustAtomicOrdering::Relaxed) {
-            // we don't add these for the test suite
-            // BUG: Signals are commented out until we add them to lind-wasm
-            // let mainsigsetatomic = self
-            //     .sigset
-            //     .get(
-            //         &self
-            //             .main_threadid
-            //             .load(interface::RustAtomicOrdering::Relaxed),
-            //     )
-            //     .unwrap();
-            // let mainsigset = interface::RustAtomicU64::new(
-            //     mainsigsetatomic.load(interface::RustAtomicOrdering::Relaxed),
-            // );
-            // newsigset.insert(0, mainsigset);
+        let mainthreadid = self.main_threadid.load(interface::RustAtomicOrdering::Relaxed);
+        if let Some(mainsigsetatomic) = self.sigset.get(&mainthreadid) {
+            let mainsigset = interface::RustAtomicU64::new(
+                mainsigsetatomic.load(interface::RustAtomicOrdering::Relaxed),
+            );
+            newsigset.insert(0, mainsigset);
+        }
+
+        // unlike fork, exec keeps the signals that are still pending for the main thread
+        let newpendingsigset = interface::RustHashMap::new();
+        if let Some(mainpendingatomic) = self.pendingsigset.get(&mainthreadid) {
+            let mainpendingset = interface::RustAtomicU64::new(
+                mainpendingatomic.load(interface::RustAtomicOrdering::Relaxed),
+            );
+            newpendingsigset.insert(0, mainpendingset);
         }
 
         let newcage = Cage {
             cageid: child_cageid,
             cwd: interface::RustLock::new(self.cwd.read().clone()),
+            lindroot: interface::RustLock::new(self.lindroot.read().clone()),
             parent: self.parent,
             cancelstatus: interface::RustAtomicBool::new(false),
-            getgid: interface::RustAtomicI32::new(-1),
-            getuid: interface::RustAtomicI32::new(-1),
-            getegid: interface::RustAtomicI32::new(-1),
-            geteuid: interface::RustAtomicI32::new(-1),
+            // credentials survive exec, and the saved ids become the effective ones
+            getgid: interface::RustAtomicI32::new(
+                self.getgid.load(interface::RustAtomicOrdering::Relaxed),
+            ),
+            getuid: interface::RustAtomicI32::new(
+                self.getuid.load(interface::RustAtomicOrdering::Relaxed),
+            ),
+            getegid: interface::RustAtomicI32::new(
+                self.getegid.load(interface::RustAtomicOrdering::Relaxed),
+            ),
+            geteuid: interface::RustAtomicI32::new(
+                self.geteuid.load(interface::RustAtomicOrdering::Relaxed),
+            ),
+            getsgid: interface::RustAtomicI32::new(
+                self.getegid.load(interface::RustAtomicOrdering::Relaxed),
+            ),
+            getsuid: interface::RustAtomicI32::new(
+                self.geteuid.load(interface::RustAtomicOrdering::Relaxed),
+            ),
+            groups: interface::RustLock::new(self.groups.read().clone()),
+            umask: interface::RustAtomicU32::new(
+                self.umask.load(interface::RustAtomicOrdering::Relaxed),
+            ),
             rev_shm: interface::Mutex::new(vec![]),
             mutex_table: interface::RustLock::new(vec![]),
             cv_table: interface::RustLock::new(vec![]),
             sem_table: interface::RustHashMap::new(),
+            named_sem_table: interface::RustHashMap::new(),
             thread_table: interface::RustHashMap::new(),
             signalhandler: interface::RustHashMap::new(),
             sigset: newsigset,
-            pendingsigset: interface::RustHashMap::new(),
+            pendingsigset: newpendingsigset,
             main_threadid: interface::RustAtomicU64::new(0),
             interval_timer: self.interval_timer.clone_with_new_cageid(child_cageid),
+            // filled in below, once the new cage is in the table
+            zombies: interface::Mutex::new(vec![]),
+            zombie_cv: interface::Condvar::new(),
+            child_num: interface::RustAtomicU64::new(0),
+            // limits survive exec, the mappings do not
+            rlimits: self.rlimits.clone(),
+            mapped_ranges: interface::Mutex::new(vec![]),
+            netns: interface::RustAtomicU64::new(self.netns()),
         };
         //wasteful clone of fdtable, but mutability constraints exist
 
+        // exec doesn't change who our children are, so their zombies and the count of running
+        // ones move to the new cage. Holding our zombies lock while the new cage goes into the
+        // table means a child exiting meanwhile reports either before the move or to the new cage.
+        let mut zombies = self.zombies.lock();
+        interface::cagetable_remove(self.cageid);
         interface::cagetable_insert(child_cageid, newcage);
+        let newcage = interface::cagetable_getref(child_cageid);
+        newcage.zombies.lock().append(&mut zombies);
+        newcage.child_num.store(
+            self.child_num.load(interface::RustAtomicOrdering::SeqCst),
+            interface::RustAtomicOrdering::SeqCst,
+        );
         0
     }
 
@@ -252,25 +331,118 @@ impl Cage {
         //flush anything left in stdout
         interface::flush_stdout();
         self.unmap_shm_mappings();
+        self.undo_semops(None);
 
         let _ = fdtables::remove_cage_from_fdtable(self.cageid);
 
+        // leave our exit status with the parent (init is its own parent and has nobody to report to)
+        // this happens before we leave the cage table so a waiting parent always sees us either
+        // still running or as a zombie
+        if self.cageid != self.parent {
+            while let Some(parentcage) = interface::cagetable_getref_opt(self.parent) {
+                let mut zombies = parentcage.zombies.lock();
+                // the parent exec'd before we got the lock, the zombie belongs with the new cage
+                match interface::cagetable_getref_opt(self.parent) {
+                    Some(current) if interface::RustRfc::ptr_eq(&current, &parentcage) => {}
+                    _ => continue,
+                }
+                zombies.push(Zombie {
+                    cageid: self.cageid,
+                    exit_code: status,
+                    rusage: interface::Rusage::default(),
+                });
+                parentcage.child_num.fetch_sub(1, interface::RustAtomicOrdering::SeqCst);
+                parentcage.zombie_cv.notify_all();
+                break;
+            }
+        }
+
         //may not be removable in case of lindrustfinalize, we don't unwrap the remove result
         interface::cagetable_remove(self.cageid);
 
-        // Trigger SIGCHLD
-        if !interface::RUSTPOSIX_TESTSUITE.load(interface::RustAtomicOrdering::Relaxed) {
-            // dont trigger SIGCHLD for test suite
-            // BUG: Signals are commented out until we add them to lind-wasm
-            // if self.cageid != self.parent {
-            //     interface::lind_kill_from_id(self.parent, libc::SIGCHLD);
-            // }
+        // Trigger SIGCHLD, the zombie is already in place if the parent's handler calls waitpid
+        if self.cageid != self.parent {
+            interface::lind_kill_from_id(self.parent, libc::SIGCHLD);
         }
 
         //fdtable will be dropped at end of dispatcher scope because of Arc
         status
     }
 
+    //------------------------------------WAITPID SYSCALL------------------------------------
+    /*
+    *   waitpid() reaps a zombie left behind by exit_syscall of one of our children
+    *   pid > 0 waits for that child, pid -1 or 0 for any child (all cages share one process group)
+    *   WNOHANG returns 0 instead of blocking, WUNTRACED is accepted but cages are never stopped
+    *   returns the cageid of the reaped child, or the negated errno on failure
+    */
+    pub fn waitpid_syscall(&self, cageid: i32, status: Option<&mut i32>, options: i32) -> i32 {
+        self.wait4_syscall(cageid, status, options, None)
+    }
+
+    /*
+    *   wait() is waitpid(-1, status, 0)
+    */
+    pub fn wait_syscall(&self, status: Option<&mut i32>) -> i32 {
+        self.wait4_syscall(-1, status, 0, None)
+    }
+
+    /*
+    *   wait4() is waitpid() that also reports the child's resource usage
+    */
+    pub fn wait4_syscall(
+        &self,
+        cageid: i32,
+        status: Option<&mut i32>,
+        options: i32,
+        rusage: Option<&mut interface::Rusage>,
+    ) -> i32 {
+        if options & !(libc::WNOHANG | libc::WUNTRACED | libc::WCONTINUED) != 0 {
+            return syscall_error(Errno::EINVAL, "waitpid", "Invalid options");
+        }
+        if cageid < -1 {
+            return syscall_error(Errno::ECHILD, "waitpid", "No child in the requested process group");
+        }
+
+        let mut zombies = self.zombies.lock();
+        loop {
+            let found = zombies
+                .iter()
+                .position(|zombie| cageid <= 0 || zombie.cageid == cageid as u64);
+            if let Some(index) = found {
+                let zombie = zombies.remove(index);
+                if let Some(status) = status {
+                    // encoded the way WIFEXITED/WEXITSTATUS expect
+                    *status = (zombie.exit_code & 0xff) << 8;
+                }
+                if let Some(rusage) = rusage {
+                    *rusage = zombie.rusage;
+                }
+                return zombie.cageid as i32;
+            }
+
+            // a child that is still running can't exit without taking our zombies lock,
+            // so it can't slip between this check and the wait below
+            let has_child = if cageid <= 0 {
+                self.child_num.load(interface::RustAtomicOrdering::SeqCst) > 0
+            } else if cageid >= interface::MAXCAGEID {
+                false
+            } else {
+                match interface::cagetable_getref_opt(cageid as u64) {
+                    Some(child) => child.parent == self.cageid && child.cageid != self.cageid,
+                    None => false,
+                }
+            };
+            if !has_child {
+                return syscall_error(Errno::ECHILD, "waitpid", "No child processes to wait for");
+            }
+            if options & libc::WNOHANG != 0 {
+                return 0;
+            }
+            self.zombie_cv.wait(&mut zombies);
+        }
+    }
+
     pub fn getpid_syscall(&self) -> i32 {
         self.cageid as i32 //not sure if this is quite what we want but it's easy enough to change later
     }
@@ -288,7 +460,7 @@ impl Cage {
                 .store(DEFAULT_GID as i32, interface::RustAtomicOrdering::Relaxed);
             return -1;
         }
-        DEFAULT_GID as i32 //Lind is only run in one group so a default value is returned
+        self.gid() as i32
     }
     pub fn getegid_syscall(&self) -> i32 {
         if self.getegid.load(interface::RustAtomicOrdering::Relaxed) == -1 {
@@ -296,7 +468,7 @@ impl Cage {
                 .store(DEFAULT_GID as i32, interface::RustAtomicOrdering::Relaxed);
             return -1;
         }
-        DEFAULT_GID as i32 //Lind is only run in one group so a default value is returned
+        self.egid() as i32
     }
 
     pub fn getuid_syscall(&self) -> i32 {
@@ -305,7 +477,7 @@ impl Cage {
                 .store(DEFAULT_UID as i32, interface::RustAtomicOrdering::Relaxed);
             return -1;
         }
-        DEFAULT_UID as i32 //Lind is only run as one user so a default value is returned
+        self.uid() as i32
     }
     pub fn geteuid_syscall(&self) -> i32 {
         if self.geteuid.load(interface::RustAtomicOrdering::Relaxed) == -1 {
@@ -313,7 +485,209 @@ impl Cage {
                 .store(DEFAULT_UID as i32, interface::RustAtomicOrdering::Relaxed);
             return -1;
         }
-        DEFAULT_UID as i32 //Lind is only run as one user so a default value is returned
+        self.euid() as i32
+    }
+
+    /*
+    *   The set*id calls share their rules with Linux: root may take on any id, everyone
+    *   else may only shuffle around the real, effective and saved ids it already holds.
+    *   An id of -1 leaves that id as it is.
+    */
+    fn may_take_id(&self, id: u32, held: [u32; 3]) -> bool {
+        self.euid() == sys_constants::ROOT_UID || held.contains(&id)
+    }
+
+    // stores a (real, effective, saved) triple, None keeps the current value
+    fn set_ids(
+        ids: [&interface::RustAtomicI32; 3],
+        newids: [Option<u32>; 3],
+    ) {
+        for (id, newid) in ids.iter().zip(newids.iter()) {
+            if let Some(newid) = newid {
+                id.store(*newid as i32, interface::RustAtomicOrdering::Relaxed);
+            }
+        }
+    }
+
+    // -1 is the "leave it alone" value the *id calls take
+    fn optional_id(id: u32) -> Option<u32> {
+        if id == u32::MAX {
+            None
+        } else {
+            Some(id)
+        }
+    }
+
+    //------------------------------------SETUID SYSCALL------------------------------------
+    /*
+    *   setuid() sets all three uids when called by root, and only the effective uid otherwise
+    *   returns 0 on success, EPERM if the uid is not one the cage may take on
+    */
+    pub fn setuid_syscall(&self, uid: u32) -> i32 {
+        let ids = [&self.getuid, &self.geteuid, &self.getsuid];
+        if self.euid() == sys_constants::ROOT_UID {
+            Self::set_ids(ids, [Some(uid), Some(uid), Some(uid)]);
+        } else if uid == self.uid() || uid == self.suid() {
+            Self::set_ids(ids, [None, Some(uid), None]);
+        } else {
+            return syscall_error(Errno::EPERM, "setuid", "uid is not the real or saved uid");
+        }
+        0
+    }
+
+    //------------------------------------SETEUID SYSCALL------------------------------------
+    /*
+    *   seteuid() only changes the effective uid
+    *   returns 0 on success, EPERM if the uid is not one the cage may take on
+    */
+    pub fn seteuid_syscall(&self, euid: u32) -> i32 {
+        self.setresuid_syscall(u32::MAX, euid, u32::MAX)
+    }
+
+    //------------------------------------SETREUID SYSCALL------------------------------------
+    /*
+    *   setreuid() sets the real and effective uids. The saved uid follows the new effective
+    *   uid whenever the real uid is set or the effective one is set to something else than
+    *   the old real uid.
+    *   returns 0 on success, EPERM if either uid is not one the cage may take on
+    */
+    pub fn setreuid_syscall(&self, ruid: u32, euid: u32) -> i32 {
+        let (olduid, oldeuid, oldsuid) = (self.uid(), self.euid(), self.suid());
+        let ruid = Self::optional_id(ruid);
+        let euid = Self::optional_id(euid);
+        if ruid.map_or(false, |id| !self.may_take_id(id, [olduid, oldeuid, oldeuid])) {
+            return syscall_error(Errno::EPERM, "setreuid", "real uid may not be changed to that value");
+        }
+        if euid.map_or(false, |id| !self.may_take_id(id, [olduid, oldeuid, oldsuid])) {
+            return syscall_error(Errno::EPERM, "setreuid", "effective uid may not be changed to that value");
+        }
+        let suid = if ruid.is_some() || euid.map_or(false, |id| id != olduid) {
+            Some(euid.unwrap_or(oldeuid))
+        } else {
+            None
+        };
+        Self::set_ids([&self.getuid, &self.geteuid, &self.getsuid], [ruid, euid, suid]);
+        0
+    }
+
+    //------------------------------------SETRESUID SYSCALL------------------------------------
+    /*
+    *   setresuid() sets the real, effective and saved uids independently
+    *   returns 0 on success, EPERM if any of them is not one the cage may take on
+    */
+    pub fn setresuid_syscall(&self, ruid: u32, euid: u32, suid: u32) -> i32 {
+        let held = [self.uid(), self.euid(), self.suid()];
+        let newids = [Self::optional_id(ruid), Self::optional_id(euid), Self::optional_id(suid)];
+        if newids.iter().flatten().any(|id| !self.may_take_id(*id, held)) {
+            return syscall_error(Errno::EPERM, "setresuid", "uid may not be changed to that value");
+        }
+        Self::set_ids([&self.getuid, &self.geteuid, &self.getsuid], newids);
+        0
+    }
+
+    //------------------------------------GETRESUID SYSCALL------------------------------------
+    pub fn getresuid_syscall(&self, ruid: &mut u32, euid: &mut u32, suid: &mut u32) -> i32 {
+        *ruid = self.uid();
+        *euid = self.euid();
+        *suid = self.suid();
+        0
+    }
+
+    //------------------------------------SETGID SYSCALL------------------------------------
+    /*
+    *   setgid() sets all three gids when called by root, and only the effective gid otherwise
+    *   returns 0 on success, EPERM if the gid is not one the cage may take on
+    */
+    pub fn setgid_syscall(&self, gid: u32) -> i32 {
+        let ids = [&self.getgid, &self.getegid, &self.getsgid];
+        if self.euid() == sys_constants::ROOT_UID {
+            Self::set_ids(ids, [Some(gid), Some(gid), Some(gid)]);
+        } else if gid == self.gid() || gid == self.sgid() {
+            Self::set_ids(ids, [None, Some(gid), None]);
+        } else {
+            return syscall_error(Errno::EPERM, "setgid", "gid is not the real or saved gid");
+        }
+        0
+    }
+
+    //------------------------------------SETEGID SYSCALL------------------------------------
+    pub fn setegid_syscall(&self, egid: u32) -> i32 {
+        self.setresgid_syscall(u32::MAX, egid, u32::MAX)
+    }
+
+    //------------------------------------SETREGID SYSCALL------------------------------------
+    /*
+    *   setregid() follows the same rules as setreuid()
+    */
+    pub fn setregid_syscall(&self, rgid: u32, egid: u32) -> i32 {
+        let (oldgid, oldegid, oldsgid) = (self.gid(), self.egid(), self.sgid());
+        let rgid = Self::optional_id(rgid);
+        let egid = Self::optional_id(egid);
+        if rgid.map_or(false, |id| !self.may_take_id(id, [oldgid, oldegid, oldegid])) {
+            return syscall_error(Errno::EPERM, "setregid", "real gid may not be changed to that value");
+        }
+        if egid.map_or(false, |id| !self.may_take_id(id, [oldgid, oldegid, oldsgid])) {
+            return syscall_error(Errno::EPERM, "setregid", "effective gid may not be changed to that value");
+        }
+        let sgid = if rgid.is_some() || egid.map_or(false, |id| id != oldgid) {
+            Some(egid.unwrap_or(oldegid))
+        } else {
+            None
+        };
+        Self::set_ids([&self.getgid, &self.getegid, &self.getsgid], [rgid, egid, sgid]);
+        0
+    }
+
+    //------------------------------------SETRESGID SYSCALL------------------------------------
+    pub fn setresgid_syscall(&self, rgid: u32, egid: u32, sgid: u32) -> i32 {
+        let held = [self.gid(), self.egid(), self.sgid()];
+        let newids = [Self::optional_id(rgid), Self::optional_id(egid), Self::optional_id(sgid)];
+        if newids.iter().flatten().any(|id| !self.may_take_id(*id, held)) {
+            return syscall_error(Errno::EPERM, "setresgid", "gid may not be changed to that value");
+        }
+        Self::set_ids([&self.getgid, &self.getegid, &self.getsgid], newids);
+        0
+    }
+
+    //------------------------------------GETRESGID SYSCALL------------------------------------
+    pub fn getresgid_syscall(&self, rgid: &mut u32, egid: &mut u32, sgid: &mut u32) -> i32 {
+        *rgid = self.gid();
+        *egid = self.egid();
+        *sgid = self.sgid();
+        0
+    }
+
+    //------------------------------------GETGROUPS SYSCALL------------------------------------
+    /*
+    *   getgroups() copies the supplementary gids into list, or just counts them when size is 0
+    *   returns the number of gids, EINVAL if list is too small to hold them
+    */
+    pub fn getgroups_syscall(&self, size: i32, list: &mut [u32]) -> i32 {
+        let groups = self.groups.read();
+        if size == 0 {
+            return groups.len() as i32;
+        }
+        if size < 0 || (size as usize) < groups.len() || list.len() < groups.len() {
+            return syscall_error(Errno::EINVAL, "getgroups", "size is less than the number of supplementary gids");
+        }
+        list[..groups.len()].copy_from_slice(&groups);
+        groups.len() as i32
+    }
+
+    //------------------------------------SETGROUPS SYSCALL------------------------------------
+    /*
+    *   setgroups() replaces the supplementary gids, which only root may do
+    *   returns 0 on success, EPERM for anyone but root, EINVAL for more than NGROUPS_MAX gids
+    */
+    pub fn setgroups_syscall(&self, list: &[u32]) -> i32 {
+        if self.euid() != sys_constants::ROOT_UID {
+            return syscall_error(Errno::EPERM, "setgroups", "only root may set the supplementary gids");
+        }
+        if list.len() > sys_constants::NGROUPS_MAX {
+            return syscall_error(Errno::EINVAL, "setgroups", "more than NGROUPS_MAX gids");
+        }
+        *self.groups.write() = list.to_vec();
+        0
     }
 
     pub fn sigaction_syscall(
@@ -382,6 +756,10 @@ impl Cage {
 
         if let Some(some_set) = set {
             let curr_sigset = sigset.load(interface::RustAtomicOrdering::Relaxed);
+            let pendingsigs = match self.pendingsigset.get(&pthreadid) {
+                Some(pendingset) => pendingset.load(interface::RustAtomicOrdering::Relaxed),
+                None => 0,
+            };
             res = match how {
                 cage::SIG_BLOCK => {
                     // Block signals in set
@@ -394,14 +772,16 @@ impl Cage {
                 cage::SIG_UNBLOCK => {
                     // Unblock signals in set
                     let newset = curr_sigset & !*some_set;
-                    let pendingsignals = curr_sigset & some_set;
+                    // only signals that actually arrived while blocked get delivered
+                    let pendingsignals = pendingsigs & some_set;
                     sigset.store(newset, interface::RustAtomicOrdering::Relaxed);
                     self.send_pending_signals(pendingsignals, pthreadid);
                     0
                 }
                 cage::SIG_SETMASK => {
-                    // Set sigset to set
+                    // Set sigset to set, delivering whatever the new mask lets through
                     sigset.store(*some_set, interface::RustAtomicOrdering::Relaxed);
+                    self.send_pending_signals(pendingsigs & !*some_set, pthreadid);
                     0
                 }
                 _ => syscall_error(Errno::EINVAL, "sigprocmask", "Invalid value for how"),
@@ -445,32 +825,43 @@ impl Cage {
         0
     }
 
-    pub fn getrlimit(&self, res_type: u64, rlimit: &mut interface::Rlimit) -> i32 {
-        match res_type {
-            sys_constants::RLIMIT_NOFILE => {
-                rlimit.rlim_cur = NOFILE_CUR;
-                rlimit.rlim_max = NOFILE_MAX;
-            }
-            sys_constants::RLIMIT_STACK => {
-                rlimit.rlim_cur = STACK_CUR;
-                rlimit.rlim_max = STACK_MAX;
+    //------------------------------------GETRLIMIT SYSCALL------------------------------------
+    /*
+    *   getrlimit() fills in the soft and hard limit this cage has for the resource
+    *   returns 0 on success, EINVAL for a resource we keep no limit for
+    */
+    pub fn getrlimit_syscall(&self, res_type: u64, rlimit: &mut interface::Rlimit) -> i32 {
+        match self.rlimits.get(&res_type) {
+            Some(limit) => {
+                *rlimit = *limit;
+                0
             }
-            _ => return -1,
+            None => syscall_error(Errno::EINVAL, "getrlimit", "resource is not supported"),
         }
-        0
     }
 
-    pub fn setrlimit(&self, res_type: u64, _limit_value: u64) -> i32 {
-        match res_type {
-            sys_constants::RLIMIT_NOFILE => {
-                if NOFILE_CUR > NOFILE_MAX {
-                    -1
-                } else {
-                    0
-                }
-                //FIXME: not implemented yet to update value in program
-            }
-            _ => -1,
+    //------------------------------------SETRLIMIT SYSCALL------------------------------------
+    /*
+    *   setrlimit() changes a limit of this cage, children forked afterwards inherit it
+    *   the soft limit may not exceed the hard limit, and the hard limit can only be lowered
+    *   returns 0 on success, EINVAL or EPERM otherwise
+    */
+    pub fn setrlimit_syscall(&self, res_type: u64, newlimit: &interface::Rlimit) -> i32 {
+        let mut limit = match self.rlimits.get_mut(&res_type) {
+            Some(limit) => limit,
+            None => return syscall_error(Errno::EINVAL, "setrlimit", "resource is not supported"),
+        };
+        if newlimit.rlim_cur > newlimit.rlim_max {
+            return syscall_error(Errno::EINVAL, "setrlimit", "soft limit is greater than the hard limit");
+        }
+        if newlimit.rlim_max > limit.rlim_max {
+            return syscall_error(Errno::EPERM, "setrlimit", "hard limit can not be raised");
         }
+        // the hard limit never exceeds NOFILE_MAX, which the fd table can hold
+        if res_type == sys_constants::RLIMIT_NOFILE {
+            fdtables::set_fd_limit(self.cageid, newlimit.rlim_cur).unwrap();
+        }
+        *limit = *newlimit;
+        0
     }
 }
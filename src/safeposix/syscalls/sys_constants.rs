@@ -12,18 +12,38 @@ use crate::interface;
 pub const DEFAULT_UID: u32 = 1000;
 pub const DEFAULT_GID: u32 = 1000;
 
+// root, the only user that may take on ids it does not already hold
+pub const ROOT_UID: u32 = 0;
+
+// most supplementary groups a cage can be in, as on Linux
+pub const NGROUPS_MAX: usize = 65536;
+
 // RESOURCE LIMITS
 
 pub const SIGNAL_MAX: i32 = 64;
 
+pub const RLIM_INFINITY: u64 = u64::MAX;
+
+// the fd table of a cage has room for 1024 fds, so that is also the hard limit
 pub const NOFILE_CUR: u64 = 1024;
-pub const NOFILE_MAX: u64 = 4 * 1024;
+pub const NOFILE_MAX: u64 = 1024;
 
 pub const STACK_CUR: u64 = 8192 * 1024;
 pub const STACK_MAX: u64 = 1 << 32;
 
-pub const RLIMIT_STACK: u64 = 0;
-pub const RLIMIT_NOFILE: u64 = 1;
+// no core dumps by default, as on most Linux distributions
+pub const CORE_CUR: u64 = 0;
+pub const CORE_MAX: u64 = RLIM_INFINITY;
+
+// Resource numbers, same as Linux
+pub const RLIMIT_CPU: u64 = 0;
+pub const RLIMIT_FSIZE: u64 = 1;
+pub const RLIMIT_DATA: u64 = 2;
+pub const RLIMIT_STACK: u64 = 3;
+pub const RLIMIT_CORE: u64 = 4;
+pub const RLIMIT_NPROC: u64 = 6;
+pub const RLIMIT_NOFILE: u64 = 7;
+pub const RLIMIT_AS: u64 = 9;
 
 // Constants for exit_syscall status
 
@@ -16,6 +16,7 @@ pub mod fs_tests {
     pub use std::ffi::CStr as RustCStr;
     use std::mem;
     use crate::fdtables::FDTABLE;
+    use crate::fdtables;
 
     #[test]
     pub fn ut_lind_fs_simple() {
@@ -400,6 +401,454 @@ pub mod fs_tests {
         lindrustfinalize();
     }
 
+    #[test]
+    pub fn ut_lind_fs_chown() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage = interface::cagetable_getref(1);
+        let filepath = "/chownTestFile";
+        let fifopath = "/chownTestFifo";
+        let _ = cage.unlink_syscall(filepath);
+        let _ = cage.unlink_syscall(fifopath);
+
+        let mut statdata = StatData::default();
+        let fd = cage.open_syscall(filepath, O_CREAT | O_RDWR, 0o6755);
+        assert!(fd >= 0);
+        assert_eq!(cage.stat_syscall(filepath, &mut statdata), 0);
+        assert_eq!((statdata.st_uid, statdata.st_gid), (DEFAULT_UID, DEFAULT_GID));
+
+        //an ordinary owner can't give the file away, nor move it to a group it isn't in
+        assert_eq!(cage.chown_syscall(filepath, 0, u32::MAX), -(Errno::EPERM as i32));
+        assert_eq!(cage.chown_syscall(filepath, u32::MAX, 0), -(Errno::EPERM as i32));
+        //but it may move it to one of its own groups, which clears set-user-ID and set-group-ID
+        cage.groups.write().push(2000);
+        assert_eq!(cage.chown_syscall(filepath, u32::MAX, 2000), 0);
+        assert_eq!(cage.fstat_syscall(fd, &mut statdata), 0);
+        assert_eq!((statdata.st_uid, statdata.st_gid), (DEFAULT_UID, 2000));
+        assert_eq!(statdata.st_mode, 0o755 | S_IFREG as u32);
+
+        //root may hand the file to anyone, after which the old owner can't touch it
+        cage.geteuid.store(0, interface::RustAtomicOrdering::Relaxed);
+        assert_eq!(cage.fchown_syscall(fd, 3000, 3000), 0);
+        cage.geteuid.store(DEFAULT_UID as i32, interface::RustAtomicOrdering::Relaxed);
+        assert_eq!(cage.stat_syscall(filepath, &mut statdata), 0);
+        assert_eq!((statdata.st_uid, statdata.st_gid), (3000, 3000));
+        assert_eq!(cage.chmod_syscall(filepath, 0o777), -(Errno::EPERM as i32));
+        assert_eq!(cage.fchown_syscall(fd, u32::MAX, 2000), -(Errno::EPERM as i32));
+        //leaving both ids alone is not a change, so anyone may do it
+        assert_eq!(cage.chown_syscall(filepath, u32::MAX, u32::MAX), 0);
+
+        //a fifo can't carry the ownership xattr, and still reports its new owner
+        assert_eq!(cage.mknod_syscall(fifopath, S_IFIFO | 0o644, 0), 0);
+        cage.geteuid.store(0, interface::RustAtomicOrdering::Relaxed);
+        assert_eq!(cage.lchown_syscall(fifopath, 4000, 4000), 0);
+        assert_eq!(cage.stat_syscall(fifopath, &mut statdata), 0);
+        assert_eq!((statdata.st_uid, statdata.st_gid), (4000, 4000));
+
+        assert_eq!(cage.close_syscall(fd), 0);
+        assert_eq!(cage.unlink_syscall(filepath), 0);
+        assert_eq!(cage.unlink_syscall(fifopath), 0);
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_fs_umask() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage = interface::cagetable_getref(1);
+        let filepath = "/umaskTestFile";
+        let dirpath = "/umaskTestDir";
+        let _ = cage.unlink_syscall(filepath);
+        let _ = cage.rmdir_syscall(dirpath);
+
+        //a cage starts with the usual 022, and the host's own umask plays no part
+        assert_eq!(cage.umask_syscall(0o077), DEFAULT_UMASK);
+        let mut statdata = StatData::default();
+        let fd = cage.open_syscall(filepath, O_CREAT | O_RDWR, 0o666);
+        assert_eq!(cage.fstat_syscall(fd, &mut statdata), 0);
+        assert_eq!(statdata.st_mode, 0o600 | S_IFREG as u32);
+        assert_eq!(cage.close_syscall(fd), 0);
+        assert_eq!(cage.mkdir_syscall(dirpath, 0o777), 0);
+        assert_eq!(cage.stat_syscall(dirpath, &mut statdata), 0);
+        assert_eq!(statdata.st_mode, 0o700 | S_IFDIR as u32);
+
+        //opening a file that exists leaves its mode alone
+        assert_eq!(cage.umask_syscall(0), 0o077);
+        let fd = cage.open_syscall(filepath, O_CREAT | O_RDWR, 0o777);
+        assert_eq!(cage.fstat_syscall(fd, &mut statdata), 0);
+        assert_eq!(statdata.st_mode, 0o600 | S_IFREG as u32);
+        assert_eq!(cage.close_syscall(fd), 0);
+
+        //children inherit the mask
+        assert_eq!(cage.umask_syscall(0o027), 0);
+        assert_eq!(cage.fork_syscall(2), 0);
+        let child = interface::cagetable_getref(2);
+        assert_eq!(child.umask_syscall(0o022), 0o027);
+        assert_eq!(child.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+
+        assert_eq!(cage.unlink_syscall(filepath), 0);
+        assert_eq!(cage.rmdir_syscall(dirpath), 0);
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_fs_at_dirfd_relative() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage = interface::cagetable_getref(1);
+        let dirpath = "/atTestDir";
+        let _ = cage.unlink_syscall("/atTestDir/file");
+        let _ = cage.unlink_syscall("/atTestDir/link");
+        let _ = cage.unlink_syscall("/atTestDir/fdlink");
+        let _ = cage.rmdir_syscall("/atTestDir/sub");
+        let _ = cage.rmdir_syscall(dirpath);
+
+        assert_eq!(cage.mkdirat_syscall(AT_FDCWD, dirpath, 0o755), 0);
+        let dirfd = cage.open_syscall(dirpath, O_RDONLY | O_DIRECTORY, 0);
+        assert!(dirfd >= 0);
+
+        //relative paths are looked up beneath dirfd rather than the cwd
+        let fd = cage.openat_syscall(dirfd, "file", O_CREAT | O_RDWR, 0o644);
+        assert!(fd >= 0);
+        assert_eq!(cage.write_syscall(fd, "hello".as_ptr(), 5), 5);
+        let mut statdata = StatData::default();
+        assert_eq!(cage.fstatat_syscall(dirfd, "file", &mut statdata, 0), 0);
+        assert_eq!(statdata.st_size, 5);
+        assert_eq!(cage.stat_syscall("/atTestDir/file", &mut statdata), 0);
+        assert_eq!(statdata.st_size, 5);
+        assert_eq!(cage.mkdirat_syscall(dirfd, "sub", 0o755), 0);
+        assert_eq!(cage.fstatat_syscall(dirfd, "sub/../file", &mut statdata, AT_SYMLINK_NOFOLLOW), 0);
+        assert_eq!(statdata.st_size, 5);
+
+        //AT_EMPTY_PATH stats dirfd itself, which need not be a directory
+        assert_eq!(cage.fstatat_syscall(fd, "", &mut statdata, AT_EMPTY_PATH), 0);
+        assert_eq!(statdata.st_mode & S_IFMT, S_IFREG);
+        assert_eq!(cage.fstatat_syscall(dirfd, "", &mut statdata, 0), -(Errno::ENOENT as i32));
+
+        //absolute paths ignore dirfd, and a relative path needs a directory to start from
+        assert_eq!(cage.fstatat_syscall(fd, "/atTestDir/file", &mut statdata, 0), 0);
+        assert_eq!(cage.fstatat_syscall(fd, "file", &mut statdata, 0), -(Errno::ENOTDIR as i32));
+        assert_eq!(cage.fstatat_syscall(1000, "file", &mut statdata, 0), -(Errno::EBADF as i32));
+        assert_eq!(cage.fstatat_syscall(dirfd, "file", &mut statdata, 0x8000), -(Errno::EINVAL as i32));
+
+        //linkat names the same inode, either by path or by the fd with AT_EMPTY_PATH
+        assert_eq!(cage.linkat_syscall(dirfd, "file", dirfd, "link", 0), 0);
+        assert_eq!(cage.linkat_syscall(fd, "", dirfd, "fdlink", AT_EMPTY_PATH), 0);
+        assert_eq!(cage.fstatat_syscall(dirfd, "file", &mut statdata, 0), 0);
+        assert_eq!(statdata.st_nlink, 3);
+
+        assert_eq!(cage.fchmodat_syscall(dirfd, "link", 0o600, 0), 0);
+        assert_eq!(cage.fstat_syscall(fd, &mut statdata), 0);
+        assert_eq!(statdata.st_mode, 0o600 | S_IFREG as u32);
+        assert_eq!(cage.fchmodat_syscall(dirfd, "link", 0o600, AT_REMOVEDIR), -(Errno::EINVAL as i32));
+
+        //unlinkat removes directories only with AT_REMOVEDIR
+        assert_eq!(cage.unlinkat_syscall(dirfd, "sub", 0), -(Errno::EISDIR as i32));
+        assert_eq!(cage.unlinkat_syscall(dirfd, "sub", AT_REMOVEDIR), 0);
+        assert_eq!(cage.unlinkat_syscall(dirfd, "link", AT_SYMLINK_NOFOLLOW), -(Errno::EINVAL as i32));
+        assert_eq!(cage.unlinkat_syscall(dirfd, "link", 0), 0);
+        assert_eq!(cage.unlinkat_syscall(dirfd, "fdlink", 0), 0);
+        assert_eq!(cage.unlinkat_syscall(dirfd, "file", 0), 0);
+        assert_eq!(cage.fstat_syscall(fd, &mut statdata), 0);
+        assert_eq!(statdata.st_nlink, 0);
+
+        assert_eq!(cage.close_syscall(fd), 0);
+        assert_eq!(cage.close_syscall(dirfd), 0);
+        assert_eq!(cage.unlinkat_syscall(AT_FDCWD, dirpath, AT_REMOVEDIR), 0);
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_fs_at_dirfd_renamed_and_removed() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage = interface::cagetable_getref(1);
+        let _ = cage.unlink_syscall("/atMovedDir/file");
+        let _ = cage.rmdir_syscall("/atMovedDir");
+        let _ = cage.rmdir_syscall("/atMoveDir");
+
+        assert_eq!(cage.mkdir_syscall("/atMoveDir", 0o755), 0);
+        let dirfd = cage.open_syscall("/atMoveDir", O_RDONLY | O_DIRECTORY, 0);
+        assert!(dirfd >= 0);
+
+        //the lookup starts at the directory dirfd refers to, wherever it has been moved
+        assert_eq!(cage.rename_syscall("/atMoveDir", "/atMovedDir"), 0);
+        let fd = cage.openat_syscall(dirfd, "file", O_CREAT | O_WRONLY, 0o644);
+        assert!(fd >= 0);
+        assert_eq!(cage.close_syscall(fd), 0);
+        let mut statdata = StatData::default();
+        assert_eq!(cage.stat_syscall("/atMovedDir/file", &mut statdata), 0);
+        assert_eq!(cage.access_syscall("/atMoveDir", F_OK), -(Errno::ENOENT as i32));
+
+        //".." leads up from dirfd, and no further than the root
+        assert_eq!(cage.fstatat_syscall(dirfd, "../atMovedDir/file", &mut statdata, 0), 0);
+        assert_eq!(cage.fstatat_syscall(dirfd, "../../atMovedDir/file", &mut statdata, 0), -(Errno::EXDEV as i32));
+
+        //a removed directory has no entries to find or make
+        assert_eq!(cage.unlinkat_syscall(dirfd, "file", 0), 0);
+        assert_eq!(cage.rmdir_syscall("/atMovedDir"), 0);
+        assert_eq!(cage.fstatat_syscall(dirfd, "file", &mut statdata, 0), -(Errno::ENOENT as i32));
+        assert_eq!(cage.openat_syscall(dirfd, "file", O_CREAT | O_WRONLY, 0o644), -(Errno::ENOENT as i32));
+        assert_eq!(cage.access_syscall("/atMovedDir", F_OK), -(Errno::ENOENT as i32));
+
+        assert_eq!(cage.close_syscall(dirfd), 0);
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_fs_renameat2_flags() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage = interface::cagetable_getref(1);
+        let dirpath = "/renameatTestDir";
+        let _ = cage.unlink_syscall("/renameatTestDir/a");
+        let _ = cage.unlink_syscall("/renameatTestDir/b");
+        let _ = cage.unlink_syscall("/renameatTestDir/c");
+        let _ = cage.rmdir_syscall(dirpath);
+
+        assert_eq!(cage.mkdir_syscall(dirpath, 0o755), 0);
+        let dirfd = cage.open_syscall(dirpath, O_RDONLY | O_DIRECTORY, 0);
+        let fd = cage.openat_syscall(dirfd, "a", O_CREAT | O_WRONLY, 0o644);
+        assert_eq!(cage.write_syscall(fd, "a".as_ptr(), 1), 1);
+        assert_eq!(cage.close_syscall(fd), 0);
+        let fd = cage.openat_syscall(dirfd, "b", O_CREAT | O_WRONLY, 0o644);
+        assert_eq!(cage.write_syscall(fd, "bb".as_ptr(), 2), 2);
+        assert_eq!(cage.close_syscall(fd), 0);
+
+        //RENAME_NOREPLACE refuses to clobber an existing file
+        assert_eq!(
+            cage.renameat2_syscall(dirfd, "a", dirfd, "b", RENAME_NOREPLACE),
+            -(Errno::EEXIST as i32)
+        );
+        assert_eq!(
+            cage.renameat2_syscall(dirfd, "a", dirfd, "b", RENAME_NOREPLACE | RENAME_EXCHANGE),
+            -(Errno::EINVAL as i32)
+        );
+
+        //RENAME_EXCHANGE swaps the two files
+        let mut statdata = StatData::default();
+        assert_eq!(cage.renameat2_syscall(dirfd, "a", AT_FDCWD, "/renameatTestDir/b", RENAME_EXCHANGE), 0);
+        assert_eq!(cage.fstatat_syscall(dirfd, "a", &mut statdata, 0), 0);
+        assert_eq!(statdata.st_size, 2);
+        assert_eq!(cage.fstatat_syscall(dirfd, "b", &mut statdata, 0), 0);
+        assert_eq!(statdata.st_size, 1);
+        assert_eq!(
+            cage.renameat2_syscall(dirfd, "a", dirfd, "c", RENAME_EXCHANGE),
+            -(Errno::ENOENT as i32)
+        );
+
+        //without flags it is a plain rename
+        assert_eq!(cage.renameat2_syscall(dirfd, "a", dirfd, "c", RENAME_NOREPLACE), 0);
+        assert_eq!(cage.renameat2_syscall(dirfd, "c", dirfd, "b", 0), 0);
+        assert_eq!(cage.fstatat_syscall(dirfd, "b", &mut statdata, 0), 0);
+        assert_eq!(statdata.st_size, 2);
+
+        assert_eq!(cage.unlinkat_syscall(dirfd, "b", 0), 0);
+        assert_eq!(cage.close_syscall(dirfd), 0);
+        assert_eq!(cage.rmdir_syscall(dirpath), 0);
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_fs_symlink_readlink_lstat() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage = interface::cagetable_getref(1);
+        for path in ["/symTestLink", "/symTestRelLink", "/symTestDangling", "/symTestCreated",
+                     "/symTestDirLink", "/symTestDir/file", "/symTestFile"] {
+            let _ = cage.unlink_syscall(path);
+        }
+        let _ = cage.rmdir_syscall("/symTestDir");
+
+        let fd = cage.open_syscall("/symTestFile", O_CREAT | O_RDWR, 0o644);
+        assert_eq!(cage.write_syscall(fd, "hello".as_ptr(), 5), 5);
+        assert_eq!(cage.close_syscall(fd), 0);
+
+        //absolute targets are cage paths, and readlink gives back what was stored
+        assert_eq!(cage.symlink_syscall("/symTestFile", "/symTestLink"), 0);
+        assert_eq!(cage.symlink_syscall("symTestFile", "/symTestRelLink"), 0);
+        assert_eq!(cage.symlink_syscall("/symTestFile", "/symTestLink"), -(Errno::EEXIST as i32));
+        let mut buf = [0u8; 64];
+        assert_eq!(cage.readlink_syscall("/symTestLink", buf.as_mut_ptr(), buf.len()), 12);
+        assert_eq!(&buf[..12], b"/symTestFile");
+        assert_eq!(cage.readlink_syscall("/symTestRelLink", buf.as_mut_ptr(), buf.len()), 11);
+        assert_eq!(&buf[..11], b"symTestFile");
+        //the target is truncated to the buffer, without a null terminator
+        let mut small = [0u8; 4];
+        assert_eq!(cage.readlink_syscall("/symTestLink", small.as_mut_ptr(), small.len()), 4);
+        assert_eq!(&small, b"/sym");
+        assert_eq!(cage.readlink_syscall("/symTestFile", buf.as_mut_ptr(), buf.len()), -(Errno::EINVAL as i32));
+
+        //stat follows the link, lstat and fstatat(AT_SYMLINK_NOFOLLOW) do not
+        let mut statdata = StatData::default();
+        assert_eq!(cage.stat_syscall("/symTestLink", &mut statdata), 0);
+        assert_eq!(statdata.st_mode & S_IFMT, S_IFREG);
+        assert_eq!(statdata.st_size, 5);
+        assert_eq!(cage.lstat_syscall("/symTestLink", &mut statdata), 0);
+        assert_eq!(statdata.st_mode, S_IFLNK | 0o777);
+        assert_eq!(statdata.st_size, 12);
+        assert_eq!(cage.fstatat_syscall(AT_FDCWD, "/symTestRelLink", &mut statdata, AT_SYMLINK_NOFOLLOW), 0);
+        assert_eq!(statdata.st_mode & S_IFMT, S_IFLNK);
+        assert_eq!(cage.lstat_syscall("/symTestFile", &mut statdata), 0);
+        assert_eq!(statdata.st_mode & S_IFMT, S_IFREG);
+
+        let fd = cage.open_syscall("/symTestLink", O_RDONLY, 0);
+        let mut readbuf = [0u8; 5];
+        assert_eq!(cage.read_syscall(fd, readbuf.as_mut_ptr(), 5), 5);
+        assert_eq!(&readbuf, b"hello");
+        assert_eq!(cage.close_syscall(fd), 0);
+
+        //a dangling link can be lstat'ed, and opening it with O_CREAT makes its target
+        assert_eq!(cage.symlink_syscall("/symTestCreated", "/symTestDangling"), 0);
+        assert_eq!(cage.stat_syscall("/symTestDangling", &mut statdata), -(Errno::ENOENT as i32));
+        assert_eq!(cage.lstat_syscall("/symTestDangling", &mut statdata), 0);
+        let fd = cage.open_syscall("/symTestDangling", O_CREAT | O_WRONLY, 0o644);
+        assert!(fd >= 0);
+        assert_eq!(cage.close_syscall(fd), 0);
+        assert_eq!(cage.stat_syscall("/symTestCreated", &mut statdata), 0);
+
+        //absolute links work in the middle of a path too
+        assert_eq!(cage.mkdir_syscall("/symTestDir", 0o755), 0);
+        assert_eq!(cage.symlinkat_syscall("/symTestDir", AT_FDCWD, "/symTestDirLink"), 0);
+        let fd = cage.open_syscall("/symTestDirLink/file", O_CREAT | O_WRONLY, 0o644);
+        assert!(fd >= 0);
+        assert_eq!(cage.close_syscall(fd), 0);
+        assert_eq!(cage.stat_syscall("/symTestDir/file", &mut statdata), 0);
+
+        //readlinkat with an empty path reads the link an O_PATH fd refers to
+        let linkfd = cage.open_syscall("/symTestDirLink", O_PATH | O_NOFOLLOW, 0);
+        assert!(linkfd >= 0);
+        assert_eq!(cage.readlinkat_syscall(linkfd, "", buf.as_mut_ptr(), buf.len()), 11);
+        assert_eq!(&buf[..11], b"/symTestDir");
+        assert_eq!(cage.close_syscall(linkfd), 0);
+
+        //removing a link leaves its target alone
+        for path in ["/symTestLink", "/symTestRelLink", "/symTestDangling", "/symTestDirLink"] {
+            assert_eq!(cage.unlink_syscall(path), 0);
+        }
+        assert_eq!(cage.stat_syscall("/symTestFile", &mut statdata), 0);
+        assert_eq!(cage.unlink_syscall("/symTestFile"), 0);
+        assert_eq!(cage.unlink_syscall("/symTestCreated"), 0);
+        assert_eq!(cage.unlink_syscall("/symTestDir/file"), 0);
+        assert_eq!(cage.rmdir_syscall("/symTestDir"), 0);
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_fs_vectored_io() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage = interface::cagetable_getref(1);
+        let filepath = "/vectoredTestFile";
+        let _ = cage.unlink_syscall(filepath);
+        let fd = cage.open_syscall(filepath, O_CREAT | O_RDWR, 0o644);
+
+        //pwritev gathers the buffers at the offset and leaves the file offset alone
+        let (hello, world) = (b"hello", b"world");
+        let iovs = [
+            interface::IovecStruct { iov_base: hello.as_ptr() as *mut c_void, iov_len: 5 },
+            interface::IovecStruct { iov_base: world.as_ptr() as *mut c_void, iov_len: 5 },
+        ];
+        assert_eq!(cage.pwritev_syscall(fd, iovs.as_ptr(), 2, 10), 10);
+        assert_eq!(cage.lseek_syscall(fd, 0, SEEK_CUR), 0);
+        assert_eq!(cage.writev_syscall(fd, iovs.as_ptr(), 1), 5);
+        assert_eq!(cage.lseek_syscall(fd, 0, SEEK_CUR), 5);
+
+        //preadv scatters in order, readv reads at the file offset
+        let mut first = [0u8; 3];
+        let mut second = [0u8; 7];
+        let readiovs = [
+            interface::IovecStruct { iov_base: first.as_mut_ptr() as *mut c_void, iov_len: 3 },
+            interface::IovecStruct { iov_base: second.as_mut_ptr() as *mut c_void, iov_len: 7 },
+        ];
+        assert_eq!(cage.preadv_syscall(fd, readiovs.as_ptr(), 2, 10), 10);
+        assert_eq!(&first, b"hel");
+        assert_eq!(&second, b"loworld");
+        assert_eq!(cage.lseek_syscall(fd, 10, SEEK_SET), 10);
+        assert_eq!(cage.readv_syscall(fd, readiovs.as_ptr(), 2), 10);
+        assert_eq!(cage.lseek_syscall(fd, 0, SEEK_CUR), 20);
+        assert_eq!(cage.readv_syscall(fd, readiovs.as_ptr(), 2), 0);
+
+        //the v2 calls take RWF_* flags, and an offset of -1 means the file offset
+        assert_eq!(cage.lseek_syscall(fd, 0, SEEK_SET), 0);
+        assert_eq!(cage.preadv2_syscall(fd, readiovs.as_ptr(), 1, -1, 0), 3);
+        assert_eq!(&first, b"hel");
+        assert_eq!(cage.lseek_syscall(fd, 0, SEEK_CUR), 3);
+        assert_eq!(cage.pwritev2_syscall(fd, iovs[1..].as_ptr(), 1, 0, RWF_APPEND), 5);
+        let mut statdata = StatData::default();
+        assert_eq!(cage.fstat_syscall(fd, &mut statdata), 0);
+        assert_eq!(statdata.st_size, 25);
+        assert_eq!(cage.pwritev2_syscall(fd, iovs.as_ptr(), 1, 0, 0x4000_0000), -(Errno::EOPNOTSUPP as i32));
+
+        assert_eq!(cage.readv_syscall(fd, readiovs.as_ptr(), -1), -(Errno::EINVAL as i32));
+        assert_eq!(cage.readv_syscall(fd, readiovs.as_ptr(), libc::UIO_MAXIOV + 1), -(Errno::EINVAL as i32));
+        assert_eq!(cage.preadv_syscall(fd, readiovs.as_ptr(), 2, -1), -(Errno::EINVAL as i32));
+        assert_eq!(cage.close_syscall(fd), 0);
+
+        //on a pipe, one readv takes what is there and there is nothing to seek
+        let mut pipe_fds = PipeArray::default();
+        assert_eq!(cage.pipe_syscall(&mut pipe_fds), 0);
+        assert_eq!(cage.writev_syscall(pipe_fds.writefd, iovs.as_ptr(), 2), 10);
+        assert_eq!(cage.preadv_syscall(pipe_fds.readfd, readiovs.as_ptr(), 2, 0), -(Errno::ESPIPE as i32));
+        assert_eq!(cage.preadv2_syscall(pipe_fds.readfd, readiovs.as_ptr(), 2, -1, 0), 10);
+        assert_eq!(&first, b"hel");
+        assert_eq!(&second, b"loworld");
+
+        //a read can't return more than the pipe holds, however much room the iovecs claim
+        assert_eq!(cage.writev_syscall(pipe_fds.writefd, iovs.as_ptr(), 2), 10);
+        first = [0u8; 3];
+        second = [0u8; 7];
+        let hugeiovs = [
+            interface::IovecStruct { iov_base: first.as_mut_ptr() as *mut c_void, iov_len: 3 },
+            interface::IovecStruct { iov_base: second.as_mut_ptr() as *mut c_void, iov_len: 1 << 40 },
+        ];
+        assert_eq!(cage.readv_syscall(pipe_fds.readfd, hugeiovs.as_ptr(), 2), 10);
+        assert_eq!(&first, b"hel");
+        assert_eq!(&second, b"loworld");
+        assert_eq!(cage.close_syscall(pipe_fds.readfd), 0);
+        assert_eq!(cage.close_syscall(pipe_fds.writefd), 0);
+
+        //iovecs from a cage hold offsets into its memory, which get translated
+        let mut memory = [0u8; 64];
+        let start_address = memory.as_mut_ptr() as u64;
+        let wasmiovs = [
+            interface::WasmIovecStruct { iov_base: 32, iov_len: 4 },
+            interface::WasmIovecStruct { iov_base: 48, iov_len: 8 },
+        ];
+        unsafe {
+            std::ptr::copy_nonoverlapping(wasmiovs.as_ptr() as *const u8, memory.as_mut_ptr().add(8), mem::size_of_val(&wasmiovs));
+        }
+        let hostiovs = interface::get_iovecstruct(start_address, 8, 2).unwrap();
+        assert_eq!(hostiovs[0].iov_base as u64, start_address + 32);
+        assert_eq!(hostiovs[1].iov_base as u64, start_address + 48);
+        assert_eq!(hostiovs[1].iov_len, 8);
+        assert!(interface::get_iovecstruct(start_address, 0, 1).is_err());
+
+        assert_eq!(cage.unlink_syscall(filepath), 0);
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
     #[test]
     pub fn ut_lind_fs_mmap_zerolen() {
         //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
@@ -1321,6 +1770,17 @@ pub mod fs_tests {
         let filefd2 = cage.fcntl_syscall(filefd1, F_DUPFD, 100);
         assert!(filefd2 >= 100 && filefd2 != filefd1);
 
+        //the lowest free fd from arg on is taken, an open one is left alone
+        assert_eq!(cage.fcntl_syscall(filefd1, F_DUPFD, filefd2), filefd2 + 1);
+        assert_eq!(cage.fcntl_syscall(filefd2, F_GETFD, 0), 0);
+        assert_eq!(cage.fcntl_syscall(filefd2 + 1, F_GETFD, 0), 0);
+        assert_eq!(cage.close_syscall(filefd2 + 1), 0);
+        //F_DUPFD_CLOEXEC also sets close-on-exec on the new fd only
+        assert_eq!(cage.fcntl_syscall(filefd1, libc::F_DUPFD_CLOEXEC, filefd2), filefd2 + 1);
+        assert_eq!(cage.fcntl_syscall(filefd2 + 1, F_GETFD, 0), FD_CLOEXEC);
+        assert_eq!(cage.fcntl_syscall(filefd1, F_GETFD, 0), 0);
+        assert_eq!(cage.close_syscall(filefd2 + 1), 0);
+
         //to check if both file descriptors refer to the same fie, we can write into a
         // file using one file descriptor, read from the file using another file
         // descriptor, and make sure that the contents are the same
@@ -2866,7 +3326,7 @@ pub mod fs_tests {
         let mut shmidstruct = ShmidsStruct::default();
 
         // shmget returns an identifier in shmid
-        let shmid = cage.shmget_syscall(key, 1024, 0666 | IPC_CREAT);
+        let shmid = cage.shmget_syscall(key, 1024, 0o666 | IPC_CREAT);
 
         // shmat to attach to shared memory
         let shmatret = cage.shmat_syscall(shmid, 0xfffff000 as *mut u8, 0);
@@ -2941,7 +3401,7 @@ pub mod fs_tests {
         // shared between the parent and child process.
         // IPC_CREAT tells the system to create a new memory segment for the shared
         // memory and 0666 sets the access permissions of the memory segment.
-        let shmid = cage.shmget_syscall(key, 1024, 0666 | IPC_CREAT);
+        let shmid = cage.shmget_syscall(key, 1024, 0o666 | IPC_CREAT);
 
         // Attach shared memory for semaphore access.
         let shmatret = cage.shmat_syscall(shmid, 0xfffff000 as *mut u8, 0);
@@ -3015,7 +3475,7 @@ pub mod fs_tests {
         //This region will be shared between the parent and child process.
         // IPC_CREAT tells the system to create a new memory segment for the shared
         // memory and 0666 sets the access permissions of the memory segment.
-        let shmid = cage.shmget_syscall(key, 1024, 0666 | IPC_CREAT);
+        let shmid = cage.shmget_syscall(key, 1024, 0o666 | IPC_CREAT);
         // Attach the shared memory region to the address space of the process
         // to make sure for both processes to access the shared semaphore.
         let shmatret = cage.shmat_syscall(shmid, 0xfffff000 as *mut u8, 0);
@@ -3081,7 +3541,7 @@ pub mod fs_tests {
         let cage = interface::cagetable_getref(1);
         let key = 31337;
         // Create a shared memory region
-        let shmid = cage.shmget_syscall(key, 1024, 0666 | IPC_CREAT);
+        let shmid = cage.shmget_syscall(key, 1024, 0o666 | IPC_CREAT);
         // Attach the shared memory region
         let shmatret = cage.shmat_syscall(shmid, 0xfffff000 as *mut u8, 0);
         assert_ne!(shmatret, -1);
@@ -4387,11 +4847,9 @@ pub mod fs_tests {
             -(Errno::EEXIST as i32)
         );
 
-        // Check error when passing IPC_CREAT flag as the key
-        assert_eq!(
-            cage.shmget_syscall(IPC_PRIVATE, 1024, IPC_PRIVATE),
-            -(Errno::ENOENT as i32)
-        );
+        // IPC_PRIVATE always creates a new segment, even without IPC_CREAT
+        let privateid = cage.shmget_syscall(IPC_PRIVATE, 1024, IPC_PRIVATE);
+        assert!(privateid > 0 && privateid != shmid);
 
         // Check if the function returns a correct shmid upon asking with a key that we
         // know exists
@@ -4730,67 +5188,1279 @@ pub mod fs_tests {
     }
 
     #[test]
-    pub fn ut_lind_fs_close_chardev() {
+    pub fn ut_lind_fs_pipe_eof_and_epipe() {
         // acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
         // and also performs clean env setup
         let _thelock = setup::lock_and_init();
 
         let cage = interface::cagetable_getref(1);
 
-        // Ideally, we should have a character device file in the system
-        // and use that instead of creating a new file. But this is
-        // an sandboxed environment, so we need to create a file.
-        // Open a character device file.
-        let fd = cage.open_syscall("/dev/zero", O_RDWR | O_CREAT, S_IRWXA);
-        assert!(fd >= 0);
+        let mut pipe_fds = PipeArray::default();
+        assert_eq!(cage.pipe_syscall(&mut pipe_fds), 0);
+        let read_fd = pipe_fds.readfd;
+        let write_fd = pipe_fds.writefd;
 
-        // Close the character device file descriptor, which should succeed.
-        assert_eq!(cage.close_syscall(fd), 0);
+        // Each end only works in its own direction.
+        let mut buf = sizecbuf(7);
+        assert_eq!(cage.write_syscall(read_fd, "x".as_ptr(), 1), -(Errno::EBADF as i32));
+        assert_eq!(
+            cage.read_syscall(write_fd, buf.as_mut_ptr(), buf.len()),
+            -(Errno::EBADF as i32)
+        );
+
+        // A duplicate of the write end keeps the pipe open after the original is closed.
+        let write_dup = cage.dup_syscall(write_fd, None);
+        assert!(write_dup > 0);
+        assert_eq!(cage.write_syscall(write_fd, "Testing".as_ptr(), 7), 7);
+        assert_eq!(cage.close_syscall(write_fd), 0);
+        assert_eq!(cage.read_syscall(read_fd, buf.as_mut_ptr(), buf.len()), 7);
+        assert_eq!(cbuf2str(&buf), "Testing");
+        assert_eq!(cage.fcntl_syscall(read_fd, F_SETFL, O_NONBLOCK), 0);
+        assert_eq!(cage.fcntl_syscall(read_fd, F_GETFL, 0), O_RDONLY | O_NONBLOCK);
+        assert_eq!(
+            cage.read_syscall(read_fd, buf.as_mut_ptr(), buf.len()),
+            -(Errno::EAGAIN as i32)
+        );
+
+        // Once the last write end is gone the reader gets EOF.
+        assert_eq!(cage.close_syscall(write_dup), 0);
+        assert_eq!(cage.read_syscall(read_fd, buf.as_mut_ptr(), buf.len()), 0);
+        assert_eq!(cage.close_syscall(read_fd), 0);
+
+        // Writing without any reader fails with EPIPE and raises SIGPIPE, which stays
+        // parked because the cage's main thread is not set up in the tests.
+        assert_eq!(cage.pipe_syscall(&mut pipe_fds), 0);
+        assert_eq!(cage.close_syscall(pipe_fds.readfd), 0);
+        assert_eq!(
+            cage.write_syscall(pipe_fds.writefd, "x".as_ptr(), 1),
+            -(Errno::EPIPE as i32)
+        );
+        let sigpipe = interface::lind_sigaddset(interface::lind_sigemptyset(), libc::SIGPIPE);
+        assert_eq!(
+            cage.pendingsigset.get(&0).unwrap().load(interface::RustAtomicOrdering::Relaxed) & sigpipe,
+            sigpipe
+        );
+        assert_eq!(cage.close_syscall(pipe_fds.writefd), 0);
 
-        // Attempt to close the file descriptor again to ensure it's already closed.
-        // Expect an error for "Invalid File Descriptor".
-        assert_eq!(cage.close_syscall(fd), -(Errno::EBADF as i32));
-        // Remove the file to clean up the environment
-        let _ = cage.unlink_syscall("/dev/zero");
         assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
         lindrustfinalize();
     }
 
-    // #[test]
-    // pub fn ut_lind_fs_stat_syscall_tests() {
-    //     // acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
-    //     // and also performs clean env setup
-    //     let _thelock = setup::lock_and_init();
+    #[test]
+    pub fn ut_lind_fs_pipe_full_and_blocking() {
+        // acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
 
-    //     let cage = interface::cagetable_getref(1);
-    //     let mut statdata = StatData::default();
+        let cage = interface::cagetable_getref(1);
 
-    //     // test out whether an error is output for a non existent file path
-    //     // (ENOENT[-2])
-    //     assert_eq!(
-    //         cage.stat_syscall("non_existent_file_path", &mut statdata),
-    //         syscall_error(Errno::ENOENT, "stat", "test_failure")
-    //     );
+        let mut pipe_fds = PipeArray::default();
+        assert_eq!(cage.pipe2_syscall(&mut pipe_fds, O_NONBLOCK | O_CLOEXEC), 0);
+        let read_fd = pipe_fds.readfd;
+        let write_fd = pipe_fds.writefd;
+        assert_eq!(cage.fcntl_syscall(write_fd, F_GETFD, 0), FD_CLOEXEC);
+        assert_eq!(cage.pipe2_syscall(&mut pipe_fds, O_APPEND), -(Errno::EINVAL as i32));
+
+        // Fill the pipe up to its capacity, after which a non-blocking write gives EAGAIN.
+        let capacity = cage.fcntl_syscall(write_fd, F_GETPIPE_SZ, 0);
+        assert_eq!(capacity, 65536);
+        let data = vec![7u8; capacity as usize];
+        assert_eq!(cage.write_syscall(write_fd, data.as_ptr(), data.len()), capacity);
+        assert_eq!(cage.write_syscall(write_fd, data.as_ptr(), 1), -(Errno::EAGAIN as i32));
+
+        // Room comes a page at a time, so reading less than a page still leaves no room.
+        let mut buf = sizecbuf(4096);
+        assert_eq!(cage.read_syscall(read_fd, buf.as_mut_ptr(), 100), 100);
+        assert_eq!(cage.write_syscall(write_fd, data.as_ptr(), 1), -(Errno::EAGAIN as i32));
+
+        // In blocking mode the writer waits until the reader makes room.
+        assert_eq!(cage.fcntl_syscall(write_fd, F_SETFL, 0), 0);
+        let reader = interface::cagetable_getref(1);
+        let thread = interface::helper_thread(move || {
+            interface::sleep(interface::RustDuration::from_millis(50));
+            assert_eq!(reader.read_syscall(read_fd, buf.as_mut_ptr(), 3996), 3996);
+        });
+        assert_eq!(cage.write_syscall(write_fd, data.as_ptr(), 10), 10);
+        thread.join().unwrap();
+
+        // And a blocking reader waits until data shows up.
+        assert_eq!(cage.fcntl_syscall(read_fd, F_SETFL, 0), 0);
+        let mut buf = sizecbuf(capacity as usize);
+        assert_eq!(cage.read_syscall(read_fd, buf.as_mut_ptr(), buf.len()), capacity - 4086);
+        let writer = interface::cagetable_getref(1);
+        let thread = interface::helper_thread(move || {
+            interface::sleep(interface::RustDuration::from_millis(50));
+            assert_eq!(writer.write_syscall(write_fd, "hello".as_ptr(), 5), 5);
+        });
+        assert_eq!(cage.read_syscall(read_fd, buf.as_mut_ptr(), buf.len()), 5);
+        assert_eq!(cbuf2str(&buf[..5]), "hello");
+        thread.join().unwrap();
 
-    //     // setting up directory inode object '/tmp' for testing stat_syscall with a
-    //     // directory
-    //     let dir_path = "/tmp"; // since setup already initializes tmp, assuming it is there
-    //     assert_eq!(cage.stat_syscall(dir_path, &mut statdata), 0);
+        assert_eq!(cage.close_syscall(read_fd), 0);
+        assert_eq!(cage.close_syscall(write_fd), 0);
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
 
-    //     // setting up generic inode object "/tmp/generic" for testing stat_syscall with
-    //     // a generic file
-    //     let generic_path = "/tmp/generic";
-    //     let creat_fd = cage.creat_syscall(generic_path, S_IRWXA);
-    //     assert!(creat_fd > 0);
-    //     assert_eq!(cage.stat_syscall(generic_path, &mut statdata), 0);
+    #[test]
+    pub fn ut_lind_fs_pipe_poll_select_epoll() {
+        // acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
 
-    //     // setting up character device inode object "/chardev" for testing stat_syscall
-    //     // with a character device
-    //     let dev = makedev(&DevNo { major: 1, minor: 3 });
-    //     let chardev_path = "/chardev";
-    //     assert_eq!(
-    //         cage.mknod_syscall(chardev_path, S_IRWXA | S_IFCHR as u32, dev),
-    //         0
+        let cage = interface::cagetable_getref(1);
+
+        let mut pipe_fds = PipeArray::default();
+        assert_eq!(cage.pipe_syscall(&mut pipe_fds), 0);
+        let read_fd = pipe_fds.readfd;
+        let write_fd = pipe_fds.writefd;
+
+        // poll: the empty pipe is only writable
+        let mut pollfds = vec![
+            PollStruct { fd: read_fd, events: libc::POLLIN, revents: 0 },
+            PollStruct { fd: write_fd, events: libc::POLLOUT, revents: 0 },
+        ];
+        assert_eq!(cage.poll_syscall(&mut pollfds, 2, 0), 1);
+        assert_eq!(pollfds[0].revents, 0);
+        assert_eq!(pollfds[1].revents, libc::POLLOUT);
+
+        // a write from another thread wakes up a poll waiting on the read end
+        let writer = interface::cagetable_getref(1);
+        let thread = interface::helper_thread(move || {
+            interface::sleep(interface::RustDuration::from_millis(50));
+            assert_eq!(writer.write_syscall(write_fd, "hi".as_ptr(), 2), 2);
+        });
+        assert_eq!(cage.poll_syscall(&mut pollfds[..1], 1, -1), 1);
+        assert_eq!(pollfds[0].revents, libc::POLLIN);
+        thread.join().unwrap();
+
+        // select sees the same readiness
+        let mut readfds = fdtables::_init_fd_set();
+        let mut writefds = fdtables::_init_fd_set();
+        fdtables::_fd_set(read_fd as u64, &mut readfds);
+        fdtables::_fd_set(write_fd as u64, &mut writefds);
+        let nfds = read_fd.max(write_fd) + 1;
+        assert_eq!(
+            cage.select_syscall(nfds, Some(&mut readfds), Some(&mut writefds), None, Some(interface::RustDuration::ZERO)),
+            2
+        );
+        assert!(fdtables::_fd_isset(read_fd as u64, &readfds));
+        assert!(fdtables::_fd_isset(write_fd as u64, &writefds));
+
+        // epoll reports the pipe until it is drained, and the hangup once the writer is gone
+        let epfd = cage.epoll_create_syscall(1);
+        let mut event = EpollEvent { events: libc::EPOLLIN as u32, fd: read_fd };
+        assert_eq!(cage.epoll_ctl_syscall(epfd, libc::EPOLL_CTL_ADD, read_fd, &mut event), 0);
+        assert_eq!(
+            cage.epoll_ctl_syscall(epfd, libc::EPOLL_CTL_ADD, read_fd, &mut event),
+            -(Errno::EEXIST as i32)
+        );
+        let mut events = vec![EpollEvent { events: 0, fd: 0 }; 4];
+        assert_eq!(cage.epoll_wait_syscall(epfd, &mut events, 4, 0), 1);
+        assert_eq!(events[0].fd, read_fd);
+        assert_eq!(events[0].events, libc::EPOLLIN as u32);
+
+        let mut buf = sizecbuf(2);
+        assert_eq!(cage.read_syscall(read_fd, buf.as_mut_ptr(), buf.len()), 2);
+        assert_eq!(cage.epoll_wait_syscall(epfd, &mut events, 4, 20), 0);
+
+        assert_eq!(cage.close_syscall(write_fd), 0);
+        assert_eq!(cage.epoll_wait_syscall(epfd, &mut events, 4, -1), 1);
+        assert_eq!(events[0].events, libc::EPOLLHUP as u32);
+        assert_eq!(cage.epoll_ctl_syscall(epfd, libc::EPOLL_CTL_DEL, read_fd, &mut event), 0);
+
+        assert_eq!(cage.close_syscall(read_fd), 0);
+        assert_eq!(cage.close_syscall(epfd), 0);
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_fs_pipe_across_fork() {
+        // acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage = interface::cagetable_getref(1);
+
+        let mut pipe_fds = PipeArray::default();
+        assert_eq!(cage.pipe_syscall(&mut pipe_fds), 0);
+        let read_fd = pipe_fds.readfd;
+        let write_fd = pipe_fds.writefd;
+
+        // The child inherits both ends; the reader only sees EOF after the parent's
+        // write end is closed and the child has exited.
+        assert_eq!(cage.fork_syscall(2), 0);
+        let child = interface::cagetable_getref(2);
+        assert_eq!(child.close_syscall(read_fd), 0);
+        assert_eq!(child.write_syscall(write_fd, "child".as_ptr(), 5), 5);
+        assert_eq!(cage.close_syscall(write_fd), 0);
+
+        let mut buf = sizecbuf(5);
+        assert_eq!(cage.read_syscall(read_fd, buf.as_mut_ptr(), buf.len()), 5);
+        assert_eq!(cbuf2str(&buf), "child");
+        assert_eq!(cage.fcntl_syscall(read_fd, F_SETFL, O_NONBLOCK), 0);
+        assert_eq!(
+            cage.read_syscall(read_fd, buf.as_mut_ptr(), buf.len()),
+            -(Errno::EAGAIN as i32)
+        );
+
+        assert_eq!(child.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        assert_eq!(cage.read_syscall(read_fd, buf.as_mut_ptr(), buf.len()), 0);
+
+        assert_eq!(cage.close_syscall(read_fd), 0);
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_fs_epoll_dup_fork_close() {
+        // acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage = interface::cagetable_getref(1);
+
+        let mut pipe_fds = PipeArray::default();
+        assert_eq!(cage.pipe_syscall(&mut pipe_fds), 0);
+        let read_fd = pipe_fds.readfd;
+        let write_fd = pipe_fds.writefd;
+        let udp_fd = cage.socket_syscall(libc::AF_INET, libc::SOCK_DGRAM, 0);
+        assert!(udp_fd > 0);
+
+        // an in-memory pipe and a kernel socket in the same instance
+        let epfd = cage.epoll_create_syscall(1);
+        assert!(epfd > 0);
+        let mut statdata = StatData::default();
+        assert_eq!(cage.fstat_syscall(epfd, &mut statdata), 0);
+        assert_eq!(statdata.st_mode & libc::S_IFMT, 0);
+        let mut pipe_event = EpollEvent { events: libc::EPOLLIN as u32, fd: read_fd };
+        let mut udp_event = EpollEvent { events: libc::EPOLLOUT as u32, fd: udp_fd };
+        assert_eq!(cage.epoll_ctl_syscall(epfd, libc::EPOLL_CTL_ADD, read_fd, &mut pipe_event), 0);
+        assert_eq!(cage.epoll_ctl_syscall(epfd, libc::EPOLL_CTL_ADD, udp_fd, &mut udp_event), 0);
+        assert_eq!(cage.write_syscall(write_fd, "hi".as_ptr(), 2), 2);
+
+        // a duplicate and a forked copy share the registrations
+        let dup_epfd = cage.dup_syscall(epfd, None);
+        assert!(dup_epfd > 0);
+        let mut events = vec![EpollEvent { events: 0, fd: 0 }; 4];
+        assert_eq!(cage.epoll_wait_syscall(dup_epfd, &mut events, 4, 0), 2);
+        let mut ready: Vec<i32> = events[..2].iter().map(|event| event.fd).collect();
+        ready.sort();
+        assert_eq!(ready, vec![read_fd.min(udp_fd), read_fd.max(udp_fd)]);
+
+        assert_eq!(cage.fork_syscall(2), 0);
+        let child = interface::cagetable_getref(2);
+        assert_eq!(child.epoll_ctl_syscall(epfd, libc::EPOLL_CTL_DEL, udp_fd, &mut udp_event), 0);
+        assert_eq!(child.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        assert_eq!(cage.epoll_wait_syscall(epfd, &mut events, 4, 0), 1);
+        assert_eq!(events[0].fd, read_fd);
+
+        // a registration does not outlive its fd number being reused
+        assert_eq!(cage.close_syscall(read_fd), 0);
+        assert_eq!(cage.close_syscall(write_fd), 0);
+        assert_eq!(cage.epoll_wait_syscall(epfd, &mut events, 4, 0), 0);
+        assert_eq!(cage.pipe_syscall(&mut pipe_fds), 0);
+        assert_eq!(pipe_fds.readfd, read_fd);
+        assert_eq!(cage.epoll_ctl_syscall(epfd, libc::EPOLL_CTL_ADD, read_fd, &mut pipe_event), 0);
+        assert_eq!(cage.write_syscall(pipe_fds.writefd, "hi".as_ptr(), 2), 2);
+        assert_eq!(cage.epoll_wait_syscall(epfd, &mut events, 4, 0), 1);
+        assert_eq!(events[0].fd, read_fd);
+
+        // closing the last fd of a kernel socket drops its registration, so a new socket
+        // that gets the same fd number (and host fd) can be added again
+        assert_eq!(cage.epoll_ctl_syscall(epfd, libc::EPOLL_CTL_ADD, udp_fd, &mut udp_event), 0);
+        assert_eq!(cage.close_syscall(udp_fd), 0);
+        let registrations = fdtables::get_virtual_epoll_wait_data(cage.cageid, epfd as u64).unwrap();
+        assert!(registrations.get(&0).map_or(true, |fdmap| !fdmap.contains_key(&(udp_fd as u64))));
+        assert_eq!(cage.socket_syscall(libc::AF_INET, libc::SOCK_DGRAM, 0), udp_fd);
+        assert_eq!(cage.epoll_ctl_syscall(epfd, libc::EPOLL_CTL_ADD, udp_fd, &mut udp_event), 0);
+        assert_eq!(cage.epoll_wait_syscall(epfd, &mut events, 4, 0), 2);
+
+        // what the kernel refuses is not registered either
+        let file_fd = cage.open_syscall("/epollfile", O_CREAT | O_RDWR, S_IRWXA);
+        assert!(file_fd > 0);
+        let mut file_event = EpollEvent { events: libc::EPOLLIN as u32, fd: file_fd };
+        assert_eq!(cage.epoll_ctl_syscall(epfd, libc::EPOLL_CTL_ADD, file_fd, &mut file_event), -(Errno::EPERM as i32));
+        assert_eq!(cage.epoll_ctl_syscall(epfd, libc::EPOLL_CTL_DEL, file_fd, &mut file_event), -(Errno::ENOENT as i32));
+        assert_eq!(cage.close_syscall(file_fd), 0);
+        assert_eq!(cage.unlink_syscall("/epollfile"), 0);
+
+        // the kernel instance goes away with the last epfd referring to it
+        let kernel_epfd = fdtables::epoll_get_underfd_hashmap(cage.cageid, epfd as u64).unwrap()[&0] as i32;
+        assert_eq!(cage.close_syscall(epfd), 0);
+        assert!(unsafe { libc::fcntl(kernel_epfd, libc::F_GETFD) } >= 0);
+        assert_eq!(cage.close_syscall(dup_epfd), 0);
+        assert_eq!(unsafe { libc::fcntl(kernel_epfd, libc::F_GETFD) }, -1);
+
+        assert_eq!(cage.close_syscall(pipe_fds.readfd), 0);
+        assert_eq!(cage.close_syscall(pipe_fds.writefd), 0);
+        assert_eq!(cage.close_syscall(udp_fd), 0);
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_fs_epoll_nested() {
+        // acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage = interface::cagetable_getref(1);
+
+        let mut pipe_fds = PipeArray::default();
+        assert_eq!(cage.pipe_syscall(&mut pipe_fds), 0);
+        let read_fd = pipe_fds.readfd;
+        let write_fd = pipe_fds.writefd;
+
+        // the inner instance watches the pipe, the outer one watches the inner one
+        let inner = cage.epoll_create_syscall(1);
+        let outer = cage.epoll_create_syscall(1);
+        let mut pipe_event = EpollEvent { events: libc::EPOLLIN as u32, fd: read_fd };
+        let mut inner_event = EpollEvent { events: libc::EPOLLIN as u32, fd: inner };
+        let mut outer_event = EpollEvent { events: libc::EPOLLIN as u32, fd: outer };
+        assert_eq!(cage.epoll_ctl_syscall(inner, libc::EPOLL_CTL_ADD, read_fd, &mut pipe_event), 0);
+        assert_eq!(cage.epoll_ctl_syscall(outer, libc::EPOLL_CTL_ADD, inner, &mut inner_event), 0);
+        assert_eq!(
+            cage.epoll_ctl_syscall(inner, libc::EPOLL_CTL_ADD, outer, &mut outer_event),
+            -(Errno::ELOOP as i32)
+        );
+
+        let mut events = vec![EpollEvent { events: 0, fd: 0 }; 4];
+        assert_eq!(cage.epoll_wait_syscall(outer, &mut events, 4, 0), 0);
+
+        // a write from another thread wakes up the outer instance through the inner one
+        let writer = interface::cagetable_getref(1);
+        let thread = interface::helper_thread(move || {
+            interface::sleep(interface::RustDuration::from_millis(50));
+            assert_eq!(writer.write_syscall(write_fd, "hi".as_ptr(), 2), 2);
+        });
+        assert_eq!(cage.epoll_wait_syscall(outer, &mut events, 4, -1), 1);
+        assert_eq!(events[0].fd, inner);
+        assert_eq!(events[0].events, libc::EPOLLIN as u32);
+        thread.join().unwrap();
+        let mut pollfds = vec![PollStruct { fd: inner, events: libc::POLLIN, revents: 0 }];
+        assert_eq!(cage.poll_syscall(&mut pollfds, 1, 0), 1);
+        assert_eq!(pollfds[0].revents, libc::POLLIN);
+
+        // kernel fds in the inner instance count as well
+        let udp_fd = cage.socket_syscall(libc::AF_INET, libc::SOCK_DGRAM, 0);
+        let mut udp_event = EpollEvent { events: libc::EPOLLOUT as u32, fd: udp_fd };
+        assert_eq!(cage.epoll_ctl_syscall(inner, libc::EPOLL_CTL_DEL, read_fd, &mut pipe_event), 0);
+        assert_eq!(cage.epoll_wait_syscall(outer, &mut events, 4, 0), 0);
+        assert_eq!(cage.epoll_ctl_syscall(inner, libc::EPOLL_CTL_ADD, udp_fd, &mut udp_event), 0);
+        assert_eq!(cage.epoll_wait_syscall(outer, &mut events, 4, 0), 1);
+        assert_eq!(events[0].fd, inner);
+
+        // closing the inner instance takes it out of the outer one
+        assert_eq!(cage.close_syscall(inner), 0);
+        assert_eq!(cage.epoll_wait_syscall(outer, &mut events, 4, 0), 0);
+
+        // at most EP_MAX_NESTS levels of nesting below the outermost instance
+        let mut chain = vec![outer];
+        for _ in 0..fdtables::EP_MAX_NESTS {
+            let nested = cage.epoll_create_syscall(1);
+            let mut nested_event = EpollEvent { events: libc::EPOLLIN as u32, fd: nested };
+            assert_eq!(cage.epoll_ctl_syscall(*chain.last().unwrap(), libc::EPOLL_CTL_ADD, nested, &mut nested_event), 0);
+            chain.push(nested);
+        }
+        let too_deep = cage.epoll_create_syscall(1);
+        let mut too_deep_event = EpollEvent { events: libc::EPOLLIN as u32, fd: too_deep };
+        assert_eq!(
+            cage.epoll_ctl_syscall(*chain.last().unwrap(), libc::EPOLL_CTL_ADD, too_deep, &mut too_deep_event),
+            -(Errno::ELOOP as i32)
+        );
+
+        for epfd in chain {
+            assert_eq!(cage.close_syscall(epfd), 0);
+        }
+        assert_eq!(cage.close_syscall(too_deep), 0);
+        assert_eq!(cage.close_syscall(udp_fd), 0);
+        assert_eq!(cage.close_syscall(read_fd), 0);
+        assert_eq!(cage.close_syscall(write_fd), 0);
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_fs_unix_socketpair_stream() {
+        // acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage = interface::cagetable_getref(1);
+
+        let mut socketpair = interface::SockPair::default();
+        assert_eq!(cage.socketpair_syscall(libc::AF_UNIX, libc::SOCK_STREAM, 0, &mut socketpair), 0);
+        let (sock1, sock2) = (socketpair.sock1, socketpair.sock2);
+        assert_eq!(translate_virtual_fd(cage.cageid, sock1 as u64).unwrap().fdkind, 2);
+
+        let mut statdata = StatData::default();
+        assert_eq!(cage.fstat_syscall(sock1, &mut statdata), 0);
+        assert_eq!(statdata.st_mode & libc::S_IFMT, libc::S_IFSOCK);
+
+        // read/write and send/recv go through the same in-memory socket, both ways
+        assert_eq!(cage.write_syscall(sock1, "hello".as_ptr(), 5), 5);
+        assert_eq!(cage.send_syscall(sock1, " world".as_ptr(), 6, 0), 6);
+        let mut buf = sizecbuf(11);
+        assert_eq!(cage.recv_syscall(sock2, buf.as_mut_ptr(), 11, libc::MSG_WAITALL), 11);
+        assert_eq!(cbuf2str(&buf), "hello world");
+        assert_eq!(cage.write_syscall(sock2, "back".as_ptr(), 4), 4);
+        let mut buf = sizecbuf(4);
+        assert_eq!(cage.read_syscall(sock1, buf.as_mut_ptr(), 4), 4);
+        assert_eq!(cbuf2str(&buf), "back");
+
+        // nothing queued: a non-blocking receive fails instead of waiting
+        assert_eq!(
+            cage.recv_syscall(sock1, buf.as_mut_ptr(), 4, libc::MSG_DONTWAIT),
+            -(Errno::EAGAIN as i32)
+        );
+
+        // shutting down the write side gives the peer EOF
+        assert_eq!(cage.shutdown_syscall(sock1, libc::SHUT_WR), 0);
+        assert_eq!(cage.read_syscall(sock2, buf.as_mut_ptr(), 4), 0);
+
+        // once the peer is gone sends fail with EPIPE
+        assert_eq!(cage.close_syscall(sock1), 0);
+        assert_eq!(
+            cage.send_syscall(sock2, "x".as_ptr(), 1, libc::MSG_NOSIGNAL),
+            -(Errno::EPIPE as i32)
+        );
+
+        assert_eq!(cage.close_syscall(sock2), 0);
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_fs_unix_socketpair_dgram() {
+        // acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage = interface::cagetable_getref(1);
+
+        let mut socketpair = interface::SockPair::default();
+        assert_eq!(
+            cage.socketpair_syscall(libc::AF_UNIX, libc::SOCK_DGRAM | libc::SOCK_NONBLOCK, 0, &mut socketpair),
+            0
+        );
+        let (sock1, sock2) = (socketpair.sock1, socketpair.sock2);
+        assert_eq!(cage.fcntl_syscall(sock1, F_GETFL, 0), O_RDWR | O_NONBLOCK);
+
+        // message boundaries are kept, a short buffer truncates the message
+        assert_eq!(cage.send_syscall(sock1, "first".as_ptr(), 5, 0), 5);
+        assert_eq!(cage.send_syscall(sock1, "second".as_ptr(), 6, 0), 6);
+        let mut buf = sizecbuf(3);
+        assert_eq!(cage.recv_syscall(sock2, buf.as_mut_ptr(), 3, libc::MSG_TRUNC), 5);
+        assert_eq!(cbuf2str(&buf), "fir");
+        let mut buf = sizecbuf(16);
+        assert_eq!(cage.recv_syscall(sock2, buf.as_mut_ptr(), 16, libc::MSG_PEEK), 6);
+        assert_eq!(cage.recv_syscall(sock2, buf.as_mut_ptr(), 16, 0), 6);
+        assert_eq!(
+            cage.recv_syscall(sock2, buf.as_mut_ptr(), 16, 0),
+            -(Errno::EAGAIN as i32)
+        );
+
+        // a socketpair has no name
+        let mut addr = interface::GenSockaddr::V4(interface::SockaddrV4::default());
+        assert_eq!(cage.getsockname_syscall(sock1, &mut Some(&mut addr)), 0);
+        match addr {
+            interface::GenSockaddr::Unix(unixaddr) => assert_eq!(unixaddr.sun_path, [0; 108]),
+            _ => panic!("expected an AF_UNIX address"),
+        }
+
+        assert_eq!(cage.close_syscall(sock1), 0);
+        assert_eq!(cage.close_syscall(sock2), 0);
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_fs_unix_socket_path_connect_accept() {
+        // acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage = interface::cagetable_getref(1);
+        let sockpath = "/unixsock_path_test.sock";
+        let _ = cage.unlink_syscall(sockpath);
+        let sockaddr = interface::GenSockaddr::Unix(interface::new_sockaddr_unix(
+            libc::AF_UNIX as u16,
+            sockpath.as_bytes(),
+        ));
+
+        let serverfd = cage.socket_syscall(libc::AF_UNIX, libc::SOCK_STREAM, 0);
+        let clientfd = cage.socket_syscall(libc::AF_UNIX, libc::SOCK_STREAM, 0);
+        assert!(serverfd >= 0 && clientfd >= 0);
+
+        // nobody is bound yet
+        assert_eq!(cage.connect_syscall(clientfd, &sockaddr), -(Errno::ENOENT as i32));
+
+        // binding creates the socket file, and the path can only be taken once
+        assert_eq!(cage.bind_syscall(serverfd, &sockaddr), 0);
+        let mut statdata = StatData::default();
+        assert_eq!(cage.stat_syscall(sockpath, &mut statdata), 0);
+        assert_eq!(statdata.st_mode & libc::S_IFMT, libc::S_IFSOCK);
+        let otherfd = cage.socket_syscall(libc::AF_UNIX, libc::SOCK_STREAM, 0);
+        assert_eq!(cage.bind_syscall(otherfd, &sockaddr), -(Errno::EADDRINUSE as i32));
+
+        // bound but not listening
+        assert_eq!(cage.connect_syscall(clientfd, &sockaddr), -(Errno::ECONNREFUSED as i32));
+
+        assert_eq!(cage.listen_syscall(serverfd, 4), 0);
+        assert_eq!(cage.connect_syscall(clientfd, &sockaddr), 0);
+        assert_eq!(cage.connect_syscall(clientfd, &sockaddr), -(Errno::EISCONN as i32));
+
+        // the listener is readable once a connection is waiting
+        let mut pollfds = vec![interface::PollStruct { fd: serverfd, events: libc::POLLIN, revents: 0 }];
+        assert_eq!(cage.poll_syscall(&mut pollfds, 1, 0), 1);
+        assert_eq!(pollfds[0].revents, libc::POLLIN);
+
+        let mut peeraddr = interface::GenSockaddr::V4(interface::SockaddrV4::default());
+        let acceptedfd = cage.accept_syscall(serverfd, &mut Some(&mut peeraddr));
+        assert!(acceptedfd >= 0);
+        // the client never bound, so it shows up unnamed
+        match peeraddr {
+            interface::GenSockaddr::Unix(unixaddr) => assert_eq!(unixaddr.sun_path, [0; 108]),
+            _ => panic!("expected an AF_UNIX address"),
+        }
+
+        // the client's peer is the path it connected to
+        let mut addr = interface::GenSockaddr::V4(interface::SockaddrV4::default());
+        assert_eq!(cage.getpeername_syscall(clientfd, &mut Some(&mut addr)), 0);
+        match addr {
+            interface::GenSockaddr::Unix(unixaddr) => {
+                assert_eq!(&unixaddr.sun_path[..sockpath.len()], sockpath.as_bytes())
+            }
+            _ => panic!("expected an AF_UNIX address"),
+        }
+
+        assert_eq!(cage.send_syscall(clientfd, "ping".as_ptr(), 4, 0), 4);
+        let mut buf = sizecbuf(4);
+        assert_eq!(cage.recv_syscall(acceptedfd, buf.as_mut_ptr(), 4, 0), 4);
+        assert_eq!(cbuf2str(&buf), "ping");
+
+        // closing the accepted end gives the client EOF
+        assert_eq!(cage.close_syscall(acceptedfd), 0);
+        assert_eq!(cage.recv_syscall(clientfd, buf.as_mut_ptr(), 4, 0), 0);
+
+        // the file stays behind after the socket is closed, like in Linux
+        assert_eq!(cage.close_syscall(serverfd), 0);
+        assert_eq!(cage.close_syscall(clientfd), 0);
+        assert_eq!(cage.close_syscall(otherfd), 0);
+        assert_eq!(cage.access_syscall(sockpath, F_OK), 0);
+        assert_eq!(cage.unlink_syscall(sockpath), 0);
+
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_fs_unix_socket_abstract_dgram() {
+        // acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage = interface::cagetable_getref(1);
+        let serveraddr = interface::GenSockaddr::Unix(interface::new_sockaddr_unix(
+            libc::AF_UNIX as u16,
+            b"\0lind_abstract_server",
+        ));
+        let clientaddr = interface::GenSockaddr::Unix(interface::new_sockaddr_unix(
+            libc::AF_UNIX as u16,
+            b"\0lind_abstract_client",
+        ));
+
+        let serverfd = cage.socket_syscall(libc::AF_UNIX, libc::SOCK_DGRAM, 0);
+        let clientfd = cage.socket_syscall(libc::AF_UNIX, libc::SOCK_DGRAM, 0);
+        assert_eq!(cage.bind_syscall(serverfd, &serveraddr), 0);
+        assert_eq!(cage.bind_syscall(clientfd, &clientaddr), 0);
+        assert_eq!(cage.bind_syscall(clientfd, &serveraddr), -(Errno::EINVAL as i32));
+
+        // abstract names never touch the filesystem
+        let mut statdata = StatData::default();
+        assert_eq!(
+            cage.stat_syscall("/lind_abstract_server", &mut statdata),
+            -(Errno::ENOENT as i32)
+        );
+
+        // the receiver learns who sent the datagram
+        assert_eq!(cage.sendto_syscall(clientfd, "hi".as_ptr(), 2, 0, &serveraddr), 2);
+        let mut buf = sizecbuf(2);
+        let mut fromaddr = interface::GenSockaddr::V4(interface::SockaddrV4::default());
+        assert_eq!(
+            cage.recvfrom_syscall(serverfd, buf.as_mut_ptr(), 2, 0, &mut Some(&mut fromaddr)),
+            2
+        );
+        assert_eq!(cbuf2str(&buf), "hi");
+        match (fromaddr, clientaddr) {
+            (interface::GenSockaddr::Unix(from), interface::GenSockaddr::Unix(client)) => {
+                assert_eq!(from.sun_path, client.sun_path)
+            }
+            _ => panic!("expected an AF_UNIX address"),
+        }
+
+        // without a destination, an unconnected datagram socket has nowhere to send
+        assert_eq!(cage.send_syscall(serverfd, "x".as_ptr(), 1, 0), -(Errno::ENOTCONN as i32));
+
+        // the name is released on close
+        assert_eq!(cage.close_syscall(serverfd), 0);
+        assert_eq!(
+            cage.sendto_syscall(clientfd, "hi".as_ptr(), 2, 0, &serveraddr),
+            -(Errno::ECONNREFUSED as i32)
+        );
+
+        assert_eq!(cage.close_syscall(clientfd), 0);
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_fs_unix_scm_rights_across_cages() {
+        // acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage = interface::cagetable_getref(1);
+        let mut socketpair = interface::SockPair::default();
+        assert_eq!(cage.socketpair_syscall(libc::AF_UNIX, libc::SOCK_DGRAM, 0, &mut socketpair), 0);
+        let (sock1, sock2) = (socketpair.sock1, socketpair.sock2);
+        assert_eq!(cage.fork_syscall(2), 0);
+        let child = interface::cagetable_getref(2);
+
+        // the file is opened after the fork, so the child only gets it through the socket
+        let filepath = "/scm_rights_test.txt";
+        let filefd = cage.open_syscall(filepath, O_CREAT | O_TRUNC | O_RDWR, S_IRWXA);
+        assert_eq!(cage.write_syscall(filefd, "passed".as_ptr(), 6), 6);
+
+        let mut on: i32 = 1;
+        assert_eq!(child.setsockopt_syscall(sock2, libc::SOL_SOCKET, libc::SO_PASSCRED, &mut on as *mut i32 as *mut u8, 4), 0);
+
+        let mut data = *b"fd";
+        let mut iov = libc::iovec { iov_base: data.as_mut_ptr() as *mut c_void, iov_len: 2 };
+        let mut control = [0u64; 8];
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut c_void;
+        msg.msg_controllen = unsafe { CMSG_SPACE(4) } as usize;
+        let cmsg = unsafe { CMSG_FIRSTHDR(&msg) };
+        unsafe {
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = CMSG_LEN(4) as usize;
+            *(CMSG_DATA(cmsg) as *mut i32) = filefd;
+        }
+        // the message keeps the file open after the sender closes it
+        assert_eq!(cage.sendmsg_syscall(sock1, &msg, 0), 2);
+        assert_eq!(cage.close_syscall(filefd), 0);
+
+        let mut recvdata = [0u8; 8];
+        let mut recviov = libc::iovec { iov_base: recvdata.as_mut_ptr() as *mut c_void, iov_len: 8 };
+        let mut recvcontrol = [0u64; 16];
+        let mut recvmsg: libc::msghdr = unsafe { mem::zeroed() };
+        recvmsg.msg_iov = &mut recviov;
+        recvmsg.msg_iovlen = 1;
+        recvmsg.msg_control = recvcontrol.as_mut_ptr() as *mut c_void;
+        recvmsg.msg_controllen = mem::size_of_val(&recvcontrol);
+        assert_eq!(child.recvmsg_syscall(sock2, &mut recvmsg, libc::MSG_CMSG_CLOEXEC), 2);
+        assert_eq!(&recvdata[..2], b"fd");
+        assert_eq!(recvmsg.msg_flags, 0);
+        let (creds, newfd) = unsafe {
+            let first = CMSG_FIRSTHDR(&recvmsg);
+            assert_eq!((*first).cmsg_type, libc::SCM_CREDENTIALS);
+            let second = CMSG_NXTHDR(&recvmsg, first);
+            assert_eq!((*second).cmsg_type, libc::SCM_RIGHTS);
+            assert_eq!((*second).cmsg_len, CMSG_LEN(4) as usize);
+            assert!(CMSG_NXTHDR(&recvmsg, second).is_null());
+            ((CMSG_DATA(first) as *const ucred).read_unaligned(), *(CMSG_DATA(second) as *const i32))
+        };
+        // credentials name the sending cage and its virtual ids
+        assert_eq!((creds.pid, creds.uid, creds.gid), (1, DEFAULT_UID, DEFAULT_GID));
+        // the fd is the child's own, close-on-exec, and refers to the same file
+        assert!(translate_virtual_fd(child.cageid, newfd as u64).unwrap().should_cloexec);
+        let mut buf = sizecbuf(6);
+        assert_eq!(child.pread_syscall(newfd, buf.as_mut_ptr(), 6, 0), 6);
+        assert_eq!(cbuf2str(&buf), "passed");
+        assert_eq!(child.close_syscall(newfd), 0);
+
+        // fds that don't fit in msg_control are closed and libc::MSG_CTRUNC is set
+        let mut off: i32 = 0;
+        assert_eq!(child.setsockopt_syscall(sock2, libc::SOL_SOCKET, libc::SO_PASSCRED, &mut off as *mut i32 as *mut u8, 4), 0);
+        msg.msg_controllen = unsafe { CMSG_SPACE(8) } as usize;
+        unsafe {
+            (*cmsg).cmsg_len = CMSG_LEN(8) as usize;
+            let fds = CMSG_DATA(cmsg) as *mut i32;
+            *fds = sock1;
+            *fds.add(1) = sock1;
+        }
+        assert_eq!(cage.sendmsg_syscall(sock1, &msg, 0), 2);
+        recvmsg.msg_controllen = unsafe { CMSG_LEN(4) } as usize;
+        assert_eq!(child.recvmsg_syscall(sock2, &mut recvmsg, 0), 2);
+        assert_eq!(recvmsg.msg_flags, libc::MSG_CTRUNC);
+        assert_eq!(recvmsg.msg_controllen, unsafe { CMSG_LEN(4) } as usize);
+        let passedfd = unsafe { *(CMSG_DATA(CMSG_FIRSTHDR(&recvmsg)) as *const i32) };
+        assert_eq!(child.fcntl_syscall(passedfd, F_GETFD, 0), 0);
+        assert_eq!(child.close_syscall(passedfd), 0);
+
+        // only root may claim other credentials, and every passed fd must be open
+        msg.msg_controllen = unsafe { CMSG_SPACE(mem::size_of::<ucred>() as u32) } as usize;
+        unsafe {
+            (*cmsg).cmsg_type = libc::SCM_CREDENTIALS;
+            (*cmsg).cmsg_len = CMSG_LEN(mem::size_of::<ucred>() as u32) as usize;
+            (CMSG_DATA(cmsg) as *mut ucred).write_unaligned(ucred { pid: 2, uid: DEFAULT_UID, gid: DEFAULT_GID });
+        }
+        assert_eq!(cage.sendmsg_syscall(sock1, &msg, 0), -(Errno::EPERM as i32));
+        msg.msg_controllen = unsafe { CMSG_SPACE(4) } as usize;
+        unsafe {
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = CMSG_LEN(4) as usize;
+            *(CMSG_DATA(cmsg) as *mut i32) = 999;
+        }
+        assert_eq!(cage.sendmsg_syscall(sock1, &msg, 0), -(Errno::EBADF as i32));
+
+        // only what arrives is buffered, however much room the iovecs claim
+        msg.msg_control = std::ptr::null_mut();
+        msg.msg_controllen = 0;
+        assert_eq!(cage.sendmsg_syscall(sock1, &msg, 0), 2);
+        recvdata = [0u8; 8];
+        unsafe { (*recvmsg.msg_iov).iov_len = 1 << 40 };
+        recvmsg.msg_control = std::ptr::null_mut();
+        recvmsg.msg_controllen = 0;
+        assert_eq!(child.recvmsg_syscall(sock2, &mut recvmsg, 0), 2);
+        assert_eq!(&recvdata[..2], b"fd");
+
+        // a cage's msghdr and control messages are translated from and to the wasm32 layout
+        let mut memory = [0u32; 64];
+        let start_address = memory.as_mut_ptr() as u64;
+        let wasmhdr = interface::WasmMsghdr { msg_iov: 64, msg_iovlen: 1, msg_control: 96, msg_controllen: 16, ..Default::default() };
+        let wasmiov = interface::WasmIovecStruct { iov_base: 80, iov_len: 2 };
+        unsafe {
+            let base = memory.as_mut_ptr() as *mut u8;
+            std::ptr::write_unaligned(base.add(4) as *mut interface::WasmMsghdr, wasmhdr);
+            std::ptr::write_unaligned(base.add(128) as *mut interface::WasmMsghdr, wasmhdr);
+            std::ptr::write_unaligned(base.add(64) as *mut interface::WasmIovecStruct, wasmiov);
+            std::ptr::copy_nonoverlapping(b"ok".as_ptr(), base.add(80), 2);
+        }
+        memory[24..28].copy_from_slice(&[16, libc::SOL_SOCKET as u32, libc::SCM_RIGHTS as u32, sock2 as u32]);
+        let sendhdr = interface::get_msghdr(start_address, 4, false).unwrap();
+        assert_eq!(cage.sendmsg_syscall(sock1, &sendhdr.hdr, 0), 2);
+        memory[20] = 0;
+        memory[24..28].copy_from_slice(&[0; 4]);
+        let mut recvhdr = interface::get_msghdr(start_address, 128, true).unwrap();
+        assert_eq!(child.recvmsg_syscall(sock2, &mut recvhdr.hdr, 0), 2);
+        assert!(interface::copy_out_msghdr(start_address, 128, &recvhdr).is_empty());
+        assert_eq!(memory[32 + 5], 16);
+        assert_eq!(&memory[24..27], &[16, libc::SOL_SOCKET as u32, libc::SCM_RIGHTS as u32]);
+        assert_eq!(memory[20] & 0xffff, u16::from_ne_bytes(*b"ok") as u32);
+        assert_eq!(child.close_syscall(memory[27] as i32), 0);
+
+        assert_eq!(cage.unlink_syscall(filepath), 0);
+        assert_eq!(child.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_fs_socket_cloexec_and_accept4() {
+        // acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage = interface::cagetable_getref(1);
+
+        // SOCK_CLOEXEC is recorded in the virtual fd of kernel sockets too
+        let inetfd = cage.socket_syscall(libc::AF_INET, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0);
+        let plainfd = cage.socket_syscall(libc::AF_INET, libc::SOCK_STREAM, 0);
+        assert!(translate_virtual_fd(cage.cageid, inetfd as u64).unwrap().should_cloexec);
+        assert!(!translate_virtual_fd(cage.cageid, plainfd as u64).unwrap().should_cloexec);
+
+        let listenaddr = interface::GenSockaddr::Unix(interface::new_sockaddr_unix(
+            libc::AF_UNIX as u16,
+            b"\0lind_accept4_test",
+        ));
+        let listenfd = cage.socket_syscall(libc::AF_UNIX, libc::SOCK_STREAM, 0);
+        let clientfd = cage.socket_syscall(libc::AF_UNIX, libc::SOCK_STREAM, 0);
+        assert_eq!(cage.bind_syscall(listenfd, &listenaddr), 0);
+        assert_eq!(cage.listen_syscall(listenfd, 4), 0);
+        assert_eq!(cage.connect_syscall(clientfd, &listenaddr), 0);
+
+        assert_eq!(cage.accept4_syscall(listenfd, &mut None, libc::O_RDWR), -(Errno::EINVAL as i32));
+        let acceptedfd = cage.accept4_syscall(listenfd, &mut None, libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC);
+        assert!(acceptedfd >= 0);
+        assert_eq!(cage.fcntl_syscall(acceptedfd, F_GETFD, 0), FD_CLOEXEC);
+        assert_eq!(cage.fcntl_syscall(acceptedfd, F_GETFL, 0), O_RDWR | O_NONBLOCK);
+        let mut buf = sizecbuf(4);
+        assert_eq!(cage.recv_syscall(acceptedfd, buf.as_mut_ptr(), 4, 0), -(Errno::EAGAIN as i32));
+
+        // exec closes the close-on-exec sockets and keeps the others
+        assert_eq!(cage.exec_syscall(2), 0);
+        let child = interface::cagetable_getref(2);
+        for (fd, kept) in [(inetfd, false), (plainfd, true), (acceptedfd, false), (clientfd, true)] {
+            assert_eq!(translate_virtual_fd(child.cageid, fd as u64).is_ok(), kept);
+        }
+
+        assert_eq!(child.close_syscall(plainfd), 0);
+        assert_eq!(child.close_syscall(listenfd), 0);
+        assert_eq!(child.close_syscall(clientfd), 0);
+        assert_eq!(child.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_fs_unix_sockopt_lengths_and_peercred() {
+        // acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage = interface::cagetable_getref(1);
+        let ucredlen = size_of::<libc::ucred>() as u32;
+
+        // both ends of a socketpair see the cage that created it
+        let mut socketpair = interface::SockPair::default();
+        assert_eq!(cage.socketpair_syscall(libc::AF_UNIX, libc::SOCK_STREAM, 0, &mut socketpair), 0);
+        let mut cred = libc::ucred { pid: -1, uid: 0, gid: 0 };
+        let mut optlen = ucredlen;
+        assert_eq!(cage.getsockopt_syscall(socketpair.sock1, libc::SOL_SOCKET, libc::SO_PEERCRED, &mut cred as *mut libc::ucred as *mut u8, &mut optlen), 0);
+        assert_eq!(optlen, ucredlen);
+        assert_eq!((cred.pid, cred.uid, cred.gid), (1, DEFAULT_UID as u32, DEFAULT_GID as u32));
+        assert_eq!(cage.setsockopt_syscall(socketpair.sock1, libc::SOL_SOCKET, libc::SO_PEERCRED, &mut cred as *mut libc::ucred as *mut u8, ucredlen), -(Errno::ENOPROTOOPT as i32));
+
+        // struct options round trip, and a value shorter than the option is refused
+        let mut linger = libc::linger { l_onoff: 1, l_linger: 7 };
+        let lingerlen = size_of::<libc::linger>() as u32;
+        assert_eq!(cage.setsockopt_syscall(socketpair.sock1, libc::SOL_SOCKET, libc::SO_LINGER, &mut linger as *mut libc::linger as *mut u8, 4), -(Errno::EINVAL as i32));
+        assert_eq!(cage.setsockopt_syscall(socketpair.sock1, libc::SOL_SOCKET, libc::SO_LINGER, &mut linger as *mut libc::linger as *mut u8, lingerlen), 0);
+        let mut timeout = libc::timeval { tv_sec: 3, tv_usec: 500 };
+        let timevallen = size_of::<libc::timeval>() as u32;
+        assert_eq!(cage.setsockopt_syscall(socketpair.sock1, libc::SOL_SOCKET, libc::SO_RCVTIMEO, &mut timeout as *mut libc::timeval as *mut u8, timevallen), 0);
+
+        let mut gotlinger = libc::linger { l_onoff: 0, l_linger: 0 };
+        let mut optlen = lingerlen;
+        assert_eq!(cage.getsockopt_syscall(socketpair.sock1, libc::SOL_SOCKET, libc::SO_LINGER, &mut gotlinger as *mut libc::linger as *mut u8, &mut optlen), 0);
+        assert_eq!((optlen, gotlinger.l_onoff, gotlinger.l_linger), (lingerlen, 1, 7));
+        let mut gottimeout = libc::timeval { tv_sec: 0, tv_usec: 0 };
+        let mut optlen = timevallen;
+        assert_eq!(cage.getsockopt_syscall(socketpair.sock1, libc::SOL_SOCKET, libc::SO_RCVTIMEO, &mut gottimeout as *mut libc::timeval as *mut u8, &mut optlen), 0);
+        assert_eq!((optlen, gottimeout.tv_sec, gottimeout.tv_usec), (timevallen, 3, 500));
+
+        // a short optlen gets the value cut short, and is set to what was copied
+        let mut onoff: i32 = 0;
+        let mut optlen = 4;
+        assert_eq!(cage.getsockopt_syscall(socketpair.sock1, libc::SOL_SOCKET, libc::SO_LINGER, &mut onoff as *mut i32 as *mut u8, &mut optlen), 0);
+        assert_eq!((optlen, onoff), (4, 1));
+        let mut optlen = 0;
+        assert_eq!(cage.getsockopt_syscall(socketpair.sock1, libc::SOL_SOCKET, libc::SO_LINGER, std::ptr::null_mut(), &mut optlen), 0);
+        assert_eq!(optlen, 0);
+
+        // an accepted connection sees the connecting cage, and the client sees the listener
+        let listenaddr = interface::GenSockaddr::Unix(interface::new_sockaddr_unix(
+            libc::AF_UNIX as u16,
+            b"\0lind_peercred_test",
+        ));
+        let listenfd = cage.socket_syscall(libc::AF_UNIX, libc::SOCK_STREAM, 0);
+        assert_eq!(cage.bind_syscall(listenfd, &listenaddr), 0);
+        assert_eq!(cage.listen_syscall(listenfd, 4), 0);
+        assert_eq!(cage.fork_syscall(2), 0);
+        let child = interface::cagetable_getref(2);
+        let clientfd = child.socket_syscall(libc::AF_UNIX, libc::SOCK_STREAM, 0);
+        assert_eq!(child.connect_syscall(clientfd, &listenaddr), 0);
+        let acceptedfd = cage.accept_syscall(listenfd, &mut None);
+        assert!(acceptedfd >= 0);
+
+        let mut optlen = ucredlen;
+        assert_eq!(cage.getsockopt_syscall(acceptedfd, libc::SOL_SOCKET, libc::SO_PEERCRED, &mut cred as *mut libc::ucred as *mut u8, &mut optlen), 0);
+        assert_eq!(cred.pid, 2);
+        let mut optlen = ucredlen;
+        assert_eq!(child.getsockopt_syscall(clientfd, libc::SOL_SOCKET, libc::SO_PEERCRED, &mut cred as *mut libc::ucred as *mut u8, &mut optlen), 0);
+        assert_eq!(cred.pid, 1);
+
+        // kernel sockets get the caller's optlen as well
+        let inetfd = cage.socket_syscall(libc::AF_INET, libc::SOCK_STREAM, 0);
+        assert_eq!(cage.setsockopt_syscall(inetfd, libc::SOL_SOCKET, libc::SO_LINGER, &mut linger as *mut libc::linger as *mut u8, lingerlen), 0);
+        let mut gotlinger = libc::linger { l_onoff: 0, l_linger: 0 };
+        let mut optlen = lingerlen;
+        assert_eq!(cage.getsockopt_syscall(inetfd, libc::SOL_SOCKET, libc::SO_LINGER, &mut gotlinger as *mut libc::linger as *mut u8, &mut optlen), 0);
+        assert_eq!((optlen, gotlinger.l_onoff, gotlinger.l_linger), (lingerlen, 1, 7));
+
+        assert_eq!(child.close_syscall(clientfd), 0);
+        assert_eq!(child.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        for fd in [socketpair.sock1, socketpair.sock2, listenfd, acceptedfd, inetfd] {
+            assert_eq!(cage.close_syscall(fd), 0);
+        }
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_fs_getifaddrs_layout_and_policy() {
+        // acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage = interface::cagetable_getref(1);
+
+        // the loopback interface is there with its IPv4 address and netmask
+        let mut ifaddrs = Vec::new();
+        assert_eq!(cage.getifaddrs_syscall(&mut ifaddrs), 0);
+        let lo = ifaddrs
+            .iter()
+            .find(|ifa| ifa.name == "lo" && ifa.addr.as_ref().map_or(false, |addr| addr.len() == size_of::<libc::sockaddr_in>()))
+            .expect("no IPv4 address on lo")
+            .clone();
+        assert_ne!(lo.flags & libc::IFF_LOOPBACK as u32, 0);
+        assert_eq!(lo.addr.as_ref().unwrap()[4..8], [127, 0, 0, 1]);
+        assert_eq!(lo.netmask.as_ref().unwrap()[4..8], [255, 0, 0, 0]);
+
+        // the list is laid out in the cage's memory with 32 bit pointers, the head first
+        let mut memory = vec![0u8; 8 + 8192];
+        let start = memory.as_mut_ptr() as u64;
+        assert_eq!(interface::copy_out_ifaddrs(start, 8, 64, &ifaddrs), -(Errno::ENOBUFS as i32));
+        let count = interface::copy_out_ifaddrs(start, 8, 8192, &ifaddrs);
+        assert_eq!(count as usize, ifaddrs.len());
+        let mut node = 8u32;
+        let mut seen = 0;
+        while node != 0 {
+            let wasmifa = unsafe { ((start + node as u64) as *const interface::WasmIfaddrs).read_unaligned() };
+            let name = unsafe { RustCStr::from_ptr((start + wasmifa.ifa_name as u64) as *const libc::c_char) };
+            assert_eq!(name.to_str().unwrap(), ifaddrs[seen].name);
+            assert_eq!(wasmifa.ifa_flags, ifaddrs[seen].flags);
+            if let Some(addr) = &ifaddrs[seen].addr {
+                assert_eq!(&memory[wasmifa.ifa_addr as usize..wasmifa.ifa_addr as usize + addr.len()], &addr[..]);
+            } else {
+                assert_eq!(wasmifa.ifa_addr, 0);
+            }
+            node = wasmifa.ifa_next;
+            seen += 1;
+        }
+        assert_eq!(seen, ifaddrs.len());
+
+        // host interfaces can be renamed or hidden
+        interface::set_interface_policy("lo", Some("cagelo"));
+        let mut renamed = Vec::new();
+        assert_eq!(cage.getifaddrs_syscall(&mut renamed), 0);
+        assert!(renamed.iter().any(|ifa| ifa.name == "cagelo"));
+        assert!(!renamed.iter().any(|ifa| ifa.name == "lo"));
+        interface::set_interface_policy("lo", None);
+        let mut hidden = Vec::new();
+        assert_eq!(cage.getifaddrs_syscall(&mut hidden), 0);
+        assert!(!hidden.iter().any(|ifa| ifa.name == "lo" || ifa.name == "cagelo"));
+        interface::clear_interface_policy();
+
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_fs_netns_same_port_in_two_cages() {
+        // acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let loopback = |port: u16| {
+            interface::GenSockaddr::V4(interface::SockaddrV4 {
+                sin_family: libc::AF_INET as u16,
+                sin_port: port.to_be(),
+                sin_addr: interface::V4Addr { s_addr: u32::from_ne_bytes([127, 0, 0, 1]) },
+                padding: 0,
+            })
+        };
+        let nsa = interface::netns_create();
+        let nsb = interface::netns_create();
+        assert_eq!(lindsetcagenetns(1, nsa), 0);
+        assert_eq!(lindsetcagenetns(1, 12345), -(Errno::EINVAL as i32));
+        let cage = interface::cagetable_getref(1);
+        assert_eq!(cage.fork_syscall(2), 0);
+        let child = interface::cagetable_getref(2);
+        assert_eq!(child.netns(), nsa);
+        assert_eq!(lindsetcagenetns(2, nsb), 0);
+
+        // both cages run a server on 127.0.0.1:8080, each in its own namespace
+        let servera = cage.socket_syscall(libc::AF_INET, libc::SOCK_STREAM, 0);
+        let serverb = child.socket_syscall(libc::AF_INET, libc::SOCK_STREAM, 0);
+        assert_eq!(cage.bind_syscall(servera, &loopback(8080)), 0);
+        assert_eq!(child.bind_syscall(serverb, &loopback(8080)), 0);
+        assert_eq!(cage.listen_syscall(servera, 4), 0);
+        assert_eq!(child.listen_syscall(serverb, 4), 0);
+        let taken = cage.socket_syscall(libc::AF_INET, libc::SOCK_STREAM, 0);
+        assert_eq!(cage.bind_syscall(taken, &loopback(8080)), -(Errno::EADDRINUSE as i32));
+        let mut name = loopback(0);
+        assert_eq!(cage.getsockname_syscall(servera, &mut Some(&mut name)), 0);
+        assert_eq!(u16::from_be(name.port()), 8080);
+
+        // a connection to the loopback address reaches the server of the same namespace
+        let client = child.socket_syscall(libc::AF_INET, libc::SOCK_STREAM, 0);
+        assert_eq!(child.connect_syscall(client, &loopback(8080)), 0);
+        let mut pollfds = vec![PollStruct { fd: servera, events: libc::POLLIN, revents: 0 }];
+        assert_eq!(cage.poll_syscall(&mut pollfds, 1, 0), 0);
+        let mut pollfds = vec![PollStruct { fd: serverb, events: libc::POLLIN, revents: 0 }];
+        assert_eq!(child.poll_syscall(&mut pollfds, 1, 1000), 1);
+        let mut peer = loopback(0);
+        assert_eq!(child.getpeername_syscall(client, &mut Some(&mut peer)), 0);
+        assert_eq!(u16::from_be(peer.port()), 8080);
+        let accepted = child.accept_syscall(serverb, &mut None);
+        assert!(accepted >= 0);
+        assert_eq!(child.send_syscall(client, "b".as_ptr(), 1, 0), 1);
+        let mut buf = sizecbuf(1);
+        assert_eq!(child.recv_syscall(accepted, buf.as_mut_ptr(), 1, 0), 1);
+        assert_eq!(buf[0], b'b');
+
+        // sendmsg and recvmsg see the namespace's ports in msg_name, like sendto and recvfrom
+        let udprecv = cage.socket_syscall(libc::AF_INET, libc::SOCK_DGRAM, 0);
+        let udpsend = cage.socket_syscall(libc::AF_INET, libc::SOCK_DGRAM, 0);
+        assert_eq!(cage.bind_syscall(udprecv, &loopback(7070)), 0);
+        assert_eq!(cage.bind_syscall(udpsend, &loopback(7071)), 0);
+        let mut to = libc::sockaddr_in {
+            sin_family: libc::AF_INET as u16,
+            sin_port: 7070u16.to_be(),
+            sin_addr: libc::in_addr { s_addr: u32::from_ne_bytes([127, 0, 0, 1]) },
+            sin_zero: [0; 8],
+        };
+        let mut data = *b"u";
+        let mut iov = libc::iovec { iov_base: data.as_mut_ptr() as *mut c_void, iov_len: 1 };
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_name = &mut to as *mut libc::sockaddr_in as *mut c_void;
+        msg.msg_namelen = mem::size_of::<libc::sockaddr_in>() as u32;
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        assert_eq!(cage.sendmsg_syscall(udpsend, &msg, 0), 1);
+        let mut from: libc::sockaddr_in = unsafe { mem::zeroed() };
+        let mut recvdata = [0u8; 4];
+        let mut recviov = libc::iovec { iov_base: recvdata.as_mut_ptr() as *mut c_void, iov_len: 4 };
+        let mut recvmsg: libc::msghdr = unsafe { mem::zeroed() };
+        recvmsg.msg_name = &mut from as *mut libc::sockaddr_in as *mut c_void;
+        recvmsg.msg_namelen = mem::size_of::<libc::sockaddr_in>() as u32;
+        recvmsg.msg_iov = &mut recviov;
+        recvmsg.msg_iovlen = 1;
+        assert_eq!(cage.recvmsg_syscall(udprecv, &mut recvmsg, 0), 1);
+        assert_eq!(recvdata[0], b'u');
+        assert_eq!(u16::from_be(from.sin_port), 7071);
+
+        // loopback ports nobody bound in the namespace are closed, whatever the host has there
+        let refused = child.socket_syscall(libc::AF_INET, libc::SOCK_STREAM, 0);
+        assert_eq!(child.connect_syscall(refused, &loopback(8081)), -(Errno::ECONNREFUSED as i32));
+
+        // closing the server frees its port in the namespace
+        assert_eq!(cage.close_syscall(servera), 0);
+        assert_eq!(cage.bind_syscall(taken, &loopback(8080)), 0);
+
+        // a published port is bound to the host port it is published on
+        let hostport = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        assert!(interface::netns_publish_port(nsa, libc::SOCK_STREAM, 9090, hostport));
+        let published = cage.socket_syscall(libc::AF_INET, libc::SOCK_STREAM, 0);
+        assert_eq!(cage.bind_syscall(published, &loopback(9090)), 0);
+        assert_eq!(cage.listen_syscall(published, 4), 0);
+        assert!(std::net::TcpStream::connect(("127.0.0.1", hostport)).is_ok());
+
+        for fd in [client, accepted, refused, serverb] {
+            assert_eq!(child.close_syscall(fd), 0);
+        }
+        assert_eq!(child.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        for fd in [taken, published, udprecv, udpsend] {
+            assert_eq!(cage.close_syscall(fd), 0);
+        }
+        interface::netns_remove(nsa);
+        interface::netns_remove(nsb);
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_fs_sockopt_struct_layouts() {
+        // acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage = interface::cagetable_getref(1);
+        // a cage's memory, with the optvals at offset 8
+        let mut memory = [0u8; 512];
+        let start_address = memory.as_mut_ptr() as u64;
+
+        // struct options laid out alike in the cage are copied as they are
+        let mreq = libc::ip_mreq {
+            imr_multiaddr: libc::in_addr { s_addr: u32::from_ne_bytes([224, 0, 0, 251]) },
+            imr_interface: libc::in_addr { s_addr: u32::from_ne_bytes([127, 0, 0, 1]) },
+        };
+        unsafe { std::ptr::write_unaligned(memory.as_mut_ptr().add(8) as *mut libc::ip_mreq, mreq) };
+        let optval = interface::get_sockoptval(start_address, 8, 8, libc::IPPROTO_IP, libc::IP_ADD_MEMBERSHIP).unwrap();
+        assert_eq!(optval, &memory[8..16]);
+        for (level, optname, len) in [
+            (libc::SOL_SOCKET, libc::SO_LINGER, size_of::<libc::linger>()),
+            (libc::SOL_SOCKET, libc::SO_RCVTIMEO, size_of::<libc::timeval>()),
+            (libc::SOL_SOCKET, libc::SO_PEERCRED, size_of::<libc::ucred>()),
+            (libc::IPPROTO_TCP, libc::TCP_INFO, size_of::<libc::tcp_info>()),
+        ] {
+            assert_eq!(interface::get_sockoptval(start_address, 8, len as u32, level, optname).unwrap().len(), len);
+        }
+
+        // an option that takes an int refuses anything longer
+        assert_eq!(interface::get_sockoptval(start_address, 8, 4, libc::SOL_SOCKET, libc::SO_REUSEADDR).unwrap().len(), 4);
+        assert_eq!(interface::get_sockoptval(start_address, 8, 8, libc::SOL_SOCKET, libc::SO_REUSEADDR), Err(-(Errno::EINVAL as i32)));
+
+        // struct group_req gets the padding the host has before its sockaddr_storage
+        memory[8..12].copy_from_slice(&2u32.to_ne_bytes());
+        memory[12..14].copy_from_slice(&(libc::AF_INET as u16).to_ne_bytes());
+        let optval = interface::get_sockoptval(start_address, 8, 132, libc::IPPROTO_IP, libc::MCAST_JOIN_GROUP).unwrap();
+        assert_eq!(optval.len(), 136);
+        assert_eq!(&optval[..4], &2u32.to_ne_bytes());
+        assert_eq!(&optval[8..10], &(libc::AF_INET as u16).to_ne_bytes());
+
+        // the kernel takes and hands out those values in the same layout
+        let udpfd = cage.socket_syscall(libc::AF_INET, libc::SOCK_DGRAM, 0);
+        let mut optval = interface::get_sockoptval(start_address, 8, 0, libc::IPPROTO_IP, libc::IP_ADD_MEMBERSHIP).unwrap();
+        assert!(optval.is_empty());
+        let mut mreqval = unsafe { std::slice::from_raw_parts(&mreq as *const libc::ip_mreq as *const u8, 8) }.to_vec();
+        let ret = cage.setsockopt_syscall(udpfd, libc::IPPROTO_IP, libc::IP_ADD_MEMBERSHIP, mreqval.as_mut_ptr(), 8);
+        // hosts without a multicast capable interface have nowhere to join the group
+        assert!(ret == 0 || ret == -(Errno::ENODEV as i32), "{}", ret);
+        assert_eq!(cage.setsockopt_syscall(udpfd, libc::IPPROTO_IP, libc::IP_ADD_MEMBERSHIP, optval.as_mut_ptr(), 0), -(Errno::EINVAL as i32));
+        let tcpfd = cage.socket_syscall(libc::AF_INET, libc::SOCK_STREAM, 0);
+        let mut info: libc::tcp_info = unsafe { mem::zeroed() };
+        let mut optlen = size_of::<libc::tcp_info>() as u32;
+        assert_eq!(cage.getsockopt_syscall(tcpfd, libc::IPPROTO_TCP, libc::TCP_INFO, &mut info as *mut libc::tcp_info as *mut u8, &mut optlen), 0);
+        assert_eq!(optlen, size_of::<libc::tcp_info>() as u32);
+        assert_eq!(info.tcpi_state, 7); // TCP_CLOSE
+
+        assert_eq!(cage.close_syscall(udpfd), 0);
+        assert_eq!(cage.close_syscall(tcpfd), 0);
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_fs_poll_select_mixed_fd_kinds() {
+        // acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage = interface::cagetable_getref(1);
+
+        // a regular file is always ready, the pipe and the socket are not yet
+        let filefd = cage.open_syscall("/poll_mixed_test.txt", O_CREAT | O_RDWR, S_IRWXA);
+        assert!(filefd >= 0);
+        let mut pipe_fds = PipeArray::default();
+        assert_eq!(cage.pipe_syscall(&mut pipe_fds), 0);
+        let mut socketpair = interface::SockPair::default();
+        assert_eq!(cage.socketpair_syscall(libc::AF_UNIX, libc::SOCK_STREAM, 0, &mut socketpair), 0);
+
+        let mut pollfds = vec![
+            PollStruct { fd: filefd, events: libc::POLLIN, revents: 0 },
+            PollStruct { fd: pipe_fds.readfd, events: libc::POLLIN, revents: 0 },
+            PollStruct { fd: socketpair.sock2, events: libc::POLLIN, revents: 0 },
+        ];
+        assert_eq!(cage.poll_syscall(&mut pollfds, 3, -1), 1);
+        assert_eq!(pollfds[0].revents, libc::POLLIN);
+        assert_eq!(pollfds[1].revents, 0);
+        assert_eq!(pollfds[2].revents, 0);
+
+        // all three kinds are reported by the same call
+        assert_eq!(cage.write_syscall(pipe_fds.writefd, "p".as_ptr(), 1), 1);
+        assert_eq!(cage.write_syscall(socketpair.sock1, "s".as_ptr(), 1), 1);
+        assert_eq!(cage.poll_syscall(&mut pollfds, 3, 0), 3);
+        assert_eq!(pollfds[1].revents, libc::POLLIN);
+        assert_eq!(pollfds[2].revents, libc::POLLIN);
+
+        let mut readfds = fdtables::_init_fd_set();
+        for fd in [filefd, pipe_fds.readfd, socketpair.sock2] {
+            fdtables::_fd_set(fd as u64, &mut readfds);
+        }
+        let nfds = filefd.max(pipe_fds.readfd).max(socketpair.sock2) + 1;
+        assert_eq!(cage.select_syscall(nfds, Some(&mut readfds), None, None, None), 3);
+
+        // with only in-memory fds left and nothing ready, the timeout is honoured
+        let mut buf = sizecbuf(1);
+        assert_eq!(cage.read_syscall(pipe_fds.readfd, buf.as_mut_ptr(), 1), 1);
+        assert_eq!(cage.read_syscall(socketpair.sock2, buf.as_mut_ptr(), 1), 1);
+        let start = interface::starttimer();
+        assert_eq!(cage.poll_syscall(&mut pollfds[1..], 2, 50), 0);
+        assert!(interface::readtimer(start) >= interface::RustDuration::from_millis(50));
+
+        // and a blocked select is woken up by a send from another thread
+        let sender = interface::cagetable_getref(1);
+        let sock1 = socketpair.sock1;
+        let thread = interface::helper_thread(move || {
+            interface::sleep(interface::RustDuration::from_millis(50));
+            assert_eq!(sender.send_syscall(sock1, "x".as_ptr(), 1, 0), 1);
+        });
+        let mut readfds = fdtables::_init_fd_set();
+        fdtables::_fd_set(pipe_fds.readfd as u64, &mut readfds);
+        fdtables::_fd_set(socketpair.sock2 as u64, &mut readfds);
+        assert_eq!(cage.select_syscall(nfds, Some(&mut readfds), None, None, None), 1);
+        assert!(fdtables::_fd_isset(socketpair.sock2 as u64, &readfds));
+        assert!(!fdtables::_fd_isset(pipe_fds.readfd as u64, &readfds));
+        thread.join().unwrap();
+
+        for fd in [filefd, pipe_fds.readfd, pipe_fds.writefd, socketpair.sock1, socketpair.sock2] {
+            assert_eq!(cage.close_syscall(fd), 0);
+        }
+        assert_eq!(cage.unlink_syscall("/poll_mixed_test.txt"), 0);
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_fs_close_chardev() {
+        // acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage = interface::cagetable_getref(1);
+
+        // Ideally, we should have a character device file in the system
+        // and use that instead of creating a new file. But this is
+        // an sandboxed environment, so we need to create a file.
+        // Open a character device file.
+        let fd = cage.open_syscall("/dev/zero", O_RDWR | O_CREAT, S_IRWXA);
+        assert!(fd >= 0);
+
+        // Close the character device file descriptor, which should succeed.
+        assert_eq!(cage.close_syscall(fd), 0);
+
+        // Attempt to close the file descriptor again to ensure it's already closed.
+        // Expect an error for "Invalid File Descriptor".
+        assert_eq!(cage.close_syscall(fd), -(Errno::EBADF as i32));
+        // Remove the file to clean up the environment
+        let _ = cage.unlink_syscall("/dev/zero");
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    // #[test]
+    // pub fn ut_lind_fs_stat_syscall_tests() {
+    //     // acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+    //     // and also performs clean env setup
+    //     let _thelock = setup::lock_and_init();
+
+    //     let cage = interface::cagetable_getref(1);
+    //     let mut statdata = StatData::default();
+
+    //     // test out whether an error is output for a non existent file path
+    //     // (ENOENT[-2])
+    //     assert_eq!(
+    //         cage.stat_syscall("non_existent_file_path", &mut statdata),
+    //         syscall_error(Errno::ENOENT, "stat", "test_failure")
+    //     );
+
+    //     // setting up directory inode object '/tmp' for testing stat_syscall with a
+    //     // directory
+    //     let dir_path = "/tmp"; // since setup already initializes tmp, assuming it is there
+    //     assert_eq!(cage.stat_syscall(dir_path, &mut statdata), 0);
+
+    //     // setting up generic inode object "/tmp/generic" for testing stat_syscall with
+    //     // a generic file
+    //     let generic_path = "/tmp/generic";
+    //     let creat_fd = cage.creat_syscall(generic_path, S_IRWXA);
+    //     assert!(creat_fd > 0);
+    //     assert_eq!(cage.stat_syscall(generic_path, &mut statdata), 0);
+
+    //     // setting up character device inode object "/chardev" for testing stat_syscall
+    //     // with a character device
+    //     let dev = makedev(&DevNo { major: 1, minor: 3 });
+    //     let chardev_path = "/chardev";
+    //     assert_eq!(
+    //         cage.mknod_syscall(chardev_path, S_IRWXA | S_IFCHR as u32, dev),
+    //         0
     //     );
     //     assert_eq!(cage.stat_syscall(chardev_path, &mut statdata), 0);
 
@@ -4956,4 +6626,109 @@ pub mod fs_tests {
     //     lindrustfinalize();
     //     return;
     // }
+
+    #[test]
+    pub fn ut_lind_fs_cage_root_override() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage = interface::cagetable_getref(1);
+
+        let res = cage.mkdir_syscall("/subroot", S_IRWXA);
+        assert!(res == 0 || res == -libc::EEXIST);
+        let fd = cage.open_syscall("/subroot/rootfile", O_CREAT | O_RDWR, S_IRWXA);
+        assert!(fd >= 0);
+        assert_eq!(cage.close_syscall(fd), 0);
+
+        // a forked child starts out with its parent's root
+        assert_eq!(cage.fork_syscall(2), 0);
+        let child = interface::cagetable_getref(2);
+        assert_eq!(*child.lindroot.read(), *cage.lindroot.read());
+
+        // re-rooting the child only changes what the child sees
+        let subroot = format!("{}/subroot/", cage.lindroot.read().to_str().unwrap());
+        child.changeroot(&subroot);
+        let mut statdata = StatData::default();
+        assert_eq!(child.stat_syscall("/rootfile", &mut statdata), 0);
+        assert_eq!(cage.stat_syscall("/rootfile", &mut statdata), -(Errno::ENOENT as i32));
+        assert_eq!(cage.stat_syscall("/subroot/rootfile", &mut statdata), 0);
+
+        // an absolute symlink is stored as the cage gave it and followed from the root of
+        // whichever cage looks it up
+        assert_eq!(child.symlink_syscall("/rootfile", "/rootlink"), 0);
+        assert_eq!(child.stat_syscall("/rootlink", &mut statdata), 0);
+        assert_eq!(statdata.st_mode & S_IFMT, S_IFREG);
+        let mut buf = [0u8; 64];
+        assert_eq!(cage.readlink_syscall("/subroot/rootlink", buf.as_mut_ptr(), buf.len()), 9);
+        assert_eq!(&buf[..9], b"/rootfile");
+        assert_eq!(cage.stat_syscall("/subroot/rootlink", &mut statdata), -(Errno::ENOENT as i32));
+        assert_eq!(cage.lstat_syscall("/subroot/rootlink", &mut statdata), 0);
+        assert_eq!(statdata.st_size, 9);
+
+        assert_eq!(child.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        assert_eq!(cage.unlink_syscall("/subroot/rootlink"), 0);
+        assert_eq!(cage.unlink_syscall("/subroot/rootfile"), 0);
+        assert_eq!(cage.rmdir_syscall("/subroot"), 0);
+
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_fs_symlink_escape() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage = interface::cagetable_getref(1);
+        let hostroot = cage.lindroot.read().to_str().unwrap().to_string();
+
+        // plant links inside the tree behind the cage's back, as a hostile
+        // archive or an earlier cage could have done
+        let escape = format!("{}/escape", hostroot);
+        let up = format!("{}/up", hostroot);
+        let _ = std::fs::remove_file(&escape);
+        let _ = std::fs::remove_file(&up);
+        std::os::unix::fs::symlink("/etc", &escape).unwrap();
+        std::os::unix::fs::symlink("../../../../../../..", &up).unwrap();
+
+        let mut statdata = StatData::default();
+        // an absolute symlink is a cage path, so this one leads to the cage's /etc (which
+        // the tree doesn't have) and not the host's
+        assert_eq!(cage.open_syscall("/escape/passwd", O_RDONLY, 0), -(Errno::ENOENT as i32));
+        assert_eq!(cage.stat_syscall("/escape", &mut statdata), -(Errno::ENOENT as i32));
+        assert_eq!(cage.access_syscall("/escape/passwd", F_OK), -(Errno::ENOENT as i32));
+        assert_eq!(cage.mkdir_syscall("/escape/newdir", S_IRWXA), -(Errno::ENOENT as i32));
+        assert_eq!(cage.chdir_syscall("/escape"), -(Errno::ENOENT as i32));
+        // relative symlink climbing out with ..
+        assert_eq!(cage.open_syscall("/up/etc/passwd", O_RDONLY, 0), -(Errno::EXDEV as i32));
+        assert_eq!(cage.creat_syscall("/up/tmp/planted", S_IRWXA), -(Errno::EXDEV as i32));
+
+        // the links themselves can still be removed from inside the cage
+        assert_eq!(cage.unlink_syscall("/escape"), 0);
+        assert_eq!(cage.unlink_syscall("/up"), 0);
+
+        // a plain .. never gets above the cage's root
+        let res = cage.mkdir_syscall("/dotdotdir", S_IRWXA);
+        assert!(res == 0 || res == -libc::EEXIST);
+        let mut rootstat = StatData::default();
+        assert_eq!(cage.stat_syscall("/", &mut rootstat), 0);
+        assert_eq!(cage.chdir_syscall("/dotdotdir"), 0);
+        assert_eq!(cage.stat_syscall("../../../..", &mut statdata), 0);
+        assert_eq!(statdata.st_ino, rootstat.st_ino);
+
+        // symlinks that stay inside the tree keep working
+        let inside = format!("{}/inside", hostroot);
+        let _ = std::fs::remove_file(&inside);
+        std::os::unix::fs::symlink("dotdotdir", &inside).unwrap();
+        assert_eq!(cage.chdir_syscall("/"), 0);
+        assert_eq!(cage.stat_syscall("/inside", &mut statdata), 0);
+        assert_eq!(statdata.st_mode & S_IFMT, S_IFDIR);
+        assert_eq!(cage.unlink_syscall("/inside"), 0);
+        assert_eq!(cage.rmdir_syscall("/dotdotdir"), 0);
+
+        assert_eq!(cage.exit_syscall(libc::EXIT_SUCCESS), libc::EXIT_SUCCESS);
+        lindrustfinalize();
+    }
 }
@@ -2,7 +2,9 @@
 pub mod ipc_tests {
     use super::super::*;
     use crate::interface;
+    use crate::interface::PIPE_CAPACITY;
     use crate::safeposix::{cage::*, dispatcher::*, filesystem};
+    use crate::safeposix::syscalls::fs_constants::MSGMAX;
     use libc::c_void;
     use std::fs::OpenOptions;
     use std::os::unix::fs::PermissionsExt;
@@ -122,7 +124,7 @@ pub mod ipc_tests {
                 "".as_bytes(),
             )); // blank unix sockaddr
 
-            let sockfd = cage2.accept_syscall(serversockfd, &mut socket2); //really can only make sure that the fd is valid
+            let sockfd = cage2.accept_syscall(serversockfd, &mut Some(&mut socket2)); //really can only make sure that the fd is valid
             assert!(sockfd > 0);
 
             interface::sleep(interface::RustDuration::from_millis(100));
@@ -630,4 +632,515 @@ pub mod ipc_tests {
 
         lindrustfinalize();
     }
+
+    #[test]
+    pub fn ut_lind_ipc_shm_private_segments() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage1 = interface::cagetable_getref(1);
+
+        // every IPC_PRIVATE shmget makes a segment of its own
+        let shmid1 = cage1.shmget_syscall(libc::IPC_PRIVATE, 4096, 0o600);
+        let shmid2 = cage1.shmget_syscall(libc::IPC_PRIVATE, 4096, 0o600);
+        assert!(shmid1 > 0 && shmid2 > 0);
+        assert_ne!(shmid1, shmid2);
+
+        let mut shmidstruct = interface::ShmidsStruct::default();
+        assert_eq!(cage1.shmctl_syscall(shmid1, libc::IPC_STAT, Some(&mut shmidstruct)), 0);
+        assert_eq!(shmidstruct.shm_perm.__key, libc::IPC_PRIVATE);
+        assert_eq!(shmidstruct.shm_perm.mode & 0o777, 0o600);
+        assert_eq!(shmidstruct.shm_segsz, 4096);
+        assert_eq!(shmidstruct.shm_cpid, 1);
+        assert_eq!((shmidstruct.shm_lpid, shmidstruct.shm_atime, shmidstruct.shm_nattch), (0, 0, 0));
+
+        // attaching and detaching record when and by which cage
+        let shmaddr = 0xfffff000 as *mut u8;
+        assert_ne!(cage1.shmat_syscall(shmid1, shmaddr, 0), -1);
+        assert_eq!(cage1.fork_syscall(2), 0);
+        assert_eq!(cage1.shmctl_syscall(shmid1, libc::IPC_STAT, Some(&mut shmidstruct)), 0);
+        assert_eq!((shmidstruct.shm_lpid, shmidstruct.shm_nattch), (1, 2));
+        assert!(shmidstruct.shm_atime > 0);
+        assert_eq!(shmidstruct.shm_dtime, 0);
+
+        let cage2 = interface::cagetable_getref(2);
+        assert_eq!(cage2.shmdt_syscall(shmaddr), shmid1);
+        assert_eq!(cage1.shmctl_syscall(shmid1, libc::IPC_STAT, Some(&mut shmidstruct)), 0);
+        assert_eq!((shmidstruct.shm_lpid, shmidstruct.shm_nattch), (2, 1));
+        assert!(shmidstruct.shm_dtime > 0);
+        assert_eq!(cage2.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+
+        assert_eq!(cage1.shmdt_syscall(shmaddr), shmid1);
+        for shmid in [shmid1, shmid2] {
+            assert_eq!(cage1.shmctl_syscall(shmid, libc::IPC_RMID, None), 0);
+            assert_eq!(cage1.shmctl_syscall(shmid, libc::IPC_STAT, Some(&mut shmidstruct)), -(Errno::EINVAL as i32));
+        }
+
+        assert_eq!(cage1.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_ipc_shmctl_commands() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage1 = interface::cagetable_getref(1);
+        let key = 4242;
+        let shmid = cage1.shmget_syscall(key, 8192, libc::IPC_CREAT | 0o644);
+        assert!(shmid > 0);
+        assert_eq!(cage1.shmget_syscall(key, 16384, 0), -(Errno::EINVAL as i32));
+
+        // IPC_SET changes the permission bits and nothing else about the mode
+        let mut shmidstruct = interface::ShmidsStruct::default();
+        assert_eq!(cage1.shmctl_syscall(shmid, libc::IPC_SET, None), -(Errno::EFAULT as i32));
+        assert_eq!(cage1.shmctl_syscall(shmid, libc::IPC_STAT, Some(&mut shmidstruct)), 0);
+        shmidstruct.shm_perm.mode = 0o7600;
+        assert_eq!(cage1.shmctl_syscall(shmid, libc::IPC_SET, Some(&mut shmidstruct)), 0);
+        assert_eq!(cage1.shmctl_syscall(shmid, libc::IPC_STAT, Some(&mut shmidstruct)), 0);
+        assert_eq!(shmidstruct.shm_perm.mode, 0o600);
+        assert_eq!((shmidstruct.shm_perm.uid, shmidstruct.shm_perm.cuid), (DEFAULT_UID, DEFAULT_UID));
+
+        assert_eq!(cage1.shmctl_syscall(shmid, libc::SHM_LOCK, None), 0);
+        assert_eq!(cage1.shmctl_syscall(shmid, libc::IPC_STAT, Some(&mut shmidstruct)), 0);
+        assert_eq!(shmidstruct.shm_perm.mode, 0o600 | SHM_LOCKED as u16);
+        assert_eq!(cage1.shmctl_syscall(shmid, libc::SHM_UNLOCK, None), 0);
+        assert_eq!(cage1.shmctl_syscall(shmid, libc::IPC_STAT, Some(&mut shmidstruct)), 0);
+        assert_eq!(shmidstruct.shm_perm.mode, 0o600);
+
+        // the limits, and the segments in use, with the highest id as the return value
+        let mut infobuf = interface::ShmidsStruct::default();
+        assert!(cage1.shmctl_syscall(0, libc::IPC_INFO, Some(&mut infobuf)) >= shmid);
+        let info = unsafe { *(&infobuf as *const interface::ShmidsStruct as *const interface::ShminfoStruct) };
+        assert_eq!((info.shmmin, info.shmmni), (SHMMIN, SHMMNI));
+        assert!(cage1.shmctl_syscall(0, SHM_INFO, Some(&mut infobuf)) >= shmid);
+        let usage = unsafe { *(&infobuf as *const interface::ShmidsStruct as *const interface::ShmInfoStruct) };
+        assert!(usage.used_ids >= 1 && usage.shm_tot >= 2);
+
+        // another user may neither look at the segment nor change or remove it
+        assert_eq!(cage1.fork_syscall(2), 0);
+        let cage2 = interface::cagetable_getref(2);
+        cage2.geteuid.store(2000, interface::RustAtomicOrdering::Relaxed);
+        assert_eq!(cage2.shmctl_syscall(shmid, libc::IPC_STAT, Some(&mut shmidstruct)), -(Errno::EACCES as i32));
+        assert_eq!(cage2.shmat_syscall(shmid, 0xfffff000 as *mut u8, 0), -(Errno::EACCES as i32));
+        assert_eq!(cage2.shmctl_syscall(shmid, libc::IPC_SET, Some(&mut shmidstruct)), -(Errno::EPERM as i32));
+        assert_eq!(cage2.shmctl_syscall(shmid, libc::SHM_LOCK, None), -(Errno::EPERM as i32));
+        assert_eq!(cage2.shmctl_syscall(shmid, libc::IPC_RMID, None), -(Errno::EPERM as i32));
+        assert_eq!(cage2.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+
+        // a removed segment lives on while attached, but its key is free right away
+        let shmaddr = 0xfffff000 as *mut u8;
+        assert_ne!(cage1.shmat_syscall(shmid, shmaddr, 0), -1);
+        assert_eq!(cage1.shmctl_syscall(shmid, libc::IPC_RMID, None), 0);
+        assert_eq!(cage1.shmctl_syscall(shmid, libc::IPC_STAT, Some(&mut shmidstruct)), 0);
+        assert_eq!(shmidstruct.shm_perm.mode & SHM_DEST as u16, SHM_DEST as u16);
+        assert_eq!(shmidstruct.shm_perm.__key, libc::IPC_PRIVATE);
+        assert_eq!(cage1.shmget_syscall(key, 8192, 0), -(Errno::ENOENT as i32));
+        let newid = cage1.shmget_syscall(key, 8192, libc::IPC_CREAT | 0o600);
+        assert!(newid > 0 && newid != shmid);
+        assert_eq!(cage1.shmdt_syscall(shmaddr), shmid);
+        assert_eq!(cage1.shmctl_syscall(shmid, libc::IPC_STAT, Some(&mut shmidstruct)), -(Errno::EINVAL as i32));
+        assert_eq!(cage1.shmget_syscall(key, 8192, 0), newid);
+        assert_eq!(cage1.shmctl_syscall(newid, libc::IPC_RMID, None), 0);
+
+        assert_eq!(cage1.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_ipc_posix_shm() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage1 = interface::cagetable_getref(1);
+        let fd = cage1.shm_open_syscall("/lindshm", libc::O_CREAT | libc::O_EXCL | libc::O_RDWR, 0o600);
+        assert!(fd >= 0);
+        assert_eq!(
+            cage1.shm_open_syscall("/lindshm", libc::O_CREAT | libc::O_EXCL | libc::O_RDWR, 0o600),
+            -(Errno::EEXIST as i32)
+        );
+        assert_eq!(cage1.shm_open_syscall("/lind/shm", libc::O_RDWR, 0), -(Errno::EINVAL as i32));
+        assert_eq!(cage1.fcntl_syscall(fd, libc::F_GETFD, 0), libc::FD_CLOEXEC);
+
+        // a new object is empty until ftruncate gives it a size
+        let mut statdata = interface::StatData::default();
+        assert_eq!(cage1.fstat_syscall(fd, &mut statdata), 0);
+        assert_eq!(statdata.st_size, 0);
+        assert_eq!(cage1.ftruncate_syscall(fd, 4096), 0);
+        assert_eq!(cage1.fstat_syscall(fd, &mut statdata), 0);
+        assert_eq!(statdata.st_size, 4096);
+        assert_eq!(statdata.st_mode, libc::S_IFREG | 0o600);
+
+        // map it shared over memory reserved for the purpose
+        let region = unsafe {
+            libc::mmap(std::ptr::null_mut(), 4096, libc::PROT_NONE, libc::MAP_PRIVATE | libc::MAP_ANONYMOUS, -1, 0)
+        } as *mut u8;
+        assert_ne!(region as *mut c_void, libc::MAP_FAILED);
+        let ret = cage1.mmap_syscall(region, 4096, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED | libc::MAP_FIXED, fd, 0);
+        assert_eq!(ret, (region as i64 & 0xffffffff) as i32);
+        unsafe { std::ptr::copy_nonoverlapping(b"hello".as_ptr(), region, 5) };
+
+        // another cage finds the object under /dev/shm and sees what was written to the mapping
+        assert_eq!(cage1.fork_syscall(2), 0);
+        let cage2 = interface::cagetable_getref(2);
+        let fd2 = cage2.open_syscall("/dev/shm/lindshm", libc::O_RDONLY, 0);
+        assert!(fd2 >= 0);
+        let mut buf = [0u8; 5];
+        assert_eq!(cage2.pread_syscall(fd2, buf.as_mut_ptr(), 5, 0), 5);
+        assert_eq!(&buf, b"hello");
+
+        // the mode and owner of the object are checked like those of a file
+        cage2.geteuid.store(2000, interface::RustAtomicOrdering::Relaxed);
+        assert_eq!(cage2.shm_open_syscall("/lindshm", libc::O_RDWR, 0), -(Errno::EACCES as i32));
+        assert_eq!(cage2.shm_unlink_syscall("/lindshm"), -(Errno::EACCES as i32));
+        cage2.geteuid.store(DEFAULT_UID as i32, interface::RustAtomicOrdering::Relaxed);
+
+        // unlinking only removes the name, the fds and the mapping still share the memory
+        assert_eq!(cage1.shm_unlink_syscall("/lindshm"), 0);
+        assert_eq!(cage1.shm_open_syscall("/lindshm", libc::O_RDWR, 0), -(Errno::ENOENT as i32));
+        assert_eq!(cage1.unlink_syscall("/dev/shm/lindshm"), -(Errno::ENOENT as i32));
+        unsafe { std::ptr::copy_nonoverlapping(b"world".as_ptr(), region, 5) };
+        assert_eq!(cage2.pread_syscall(fd2, buf.as_mut_ptr(), 5, 0), 5);
+        assert_eq!(&buf, b"world");
+        assert_eq!(cage2.close_syscall(fd2), 0);
+        assert_eq!(cage2.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+
+        // the open that creates an object is let through whatever its mode, and fstat and
+        // fchmod see and change the mode the cages check
+        let rofd = cage1.shm_open_syscall("/lindshmro", libc::O_CREAT | libc::O_EXCL | libc::O_RDWR, 0o400);
+        assert!(rofd >= 0);
+        assert_eq!(cage1.pwrite_syscall(rofd, b"x".as_ptr(), 1, 0), 1);
+        assert_eq!(cage1.fstat_syscall(rofd, &mut statdata), 0);
+        assert_eq!(statdata.st_mode, libc::S_IFREG | 0o400);
+        assert_eq!(cage1.shm_open_syscall("/lindshmro", libc::O_RDWR, 0), -(Errno::EACCES as i32));
+        assert_eq!(cage1.fchmod_syscall(rofd, 0o640), 0);
+        assert_eq!(cage1.fstat_syscall(rofd, &mut statdata), 0);
+        assert_eq!(statdata.st_mode, libc::S_IFREG | 0o640);
+        let rwfd = cage1.shm_open_syscall("/lindshmro", libc::O_RDWR, 0);
+        assert!(rwfd >= 0);
+        assert_eq!(cage1.close_syscall(rwfd), 0);
+        assert_eq!(cage1.shm_unlink_syscall("/lindshmro"), 0);
+        assert_eq!(cage1.close_syscall(rofd), 0);
+        let nomodefd = cage1.shm_open_syscall("/lindshmro", libc::O_CREAT | libc::O_RDWR, 0);
+        assert!(nomodefd >= 0);
+        assert_eq!(cage1.shm_open_syscall("/lindshmro", libc::O_RDONLY, 0), -(Errno::EACCES as i32));
+        assert_eq!(cage1.shm_unlink_syscall("/lindshmro"), 0);
+        assert_eq!(cage1.close_syscall(nomodefd), 0);
+
+        // the name can be given to a new object right away
+        let newfd = cage1.open_syscall("/dev/shm/lindshm", libc::O_CREAT | libc::O_RDWR, 0o644);
+        assert!(newfd >= 0);
+        assert_eq!(cage1.fstat_syscall(newfd, &mut statdata), 0);
+        assert_eq!(statdata.st_size, 0);
+        assert_eq!(cage1.unlink_syscall("/dev/shm/lindshm"), 0);
+
+        assert_eq!(cage1.munmap_syscall(region, 4096), 0);
+        assert_eq!(cage1.close_syscall(fd), 0);
+        assert_eq!(cage1.close_syscall(newfd), 0);
+        assert_eq!(cage1.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    // a message as msgsnd/msgrcv take it: the cage's long mtype, then the text
+    fn msgbuf(mtype: i32, text: &[u8]) -> Vec<u8> {
+        let mut buf = mtype.to_ne_bytes().to_vec();
+        buf.extend_from_slice(text);
+        buf
+    }
+
+    #[test]
+    pub fn ut_lind_ipc_msg_queue_selection() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage1 = interface::cagetable_getref(1);
+        let key = 5150;
+        assert_eq!(cage1.msgget_syscall(key, 0o600), -(Errno::ENOENT as i32));
+        let msqid = cage1.msgget_syscall(key, libc::IPC_CREAT | 0o600);
+        assert!(msqid > 0);
+        assert_eq!(cage1.msgget_syscall(key, 0), msqid);
+        assert_eq!(cage1.msgget_syscall(key, libc::IPC_CREAT | libc::IPC_EXCL | 0o600), -(Errno::EEXIST as i32));
+        let private = cage1.msgget_syscall(libc::IPC_PRIVATE, 0o600);
+        assert!(private > 0 && private != msqid);
+
+        for (mtype, text) in [(3, &b"three"[..]), (1, b"one"), (2, b"two"), (1, b"uno")] {
+            let msg = msgbuf(mtype, text);
+            assert_eq!(cage1.msgsnd_syscall(msqid, msg.as_ptr(), text.len(), 0), 0);
+        }
+        let msg = msgbuf(0, b"");
+        assert_eq!(cage1.msgsnd_syscall(msqid, msg.as_ptr(), 0, 0), -(Errno::EINVAL as i32));
+
+        let mut msqidstruct = interface::MsqidsStruct::default();
+        assert_eq!(cage1.msgctl_syscall(msqid, libc::IPC_STAT, Some(&mut msqidstruct)), 0);
+        assert_eq!((msqidstruct.msg_qnum, msqidstruct.msg_cbytes), (4, 14));
+        assert_eq!((msqidstruct.msg_lspid, msqidstruct.msg_lrpid), (1, 0));
+        assert_eq!(msqidstruct.msg_perm.mode & 0o777, 0o600);
+
+        let mut buf = vec![0u8; 4 + 16];
+        let mut receive = |msgtyp: i32, msgsz: usize, msgflg: i32| -> (i32, i32, Vec<u8>) {
+            let ret = cage1.msgrcv_syscall(msqid, buf.as_mut_ptr(), msgsz, msgtyp, msgflg | libc::IPC_NOWAIT);
+            let mtype = i32::from_ne_bytes([buf[0], buf[1], buf[2], buf[3]]);
+            let len = if ret > 0 { ret as usize } else { 0 };
+            (ret, mtype, buf[4..4 + len].to_vec())
+        };
+
+        // MSG_COPY looks at a message by its position and leaves it queued
+        assert_eq!(receive(2, 16, libc::MSG_COPY), (3, 2, b"two".to_vec()));
+        // too long a message stays queued, unless MSG_NOERROR cuts it short
+        assert_eq!(receive(3, 2, 0).0, -(Errno::EBIG as i32));
+        assert_eq!(receive(3, 2, libc::MSG_NOERROR), (2, 3, b"th".to_vec()));
+        // the lowest type up to 2 is 1, and the first of those is taken
+        assert_eq!(receive(-2, 16, 0), (3, 1, b"one".to_vec()));
+        assert_eq!(receive(1, 16, libc::MSG_EXCEPT), (3, 2, b"two".to_vec()));
+        assert_eq!(receive(2, 16, 0).0, -(Errno::ENOMSG as i32));
+        assert_eq!(receive(0, 16, 0), (3, 1, b"uno".to_vec()));
+        assert_eq!(receive(0, 16, 0).0, -(Errno::ENOMSG as i32));
+
+        assert_eq!(cage1.msgctl_syscall(msqid, libc::IPC_STAT, Some(&mut msqidstruct)), 0);
+        assert_eq!((msqidstruct.msg_qnum, msqidstruct.msg_cbytes), (0, 0));
+        assert_eq!(msqidstruct.msg_lrpid, 1);
+        assert!(msqidstruct.msg_rtime > 0);
+
+        // msg_qbytes bounds what the queue holds
+        msqidstruct.msg_qbytes = 8;
+        assert_eq!(cage1.msgctl_syscall(msqid, libc::IPC_SET, Some(&mut msqidstruct)), 0);
+        let msg = msgbuf(1, b"12345");
+        assert_eq!(cage1.msgsnd_syscall(msqid, msg.as_ptr(), 5, libc::IPC_NOWAIT), 0);
+        assert_eq!(cage1.msgsnd_syscall(msqid, msg.as_ptr(), 5, libc::IPC_NOWAIT), -(Errno::EAGAIN as i32));
+        assert_eq!(cage1.msgsnd_syscall(msqid, msg.as_ptr(), MSGMAX as usize + 1, 0), -(Errno::EINVAL as i32));
+
+        // other users get what the mode allows them
+        cage1.geteuid.store(2000, interface::RustAtomicOrdering::Relaxed);
+        assert_eq!(cage1.msgget_syscall(key, 0o400), -(Errno::EACCES as i32));
+        assert_eq!(cage1.msgsnd_syscall(msqid, msg.as_ptr(), 5, 0), -(Errno::EACCES as i32));
+        assert_eq!(cage1.msgctl_syscall(msqid, libc::IPC_RMID, None), -(Errno::EPERM as i32));
+        cage1.geteuid.store(DEFAULT_UID as i32, interface::RustAtomicOrdering::Relaxed);
+
+        let mut msginfo = interface::MsqidsStruct::default();
+        assert!(cage1.msgctl_syscall(0, libc::MSG_INFO, Some(&mut msginfo)) >= private.max(msqid));
+        let info = unsafe { *(&msginfo as *const interface::MsqidsStruct as *const interface::MsginfoStruct) };
+        assert!(info.msgpool >= 2);
+        assert_eq!(info.msgmax, MSGMAX as i32);
+
+        for id in [msqid, private] {
+            assert_eq!(cage1.msgctl_syscall(id, libc::IPC_RMID, None), 0);
+            assert_eq!(cage1.msgctl_syscall(id, libc::IPC_STAT, Some(&mut msqidstruct)), -(Errno::EINVAL as i32));
+        }
+        assert_eq!(cage1.msgget_syscall(key, 0), -(Errno::ENOENT as i32));
+
+        assert_eq!(cage1.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_ipc_msg_queue_blocking() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage1 = interface::cagetable_getref(1);
+        let msqid = cage1.msgget_syscall(libc::IPC_PRIVATE, 0o600);
+        assert!(msqid > 0);
+        assert_eq!(cage1.fork_syscall(2), 0);
+
+        // a receiver waiting for type 7 sleeps through a message of another type
+        let receiver = interface::helper_thread(move || {
+            let cage2 = interface::cagetable_getref(2);
+            let mut buf = vec![0u8; 4 + 8];
+            assert_eq!(cage2.msgrcv_syscall(msqid, buf.as_mut_ptr(), 8, 7, 0), 5);
+            assert_eq!(&buf[4..9], b"hello");
+            // and one waiting on a queue that is removed wakes up to EIDRM
+            assert_eq!(cage2.msgrcv_syscall(msqid, buf.as_mut_ptr(), 8, 7, 0), -(Errno::EIDRM as i32));
+            assert_eq!(cage2.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        });
+
+        interface::sleep(interface::RustDuration::from_millis(100));
+        let other = msgbuf(3, b"other");
+        assert_eq!(cage1.msgsnd_syscall(msqid, other.as_ptr(), 5, 0), 0);
+        interface::sleep(interface::RustDuration::from_millis(100));
+        let msg = msgbuf(7, b"hello");
+        assert_eq!(cage1.msgsnd_syscall(msqid, msg.as_ptr(), 5, 0), 0);
+
+        interface::sleep(interface::RustDuration::from_millis(100));
+        let mut msqidstruct = interface::MsqidsStruct::default();
+        assert_eq!(cage1.msgctl_syscall(msqid, libc::IPC_STAT, Some(&mut msqidstruct)), 0);
+        assert_eq!((msqidstruct.msg_qnum, msqidstruct.msg_lrpid), (1, 2));
+        assert_eq!(cage1.msgctl_syscall(msqid, libc::IPC_RMID, None), 0);
+        receiver.join().unwrap();
+
+        assert_eq!(cage1.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    fn sembuf(sem_num: u16, sem_op: i16, sem_flg: i32) -> interface::SembufStruct {
+        interface::SembufStruct { sem_num, sem_op, sem_flg: sem_flg as i16 }
+    }
+
+    #[test]
+    pub fn ut_lind_ipc_semaphore_sets() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage1 = interface::cagetable_getref(1);
+        let key = 6060;
+        assert_eq!(cage1.semget_syscall(key, 3, 0o600), -(Errno::ENOENT as i32));
+        assert_eq!(cage1.semget_syscall(key, 0, libc::IPC_CREAT | 0o600), -(Errno::EINVAL as i32));
+        let semid = cage1.semget_syscall(key, 3, libc::IPC_CREAT | 0o600);
+        assert!(semid > 0);
+        assert_eq!(cage1.semget_syscall(key, 0, 0), semid);
+        assert_eq!(cage1.semget_syscall(key, 4, 0), -(Errno::EINVAL as i32));
+        assert_eq!(cage1.semget_syscall(key, 3, libc::IPC_CREAT | libc::IPC_EXCL | 0o600), -(Errno::EEXIST as i32));
+
+        let none = interface::SemunUnion { val: 0 };
+        let mut values = [2u16, 0, 5];
+        assert_eq!(cage1.semctl_syscall(semid, 0, libc::SETALL, interface::SemunUnion { array: values.as_mut_ptr() }), 0);
+        assert_eq!(cage1.semctl_syscall(semid, 2, libc::GETVAL, none), 5);
+        assert_eq!(cage1.semctl_syscall(semid, 1, libc::SETVAL, interface::SemunUnion { val: 32768 }), -(Errno::ERANGE as i32));
+        assert_eq!(cage1.semctl_syscall(semid, 3, libc::GETVAL, none), -(Errno::EINVAL as i32));
+
+        // the operations go through together or not at all
+        let sops = [sembuf(0, -1, 0), sembuf(1, -1, libc::IPC_NOWAIT)];
+        assert_eq!(cage1.semop_syscall(semid, &sops), -(Errno::EAGAIN as i32));
+        assert_eq!(cage1.semctl_syscall(semid, 0, libc::GETVAL, none), 2);
+        let sops = [sembuf(0, -2, 0), sembuf(1, 3, 0), sembuf(1, -1, 0)];
+        assert_eq!(cage1.semop_syscall(semid, &sops), 0);
+        assert_eq!(cage1.semctl_syscall(semid, 1, libc::GETPID, none), 1);
+        let mut values = [9u16; 3];
+        assert_eq!(cage1.semctl_syscall(semid, 0, libc::GETALL, interface::SemunUnion { array: values.as_mut_ptr() }), 0);
+        assert_eq!(values, [0, 2, 5]);
+
+        assert_eq!(cage1.semop_syscall(semid, &[]), -(Errno::EINVAL as i32));
+        assert_eq!(cage1.semop_syscall(semid, &[sembuf(3, 1, 0)]), -(Errno::EFBIG as i32));
+        assert_eq!(cage1.semop_syscall(semid, &[sembuf(2, 32767, 0)]), -(Errno::ERANGE as i32));
+
+        // semtimedop gives up once the time is up
+        let start = Instant::now();
+        let timeout = Some(interface::RustDuration::from_millis(50));
+        assert_eq!(cage1.semtimedop_syscall(semid, &[sembuf(0, -1, 0)], timeout), -(Errno::EAGAIN as i32));
+        assert!(start.elapsed() >= interface::RustDuration::from_millis(50));
+        assert_eq!(cage1.semtimedop_syscall(semid, &[sembuf(2, 0, 0)], timeout), -(Errno::EAGAIN as i32));
+        assert_eq!(cage1.semctl_syscall(semid, 0, libc::GETNCNT, none), 0);
+
+        let mut semidstruct = interface::SemidsStruct::default();
+        let statarg = interface::SemunUnion { buf: &mut semidstruct };
+        assert_eq!(cage1.semctl_syscall(semid, 0, libc::IPC_STAT, statarg), 0);
+        assert_eq!(semidstruct.sem_nsems, 3);
+        assert_eq!(semidstruct.sem_perm.mode & 0o777, 0o600);
+        assert!(semidstruct.sem_otime > 0);
+
+        // other users get what the mode allows them
+        cage1.geteuid.store(2000, interface::RustAtomicOrdering::Relaxed);
+        assert_eq!(cage1.semget_syscall(key, 0, 0o400), -(Errno::EACCES as i32));
+        assert_eq!(cage1.semop_syscall(semid, &[sembuf(0, 1, 0)]), -(Errno::EACCES as i32));
+        assert_eq!(cage1.semctl_syscall(semid, 0, libc::IPC_RMID, none), -(Errno::EPERM as i32));
+        cage1.geteuid.store(DEFAULT_UID as i32, interface::RustAtomicOrdering::Relaxed);
+
+        assert_eq!(cage1.semctl_syscall(semid, 0, libc::IPC_RMID, none), 0);
+        assert_eq!(cage1.semctl_syscall(semid, 0, libc::GETVAL, none), -(Errno::EINVAL as i32));
+        assert_eq!(cage1.semget_syscall(key, 0, 0), -(Errno::ENOENT as i32));
+
+        assert_eq!(cage1.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_ipc_semaphore_blocking_and_undo() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage1 = interface::cagetable_getref(1);
+        let none = interface::SemunUnion { val: 0 };
+        let semid = cage1.semget_syscall(libc::IPC_PRIVATE, 2, 0o600);
+        assert!(semid > 0);
+        assert_eq!(cage1.fork_syscall(2), 0);
+
+        // a cage waiting to decrement sleeps until another one increments
+        let waiter = interface::helper_thread(move || {
+            let cage2 = interface::cagetable_getref(2);
+            assert_eq!(cage2.semop_syscall(semid, &[sembuf(0, -1, 0)]), 0);
+            // what a cage did with SEM_UNDO is undone when it exits, even after an exec
+            assert_eq!(cage2.semop_syscall(semid, &[sembuf(1, 2, libc::SEM_UNDO)]), 0);
+            assert_eq!(cage2.exec_syscall(3), 0);
+        });
+
+        interface::sleep(interface::RustDuration::from_millis(100));
+        assert_eq!(cage1.semctl_syscall(semid, 0, libc::GETNCNT, none), 1);
+        assert_eq!(cage1.semop_syscall(semid, &[sembuf(0, 1, 0)]), 0);
+        waiter.join().unwrap();
+        assert_eq!(cage1.semctl_syscall(semid, 0, libc::GETVAL, none), 0);
+        assert_eq!(cage1.semctl_syscall(semid, 0, libc::GETNCNT, none), 0);
+        assert_eq!(cage1.semctl_syscall(semid, 1, libc::GETVAL, none), 2);
+
+        // the rollback clamps at zero rather than waiting
+        assert_eq!(cage1.semop_syscall(semid, &[sembuf(1, -1, 0)]), 0);
+        let cage3 = interface::cagetable_getref(3);
+        assert_eq!(cage3.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        assert_eq!(cage1.semctl_syscall(semid, 1, libc::GETVAL, none), 0);
+        assert_eq!(cage1.semctl_syscall(semid, 1, libc::GETPID, none), 3);
+
+        assert_eq!(cage1.semctl_syscall(semid, 0, libc::IPC_RMID, none), 0);
+        assert_eq!(cage1.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_ipc_named_semaphore() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+
+        let cage1 = interface::cagetable_getref(1);
+        let name = "/lind_named_sem";
+        assert_eq!(cage1.sem_open_syscall(name, 0, 0, 0, 0x100), -(Errno::ENOENT as i32));
+        assert_eq!(cage1.sem_open_syscall(name, libc::O_CREAT | libc::O_EXCL, 0o600, 1, 0x100), 0x100);
+        assert_eq!(cage1.sem_open_syscall(name, libc::O_CREAT | libc::O_EXCL, 0o600, 1, 0x200), -(Errno::EEXIST as i32));
+        assert_eq!(cage1.sem_open_syscall("/", libc::O_CREAT, 0o600, 1, 0x200), -(Errno::EINVAL as i32));
+
+        // another cage finds the same semaphore by its name, whatever sem_t it hands in
+        assert_eq!(cage1.fork_syscall(2), 0);
+        let cage2 = interface::cagetable_getref(2);
+        assert_eq!(cage2.exec_syscall(3), 0);
+        let cage3 = interface::cagetable_getref(3);
+        assert_eq!(cage3.sem_open_syscall("lind_named_sem", 0, 0, 0, 0x300), 0x300);
+        assert_eq!(cage3.sem_trywait_syscall(0x300), 0);
+        assert_eq!(cage1.sem_getvalue_syscall(0x100), 0);
+        assert_eq!(cage1.sem_post_syscall(0x100), 0);
+        assert_eq!(cage3.sem_getvalue_syscall(0x300), 1);
+
+        // only a cage the mode lets read and write may open it, and only the owner unlink it
+        cage3.geteuid.store(2000, interface::RustAtomicOrdering::Relaxed);
+        assert_eq!(cage3.sem_open_syscall(name, 0, 0, 0, 0x400), -(Errno::EACCES as i32));
+        assert_eq!(cage3.sem_unlink_syscall(name), -(Errno::EACCES as i32));
+
+        // opening it again returns the handle it is at, and takes a sem_close of its own
+        assert_eq!(cage1.sem_open_syscall(name, libc::O_CREAT, 0o600, 5, 0x500), 0x100);
+        assert_eq!(cage1.sem_close_syscall(0x100), 0);
+        assert_eq!(cage1.sem_getvalue_syscall(0x100), 1);
+
+        // after the unlink the cages that have it open still share it, but the name is free
+        assert_eq!(cage1.sem_unlink_syscall(name), 0);
+        assert_eq!(cage1.sem_unlink_syscall(name), -(Errno::ENOENT as i32));
+        assert_eq!(cage1.sem_open_syscall(name, 0, 0, 0, 0x500), -(Errno::ENOENT as i32));
+        assert_eq!(cage3.sem_trywait_syscall(0x300), 0);
+        assert_eq!(cage1.sem_getvalue_syscall(0x100), 0);
+        assert_eq!(cage1.sem_open_syscall(name, libc::O_CREAT | libc::O_EXCL, 0o600, 3, 0x500), 0x500);
+        assert_eq!(cage1.sem_getvalue_syscall(0x500), 3);
+        assert_eq!(cage1.sem_getvalue_syscall(0x100), 0);
+
+        assert_eq!(cage1.sem_close_syscall(0x100), 0);
+        assert_eq!(cage1.sem_getvalue_syscall(0x100), -(Errno::EINVAL as i32));
+        assert_eq!(cage1.sem_close_syscall(0x100), -(Errno::EINVAL as i32));
+        assert_eq!(cage1.sem_close_syscall(0x500), 0);
+        assert_eq!(cage1.sem_unlink_syscall(name), 0);
+
+        assert_eq!(cage3.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        assert_eq!(cage1.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        lindrustfinalize();
+    }
 }
@@ -2,7 +2,7 @@
                      // tests
 
 mod fs_tests;
-// mod ipc_tests;
+mod ipc_tests;
 // mod networking_tests;
 mod sys_tests;
 // use rand::Rng;
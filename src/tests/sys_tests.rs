@@ -135,4 +135,409 @@ pub mod sys_tests {
         
         lindrustfinalize();
     }
+
+    #[test]
+    pub fn ut_lind_waitpid() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+        let cage = interface::cagetable_getref(1);
+        let mut status = 0;
+
+        // nothing to wait for before we fork
+        assert_eq!(cage.waitpid_syscall(-1, Some(&mut status), 0), -(Errno::ECHILD as i32));
+
+        assert_eq!(cage.fork_syscall(2), 0);
+        // the child is still running, WNOHANG must not block
+        assert_eq!(cage.waitpid_syscall(2, Some(&mut status), libc::WNOHANG), 0);
+        assert_eq!(cage.waitpid_syscall(-1, Some(&mut status), libc::WNOHANG), 0);
+
+        let child = interface::cagetable_getref(2);
+        assert_eq!(child.exit_syscall(3), 3);
+
+        // the exit status survives the child leaving the cage table
+        assert_eq!(cage.waitpid_syscall(2, Some(&mut status), 0), 2);
+        assert!(libc::WIFEXITED(status));
+        assert_eq!(libc::WEXITSTATUS(status), 3);
+
+        // a child can only be reaped once
+        assert_eq!(cage.waitpid_syscall(2, Some(&mut status), 0), -(Errno::ECHILD as i32));
+        // and we can't reap someone else's child
+        assert_eq!(cage.fork_syscall(2), 0);
+        let child = interface::cagetable_getref(2);
+        assert_eq!(child.fork_syscall(3), 0);
+        assert_eq!(cage.waitpid_syscall(3, Some(&mut status), libc::WNOHANG), -(Errno::ECHILD as i32));
+        assert_eq!(cage.waitpid_syscall(-1, Some(&mut status), 0x1000), -(Errno::EINVAL as i32));
+
+        assert_eq!(interface::cagetable_getref(3).exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        assert_eq!(child.wait_syscall(None), 3);
+        assert_eq!(child.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        assert_eq!(cage.waitpid_syscall(0, None, 0), 2);
+
+        assert_eq!(cage.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_waitpid_blocking() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+        let cage = interface::cagetable_getref(1);
+        let mut status = 0;
+
+        assert_eq!(cage.fork_syscall(2), 0);
+        assert_eq!(cage.fork_syscall(3), 0);
+
+        let thread = interface::helper_thread(move || {
+            interface::sleep(interface::RustDuration::from_millis(100));
+            let child = interface::cagetable_getref(3);
+            assert_eq!(child.exit_syscall(7), 7);
+        });
+
+        // blocks until the second child exits
+        assert_eq!(cage.wait4_syscall(-1, Some(&mut status), 0, None), 3);
+        assert_eq!(libc::WEXITSTATUS(status), 7);
+        thread.join().unwrap();
+
+        let thread = interface::helper_thread(move || {
+            interface::sleep(interface::RustDuration::from_millis(100));
+            let child = interface::cagetable_getref(2);
+            assert_eq!(child.exit_syscall(EXIT_FAILURE), EXIT_FAILURE);
+        });
+
+        // the whole struct rusage of a wasm32 cage is written, not just the times
+        assert_eq!(std::mem::size_of::<interface::Rusage>(), 88);
+        let mut rusage = interface::Rusage { ru_maxrss: -1, ru_nivcsw: -1, ..Default::default() };
+        assert_eq!(cage.wait4_syscall(2, Some(&mut status), libc::WUNTRACED, Some(&mut rusage)), 2);
+        assert_eq!(libc::WEXITSTATUS(status), EXIT_FAILURE);
+        assert_eq!((rusage.ru_maxrss, rusage.ru_nivcsw), (0, 0));
+        thread.join().unwrap();
+
+        assert_eq!(cage.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_signal_sigchld_on_exit() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+        let cage = interface::cagetable_getref(1);
+        let sigchld = interface::lind_sigaddset(interface::lind_sigemptyset(), SIGCHLD);
+
+        // the parent's main thread isn't established yet, so SIGCHLD is parked at entry 0
+        assert_eq!(cage.fork_syscall(2), 0);
+        assert_eq!(interface::cagetable_getref(2).exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        assert_eq!(
+            cage.pendingsigset.get(&0).unwrap().load(interface::RustAtomicOrdering::Relaxed),
+            sigchld
+        );
+
+        // establishing the main thread delivers it (SIGCHLD is ignored by default on the host)
+        rustposix_thread_init(1, 0);
+        let pthreadid = interface::get_pthreadid();
+        assert!(cage.pendingsigset.get(&0).is_none());
+        assert_eq!(
+            cage.pendingsigset.get(&pthreadid).unwrap().load(interface::RustAtomicOrdering::Relaxed),
+            0
+        );
+
+        // a blocked SIGCHLD stays pending until it is unblocked
+        assert_eq!(cage.sigprocmask_syscall(SIG_BLOCK, Some(&sigchld), None), 0);
+        assert_eq!(cage.fork_syscall(2), 0);
+        assert_eq!(interface::cagetable_getref(2).exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        assert_eq!(lindgetsighandler(1, SIGCHLD), 1);
+        assert_eq!(
+            cage.pendingsigset.get(&pthreadid).unwrap().load(interface::RustAtomicOrdering::Relaxed),
+            sigchld
+        );
+        assert_eq!(cage.sigprocmask_syscall(SIG_UNBLOCK, Some(&sigchld), None), 0);
+        assert_eq!(
+            cage.pendingsigset.get(&pthreadid).unwrap().load(interface::RustAtomicOrdering::Relaxed),
+            0
+        );
+
+        // both children left a zombie behind
+        assert_eq!(cage.wait_syscall(None), 2);
+        assert_eq!(cage.wait_syscall(None), 2);
+
+        assert_eq!(cage.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_signal_mask_inheritance() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+        let cage = interface::cagetable_getref(1);
+        rustposix_thread_init(1, 0);
+
+        let mut mask = interface::lind_sigaddset(interface::lind_sigemptyset(), SIGUSR2);
+        mask = interface::lind_sigaddset(mask, SIGCHLD);
+        assert_eq!(cage.sigprocmask_syscall(SIG_SETMASK, Some(&mask), None), 0);
+        let mut oldmask = 0;
+        assert_eq!(cage.sigprocmask_syscall(SIG_BLOCK, None, Some(&mut oldmask)), 0);
+        assert_eq!(oldmask, mask);
+
+        // fork stores the mask at entry 0 for the child's main thread
+        assert_eq!(cage.fork_syscall(2), 0);
+        let child = interface::cagetable_getref(2);
+        assert_eq!(child.sigset.get(&0).unwrap().load(interface::RustAtomicOrdering::Relaxed), mask);
+
+        // exec keeps both the mask and the signals still pending
+        child.pendingsigset.insert(0, interface::RustAtomicU64::new(
+            interface::lind_sigaddset(interface::lind_sigemptyset(), SIGCHLD),
+        ));
+        assert_eq!(child.exec_syscall(3), 0);
+        let execed = interface::cagetable_getref(3);
+        assert_eq!(execed.sigset.get(&0).unwrap().load(interface::RustAtomicOrdering::Relaxed), mask);
+        assert_eq!(execed.getppid_syscall(), 1);
+
+        // the new main thread picks the mask up, the pending SIGCHLD stays blocked
+        let thread = interface::helper_thread(move || {
+            rustposix_thread_init(3, 0);
+            let execed = interface::cagetable_getref(3);
+            let pthreadid = interface::get_pthreadid();
+            assert!(execed.sigset.get(&0).is_none());
+            assert_eq!(
+                execed.sigset.get(&pthreadid).unwrap().load(interface::RustAtomicOrdering::Relaxed),
+                mask
+            );
+            assert_eq!(
+                execed.pendingsigset.get(&pthreadid).unwrap().load(interface::RustAtomicOrdering::Relaxed),
+                interface::lind_sigaddset(interface::lind_sigemptyset(), SIGCHLD)
+            );
+            assert_eq!(execed.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        });
+        thread.join().unwrap();
+        assert_eq!(cage.waitpid_syscall(3, None, 0), 3);
+
+        assert_eq!(cage.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_rlimit_nofile_inherited_by_fork() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+        let cage = interface::cagetable_getref(1);
+
+        let mut limit = interface::Rlimit::default();
+        assert_eq!(cage.getrlimit_syscall(RLIMIT_NOFILE, &mut limit), 0);
+        assert_eq!(limit, interface::Rlimit { rlim_cur: NOFILE_CUR, rlim_max: NOFILE_MAX });
+
+        // the soft limit may not exceed the hard one, and the hard one can only go down
+        let toohigh = interface::Rlimit { rlim_cur: 8, rlim_max: 4 };
+        assert_eq!(cage.setrlimit_syscall(RLIMIT_NOFILE, &toohigh), -(Errno::EINVAL as i32));
+        let raised = interface::Rlimit { rlim_cur: 8, rlim_max: NOFILE_MAX + 1 };
+        assert_eq!(cage.setrlimit_syscall(RLIMIT_NOFILE, &raised), -(Errno::EPERM as i32));
+
+        // stdin/stdout/stderr take fds 0-2, so one more file fits under a limit of 4
+        let lowered = interface::Rlimit { rlim_cur: 4, rlim_max: 4 };
+        assert_eq!(cage.setrlimit_syscall(RLIMIT_NOFILE, &lowered), 0);
+        let fd = cage.open_syscall("/rlimitfile", libc::O_CREAT | libc::O_RDWR, S_IRWXA);
+        assert_eq!(fd, 3);
+        assert_eq!(cage.open_syscall("/rlimitfile", libc::O_RDONLY, 0), -(Errno::EMFILE as i32));
+        assert_eq!(cage.dup_syscall(fd, None), -(Errno::EMFILE as i32));
+        assert_eq!(cage.dup2_syscall(fd, 4), -(Errno::EBADF as i32));
+
+        assert_eq!(cage.fork_syscall(2), 0);
+        let child = interface::cagetable_getref(2);
+        let mut childlimit = interface::Rlimit::default();
+        assert_eq!(child.getrlimit_syscall(RLIMIT_NOFILE, &mut childlimit), 0);
+        assert_eq!(childlimit, lowered);
+        assert_eq!(child.open_syscall("/rlimitfile", libc::O_RDONLY, 0), -(Errno::EMFILE as i32));
+        assert_eq!(child.close_syscall(fd), 0);
+        assert_eq!(child.open_syscall("/rlimitfile", libc::O_RDONLY, 0), fd);
+        assert_eq!(child.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+
+        assert_eq!(cage.close_syscall(fd), 0);
+        assert_eq!(cage.unlink_syscall("/rlimitfile"), 0);
+        assert_eq!(cage.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_rlimit_fsize_nproc_as() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+        let cage = interface::cagetable_getref(1);
+
+        // a write crossing RLIMIT_FSIZE is cut short, the next one fails with EFBIG
+        let fsize = interface::Rlimit { rlim_cur: 8, rlim_max: RLIM_INFINITY };
+        assert_eq!(cage.setrlimit_syscall(RLIMIT_FSIZE, &fsize), 0);
+        let fd = cage.open_syscall("/fsizefile", libc::O_CREAT | libc::O_TRUNC | libc::O_RDWR, S_IRWXA);
+        assert!(fd >= 0);
+        assert_eq!(cage.write_syscall(fd, "0123456789".as_ptr(), 10), 8);
+        assert_eq!(cage.write_syscall(fd, "89".as_ptr(), 2), -(Errno::EFBIG as i32));
+        assert_eq!(cage.pwrite_syscall(fd, "ab".as_ptr(), 2, 4), 2);
+        assert_eq!(cage.close_syscall(fd), 0);
+        assert_eq!(cage.unlink_syscall("/fsizefile"), 0);
+
+        // RLIMIT_NPROC counts every cage, and only cage 1 exists so far
+        let nproc = interface::Rlimit { rlim_cur: 2, rlim_max: RLIM_INFINITY };
+        assert_eq!(cage.setrlimit_syscall(RLIMIT_NPROC, &nproc), 0);
+        assert_eq!(cage.fork_syscall(2), 0);
+        assert_eq!(cage.fork_syscall(3), -(Errno::EAGAIN as i32));
+        let child = interface::cagetable_getref(2);
+        assert_eq!(child.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        assert_eq!(cage.waitpid_syscall(2, None, 0), 2);
+        assert_eq!(cage.fork_syscall(3), 0);
+        let child = interface::cagetable_getref(3);
+        assert_eq!(child.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+
+        // mappings are counted against RLIMIT_AS until they are unmapped
+        let asl = interface::Rlimit { rlim_cur: 8192, rlim_max: RLIM_INFINITY };
+        assert_eq!(cage.setrlimit_syscall(RLIMIT_AS, &asl), 0);
+        let prot = libc::PROT_READ | libc::PROT_WRITE;
+        let flags = libc::MAP_PRIVATE | libc::MAP_ANONYMOUS;
+        let addr = cage.mmap_syscall(std::ptr::null_mut(), 8192, prot, flags, -1, 0);
+        assert!(addr != -1);
+        assert_eq!(
+            cage.mmap_syscall(std::ptr::null_mut(), 4096, prot, flags, -1, 0),
+            -(Errno::ENOMEM as i32)
+        );
+
+        // remapping counted pages with MAP_FIXED counts them once, and only the counted
+        // part of what is unmapped frees room
+        let asl = interface::Rlimit { rlim_cur: 16384, rlim_max: RLIM_INFINITY };
+        assert_eq!(cage.setrlimit_syscall(RLIMIT_AS, &asl), 0);
+        let region = unsafe {
+            libc::mmap(std::ptr::null_mut(), 16384, libc::PROT_NONE, flags, -1, 0)
+        } as *mut u8;
+        assert_ne!(region as *mut libc::c_void, libc::MAP_FAILED);
+        let fixed = flags | libc::MAP_FIXED;
+        for _ in 0..3 {
+            assert_eq!(cage.mmap_syscall(region, 8192, prot, fixed, -1, 0), (region as i64 & 0xffffffff) as i32);
+        }
+        assert_eq!(cage.munmap_syscall(region, 4096), 0);
+        let upper = unsafe { region.add(8192) };
+        assert_eq!(cage.munmap_syscall(upper, 8192), 0);
+        assert_eq!(cage.mmap_syscall(upper, 8192, prot, fixed, -1, 0), -(Errno::ENOMEM as i32));
+        assert_eq!(cage.mmap_syscall(region, 8192, prot, fixed, -1, 0), (region as i64 & 0xffffffff) as i32);
+        assert_eq!(cage.mmap_syscall(upper, 4096, prot, fixed, -1, 0), -(Errno::ENOMEM as i32));
+        assert_eq!(cage.munmap_syscall(region, 8192), 0);
+
+        let mut core = interface::Rlimit::default();
+        assert_eq!(cage.getrlimit_syscall(RLIMIT_CORE, &mut core), 0);
+        assert_eq!(core.rlim_cur, 0);
+        assert_eq!(cage.getrlimit_syscall(RLIMIT_CPU, &mut core), -(Errno::EINVAL as i32));
+
+        assert_eq!(cage.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_setuid_family() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+        let cage = interface::cagetable_getref(1);
+
+        // an ordinary user can't take on ids it does not hold
+        assert_eq!(cage.setuid_syscall(0), -(Errno::EPERM as i32));
+        assert_eq!(cage.setgid_syscall(0), -(Errno::EPERM as i32));
+        assert_eq!(cage.setgroups_syscall(&[10]), -(Errno::EPERM as i32));
+
+        // a cage started as root may drop to any ids, keeping root as the saved uid
+        for id in [&cage.getuid, &cage.geteuid, &cage.getsuid] {
+            id.store(0, interface::RustAtomicOrdering::Relaxed);
+        }
+        assert_eq!(cage.setgroups_syscall(&[10, 20]), 0);
+        assert_eq!(cage.setresgid_syscall(500, 500, 500), 0);
+        assert_eq!(cage.setresuid_syscall(500, 500, u32::MAX), 0);
+        let (mut ruid, mut euid, mut suid) = (0, 0, 0);
+        assert_eq!(cage.getresuid_syscall(&mut ruid, &mut euid, &mut suid), 0);
+        assert_eq!((ruid, euid, suid), (500, 500, 0));
+        assert_eq!(cage.getuid_syscall(), 500);
+        assert_eq!(cage.getegid_syscall(), 500);
+
+        // the saved uid lets it regain root, and seteuid leaves the others alone
+        assert_eq!(cage.seteuid_syscall(0), 0);
+        assert_eq!(cage.geteuid_syscall(), 0);
+        assert_eq!(cage.seteuid_syscall(500), 0);
+        assert_eq!(cage.seteuid_syscall(600), -(Errno::EPERM as i32));
+
+        // the real uid can't be set to the saved one, and setting the real uid moves the
+        // saved uid along with the effective one
+        assert_eq!(cage.setreuid_syscall(0, 500), -(Errno::EPERM as i32));
+        assert_eq!(cage.setreuid_syscall(500, 0), 0);
+        assert_eq!(cage.getresuid_syscall(&mut ruid, &mut euid, &mut suid), 0);
+        assert_eq!((ruid, euid, suid), (500, 0, 0));
+
+        let mut groups = [0u32; 2];
+        assert_eq!(cage.getgroups_syscall(0, &mut []), 2);
+        assert_eq!(cage.getgroups_syscall(1, &mut groups[..1]), -(Errno::EINVAL as i32));
+        assert_eq!(cage.getgroups_syscall(2, &mut groups), 2);
+        assert_eq!(groups, [10, 20]);
+
+        // children inherit every id, exec turns the effective ids into the saved ones
+        assert_eq!(cage.fork_syscall(2), 0);
+        let child = interface::cagetable_getref(2);
+        assert_eq!(child.getresuid_syscall(&mut ruid, &mut euid, &mut suid), 0);
+        assert_eq!((ruid, euid, suid), (500, 0, 0));
+        assert_eq!(child.getgroups_syscall(2, &mut groups), 2);
+        assert_eq!(child.setresuid_syscall(0, 600, 0), 0);
+        assert_eq!(child.exec_syscall(3), 0);
+        let execed = interface::cagetable_getref(3);
+        assert_eq!(execed.getresuid_syscall(&mut ruid, &mut euid, &mut suid), 0);
+        assert_eq!((ruid, euid, suid), (0, 600, 600));
+        assert_eq!(execed.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+
+        assert_eq!(cage.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        lindrustfinalize();
+    }
+
+    #[test]
+    pub fn ut_lind_credentials_permission_checks() {
+        //acquiring a lock on TESTMUTEX prevents other tests from running concurrently,
+        // and also performs clean env setup
+        let _thelock = setup::lock_and_init();
+        let cage = interface::cagetable_getref(1);
+
+        // files under the root belong to the default user, whatever user runs lind
+        let fd = cage.open_syscall("/credfile", libc::O_CREAT | libc::O_TRUNC | libc::O_RDWR, 0o640);
+        assert!(fd >= 0);
+        let mut statdata = interface::StatData::default();
+        assert_eq!(cage.fstat_syscall(fd, &mut statdata), 0);
+        assert_eq!((statdata.st_uid, statdata.st_gid), (DEFAULT_UID, DEFAULT_GID));
+        assert_eq!(cage.close_syscall(fd), 0);
+        assert_eq!(cage.access_syscall("/credfile", libc::R_OK | libc::W_OK), 0);
+        assert_eq!(cage.access_syscall("/credfile", libc::X_OK), -(Errno::EACCES as i32));
+
+        // another user in the file's group may read it but not write it or change its mode
+        cage.geteuid.store(500, interface::RustAtomicOrdering::Relaxed);
+        assert_eq!(cage.open_syscall("/credfile", libc::O_WRONLY, 0), -(Errno::EACCES as i32));
+        let fd = cage.open_syscall("/credfile", libc::O_RDONLY, 0);
+        assert!(fd >= 0);
+        assert_eq!(cage.close_syscall(fd), 0);
+        assert_eq!(cage.chmod_syscall("/credfile", 0o777), -(Errno::EPERM as i32));
+        // access() goes by the real uid, which still owns the file
+        assert_eq!(cage.access_syscall("/credfile", libc::W_OK), 0);
+
+        // nor can a user outside of that group
+        cage.getegid.store(500, interface::RustAtomicOrdering::Relaxed);
+        assert_eq!(cage.open_syscall("/credfile", libc::O_RDONLY, 0), -(Errno::EACCES as i32));
+
+        // a segment made by the owner with mode 0600 is off limits to the others
+        cage.geteuid.store(DEFAULT_UID as i32, interface::RustAtomicOrdering::Relaxed);
+        let shmid = cage.shmget_syscall(4242, 1024, 0o600 | libc::IPC_CREAT);
+        assert!(shmid >= 0);
+        cage.geteuid.store(500, interface::RustAtomicOrdering::Relaxed);
+        assert_eq!(cage.shmget_syscall(4242, 1024, 0o400), -(Errno::EACCES as i32));
+        let mut shmidstruct = interface::ShmidsStruct::default();
+        assert_eq!(cage.shmctl_syscall(shmid, libc::IPC_STAT, Some(&mut shmidstruct)), -(Errno::EACCES as i32));
+        assert_eq!(cage.shmctl_syscall(shmid, libc::IPC_RMID, None), -(Errno::EPERM as i32));
+        cage.geteuid.store(DEFAULT_UID as i32, interface::RustAtomicOrdering::Relaxed);
+        assert_eq!(cage.shmget_syscall(4242, 1024, 0o600), shmid);
+        assert_eq!(cage.shmctl_syscall(shmid, libc::IPC_RMID, None), 0);
+
+        assert_eq!(cage.unlink_syscall("/credfile"), 0);
+        assert_eq!(cage.exit_syscall(EXIT_SUCCESS), EXIT_SUCCESS);
+        lindrustfinalize();
+    }
 }